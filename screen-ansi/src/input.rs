@@ -0,0 +1,345 @@
+use alloc::string::String;
+use core::num::NonZeroU16;
+use core::str::{self};
+use int_vec_2d::Point;
+use tvxaml_screen_base::*;
+
+const ONCE: NonZeroU16 = unsafe { NonZeroU16::new_unchecked(1) };
+
+const CSI_MAX_LEN: usize = 32;
+
+pub const PASTE_START: &[u8] = b"\x1B[200~";
+
+const PASTE_END: &[u8] = b"\x1B[201~";
+
+pub enum Parsed {
+    Event(Option<Event>, usize),
+    Incomplete,
+}
+
+fn decode_char(bytes: &[u8]) -> Result<(char, usize), bool> {
+    let Some(&b) = bytes.first() else { return Err(true); };
+    let len = match b {
+        0x00 ..= 0x7F => 1,
+        0xC0 ..= 0xDF => 2,
+        0xE0 ..= 0xEF => 3,
+        0xF0 ..= 0xF7 => 4,
+        _ => return Err(false),
+    };
+    if bytes.len() < len { return Err(true); }
+    match str::from_utf8(&bytes[.. len]) {
+        Ok(s) => Ok((s.chars().next().unwrap(), len)),
+        Err(_) => Err(false),
+    }
+}
+
+fn char_key(c: char) -> Key {
+    match c {
+        '\0' => Key::Ctrl(Ctrl::At),
+        '\x01' => Key::Ctrl(Ctrl::A),
+        '\x02' => Key::Ctrl(Ctrl::B),
+        '\x03' => Key::Ctrl(Ctrl::C),
+        '\x04' => Key::Ctrl(Ctrl::D),
+        '\x05' => Key::Ctrl(Ctrl::E),
+        '\x06' => Key::Ctrl(Ctrl::F),
+        '\x07' => Key::Ctrl(Ctrl::G),
+        '\x08' => Key::Backspace,
+        '\t' => Key::Tab,
+        '\x0A' => Key::Ctrl(Ctrl::J),
+        '\x0B' => Key::Ctrl(Ctrl::K),
+        '\x0C' => Key::Ctrl(Ctrl::L),
+        '\r' => Key::Enter,
+        '\x0E' => Key::Ctrl(Ctrl::N),
+        '\x0F' => Key::Ctrl(Ctrl::O),
+        '\x10' => Key::Ctrl(Ctrl::P),
+        '\x11' => Key::Ctrl(Ctrl::Q),
+        '\x12' => Key::Ctrl(Ctrl::R),
+        '\x13' => Key::Ctrl(Ctrl::S),
+        '\x14' => Key::Ctrl(Ctrl::T),
+        '\x15' => Key::Ctrl(Ctrl::U),
+        '\x16' => Key::Ctrl(Ctrl::V),
+        '\x17' => Key::Ctrl(Ctrl::W),
+        '\x18' => Key::Ctrl(Ctrl::X),
+        '\x19' => Key::Ctrl(Ctrl::Y),
+        '\x1A' => Key::Ctrl(Ctrl::Z),
+        '\x1B' => Key::Escape,
+        '\x1C' => Key::Ctrl(Ctrl::Backslash),
+        '\x1D' => Key::Ctrl(Ctrl::Bracket),
+        '\x1E' => Key::Ctrl(Ctrl::Caret),
+        '\x1F' => Key::Ctrl(Ctrl::Underscore),
+        '\x7F' => Key::Backspace,
+        c => Key::Char(c)
+    }
+}
+
+fn ss3_key(b: u8) -> Option<SpecialKey> {
+    match b {
+        b'A' => Some(SpecialKey::Up),
+        b'B' => Some(SpecialKey::Down),
+        b'C' => Some(SpecialKey::Right),
+        b'D' => Some(SpecialKey::Left),
+        b'H' => Some(SpecialKey::Home),
+        b'F' => Some(SpecialKey::End),
+        b'P' => Some(SpecialKey::F1),
+        b'Q' => Some(SpecialKey::F2),
+        b'R' => Some(SpecialKey::F3),
+        b'S' => Some(SpecialKey::F4),
+        _ => None
+    }
+}
+
+fn tilde_key(n: u16) -> Option<SpecialKey> {
+    match n {
+        1 | 7 => Some(SpecialKey::Home),
+        2 => Some(SpecialKey::Insert),
+        3 => Some(SpecialKey::Delete),
+        4 | 8 => Some(SpecialKey::End),
+        5 => Some(SpecialKey::PageUp),
+        6 => Some(SpecialKey::PageDown),
+        11 => Some(SpecialKey::F1),
+        12 => Some(SpecialKey::F2),
+        13 => Some(SpecialKey::F3),
+        14 => Some(SpecialKey::F4),
+        15 => Some(SpecialKey::F5),
+        17 => Some(SpecialKey::F6),
+        18 => Some(SpecialKey::F7),
+        19 => Some(SpecialKey::F8),
+        20 => Some(SpecialKey::F9),
+        21 => Some(SpecialKey::F10),
+        23 => Some(SpecialKey::F11),
+        24 => Some(SpecialKey::F12),
+        _ => None
+    }
+}
+
+/// Decodes xterm modifier parameter: `1 + (Shift ? 1 : 0) + (Alt ? 2 : 0) + (Ctrl ? 4 : 0) + (Meta ? 8 : 0)`.
+///
+/// Meta is treated as Alt.
+fn xterm_modifiers(param: Option<Option<u16>>) -> Option<Modifiers> {
+    let Some(param) = param else { return Some(Modifiers::empty()); };
+    let bits = param?.saturating_sub(1);
+    let mut modifiers = Modifiers::empty();
+    if bits & 1 != 0 { modifiers |= Modifiers::SHIFT; }
+    if bits & (2 | 8) != 0 { modifiers |= Modifiers::ALT; }
+    if bits & 4 != 0 { modifiers |= Modifiers::CTRL; }
+    Some(modifiers)
+}
+
+fn modified_key(params_bytes: &[u8], key: impl FnOnce(Option<u16>) -> Option<SpecialKey>) -> Option<Event> {
+    let mut params = params(params_bytes);
+    let key = key(params.next().flatten())?;
+    let modifiers = xterm_modifiers(params.next())?;
+    Some(Event::Key(ONCE, key.with(modifiers)))
+}
+
+fn params(bytes: &[u8]) -> impl Iterator<Item=Option<u16>> + '_ {
+    bytes.split(|&b| b == b';').map(|p| {
+        if p.is_empty() { return Some(0); }
+        str::from_utf8(p).ok().and_then(|p| p.parse().ok())
+    })
+}
+
+fn sgr_mouse(params_bytes: &[u8], release: bool) -> Option<Event> {
+    let mut params = params(params_bytes);
+    let b = params.next()??;
+    let x = params.next()??;
+    let y = params.next()??;
+    let button = match b & 0b11 {
+        0 => Some(MouseButton::Left),
+        1 => Some(MouseButton::Middle),
+        2 => Some(MouseButton::Right),
+        _ => None
+    };
+    let action = if b & 64 != 0 {
+        match b & 0b11 {
+            0 => MouseAction::WheelUp,
+            1 => MouseAction::WheelDown,
+            _ => return None,
+        }
+    } else if b & 32 != 0 {
+        button.map_or(MouseAction::Move, MouseAction::Drag)
+    } else if release {
+        MouseAction::Up(button?)
+    } else {
+        MouseAction::Down(button?)
+    };
+    let mut modifiers = Modifiers::empty();
+    if b & 4 != 0 { modifiers |= Modifiers::SHIFT; }
+    if b & 8 != 0 { modifiers |= Modifiers::ALT; }
+    if b & 16 != 0 { modifiers |= Modifiers::CTRL; }
+    let point = Point {
+        x: x.saturating_sub(1).try_into().unwrap_or(i16::MAX),
+        y: y.saturating_sub(1).try_into().unwrap_or(i16::MAX)
+    };
+    Some(Event::Mouse(MouseEvent { action, point, modifiers }))
+}
+
+fn csi(bytes: &[u8]) -> Parsed {
+    let Some(end) = bytes.iter().position(|&b| !(0x20 ..= 0x3F).contains(&b)) else {
+        if bytes.len() >= CSI_MAX_LEN { return Parsed::Event(None, bytes.len()); }
+        return Parsed::Incomplete;
+    };
+    if !(0x40 ..= 0x7E).contains(&bytes[end]) { return Parsed::Event(None, end); }
+    let params_bytes = &bytes[.. end];
+    let e = match bytes[end] {
+        b'M' | b'm' if params_bytes.first() == Some(&b'<') =>
+            sgr_mouse(&params_bytes[1 ..], bytes[end] == b'm'),
+        b'~' => modified_key(params_bytes, |n| n.and_then(tilde_key)),
+        b'A' | b'B' | b'C' | b'D' | b'H' | b'F' | b'P' | b'Q' | b'R' | b'S' =>
+            modified_key(params_bytes, |_| ss3_key(bytes[end])),
+        b'Z' if params_bytes.is_empty() => Some(Event::Key(ONCE, SpecialKey::Tab.with(Modifiers::SHIFT))),
+        _ => None
+    };
+    Parsed::Event(e, end + 1)
+}
+
+fn paste(bytes: &[u8]) -> Parsed {
+    let Some(len) = bytes.windows(PASTE_END.len()).position(|x| x == PASTE_END) else {
+        return Parsed::Incomplete;
+    };
+    let text = String::from_utf8_lossy(&bytes[.. len]).replace("\r\n", "\n").replace('\r', "\n");
+    Parsed::Event(Some(Event::Paste(text)), len + PASTE_END.len())
+}
+
+/// Returns `true` if the input buffer starts with a bracketed paste whose end has not arrived yet.
+pub fn is_paste_pending(bytes: &[u8]) -> bool {
+    bytes.starts_with(PASTE_START) && matches!(paste(&bytes[PASTE_START.len() ..]), Parsed::Incomplete)
+}
+
+/// Parses one event from the beginning of input buffer.
+///
+/// Returns [`Parsed::Incomplete`] if the buffer holds just a prefix of a sequence.
+/// In particular, a lone ESC byte is incomplete: the caller should treat it as the Escape key
+/// if no more input follows.
+pub fn parse(bytes: &[u8]) -> Parsed {
+    match bytes.first() {
+        None => Parsed::Incomplete,
+        Some(0x1B) => match bytes.get(1) {
+            None => Parsed::Incomplete,
+            Some(b'[') if bytes.starts_with(PASTE_START) => match paste(&bytes[PASTE_START.len() ..]) {
+                Parsed::Event(e, n) => Parsed::Event(e, PASTE_START.len() + n),
+                Parsed::Incomplete => Parsed::Incomplete,
+            },
+            Some(b'[') => match csi(&bytes[2 ..]) {
+                Parsed::Event(e, n) => Parsed::Event(e, 2 + n),
+                Parsed::Incomplete => Parsed::Incomplete,
+            },
+            Some(b'O') => match bytes.get(2) {
+                None => Parsed::Incomplete,
+                Some(&b) => Parsed::Event(ss3_key(b).map(|k| Event::Key(ONCE, k.into())), 3),
+            },
+            Some(_) => match decode_char(&bytes[1 ..]) {
+                Err(true) => Parsed::Incomplete,
+                Err(false) => Parsed::Event(Some(Event::Key(ONCE, Key::Escape)), 1),
+                Ok((c, _)) if c < ' ' || c == '\x7F' => Parsed::Event(Some(Event::Key(ONCE, Key::Escape)), 1),
+                Ok((c, n)) => Parsed::Event(Some(Event::Key(ONCE, Key::Alt(c))), 1 + n),
+            },
+        },
+        Some(_) => match decode_char(bytes) {
+            Err(true) => Parsed::Incomplete,
+            Err(false) => Parsed::Event(None, 1),
+            Ok((c, n)) => Parsed::Event(Some(Event::Key(ONCE, char_key(c))), n),
+        },
+    }
+}
+
+/// Resolves the incomplete sequence at the beginning of input buffer when no more input follows.
+///
+/// `ESC` followed by a single printable character (e.g. `ESC [` or `ESC O`) is the Alt-modified character,
+/// any other incomplete `ESC` sequence resolves to the Escape key consuming just the `ESC` byte,
+/// so the caller should parse the rest again.
+pub fn parse_incomplete(bytes: &[u8]) -> (Option<Event>, usize) {
+    debug_assert!(!bytes.is_empty());
+    if bytes[0] != 0x1B { return (None, bytes.len()); }
+    match decode_char(&bytes[1 ..]) {
+        Ok((c, n)) if 1 + n == bytes.len() && c >= ' ' && c != '\x7F' =>
+            (Some(Event::Key(ONCE, Key::Alt(c))), 1 + n),
+        _ => (Some(Event::Key(ONCE, Key::Escape)), 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(bytes: &[u8]) -> Option<(Key, usize)> {
+        match parse(bytes) {
+            Parsed::Event(Some(Event::Key(_, key)), n) => Some((key, n)),
+            _ => None
+        }
+    }
+
+    fn is_incomplete(bytes: &[u8]) -> bool {
+        matches!(parse(bytes), Parsed::Incomplete)
+    }
+
+    fn incomplete_key(bytes: &[u8]) -> Option<(Key, usize)> {
+        match parse_incomplete(bytes) {
+            (Some(Event::Key(_, key)), n) => Some((key, n)),
+            _ => None
+        }
+    }
+
+    #[test]
+    fn arrows() {
+        assert_eq!(key(b"\x1B[A"), Some((Key::Up, 3)));
+        assert_eq!(key(b"\x1B[D"), Some((Key::Left, 3)));
+        assert_eq!(key(b"\x1BOB"), Some((Key::Down, 3)));
+        assert_eq!(key(b"\x1BOCx"), Some((Key::Right, 3)));
+    }
+
+    #[test]
+    fn function_keys() {
+        assert_eq!(key(b"\x1BOP"), Some((Key::F1, 3)));
+        assert_eq!(key(b"\x1B[15~"), Some((Key::F5, 5)));
+        assert_eq!(key(b"\x1B[24~"), Some((Key::F12, 5)));
+        assert_eq!(key(b"\x1B[3~"), Some((Key::Delete, 4)));
+    }
+
+    #[test]
+    fn modifiers() {
+        assert_eq!(key(b"\x1B[1;5C"), Some((Key::Modified(Modifiers::CTRL, SpecialKey::Right), 6)));
+        assert_eq!(key(b"\x1B[1;2A"), Some((Key::Modified(Modifiers::SHIFT, SpecialKey::Up), 6)));
+        assert_eq!(key(b"\x1B[5;3~"), Some((Key::Modified(Modifiers::ALT, SpecialKey::PageUp), 6)));
+        assert_eq!(key(b"\x1B[1;6P"), Some((Key::Modified(Modifiers::SHIFT | Modifiers::CTRL, SpecialKey::F1), 6)));
+        assert_eq!(key(b"\x1B[Z"), Some((Key::Modified(Modifiers::SHIFT, SpecialKey::Tab), 3)));
+    }
+
+    #[test]
+    fn alt_letter() {
+        assert_eq!(key(b"\x1Bx"), Some((Key::Alt('x'), 2)));
+        assert_eq!(key(b"\x1BX"), Some((Key::Alt('X'), 2)));
+        assert_eq!(key("\x1Bж".as_bytes()), Some((Key::Alt('ж'), 3)));
+    }
+
+    #[test]
+    fn esc_control() {
+        assert_eq!(key(b"\x1B\x1B"), Some((Key::Escape, 1)));
+        assert_eq!(key(b"\x1B\x1B[A"), Some((Key::Escape, 1)));
+        assert_eq!(key(b"\x1B[A"), Some((Key::Up, 3)));
+        assert_eq!(key(b"\x1B\r"), Some((Key::Escape, 1)));
+    }
+
+    #[test]
+    fn utf8_split() {
+        let bytes = "ж".as_bytes();
+        assert!(is_incomplete(&bytes[.. 1]));
+        assert_eq!(key(bytes), Some((Key::Char('ж'), 2)));
+        assert!(is_incomplete(&"\x1Bж".as_bytes()[.. 2]));
+    }
+
+    #[test]
+    fn incomplete_prefix() {
+        assert!(is_incomplete(b"\x1B"));
+        assert!(is_incomplete(b"\x1B["));
+        assert!(is_incomplete(b"\x1BO"));
+        assert!(is_incomplete(b"\x1B[1;"));
+        assert_eq!(incomplete_key(b"\x1B"), Some((Key::Escape, 1)));
+        assert_eq!(incomplete_key(b"\x1B["), Some((Key::Alt('['), 2)));
+        assert_eq!(incomplete_key(b"\x1BO"), Some((Key::Alt('O'), 2)));
+        assert_eq!(incomplete_key(b"\x1B[1;"), Some((Key::Escape, 1)));
+        assert_eq!(key(b"[1;"), Some((Key::Char('['), 1)));
+        assert_eq!(incomplete_key(&"\x1Bж".as_bytes()[.. 2]), Some((Key::Escape, 1)));
+        assert_eq!(parse_incomplete(&"ж".as_bytes()[.. 1]), (None, 1));
+    }
+}
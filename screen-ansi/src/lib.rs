@@ -0,0 +1,78 @@
+#![feature(allocator_api)]
+#![feature(negative_impls)]
+
+#![deny(warnings)]
+#![doc(test(attr(deny(warnings))))]
+#![doc(test(attr(allow(dead_code))))]
+#![doc(test(attr(allow(unused_variables))))]
+#![allow(clippy::collapsible_if)]
+#![allow(clippy::many_single_char_names)]
+
+#![no_std]
+
+extern crate alloc;
+
+mod input;
+
+mod screen;
+
+#[cfg(unix)]
+mod unix;
+
+pub use screen::Screen;
+
+#[cfg(unix)]
+pub use unix::Tty;
+
+use alloc::alloc::Global;
+use alloc::boxed::Box;
+use core::alloc::Allocator;
+use core::task::Waker;
+use core::time::Duration;
+use int_vec_2d::Vector;
+use tvxaml_screen_base::Error;
+
+/// Byte stream the [`Screen`] talks to.
+pub trait Terminal {
+    /// Current terminal size.
+    fn size(&mut self) -> Result<Vector, Error>;
+
+    /// Writes all bytes to the terminal output.
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), Error>;
+
+    /// Reads available input bytes.
+    ///
+    /// Waits for input at most `timeout`, or infinitely if `timeout` is `None`, and returns `Ok(0)`
+    /// if no input arrived. Is allowed to return `Ok(0)` before the timeout expires,
+    /// e.g. to let the caller notice a terminal size change.
+    fn read(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> Result<usize, Error>;
+
+    /// Monotonic time since an arbitrary moment, used to time out incomplete input sequences.
+    fn now(&self) -> Duration;
+
+    /// Returns a handle, which makes a blocked or the next waiting [`read`](Terminal::read) call return `Ok(0)`.
+    ///
+    /// The handle can be used from any thread.
+    fn waker(&self) -> Waker;
+}
+
+/// Creates screen over the process standard input and output.
+#[cfg(unix)]
+pub fn init(
+    max_size: Option<(u16, u16)>,
+    error_alloc: Option<&'static dyn Allocator>
+) -> Result<Box<dyn tvxaml_screen_base::Screen>, Error> {
+    init_in(max_size, error_alloc, Global)
+}
+
+/// Creates screen over the process standard input and output.
+#[cfg(unix)]
+pub fn init_in<A: Allocator + Clone + 'static>(
+    max_size: Option<(u16, u16)>,
+    error_alloc: Option<&'static dyn Allocator>,
+    alloc: A
+) -> Result<Box<dyn tvxaml_screen_base::Screen>, Error> {
+    let error_alloc = error_alloc.unwrap_or(&Global);
+    let tty = Tty::new(libc::STDIN_FILENO, libc::STDOUT_FILENO, error_alloc)?;
+    Ok(Box::new(Screen::new_in(tty, max_size, alloc)?))
+}
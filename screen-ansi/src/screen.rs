@@ -0,0 +1,464 @@
+use crate::Terminal;
+use crate::input::*;
+use alloc::vec::Vec;
+use core::alloc::Allocator;
+use core::cmp::min;
+use core::task::Waker;
+use core::time::Duration;
+use int_vec_2d::{Point, Range1d, Vector, Rect};
+use panicking::panicking;
+use tvxaml_screen_base::*;
+use tvxaml_screen_base::Screen as base_Screen;
+
+const GRAPHEME_MAX: usize = 5;
+
+const INPUT_BUF_SIZE: usize = 256;
+
+/// How long to wait for the rest of an escape sequence before resolving it as typed keys.
+const ESC_DELAY: Duration = Duration::from_millis(50);
+
+/// How long to wait for more bracketed paste input before giving up on the paste end marker.
+const PASTE_DELAY: Duration = Duration::from_millis(100);
+
+/// Maximum bracketed paste size. A longer paste is delivered as typed keys.
+const PASTE_MAX: usize = 64 * 1024;
+
+/// Mouse motion is reported with no buttons pressed too (`?1003h`), because menus highlight items on hover.
+const INIT: &[u8] = b"\x1B[?1049h\x1B[?7l\x1B[?25l\x1B[?1003h\x1B[?1006h\x1B[?2004h\x1B[0m\x1B[2J";
+
+const DONE: &[u8] = b"\x1B[?2004l\x1B[?1006l\x1B[?1003l\x1B[0m\x1B[?25h\x1B[?7h\x1B[?1049l";
+
+type Ch = ([char; GRAPHEME_MAX], Fg, Bg, Attr);
+
+const SPACE: Ch = ([' ', '\0', '\0', '\0', '\0'], Fg::LightGray, Bg::None, Attr::empty());
+
+fn push_num<A: Allocator>(buf: &mut Vec<u8, A>, n: u16) {
+    let mut digits = [0u8; 5];
+    let mut i = digits.len();
+    let mut n = n;
+    loop {
+        i -= 1;
+        digits[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 { break; }
+    }
+    buf.extend_from_slice(&digits[i ..]);
+}
+
+fn push_indexed<A: Allocator>(buf: &mut Vec<u8, A>, base: u16, index: u8) {
+    push_num(buf, base);
+    buf.extend_from_slice(b";5;");
+    push_num(buf, index.into());
+}
+
+fn push_rgb<A: Allocator>(buf: &mut Vec<u8, A>, base: u16, (r, g, b): (u8, u8, u8)) {
+    push_num(buf, base);
+    buf.extend_from_slice(b";2;");
+    push_num(buf, r.into());
+    buf.push(b';');
+    push_num(buf, g.into());
+    buf.push(b';');
+    push_num(buf, b.into());
+}
+
+fn push_fg<A: Allocator>(buf: &mut Vec<u8, A>, c: Fg) {
+    let n = match c {
+        Fg::Black => 30,
+        Fg::Red => 31,
+        Fg::Green => 32,
+        Fg::Brown => 33,
+        Fg::Blue => 34,
+        Fg::Magenta => 35,
+        Fg::Cyan => 36,
+        Fg::LightGray => 37,
+        Fg::DarkGray => 90,
+        Fg::BrightRed => 91,
+        Fg::BrightGreen => 92,
+        Fg::Yellow => 93,
+        Fg::BrightBlue => 94,
+        Fg::BrightMagenta => 95,
+        Fg::BrightCyan => 96,
+        Fg::White => 97,
+        Fg::Indexed(index) => return push_indexed(buf, 38, index),
+        Fg::Rgb(r, g, b) => return push_rgb(buf, 38, (r, g, b)),
+    };
+    push_num(buf, n);
+}
+
+fn push_bg<A: Allocator>(buf: &mut Vec<u8, A>, c: Bg) {
+    let n = match c {
+        Bg::None => 49,
+        Bg::Black => 40,
+        Bg::Red => 41,
+        Bg::Green => 42,
+        Bg::Brown => 43,
+        Bg::Blue => 44,
+        Bg::Magenta => 45,
+        Bg::Cyan => 46,
+        Bg::LightGray => 47,
+        Bg::Indexed(index) => return push_indexed(buf, 48, index),
+        Bg::Rgb(r, g, b) => return push_rgb(buf, 48, (r, g, b)),
+    };
+    push_num(buf, n);
+}
+
+fn push_attr<A: Allocator>(buf: &mut Vec<u8, A>, attr: Attr) {
+    buf.push(b'0');
+    if attr.contains(Attr::BOLD) { buf.extend_from_slice(b";1"); }
+    if attr.contains(Attr::DIM) { buf.extend_from_slice(b";2"); }
+    if attr.contains(Attr::ITALIC) { buf.extend_from_slice(b";3"); }
+    if attr.contains(Attr::UNDERLINE) { buf.extend_from_slice(b";4"); }
+    if attr.contains(Attr::REVERSE) { buf.extend_from_slice(b";7"); }
+    if attr.contains(Attr::STRIKETHROUGH) { buf.extend_from_slice(b";9"); }
+}
+
+fn push_goto<A: Allocator>(buf: &mut Vec<u8, A>, p: Point) {
+    buf.extend_from_slice(b"\x1B[");
+    push_num(buf, (p.y as u16).saturating_add(1));
+    buf.push(b';');
+    push_num(buf, (p.x as u16).saturating_add(1));
+    buf.push(b'H');
+}
+
+/// ANSI/VT terminal screen over arbitrary [`Terminal`] byte stream.
+///
+/// Requires UTF-8 terminal.
+pub struct Screen<T: Terminal, A: Allocator> {
+    terminal: T,
+    max_size: Option<(u16, u16)>,
+    size: Vector,
+    lines: Vec<bool, A>,
+    cols: usize,
+    chs: Vec<Ch, A>,
+    output: Vec<u8, A>,
+    input: Vec<u8, A>,
+    /// When the incomplete sequence at the input start should be resolved without waiting for the rest.
+    input_deadline: Option<Duration>,
+    done: bool,
+}
+
+impl<T: Terminal, A: Allocator> !Sync for Screen<T, A> { }
+impl<T: Terminal, A: Allocator> !Send for Screen<T, A> { }
+
+impl<T: Terminal, A: Allocator> Screen<T, A> {
+    pub fn new_in(
+        mut terminal: T,
+        max_size: Option<(u16, u16)>,
+        alloc: A
+    ) -> Result<Self, Error> where A: Clone {
+        let size = limit_size(terminal.size()?, max_size);
+        let mut output = Vec::new_in(alloc.clone());
+        output.try_reserve(INIT.len()).map_err(|_| Error::Oom)?;
+        let mut input = Vec::new_in(alloc.clone());
+        input.try_reserve(INPUT_BUF_SIZE).map_err(|_| Error::Oom)?;
+        let mut s = Screen {
+            terminal,
+            max_size,
+            size: Vector::null(),
+            lines: Vec::new_in(alloc.clone()),
+            cols: 0,
+            chs: Vec::new_in(alloc),
+            output,
+            input,
+            input_deadline: None,
+            done: false,
+        };
+        s.terminal.write_all(INIT)?;
+        s.resize(size)?;
+        Ok(s)
+    }
+
+    pub fn terminal(&self) -> &T { &self.terminal }
+
+    pub fn terminal_mut(&mut self) -> &mut T { &mut self.terminal }
+
+    /// Restores terminal state. Called automatically on drop, but allows to handle possible error.
+    pub fn done(&mut self) -> Result<(), Error> {
+        if self.done { return Ok(()); }
+        self.done = true;
+        self.terminal.write_all(DONE)
+    }
+
+    fn resize(&mut self, size: Vector) -> Result<(), Error> {
+        let rows = usize::from(size.y as u16);
+        let cols = usize::from(size.x as u16);
+        let chs_len = rows.checked_mul(cols).ok_or(Error::Oom)?;
+        self.lines.try_reserve(rows.saturating_sub(self.lines.len())).map_err(|_| Error::Oom)?;
+        self.chs.try_reserve(chs_len.saturating_sub(self.chs.len())).map_err(|_| Error::Oom)?;
+        self.size = size;
+        self.cols = cols;
+        self.lines.clear();
+        self.lines.resize(rows, true);
+        self.chs.clear();
+        self.chs.resize(chs_len, SPACE);
+        self.terminal.write_all(b"\x1B[0m\x1B[2J")
+    }
+
+    fn start_text(line: &mut [Ch], x: i16) {
+        if x <= 0 { return; }
+        let mut x = x as u16;
+        if let Some(col) = line.get(x as usize) {
+            if col.0[0] != '\0' { return; }
+        } else {
+            return;
+        }
+        loop {
+            debug_assert!(x > 0);
+            x -= 1;
+            let col = &mut line[x as usize];
+            let stop = col.0[0] != '\0';
+            col.0[0] = ' ';
+            col.0[1] = '\0';
+            if stop { break; }
+        }
+    }
+
+    fn end_text(line: &mut [Ch], mut x: i16) {
+        if x <= 0 { return; }
+        while let Some(ref mut col) = line.get_mut(x as u16 as usize) {
+            if col.0[0] != '\0' { break; }
+            col.0[0] = ' ';
+            col.0[1] = '\0';
+            x += 1;
+        }
+    }
+
+    fn render(&mut self, cursor: Option<Point>) -> Result<(), Error> {
+        self.output.clear();
+        self.output.extend_from_slice(b"\x1B[?25l");
+        let mut attr = None;
+        if self.cols != 0 {
+            for (y, (chs, invalidated)) in self.chs.chunks(self.cols).zip(self.lines.iter_mut()).enumerate() {
+                if !*invalidated { continue; }
+                *invalidated = false;
+                push_goto(&mut self.output, Point { x: 0, y: y as i16 });
+                for ch in chs {
+                    if ch.0[0] == '\0' { continue; }
+                    if attr != Some((ch.1, ch.2, ch.3)) {
+                        attr = Some((ch.1, ch.2, ch.3));
+                        self.output.extend_from_slice(b"\x1B[");
+                        push_attr(&mut self.output, ch.3);
+                        self.output.push(b';');
+                        push_fg(&mut self.output, ch.1);
+                        self.output.push(b';');
+                        push_bg(&mut self.output, ch.2);
+                        self.output.push(b'm');
+                    }
+                    for &c in ch.0.iter().take_while(|&&c| c != '\0') {
+                        let mut buf = [0; 4];
+                        self.output.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+                    }
+                }
+            }
+        }
+        let cursor = cursor.and_then(|cursor| {
+            if (Rect { tl: Point { x: 0, y: 0 }, size: self.size }).contains(cursor) {
+                Some(cursor)
+            } else {
+                None
+            }
+        });
+        if let Some(cursor) = cursor {
+            push_goto(&mut self.output, cursor);
+            self.output.extend_from_slice(b"\x1B[?25h");
+        }
+        self.terminal.write_all(&self.output)
+    }
+
+    fn check_size(&mut self) -> Result<bool, Error> {
+        let size = limit_size(self.terminal.size()?, self.max_size);
+        if size == self.size { return Ok(false); }
+        self.resize(size)?;
+        Ok(true)
+    }
+
+    fn read_event(&mut self, timeout: Option<Duration>) -> Result<Option<Event>, Error> {
+        loop {
+            match parse(&self.input) {
+                Parsed::Event(e, n) => {
+                    self.input.drain(.. n);
+                    self.input_deadline = None;
+                    if e.is_some() { return Ok(e); }
+                    continue;
+                },
+                Parsed::Incomplete => { },
+            }
+            let pending = !self.input.is_empty();
+            let paste = is_paste_pending(&self.input);
+            if paste && self.input.len() >= PASTE_MAX {
+                self.input.drain(.. PASTE_START.len());
+                continue;
+            }
+            let deadline = if pending {
+                let delay = if paste { PASTE_DELAY } else { ESC_DELAY };
+                Some(*self.input_deadline.get_or_insert_with(|| self.terminal.now() + delay))
+            } else {
+                None
+            };
+            let timeout = if let Some(deadline) = deadline {
+                let now = self.terminal.now();
+                if now >= deadline {
+                    if paste {
+                        self.input.drain(.. PASTE_START.len());
+                        continue;
+                    }
+                    let (e, n) = parse_incomplete(&self.input);
+                    self.input.drain(.. n);
+                    if e.is_some() { return Ok(e); }
+                    continue;
+                }
+                Some(deadline - now)
+            } else {
+                timeout
+            };
+            let len = self.input.len();
+            self.input.resize(len + INPUT_BUF_SIZE, 0);
+            let read = self.terminal.read(&mut self.input[len ..], timeout);
+            let read = match read {
+                Ok(read) => read,
+                Err(e) => {
+                    self.input.truncate(len);
+                    return Err(e);
+                }
+            };
+            self.input.truncate(len + read);
+            if read != 0 {
+                // A paste is waited for while its input keeps coming.
+                if paste { self.input_deadline = None; }
+                continue;
+            }
+            if pending { continue; }
+            if self.check_size()? { return Ok(Some(Event::Resize)); }
+            return Ok(None);
+        }
+    }
+
+    fn update_raw(&mut self, cursor: Option<Point>, timeout: Option<Duration>) -> Result<Option<Event>, Error> {
+        if self.check_size()? { return Ok(Some(Event::Resize)); }
+        self.render(cursor)?;
+        let e = self.read_event(timeout)?;
+        if let Some(Event::Key(_, Key::Ctrl(Ctrl::L))) = e {
+            self.lines.iter_mut().for_each(|x| *x = true);
+            self.terminal.write_all(b"\x1B[0m\x1B[2J")?;
+        }
+        Ok(e)
+    }
+}
+
+fn limit_size(size: Vector, max_size: Option<(u16, u16)>) -> Vector {
+    let mut x = size.x.max(0);
+    let mut y = size.y.max(0);
+    if let Some(max_size) = max_size {
+        x = min(max_size.0, x as u16) as i16;
+        y = min(max_size.1, y as u16) as i16;
+    }
+    Vector { x, y }
+}
+
+impl<T: Terminal, A: Allocator> Drop for Screen<T, A> {
+    #![allow(clippy::panicking_unwrap)]
+    fn drop(&mut self) {
+        let e = self.done();
+        if e.is_err() && !panicking() { e.unwrap(); }
+    }
+}
+
+impl<T: Terminal, A: Allocator> base_Screen for Screen<T, A> {
+    fn size(&self) -> Vector { self.size }
+
+    fn out(
+        &mut self,
+        p: Point,
+        fg: Fg,
+        bg: Bg,
+        attr: Attr,
+        text: &str,
+        hard: Range1d,
+        soft: Range1d
+    ) -> Range1d {
+        debug_assert!(p.y >= 0 && p.y < self.size().y);
+        debug_assert!(hard.start >= 0 && hard.end > hard.start && hard.end <= self.size().x);
+        debug_assert!(soft.start >= 0 && soft.end > soft.start && soft.end <= self.size().x);
+        let text_end = if soft.end <= p.x { return Range1d { start: 0, end: 0 } } else { soft.end.saturating_sub(p.x) };
+        let text_start = if soft.start <= p.x { 0 } else { soft.start.saturating_sub(p.x) };
+        let line = &mut self.chs[usize::from(p.y as u16) * self.cols .. (usize::from(p.y as u16) + 1) * self.cols];
+        self.lines[p.y as u16 as usize] = true;
+        let graphemes = graphemes(text).map(|(g, w)| {
+            let mut grapheme = ['\0'; GRAPHEME_MAX];
+            let mut chars = text[g].chars();
+            grapheme[0] = chars.next().unwrap();
+            for g in grapheme[1 ..].iter_mut() {
+                if let Some(c) = chars.next() {
+                    *g = c;
+                } else {
+                    break;
+                }
+            }
+            (grapheme, w)
+        });
+        let mut x0 = None;
+        let mut x = p.x;
+        let mut n = 0i16;
+        for (g, w) in graphemes {
+            if x >= hard.end { break; }
+            if n >= text_end { break; }
+            n = n.saturating_add(w);
+            let before_text_start = n <= text_start;
+            if before_text_start {
+                x = min(hard.end, x.saturating_add(w));
+                continue;
+            }
+            if x < hard.start {
+                x = min(hard.end, x.saturating_add(w));
+                if x > hard.start {
+                    debug_assert!(x0.is_none());
+                    Self::start_text(line, hard.start);
+                    x0 = Some(hard.start);
+                    for i in hard.start .. x {
+                        let col = &mut line[i as u16 as usize];
+                        col.0[0] = ' ';
+                        col.0[1] = '\0';
+                    }
+                }
+                continue;
+            }
+            if x0.is_none() {
+                Self::start_text(line, x);
+                x0 = Some(x);
+            }
+            let next_x = min(hard.end, x.saturating_add(w));
+            if next_x - x < w {
+                for i in x .. next_x {
+                    let col = &mut line[i as u16 as usize];
+                    col.0[0] = ' ';
+                    col.0[1] = '\0';
+                }
+                x = next_x;
+                break;
+            }
+            let col = &mut line[x as u16 as usize];
+            col.0 = g;
+            col.1 = fg;
+            col.2 = bg;
+            col.3 = attr;
+            for i in x + 1 .. next_x {
+               line[i as u16 as usize].0[0] = '\0';
+            }
+            x = next_x;
+        }
+        if let Some(x0) = x0 {
+            Self::end_text(line, x);
+            Range1d { start: x0, end: x }
+        } else {
+            Range1d { start: 0, end: 0 }
+        }
+    }
+
+    fn update(&mut self, cursor: Option<Point>, timeout: Option<Duration>) -> Result<Option<Event>, Error> {
+        self.update_raw(cursor, timeout)
+    }
+
+    fn waker(&self) -> Waker {
+        self.terminal.waker()
+    }
+}
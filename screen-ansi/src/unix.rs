@@ -0,0 +1,175 @@
+use crate::Terminal;
+use alloc::boxed::Box;
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use core::alloc::Allocator;
+use core::mem::MaybeUninit;
+use core::task::Waker;
+use core::time::Duration;
+use errno_no_std::{Errno, errno};
+use int_vec_2d::Vector;
+use libc::*;
+use panicking::panicking;
+use tvxaml_screen_base::Error;
+
+const DEFAULT_SIZE: Vector = Vector { x: 80, y: 24 };
+
+const RESIZE_POLL_MS: c_int = 200;
+
+/// Self-pipe interrupting `poll` in [`Tty::read`].
+struct WakePipe {
+    read: c_int,
+    write: c_int,
+}
+
+impl WakePipe {
+    fn new() -> Result<Self, Errno> {
+        let mut fds = [0; 2];
+        if unsafe { pipe(fds.as_mut_ptr()) } != 0 { return Err(errno()); }
+        let res = WakePipe { read: fds[0], write: fds[1] };
+        for fd in fds {
+            let flags = unsafe { fcntl(fd, F_GETFL) };
+            if flags < 0 || unsafe { fcntl(fd, F_SETFL, flags | O_NONBLOCK) } < 0 { return Err(errno()); }
+            if unsafe { fcntl(fd, F_SETFD, FD_CLOEXEC) } < 0 { return Err(errno()); }
+        }
+        Ok(res)
+    }
+
+    fn drain(&self) {
+        let mut buf = [0u8; 16];
+        while unsafe { read(self.read, buf.as_mut_ptr() as _, buf.len()) } > 0 { }
+    }
+}
+
+impl Drop for WakePipe {
+    fn drop(&mut self) {
+        unsafe {
+            close(self.read);
+            close(self.write);
+        }
+    }
+}
+
+impl Wake for WakePipe {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        // A full pipe means there is a pending wake up already, so the write result can be ignored.
+        let b = 0u8;
+        unsafe { write(self.write, &b as *const u8 as _, 1) };
+    }
+}
+
+/// [`Terminal`] over a pair of Unix file descriptors.
+///
+/// Switches input into raw mode if it is a tty. If it is not (e.g. a pipe),
+/// the input is read as is, and the size falls back to 80x24 unless the output is a tty.
+pub struct Tty {
+    error_alloc: &'static dyn Allocator,
+    input: c_int,
+    output: c_int,
+    saved: Option<termios>,
+    wake_pipe: Arc<WakePipe>,
+}
+
+impl !Sync for Tty { }
+impl !Send for Tty { }
+
+impl Tty {
+    pub fn new(input: c_int, output: c_int, error_alloc: &'static dyn Allocator) -> Result<Self, Error> {
+        let wake_pipe = Arc::new(WakePipe::new().map_err(|e| Error::System(Box::new_in(e, error_alloc)))?);
+        let mut saved = MaybeUninit::<termios>::uninit();
+        let saved = if unsafe { tcgetattr(input, saved.as_mut_ptr()) } == 0 {
+            let saved = unsafe { saved.assume_init() };
+            let mut raw = saved;
+            unsafe { cfmakeraw(&mut raw) };
+            if unsafe { tcsetattr(input, TCSAFLUSH, &raw) } != 0 {
+                return Err(Error::System(Box::new_in(errno(), error_alloc)));
+            }
+            Some(saved)
+        } else {
+            None
+        };
+        Ok(Tty { error_alloc, input, output, saved, wake_pipe })
+    }
+
+    fn errno(&self) -> Error {
+        Error::System(Box::new_in(errno(), self.error_alloc))
+    }
+}
+
+impl Drop for Tty {
+    #![allow(clippy::panicking_unwrap)]
+    fn drop(&mut self) {
+        if let Some(saved) = self.saved.as_ref() {
+            let e = unsafe { tcsetattr(self.input, TCSAFLUSH, saved) };
+            if e != 0 && !panicking() { Err::<(), _>(self.errno()).unwrap(); }
+        }
+    }
+}
+
+impl Terminal for Tty {
+    fn size(&mut self) -> Result<Vector, Error> {
+        let mut ws = MaybeUninit::<winsize>::uninit();
+        for fd in [self.output, self.input] {
+            if unsafe { ioctl(fd, TIOCGWINSZ, ws.as_mut_ptr()) } == 0 {
+                let ws = unsafe { ws.assume_init() };
+                if ws.ws_col != 0 && ws.ws_row != 0 {
+                    return Ok(Vector {
+                        x: ws.ws_col.try_into().unwrap_or(i16::MAX),
+                        y: ws.ws_row.try_into().unwrap_or(i16::MAX)
+                    });
+                }
+            }
+        }
+        Ok(DEFAULT_SIZE)
+    }
+
+    fn write_all(&mut self, mut bytes: &[u8]) -> Result<(), Error> {
+        while !bytes.is_empty() {
+            let n = unsafe { write(self.output, bytes.as_ptr() as _, bytes.len()) };
+            if n < 0 {
+                if errno() == Errno(EINTR) { continue; }
+                return Err(self.errno());
+            }
+            bytes = &bytes[n as usize ..];
+        }
+        Ok(())
+    }
+
+    fn read(&mut self, buf: &mut [u8], timeout: Option<Duration>) -> Result<usize, Error> {
+        let mut fds = [
+            pollfd { fd: self.input, events: POLLIN, revents: 0 },
+            pollfd { fd: self.wake_pipe.read, events: POLLIN, revents: 0 },
+        ];
+        let timeout_ms = timeout.map_or(RESIZE_POLL_MS, |x| x.as_millis().min(RESIZE_POLL_MS as u128) as c_int);
+        let ready = unsafe { poll(fds.as_mut_ptr(), 2, timeout_ms) };
+        if ready < 0 {
+            return if errno() == Errno(EINTR) { Ok(0) } else { Err(self.errno()) };
+        }
+        if fds[1].revents != 0 { self.wake_pipe.drain(); }
+        if fds[0].revents == 0 { return Ok(0); }
+        let n = unsafe { read(self.input, buf.as_mut_ptr() as _, buf.len()) };
+        if n < 0 {
+            return if errno() == Errno(EINTR) { Ok(0) } else { Err(self.errno()) };
+        }
+        if n == 0 {
+            return Err(Error::System(Box::new_in("end of input", self.error_alloc)));
+        }
+        Ok(n as usize)
+    }
+
+    fn now(&self) -> Duration {
+        let mut ts = MaybeUninit::<timespec>::uninit();
+        let e = unsafe { clock_gettime(CLOCK_MONOTONIC, ts.as_mut_ptr()) };
+        assert!(e == 0, "monotonic clock failed");
+        let ts = unsafe { ts.assume_init() };
+        Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+    }
+
+    fn waker(&self) -> Waker {
+        Waker::from(self.wake_pipe.clone())
+    }
+}
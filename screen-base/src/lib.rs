@@ -19,14 +19,17 @@
 extern crate alloc;
 
 use alloc::boxed::Box;
+use alloc::string::String;
+use bitflags::bitflags;
 use core::alloc::Allocator;
 use core::fmt::{self, Debug, Display, Formatter};
-use core::iter::{Iterator, DoubleEndedIterator, FusedIterator};
+use core::iter::{Iterator, DoubleEndedIterator, ExactSizeIterator, FusedIterator};
 use core::num::NonZeroU16;
 use core::ops::Range;
-use enum_derive_2018::{EnumDisplay, EnumFromStr, IterVariants};
+use core::str::FromStr;
+use core::task::Waker;
+use core::time::Duration;
 use int_vec_2d::{Point, Vector, Range1d};
-use macro_attr_2018::macro_attr;
 use serde::{Serialize, Deserialize};
 use unicode_width::UnicodeWidthChar;
 
@@ -136,44 +139,288 @@ impl<'a> DoubleEndedIterator for Graphemes<'a> {
 
 impl<'a> FusedIterator for Graphemes<'a> { }
 
-macro_attr! {
-    #[derive(Eq, PartialEq, Debug, Hash, Clone, Copy, Ord, PartialOrd)]
-    #[derive(EnumDisplay!, EnumFromStr!, IterVariants!(BgVariants))]
-    #[derive(Serialize, Deserialize)]
-    pub enum Bg {
-        None,
-        Black,
-        Red,
-        Green,
-        Brown,
-        Blue,
-        Magenta,
-        Cyan,
-        LightGray
-    }
-}
-
-macro_attr! {
-    #[derive(Eq, PartialEq, Debug, Hash, Clone, Copy, Ord, PartialOrd)]
-    #[derive(EnumDisplay!, EnumFromStr!, IterVariants!(FgVariants))]
-    #[derive(Serialize, Deserialize)]
-    pub enum Fg {
-        Black,
-        Red,
-        Green,
-        Brown,
-        Blue,
-        Magenta,
-        Cyan,
-        LightGray,
-        DarkGray,
-        BrightRed,
-        BrightGreen,
-        Yellow,
-        BrightBlue,
-        BrightMagenta,
-        BrightCyan,
-        White
+#[derive(Eq, PartialEq, Debug, Hash, Clone, Copy, Ord, PartialOrd)]
+#[derive(Serialize, Deserialize)]
+pub enum Bg {
+    None,
+    Black,
+    Red,
+    Green,
+    Brown,
+    Blue,
+    Magenta,
+    Cyan,
+    LightGray,
+    /// Color from the xterm 256-color palette.
+    Indexed(u8),
+    /// 24-bit color.
+    Rgb(u8, u8, u8),
+}
+
+#[derive(Eq, PartialEq, Debug, Hash, Clone, Copy, Ord, PartialOrd)]
+#[derive(Serialize, Deserialize)]
+pub enum Fg {
+    Black,
+    Red,
+    Green,
+    Brown,
+    Blue,
+    Magenta,
+    Cyan,
+    LightGray,
+    DarkGray,
+    BrightRed,
+    BrightGreen,
+    Yellow,
+    BrightBlue,
+    BrightMagenta,
+    BrightCyan,
+    White,
+    /// Color from the xterm 256-color palette.
+    Indexed(u8),
+    /// 24-bit color.
+    Rgb(u8, u8, u8),
+}
+
+const FG_PALETTE: [(Fg, &str, (u8, u8, u8)); 16] = [
+    (Fg::Black, "Black", (0, 0, 0)),
+    (Fg::Red, "Red", (170, 0, 0)),
+    (Fg::Green, "Green", (0, 170, 0)),
+    (Fg::Brown, "Brown", (170, 85, 0)),
+    (Fg::Blue, "Blue", (0, 0, 170)),
+    (Fg::Magenta, "Magenta", (170, 0, 170)),
+    (Fg::Cyan, "Cyan", (0, 170, 170)),
+    (Fg::LightGray, "LightGray", (170, 170, 170)),
+    (Fg::DarkGray, "DarkGray", (85, 85, 85)),
+    (Fg::BrightRed, "BrightRed", (255, 85, 85)),
+    (Fg::BrightGreen, "BrightGreen", (85, 255, 85)),
+    (Fg::Yellow, "Yellow", (255, 255, 85)),
+    (Fg::BrightBlue, "BrightBlue", (85, 85, 255)),
+    (Fg::BrightMagenta, "BrightMagenta", (255, 85, 255)),
+    (Fg::BrightCyan, "BrightCyan", (85, 255, 255)),
+    (Fg::White, "White", (255, 255, 255)),
+];
+
+const BG_PALETTE: [Bg; 9] = [
+    Bg::None, Bg::Black, Bg::Red, Bg::Green, Bg::Brown, Bg::Blue, Bg::Magenta, Bg::Cyan, Bg::LightGray
+];
+
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn indexed_rgb(index: u8) -> (u8, u8, u8) {
+    match index {
+        0 ..= 15 => FG_PALETTE[usize::from(index)].2,
+        16 ..= 231 => {
+            let i = index - 16;
+            (CUBE_LEVELS[usize::from(i / 36)], CUBE_LEVELS[usize::from(i / 6 % 6)], CUBE_LEVELS[usize::from(i % 6)])
+        },
+        _ => {
+            let level = 8 + 10 * (index - 232);
+            (level, level, level)
+        },
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> i32 {
+    let dr = i32::from(a.0) - i32::from(b.0);
+    let dg = i32::from(a.1) - i32::from(b.1);
+    let db = i32::from(a.2) - i32::from(b.2);
+    dr * dr + dg * dg + db * db
+}
+
+fn nearest_palette_index(rgb: (u8, u8, u8), palette_len: usize) -> usize {
+    (0 .. palette_len).min_by_key(|&i| distance(FG_PALETTE[i].2, rgb)).unwrap()
+}
+
+/// Nearest color of the 6x6x6 cube and grayscale ramp, which look the same on all terminals.
+fn nearest_indexed(rgb: (u8, u8, u8)) -> u8 {
+    (16 ..= 255).min_by_key(|&i| distance(indexed_rgb(i), rgb)).unwrap()
+}
+
+fn parse_extended_color(s: &str) -> Option<Result<u8, (u8, u8, u8)>> {
+    if let Some(hex) = s.strip_prefix('#') {
+        if hex.len() != 6 || !hex.is_ascii() { return None; }
+        let r = u8::from_str_radix(&hex[0 .. 2], 16).ok()?;
+        let g = u8::from_str_radix(&hex[2 .. 4], 16).ok()?;
+        let b = u8::from_str_radix(&hex[4 .. 6], 16).ok()?;
+        Some(Err((r, g, b)))
+    } else {
+        let index = s.strip_prefix("Indexed(")?.strip_suffix(')')?;
+        Some(Ok(index.trim().parse().ok()?))
+    }
+}
+
+#[derive(Debug)]
+pub struct ParseColorError;
+
+impl Display for ParseColorError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "invalid color")
+    }
+}
+
+impl Fg {
+    /// Iterates over basic palette colors.
+    pub fn iter_variants() -> FgVariants {
+        FgVariants(0)
+    }
+
+    /// Color components, as shown by a typical terminal.
+    pub fn to_rgb(self) -> (u8, u8, u8) {
+        match self {
+            Fg::Indexed(index) => indexed_rgb(index),
+            Fg::Rgb(r, g, b) => (r, g, b),
+            c => FG_PALETTE.iter().find(|x| x.0 == c).unwrap().2,
+        }
+    }
+
+    /// Nearest basic palette color.
+    pub fn to_palette(self) -> Fg {
+        match self {
+            Fg::Indexed(index) if index < 16 => FG_PALETTE[usize::from(index)].0,
+            Fg::Indexed(_) | Fg::Rgb(..) => FG_PALETTE[nearest_palette_index(self.to_rgb(), 16)].0,
+            c => c,
+        }
+    }
+
+    /// Nearest xterm 256-color palette color index.
+    pub fn to_indexed(self) -> u8 {
+        match self {
+            Fg::Indexed(index) => index,
+            Fg::Rgb(r, g, b) => nearest_indexed((r, g, b)),
+            c => FG_PALETTE.iter().position(|x| x.0 == c).unwrap() as u8,
+        }
+    }
+}
+
+impl Bg {
+    /// Iterates over basic palette colors.
+    pub fn iter_variants() -> BgVariants {
+        BgVariants(0)
+    }
+
+    /// Color components, as shown by a typical terminal. `None` for the terminal default background.
+    pub fn to_rgb(self) -> Option<(u8, u8, u8)> {
+        match self {
+            Bg::None => None,
+            c => Some(Fg::try_from(c).unwrap().to_rgb()),
+        }
+    }
+
+    /// Nearest basic palette color.
+    pub fn to_palette(self) -> Bg {
+        match self {
+            Bg::Indexed(_) | Bg::Rgb(..) =>
+                Bg::try_from(FG_PALETTE[nearest_palette_index(self.to_rgb().unwrap(), 8)].0).unwrap(),
+            c => c,
+        }
+    }
+
+    /// Nearest xterm 256-color palette color index. `None` for the terminal default background.
+    pub fn to_indexed(self) -> Option<u8> {
+        match self {
+            Bg::None => None,
+            c => Some(Fg::try_from(c).unwrap().to_indexed()),
+        }
+    }
+}
+
+impl Display for Fg {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Fg::Indexed(index) => write!(f, "Indexed({index})"),
+            Fg::Rgb(r, g, b) => write!(f, "#{r:02X}{g:02X}{b:02X}"),
+            &c => write!(f, "{}", FG_PALETTE.iter().find(|x| x.0 == c).unwrap().1),
+        }
+    }
+}
+
+impl Display for Bg {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Bg::None => write!(f, "None"),
+            &c => Display::fmt(&Fg::try_from(c).unwrap(), f),
+        }
+    }
+}
+
+impl FromStr for Fg {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Fg, Self::Err> {
+        if let Some(c) = FG_PALETTE.iter().find(|x| x.1 == s) { return Ok(c.0); }
+        match parse_extended_color(s).ok_or(ParseColorError)? {
+            Ok(index) => Ok(Fg::Indexed(index)),
+            Err((r, g, b)) => Ok(Fg::Rgb(r, g, b)),
+        }
+    }
+}
+
+impl FromStr for Bg {
+    type Err = ParseColorError;
+
+    fn from_str(s: &str) -> Result<Bg, Self::Err> {
+        if s == "None" { return Ok(Bg::None); }
+        let fg = Fg::from_str(s)?;
+        Bg::try_from(fg).map_err(|_| ParseColorError)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FgVariants(usize);
+
+impl Iterator for FgVariants {
+    type Item = Fg;
+
+    fn next(&mut self) -> Option<Fg> {
+        let c = FG_PALETTE.get(self.0)?.0;
+        self.0 += 1;
+        Some(c)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = FG_PALETTE.len() - self.0;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for FgVariants { }
+
+impl FusedIterator for FgVariants { }
+
+#[derive(Debug, Clone)]
+pub struct BgVariants(usize);
+
+impl Iterator for BgVariants {
+    type Item = Bg;
+
+    fn next(&mut self) -> Option<Bg> {
+        let c = *BG_PALETTE.get(self.0)?;
+        self.0 += 1;
+        Some(c)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = BG_PALETTE.len() - self.0;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for BgVariants { }
+
+impl FusedIterator for BgVariants { }
+
+bitflags! {
+    #[derive(Default)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct Attr: u8 {
+        const BOLD = 1 << 0;
+        const DIM = 1 << 1;
+        const UNDERLINE = 1 << 2;
+        const REVERSE = 1 << 3;
+        const ITALIC = 1 << 4;
+        /// Not supported by ncurses.
+        const STRIKETHROUGH = 1 << 5;
     }
 }
 
@@ -194,6 +441,8 @@ impl TryFrom<Bg> for Fg {
             Bg::Magenta => Ok(Fg::Magenta),
             Bg::Cyan => Ok(Fg::Cyan),
             Bg::LightGray => Ok(Fg::LightGray),
+            Bg::Indexed(index) => Ok(Fg::Indexed(index)),
+            Bg::Rgb(r, g, b) => Ok(Fg::Rgb(r, g, b)),
         }
     }
 }
@@ -214,6 +463,8 @@ impl TryFrom<Fg> for Bg {
             Fg::Magenta => Ok(Bg::Magenta),
             Fg::Cyan => Ok(Bg::Cyan),
             Fg::LightGray => Ok(Bg::LightGray),
+            Fg::Indexed(index) => Ok(Bg::Indexed(index)),
+            Fg::Rgb(r, g, b) => Ok(Bg::Rgb(r, g, b)),
             _ => Err(TryFromFgError),
         }
     }
@@ -258,15 +509,125 @@ pub enum Key {
     F10,
     F11,
     F12,
+    /// Non-character key pressed with modifiers, e.g. Shift+Tab or Ctrl+Left.
+    ///
+    /// Modifiers are never empty: a key without modifiers is reported as a plain variant.
+    /// Use [`SpecialKey::with`] to build a key in the canonical form.
+    Modified(Modifiers, SpecialKey),
+}
+
+#[derive(Eq, PartialEq, Debug, Hash, Clone, Copy, Ord, PartialOrd)]
+pub enum SpecialKey {
+    Tab,
+    Down,
+    Up,
+    Left,
+    Right,
+    Home,
+    End,
+    Delete,
+    Insert,
+    PageDown,
+    PageUp,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+}
+
+impl SpecialKey {
+    pub fn with(self, modifiers: Modifiers) -> Key {
+        if !modifiers.is_empty() { return Key::Modified(modifiers, self); }
+        match self {
+            SpecialKey::Tab => Key::Tab,
+            SpecialKey::Down => Key::Down,
+            SpecialKey::Up => Key::Up,
+            SpecialKey::Left => Key::Left,
+            SpecialKey::Right => Key::Right,
+            SpecialKey::Home => Key::Home,
+            SpecialKey::End => Key::End,
+            SpecialKey::Delete => Key::Delete,
+            SpecialKey::Insert => Key::Insert,
+            SpecialKey::PageDown => Key::PageDown,
+            SpecialKey::PageUp => Key::PageUp,
+            SpecialKey::F1 => Key::F1,
+            SpecialKey::F2 => Key::F2,
+            SpecialKey::F3 => Key::F3,
+            SpecialKey::F4 => Key::F4,
+            SpecialKey::F5 => Key::F5,
+            SpecialKey::F6 => Key::F6,
+            SpecialKey::F7 => Key::F7,
+            SpecialKey::F8 => Key::F8,
+            SpecialKey::F9 => Key::F9,
+            SpecialKey::F10 => Key::F10,
+            SpecialKey::F11 => Key::F11,
+            SpecialKey::F12 => Key::F12,
+        }
+    }
+}
+
+impl From<SpecialKey> for Key {
+    fn from(key: SpecialKey) -> Key {
+        key.with(Modifiers::empty())
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Hash, Clone, Copy, Ord, PartialOrd)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+}
+
+bitflags! {
+    #[derive(Default)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+    pub struct Modifiers: u8 {
+        const SHIFT = 1 << 0;
+        const CTRL = 1 << 1;
+        const ALT = 1 << 2;
+    }
+}
+
+#[derive(Eq, PartialEq, Debug, Hash, Clone, Copy, Ord, PartialOrd)]
+#[non_exhaustive]
+pub enum MouseAction {
+    Down(MouseButton),
+    Up(MouseButton),
+    DoubleClick(MouseButton),
+    /// Motion with the button held down.
+    Drag(MouseButton),
+    /// Motion with no button held down.
+    Move,
+    WheelUp,
+    WheelDown,
 }
 
 #[derive(Eq, PartialEq, Debug, Hash, Clone, Copy)]
+pub struct MouseEvent {
+    pub action: MouseAction,
+    pub point: Point,
+    pub modifiers: Modifiers,
+}
+
+#[derive(Eq, PartialEq, Debug, Hash, Clone)]
 #[non_exhaustive]
 pub enum Event {
     Resize,
     Key(NonZeroU16, Key),
-    LmbDown(Point),
-    LmbUp(Point),
+    Mouse(MouseEvent),
+    /// Text pasted at once, reported by backends supporting bracketed paste.
+    ///
+    /// Line breaks are normalized to `'\n'`.
+    Paste(String),
 }
 
 pub enum Error {
@@ -297,10 +658,19 @@ pub trait Screen {
         p: Point,
         fg: Fg,
         bg: Bg,
+        attr: Attr,
         text: &str,
         hard: Range1d,
         soft: Range1d,
     ) -> Range1d;
 
-    fn update(&mut self, cursor: Option<Point>, wait: bool) -> Result<Option<Event>, Error>;
+    /// Renders changes, and reads an input event.
+    ///
+    /// Waits for the event at most `timeout`, or infinitely if `timeout` is `None`.
+    /// Returns `Ok(None)` if there are no events. It is allowed to do so before the timeout expires.
+    fn update(&mut self, cursor: Option<Point>, timeout: Option<Duration>) -> Result<Option<Event>, Error>;
+
+    /// Returns a handle, which makes a blocked or the next waiting [`update`](Screen::update) call
+    /// return `Ok(None)`. The handle can be used from any thread.
+    fn waker(&self) -> Waker;
 }
@@ -0,0 +1,327 @@
+#![feature(allocator_api)]
+
+#![deny(warnings)]
+#![doc(test(attr(deny(warnings))))]
+#![doc(test(attr(allow(dead_code))))]
+#![doc(test(attr(allow(unused_variables))))]
+#![allow(clippy::collapsible_if)]
+#![allow(clippy::many_single_char_names)]
+
+#![no_std]
+
+extern crate alloc;
+
+use alloc::alloc::Global;
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::sync::Arc;
+use alloc::task::Wake;
+use alloc::vec::Vec;
+use core::alloc::Allocator;
+use core::cell::RefCell;
+use core::cmp::min;
+use core::num::NonZeroU16;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::Waker;
+use core::time::Duration;
+use int_vec_2d::{Point, Range1d, Vector, Rect};
+use tvxaml_screen_base::*;
+use tvxaml_screen_base::Screen as base_Screen;
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Cell {
+    /// Grapheme shown in the cell.
+    ///
+    /// Empty for cells covered by a preceding wide grapheme.
+    pub text: String,
+    pub fg: Fg,
+    pub bg: Bg,
+    pub attr: Attr,
+}
+
+impl Default for Cell {
+    fn default() -> Self {
+        Cell { text: " ".to_string(), fg: Fg::LightGray, bg: Bg::None, attr: Attr::empty() }
+    }
+}
+
+struct ScreenData {
+    size: Vector,
+    cells: Vec<Cell>,
+    cursor: Option<Point>,
+    events: VecDeque<Event>,
+    woken: Arc<Woken>,
+    elapsed: Duration,
+    exhausted_handler: Option<Box<dyn FnMut()>>,
+}
+
+struct Woken(AtomicBool);
+
+impl Wake for Woken {
+    fn wake(self: Arc<Self>) {
+        self.0.store(true, Ordering::Release);
+    }
+}
+
+impl ScreenData {
+    fn resize(&mut self, size: Vector) {
+        let size = Vector { x: size.x.max(0), y: size.y.max(0) };
+        self.size = size;
+        self.cells.clear();
+        self.cells.resize(usize::from(size.x as u16) * usize::from(size.y as u16), Cell::default());
+    }
+
+    fn line(&mut self, y: i16) -> &mut [Cell] {
+        let cols = usize::from(self.size.x as u16);
+        &mut self.cells[usize::from(y as u16) * cols .. (usize::from(y as u16) + 1) * cols]
+    }
+}
+
+/// In-memory [`Screen`](tvxaml_screen_base::Screen) implementation.
+///
+/// All clones share the same cell grid and event queue, so one clone can be passed to the application,
+/// and another one kept to feed input and inspect rendered output.
+///
+/// The screen never blocks. A wait with a timeout advances the [`elapsed`](Screen::elapsed) virtual clock
+/// instead of sleeping. Waiting for an event with the queue exhausted calls the
+/// [exhausted handler](Screen::handle_exhausted), which normally quits the application, and returns `Ok(None)`.
+/// Without a handler such a wait fails with [`Error::System`]. A [`waker`](tvxaml_screen_base::Screen::waker)
+/// call made before that makes the wait return `Ok(None)` once instead.
+#[derive(Clone)]
+pub struct Screen(Rc<RefCell<ScreenData>>);
+
+const ONCE: NonZeroU16 = unsafe { NonZeroU16::new_unchecked(1) };
+
+impl Screen {
+    pub fn new(size: Vector) -> Self {
+        let mut data = ScreenData {
+            size: Vector::null(),
+            cells: Vec::new(),
+            cursor: None,
+            events: VecDeque::new(),
+            woken: Arc::new(Woken(AtomicBool::new(false))),
+            elapsed: Duration::ZERO,
+            exhausted_handler: None,
+        };
+        data.resize(size);
+        Screen(Rc::new(RefCell::new(data)))
+    }
+
+    /// Changes screen size, clears it, and queues [`Event::Resize`].
+    pub fn set_size(&self, size: Vector) {
+        let mut data = self.0.borrow_mut();
+        data.resize(size);
+        data.events.push_back(Event::Resize);
+    }
+
+    pub fn push_event(&self, event: Event) {
+        self.0.borrow_mut().events.push_back(event);
+    }
+
+    pub fn push_key(&self, key: Key) {
+        self.push_event(Event::Key(ONCE, key));
+    }
+
+    pub fn push_text(&self, text: &str) {
+        for c in text.chars() {
+            self.push_key(Key::Char(c));
+        }
+    }
+
+    pub fn push_paste(&self, text: &str) {
+        self.push_event(Event::Paste(text.to_string()));
+    }
+
+    pub fn pending_events(&self) -> usize {
+        self.0.borrow().events.len()
+    }
+
+    /// Sets a handler called when the application waits for an event after the last scripted one.
+    ///
+    /// The handler typically calls `App::quit`, so the application main loop returns successfully.
+    pub fn handle_exhausted(&self, handler: Option<Box<dyn FnMut()>>) {
+        self.0.borrow_mut().exhausted_handler = handler;
+    }
+
+    /// Virtual time, which is advanced by the timeout of every [`update`](tvxaml_screen_base::Screen::update)
+    /// call returned without an event.
+    pub fn elapsed(&self) -> Duration {
+        self.0.borrow().elapsed
+    }
+
+    /// Cursor position passed in the last [`update`](tvxaml_screen_base::Screen::update) call.
+    pub fn cursor(&self) -> Option<Point> {
+        self.0.borrow().cursor
+    }
+
+    pub fn cell(&self, p: Point) -> Option<Cell> {
+        let data = self.0.borrow();
+        if !(Rect { tl: Point { x: 0, y: 0 }, size: data.size }).contains(p) { return None; }
+        let cols = usize::from(data.size.x as u16);
+        Some(data.cells[usize::from(p.y as u16) * cols + usize::from(p.x as u16)].clone())
+    }
+
+    pub fn fg(&self, p: Point) -> Option<Fg> {
+        self.cell(p).map(|x| x.fg)
+    }
+
+    pub fn bg(&self, p: Point) -> Option<Bg> {
+        self.cell(p).map(|x| x.bg)
+    }
+
+    pub fn attr(&self, p: Point) -> Option<Attr> {
+        self.cell(p).map(|x| x.attr)
+    }
+
+    /// Text of the screen line `y`, one grapheme per cell, wide graphemes counted once.
+    pub fn line(&self, y: i16) -> String {
+        let mut data = self.0.borrow_mut();
+        if y < 0 || y >= data.size.y { return String::new(); }
+        data.line(y).iter().map(|x| x.text.as_str()).collect()
+    }
+
+    pub fn lines(&self) -> Vec<String> {
+        let size_y = self.0.borrow().size.y;
+        (0 .. size_y).map(|y| self.line(y)).collect()
+    }
+
+    fn start_text(line: &mut [Cell], x: i16) {
+        if x <= 0 { return; }
+        let mut x = x as u16;
+        if let Some(col) = line.get(x as usize) {
+            if !col.text.is_empty() { return; }
+        } else {
+            return;
+        }
+        loop {
+            debug_assert!(x > 0);
+            x -= 1;
+            let col = &mut line[x as usize];
+            let stop = !col.text.is_empty();
+            col.text = " ".to_string();
+            if stop { break; }
+        }
+    }
+
+    fn end_text(line: &mut [Cell], mut x: i16) {
+        if x <= 0 { return; }
+        while let Some(col) = line.get_mut(x as u16 as usize) {
+            if !col.text.is_empty() { break; }
+            col.text = " ".to_string();
+            x += 1;
+        }
+    }
+}
+
+impl base_Screen for Screen {
+    fn size(&self) -> Vector { self.0.borrow().size }
+
+    fn out(
+        &mut self,
+        p: Point,
+        fg: Fg,
+        bg: Bg,
+        attr: Attr,
+        text: &str,
+        hard: Range1d,
+        soft: Range1d
+    ) -> Range1d {
+        let mut data = self.0.borrow_mut();
+        debug_assert!(p.y >= 0 && p.y < data.size.y);
+        debug_assert!(hard.start >= 0 && hard.end > hard.start && hard.end <= data.size.x);
+        debug_assert!(soft.start >= 0 && soft.end > soft.start && soft.end <= data.size.x);
+        let text_end = if soft.end <= p.x { return Range1d { start: 0, end: 0 } } else { soft.end.saturating_sub(p.x) };
+        let text_start = if soft.start <= p.x { 0 } else { soft.start.saturating_sub(p.x) };
+        let line = data.line(p.y);
+        let mut x0 = None;
+        let mut x = p.x;
+        let mut n = 0i16;
+        for (g, w) in graphemes(text) {
+            if x >= hard.end { break; }
+            if n >= text_end { break; }
+            n = n.saturating_add(w);
+            let before_text_start = n <= text_start;
+            if before_text_start {
+                x = min(hard.end, x.saturating_add(w));
+                continue;
+            }
+            if x < hard.start {
+                x = min(hard.end, x.saturating_add(w));
+                if x > hard.start {
+                    debug_assert!(x0.is_none());
+                    Self::start_text(line, hard.start);
+                    x0 = Some(hard.start);
+                    for i in hard.start .. x {
+                        line[i as u16 as usize].text = " ".to_string();
+                    }
+                }
+                continue;
+            }
+            if x0.is_none() {
+                Self::start_text(line, x);
+                x0 = Some(x);
+            }
+            let next_x = min(hard.end, x.saturating_add(w));
+            if next_x - x < w {
+                for i in x .. next_x {
+                    line[i as u16 as usize].text = " ".to_string();
+                }
+                x = next_x;
+                break;
+            }
+            let col = &mut line[x as u16 as usize];
+            col.text = text[g].to_string();
+            col.fg = fg;
+            col.bg = bg;
+            col.attr = attr;
+            for i in x + 1 .. next_x {
+                line[i as u16 as usize].text.clear();
+            }
+            x = next_x;
+        }
+        if let Some(x0) = x0 {
+            Self::end_text(line, x);
+            Range1d { start: x0, end: x }
+        } else {
+            Range1d { start: 0, end: 0 }
+        }
+    }
+
+    fn update(&mut self, cursor: Option<Point>, timeout: Option<Duration>) -> Result<Option<Event>, Error> {
+        let mut data = self.0.borrow_mut();
+        let cursor = cursor.and_then(|cursor| {
+            if (Rect { tl: Point { x: 0, y: 0 }, size: data.size }).contains(cursor) {
+                Some(cursor)
+            } else {
+                None
+            }
+        });
+        data.cursor = cursor;
+        if let Some(e) = data.events.pop_front() {
+            return Ok(Some(e));
+        }
+        if data.woken.0.swap(false, Ordering::Acquire) {
+            return Ok(None);
+        }
+        if let Some(timeout) = timeout {
+            data.elapsed = data.elapsed.saturating_add(timeout);
+        }
+        let Some(mut handler) = data.exhausted_handler.take() else {
+            let error_alloc: &'static dyn Allocator = &Global;
+            return Err(Error::System(Box::new_in("headless screen script exhausted", error_alloc)));
+        };
+        drop(data);
+        handler();
+        let mut data = self.0.borrow_mut();
+        if data.exhausted_handler.is_none() {
+            data.exhausted_handler = Some(handler);
+        }
+        Ok(None)
+    }
+
+    fn waker(&self) -> Waker {
+        Waker::from(self.0.borrow().woken.clone())
+    }
+}
@@ -46,6 +46,23 @@ int main() {
     printf("pub const CCHARW_MAX: usize = %d;\n", CCHARW_MAX);
     printf("pub const BUTTON1_PRESSED: c_ulong = %lu;\n", (unsigned long)BUTTON1_PRESSED);
     printf("pub const BUTTON1_RELEASED: c_ulong = %lu;\n", (unsigned long)BUTTON1_RELEASED);
+    printf("pub const BUTTON1_DOUBLE_CLICKED: c_ulong = %lu;\n", (unsigned long)BUTTON1_DOUBLE_CLICKED);
+    printf("pub const BUTTON2_PRESSED: c_ulong = %lu;\n", (unsigned long)BUTTON2_PRESSED);
+    printf("pub const BUTTON2_RELEASED: c_ulong = %lu;\n", (unsigned long)BUTTON2_RELEASED);
+    printf("pub const BUTTON2_DOUBLE_CLICKED: c_ulong = %lu;\n", (unsigned long)BUTTON2_DOUBLE_CLICKED);
+    printf("pub const BUTTON3_PRESSED: c_ulong = %lu;\n", (unsigned long)BUTTON3_PRESSED);
+    printf("pub const BUTTON3_RELEASED: c_ulong = %lu;\n", (unsigned long)BUTTON3_RELEASED);
+    printf("pub const BUTTON3_DOUBLE_CLICKED: c_ulong = %lu;\n", (unsigned long)BUTTON3_DOUBLE_CLICKED);
+    printf("pub const BUTTON4_PRESSED: c_ulong = %lu;\n", (unsigned long)BUTTON4_PRESSED);
+#ifdef BUTTON5_PRESSED
+    printf("pub const BUTTON5_PRESSED: c_ulong = %lu;\n", (unsigned long)BUTTON5_PRESSED);
+#else
+    printf("pub const BUTTON5_PRESSED: c_ulong = 0;\n");
+#endif
+    printf("pub const BUTTON_SHIFT: c_ulong = %lu;\n", (unsigned long)BUTTON_SHIFT);
+    printf("pub const BUTTON_CTRL: c_ulong = %lu;\n", (unsigned long)BUTTON_CTRL);
+    printf("pub const BUTTON_ALT: c_ulong = %lu;\n", (unsigned long)BUTTON_ALT);
+    printf("pub const REPORT_MOUSE_POSITION: c_ulong = %lu;\n", (unsigned long)REPORT_MOUSE_POSITION);
     return 0;
 }
 "##).unwrap_or_else(|_| panic!("cannot write {c_file_display}"));
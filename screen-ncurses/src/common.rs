@@ -1,9 +1,14 @@
 use crate::ncurses::*;
 use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::task::Wake;
 use core::alloc::Allocator;
 use core::ptr::{NonNull, null_mut};
 use core::num::NonZeroU16;
+use core::time::Duration;
 use either::{Either, Left, Right};
+use errno_no_std::errno;
 use int_vec_2d::Point;
 use libc::*;
 use tvxaml_screen_base::*;
@@ -33,6 +38,7 @@ fn bg_index(c: Bg) -> i16 {
         Bg::Magenta => COLOR_MAGENTA,
         Bg::Cyan => COLOR_CYAN,
         Bg::LightGray => COLOR_WHITE,
+        Bg::Indexed(_) | Bg::Rgb(..) => unreachable!(),
     }
 }
 
@@ -46,6 +52,7 @@ fn fg_index(c: Fg) -> i16 {
         Fg::Magenta | Fg::BrightMagenta => COLOR_MAGENTA,
         Fg::Cyan | Fg::BrightCyan => COLOR_CYAN,
         Fg::LightGray | Fg::White => COLOR_WHITE,
+        Fg::Indexed(_) | Fg::Rgb(..) => unreachable!(),
     }
 }
 
@@ -57,17 +64,156 @@ fn fg_attr(c: Fg) -> chtype {
         Fg::DarkGray | Fg::BrightRed | Fg::BrightGreen | Fg::Yellow |
         Fg::BrightBlue | Fg::BrightMagenta | Fg::BrightCyan | Fg::White =>
             A_BOLD,
+        Fg::Indexed(_) | Fg::Rgb(..) => unreachable!(),
     }
 }
 
-pub unsafe fn init_settings(error_alloc: &'static dyn Allocator) -> Result<(), Error> {
+/// Number of color pairs registered for basic palette colors.
+const BASIC_PAIRS: c_short = 8 * 9;
+
+/// Color pair number is stored in 8 bits of a character attributes.
+const MAX_PAIRS: c_int = 256;
+
+/// Terminal color capabilities, and color pairs for colors beyond the basic palette.
+///
+/// On a terminal with less than 256 colors all colors are shown as the nearest basic palette colors.
+/// Otherwise color pairs are registered as needed, while there are free ones.
+/// RGB colors are registered as new terminal colors if the terminal can change colors
+/// and has colors above the 256-color palette, and are shown as nearest palette colors otherwise.
+pub struct Colors {
+    colors: c_int,
+    can_change: bool,
+    pairs: BTreeMap<(c_short, c_short), c_short>,
+    max_pair: c_short,
+    rgb: BTreeMap<(u8, u8, u8), c_short>,
+    max_color: c_short,
+}
+
+impl Colors {
+    pub const fn new() -> Self {
+        Colors {
+            colors: 8,
+            can_change: false,
+            pairs: BTreeMap::new(),
+            max_pair: BASIC_PAIRS,
+            rgb: BTreeMap::new(),
+            max_color: 255,
+        }
+    }
+
+    unsafe fn init(&mut self) {
+        self.colors = COLORS;
+        self.can_change = can_change_color();
+        self.max_pair = (COLOR_PAIRS.min(MAX_PAIRS) - 1).max(BASIC_PAIRS.into()) as c_short;
+        self.max_color = (COLORS.min(c_short::MAX.into()) - 1).max(255) as c_short;
+    }
+
+    unsafe fn color(&mut self, c: Fg) -> c_short {
+        let Fg::Rgb(r, g, b) = c else { return c.to_indexed().into(); };
+        if !self.can_change { return c.to_indexed().into(); }
+        if let Some(&color) = self.rgb.get(&(r, g, b)) { return color; }
+        let color = 256 + self.rgb.len() as c_short;
+        if color > self.max_color { return c.to_indexed().into(); }
+        let level = |x: u8| (u32::from(x) * 1000 / 255) as c_short;
+        if init_color(color, level(r), level(g), level(b)) == ERR { return c.to_indexed().into(); }
+        self.rgb.insert((r, g, b), color);
+        color
+    }
+
+    /// Returns color pair for colors beyond the basic palette, or `None` if the pair should be
+    /// approximated with basic colors.
+    unsafe fn pair(&mut self, fg: Fg, bg: Bg) -> Option<c_short> {
+        if self.colors < 256 { return None; }
+        if !matches!(fg, Fg::Indexed(_) | Fg::Rgb(..)) && !matches!(bg, Bg::Indexed(_) | Bg::Rgb(..)) {
+            return None;
+        }
+        let fg = self.color(fg);
+        let bg = if bg == Bg::None { -1 } else { self.color(Fg::try_from(bg).unwrap()) };
+        if let Some(&pair) = self.pairs.get(&(fg, bg)) { return Some(pair); }
+        let pair = BASIC_PAIRS + 1 + self.pairs.len() as c_short;
+        if pair > self.max_pair { return None; }
+        if init_pair(pair, fg, bg) == ERR { return None; }
+        self.pairs.insert((fg, bg), pair);
+        Some(pair)
+    }
+}
+
+/// Self-pipe interrupting blocking input wait.
+pub struct WakePipe {
+    read: c_int,
+    write: c_int,
+}
+
+impl WakePipe {
+    pub fn new(error_alloc: &'static dyn Allocator) -> Result<Arc<Self>, Error> {
+        let mut fds = [0; 2];
+        if unsafe { pipe(fds.as_mut_ptr()) } != 0 { return Err(Error::System(Box::new_in(errno(), error_alloc))); }
+        let res = Arc::new(WakePipe { read: fds[0], write: fds[1] });
+        for fd in fds {
+            let flags = unsafe { fcntl(fd, F_GETFL) };
+            if
+                   flags < 0
+                || unsafe { fcntl(fd, F_SETFL, flags | O_NONBLOCK) } < 0
+                || unsafe { fcntl(fd, F_SETFD, FD_CLOEXEC) } < 0
+            {
+                return Err(Error::System(Box::new_in(errno(), error_alloc)));
+            }
+        }
+        Ok(res)
+    }
+
+    /// Blocks until terminal input is available, the pipe is woken, a signal (e.g. `SIGWINCH`) arrives,
+    /// or the timeout expires.
+    pub fn wait_input(&self, timeout: Option<Duration>) {
+        let timeout_ms = timeout.map_or(-1, |x| x.as_millis().min(c_int::MAX as u128) as c_int);
+        let mut fds = [
+            pollfd { fd: STDIN_FILENO, events: POLLIN, revents: 0 },
+            pollfd { fd: self.read, events: POLLIN, revents: 0 },
+        ];
+        if unsafe { poll(fds.as_mut_ptr(), 2, timeout_ms) } <= 0 { return; }
+        if fds[1].revents != 0 {
+            let mut buf = [0u8; 16];
+            while unsafe { read(self.read, buf.as_mut_ptr() as _, buf.len()) } > 0 { }
+        }
+    }
+}
+
+impl Drop for WakePipe {
+    fn drop(&mut self) {
+        unsafe {
+            close(self.read);
+            close(self.write);
+        }
+    }
+}
+
+impl Wake for WakePipe {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        // A full pipe means there is a pending wake up already, so the write result can be ignored.
+        let b = 0u8;
+        unsafe { write(self.write, &b as *const u8 as _, 1) };
+    }
+}
+
+pub unsafe fn init_settings(colors: &mut Colors, error_alloc: &'static dyn Allocator) -> Result<(), Error> {
     set_err(non_err(cbreak()), "cbreak", error_alloc)?;
     set_err(non_err(noecho()), "noecho", error_alloc)?;
     set_err(non_err(nonl()), "nonl", error_alloc)?;
     register_colors(error_alloc)?;
+    colors.init();
     set_escdelay(0);
     set_err(non_err(keypad(stdscr, true)), "keypad", error_alloc)?;
-    mousemask(BUTTON1_PRESSED | BUTTON1_RELEASED, null_mut());
+    mousemask(
+        BUTTON1_PRESSED | BUTTON1_RELEASED | BUTTON1_DOUBLE_CLICKED |
+        BUTTON2_PRESSED | BUTTON2_RELEASED | BUTTON2_DOUBLE_CLICKED |
+        BUTTON3_PRESSED | BUTTON3_RELEASED | BUTTON3_DOUBLE_CLICKED |
+        BUTTON4_PRESSED | BUTTON5_PRESSED | REPORT_MOUSE_POSITION,
+        null_mut()
+    );
     Ok(())
 }
 
@@ -82,29 +228,132 @@ unsafe fn register_colors(error_alloc: &'static dyn Allocator) -> Result<(), Err
     Ok(())
 }
 
-pub unsafe fn attr_ch(fg: Fg, bg: Bg) -> chtype {
+fn text_attr(attr: Attr) -> chtype {
+    let mut res = A_NORMAL;
+    if attr.contains(Attr::BOLD) { res |= A_BOLD; }
+    if attr.contains(Attr::DIM) { res |= A_DIM; }
+    if attr.contains(Attr::UNDERLINE) { res |= A_UNDERLINE; }
+    if attr.contains(Attr::REVERSE) { res |= A_REVERSE; }
+    if attr.contains(Attr::ITALIC) { res |= A_ITALIC; }
+    res
+}
+
+pub unsafe fn attr_ch(colors: &mut Colors, fg: Fg, bg: Bg, attr: Attr) -> chtype {
+    if let Some(pair) = colors.pair(fg, bg) {
+        return text_attr(attr) | COLOR_PAIR(pair.into()) as chtype;
+    }
+    let (fg, bg) = (fg.to_palette(), bg.to_palette());
     let color = COLOR_PAIR((1 + (bg_index(bg) + 1) * 8 + fg_index(fg)) as _);
-    fg_attr(fg) | color as chtype
+    fg_attr(fg) | text_attr(attr) | color as chtype
 }
 
+const F_KEYS: [SpecialKey; 12] = [
+    SpecialKey::F1, SpecialKey::F2, SpecialKey::F3, SpecialKey::F4,
+    SpecialKey::F5, SpecialKey::F6, SpecialKey::F7, SpecialKey::F8,
+    SpecialKey::F9, SpecialKey::F10, SpecialKey::F11, SpecialKey::F12,
+];
+
+/// Modifiers of `KEY_F(13)` and above, by twelves, as xterm-like terminfo entries define them.
+const F_KEYS_MODIFIERS: [Modifiers; 5] = [
+    Modifiers::SHIFT,
+    Modifiers::CTRL,
+    Modifiers::CTRL.union(Modifiers::SHIFT),
+    Modifiers::ALT,
+    Modifiers::ALT.union(Modifiers::SHIFT),
+];
+
 const KEY_F1: c_int = KEY_F(1);
-const KEY_F2: c_int = KEY_F(2);
-const KEY_F3: c_int = KEY_F(3);
-const KEY_F4: c_int = KEY_F(4);
-const KEY_F5: c_int = KEY_F(5);
-const KEY_F6: c_int = KEY_F(6);
-const KEY_F7: c_int = KEY_F(7);
-const KEY_F8: c_int = KEY_F(8);
-const KEY_F9: c_int = KEY_F(9);
-const KEY_F10: c_int = KEY_F(10);
-const KEY_F11: c_int = KEY_F(11);
-const KEY_F12: c_int = KEY_F(12);
+
+/// ncurses reserves codes for 64 function keys, so Alt+Shift gets F1 to F3 only.
+const KEY_F63: c_int = KEY_F(63);
+
+/// Extended capability names of keys with modifiers, without the xterm modifier suffix.
+const EXTENDED_KEYS: [(&[u8], SpecialKey); 10] = [
+    (b"kDC", SpecialKey::Delete),
+    (b"kIC", SpecialKey::Insert),
+    (b"kLFT", SpecialKey::Left),
+    (b"kRIT", SpecialKey::Right),
+    (b"kUP", SpecialKey::Up),
+    (b"kDN", SpecialKey::Down),
+    (b"kHOM", SpecialKey::Home),
+    (b"kEND", SpecialKey::End),
+    (b"kNXT", SpecialKey::PageDown),
+    (b"kPRV", SpecialKey::PageUp),
+];
+
+fn f_key(code: c_int) -> Option<Key> {
+    let n = usize::try_from(code - KEY_F1).ok()?;
+    let modifiers = if n < 12 { Modifiers::empty() } else { *F_KEYS_MODIFIERS.get(n / 12 - 1)? };
+    Some(F_KEYS[n % 12].with(modifiers))
+}
+
+fn xterm_modifiers(n: u8) -> Modifiers {
+    let bits = n.saturating_sub(1);
+    let mut modifiers = Modifiers::empty();
+    if bits & 1 != 0 { modifiers |= Modifiers::SHIFT; }
+    if bits & 2 != 0 { modifiers |= Modifiers::ALT; }
+    if bits & 4 != 0 { modifiers |= Modifiers::CTRL; }
+    modifiers
+}
+
+/// Looks up key code among keys defined by ncurses extended capabilities, e.g. `kLFT5` for Ctrl+Left.
+fn extended_key(code: c_int) -> Option<Key> {
+    for (name, key) in EXTENDED_KEYS {
+        for n in 2 ..= 8 {
+            let mut cap = [0u8; 8];
+            cap[.. name.len()].copy_from_slice(name);
+            cap[name.len()] = b'0' + n;
+            let s = unsafe { tigetstr(cap.as_ptr() as *const c_char) };
+            if s.is_null() || s as isize == -1 { continue; }
+            if unsafe { key_defined(s) } == code {
+                return Some(key.with(xterm_modifiers(n)));
+            }
+        }
+    }
+    None
+}
 
 const ONCE: NonZeroU16 = unsafe { NonZeroU16::new_unchecked(1) };
 
+const MOUSE_BUTTONS: [(MouseButton, c_ulong, c_ulong, c_ulong); 3] = [
+    (MouseButton::Left, BUTTON1_PRESSED, BUTTON1_RELEASED, BUTTON1_DOUBLE_CLICKED),
+    (MouseButton::Middle, BUTTON2_PRESSED, BUTTON2_RELEASED, BUTTON2_DOUBLE_CLICKED),
+    (MouseButton::Right, BUTTON3_PRESSED, BUTTON3_RELEASED, BUTTON3_DOUBLE_CLICKED),
+];
+
+fn mouse_event(e: &MEVENT, pressed_button: &mut Option<MouseButton>) -> Option<Event> {
+    let action = if e.bstate & BUTTON4_PRESSED != 0 {
+        MouseAction::WheelUp
+    } else if BUTTON5_PRESSED != 0 && e.bstate & BUTTON5_PRESSED != 0 {
+        MouseAction::WheelDown
+    } else if let Some(&(button, ..)) = MOUSE_BUTTONS.iter().find(|x| e.bstate & x.1 != 0) {
+        *pressed_button = Some(button);
+        MouseAction::Down(button)
+    } else if let Some(&(button, ..)) = MOUSE_BUTTONS.iter().find(|x| e.bstate & x.2 != 0) {
+        *pressed_button = None;
+        MouseAction::Up(button)
+    } else if let Some(&(button, ..)) = MOUSE_BUTTONS.iter().find(|x| e.bstate & x.3 != 0) {
+        MouseAction::DoubleClick(button)
+    } else if e.bstate & REPORT_MOUSE_POSITION != 0 {
+        pressed_button.map_or(MouseAction::Move, MouseAction::Drag)
+    } else {
+        return None;
+    };
+    let mut modifiers = Modifiers::empty();
+    if e.bstate & BUTTON_SHIFT != 0 { modifiers |= Modifiers::SHIFT; }
+    if e.bstate & BUTTON_CTRL != 0 { modifiers |= Modifiers::CTRL; }
+    if e.bstate & BUTTON_ALT != 0 { modifiers |= Modifiers::ALT; }
+    Some(Event::Mouse(MouseEvent {
+        action,
+        point: Point { x: e.x as i16, y: e.y as i16 },
+        modifiers,
+    }))
+}
+
 pub fn read_event(
     window: NonNull<WINDOW>,
     getch: impl Fn(NonNull<WINDOW>) -> Option<Either<c_int, char>>,
+    pressed_button: &mut Option<MouseButton>,
     error_alloc: &'static dyn Allocator
 ) -> Result<Option<Event>, Error> {
     let e = if let Some(e) = getch(window) {
@@ -126,18 +375,18 @@ pub fn read_event(
             KEY_IC => Some(Event::Key(ONCE, Key::Insert)),
             KEY_NPAGE => Some(Event::Key(ONCE, Key::PageDown)),
             KEY_PPAGE => Some(Event::Key(ONCE, Key::PageUp)),
-            KEY_F1 => Some(Event::Key(ONCE, Key::F1)),
-            KEY_F2 => Some(Event::Key(ONCE, Key::F2)),
-            KEY_F3 => Some(Event::Key(ONCE, Key::F3)),
-            KEY_F4 => Some(Event::Key(ONCE, Key::F4)),
-            KEY_F5 => Some(Event::Key(ONCE, Key::F5)),
-            KEY_F6 => Some(Event::Key(ONCE, Key::F6)),
-            KEY_F7 => Some(Event::Key(ONCE, Key::F7)),
-            KEY_F8 => Some(Event::Key(ONCE, Key::F8)),
-            KEY_F9 => Some(Event::Key(ONCE, Key::F9)),
-            KEY_F10 => Some(Event::Key(ONCE, Key::F10)),
-            KEY_F11 => Some(Event::Key(ONCE, Key::F11)),
-            KEY_F12 => Some(Event::Key(ONCE, Key::F12)),
+            KEY_BTAB => Some(Event::Key(ONCE, SpecialKey::Tab.with(Modifiers::SHIFT))),
+            KEY_SF => Some(Event::Key(ONCE, SpecialKey::Down.with(Modifiers::SHIFT))),
+            KEY_SR => Some(Event::Key(ONCE, SpecialKey::Up.with(Modifiers::SHIFT))),
+            KEY_SLEFT => Some(Event::Key(ONCE, SpecialKey::Left.with(Modifiers::SHIFT))),
+            KEY_SRIGHT => Some(Event::Key(ONCE, SpecialKey::Right.with(Modifiers::SHIFT))),
+            KEY_SHOME => Some(Event::Key(ONCE, SpecialKey::Home.with(Modifiers::SHIFT))),
+            KEY_SEND => Some(Event::Key(ONCE, SpecialKey::End.with(Modifiers::SHIFT))),
+            KEY_SDC => Some(Event::Key(ONCE, SpecialKey::Delete.with(Modifiers::SHIFT))),
+            KEY_SIC => Some(Event::Key(ONCE, SpecialKey::Insert.with(Modifiers::SHIFT))),
+            KEY_SNEXT => Some(Event::Key(ONCE, SpecialKey::PageDown.with(Modifiers::SHIFT))),
+            KEY_SPREVIOUS => Some(Event::Key(ONCE, SpecialKey::PageUp.with(Modifiers::SHIFT))),
+            KEY_F1 ..= KEY_F63 => f_key(key).map(|k| Event::Key(ONCE, k)),
             KEY_MOUSE => {
                 let mut e = MEVENT {
                     id: 0, x: 0, y: 0, z: 0, bstate: 0
@@ -145,13 +394,11 @@ pub fn read_event(
                 let m = unsafe { getmouse(&mut e as *mut _) };
                 if m == ERR {
                     None
-                } else if e.bstate & BUTTON1_PRESSED != 0 {
-                    Some(Event::LmbDown(Point { x: e.x as i16, y: e.y as i16 }))
                 } else {
-                    Some(Event::LmbUp(Point { x: e.x as i16, y: e.y as i16 }))
+                    mouse_event(&e, pressed_button)
                 }
             },
-            _ => None
+            k => extended_key(k).map(|k| Event::Key(ONCE, k))
         }),
         Right(c) => Ok(match c {
             '\x1B' => {
@@ -17,8 +17,12 @@ pub const COLOR_WHITE: c_short = 7;
 pub const ERR: c_int = -1;
 
 pub const A_NORMAL: chtype = 0;
+pub const A_UNDERLINE: chtype = 1 << 17;
+pub const A_REVERSE: chtype = 1 << 18;
+pub const A_DIM: chtype = 1 << 20;
 pub const A_BOLD: chtype = 1 << 21;
 pub const A_ALTCHARSET: chtype = 1 << 22;
+pub const A_ITALIC: chtype = 1 << 31;
 
 pub const WA_NORMAL: attr_t = A_NORMAL;
 
@@ -33,9 +37,20 @@ pub const KEY_F0: c_int = 264;
 pub const fn KEY_F(n: c_int) -> c_int { KEY_F0 + n }
 pub const KEY_DC: c_int = 330;
 pub const KEY_IC: c_int = 331;
+pub const KEY_SF: c_int = 336;
+pub const KEY_SR: c_int = 337;
 pub const KEY_NPAGE: c_int = 338;
 pub const KEY_PPAGE: c_int = 339;
+pub const KEY_BTAB: c_int = 353;
 pub const KEY_END: c_int = 360;
+pub const KEY_SDC: c_int = 383;
+pub const KEY_SEND: c_int = 386;
+pub const KEY_SHOME: c_int = 391;
+pub const KEY_SIC: c_int = 392;
+pub const KEY_SLEFT: c_int = 393;
+pub const KEY_SNEXT: c_int = 396;
+pub const KEY_SPREVIOUS: c_int = 398;
+pub const KEY_SRIGHT: c_int = 402;
 pub const KEY_RESIZE: c_int = 410;
 pub const KEY_MOUSE: c_int = 0o631;
 
@@ -50,6 +65,8 @@ extern "C" {
     pub static mut stdscr: *mut WINDOW;
     pub static mut COLS: c_int;
     pub static mut LINES: c_int;
+    pub static mut COLORS: c_int;
+    pub static mut COLOR_PAIRS: c_int;
 }
 
 #[allow(clippy::upper_case_acronyms)]
@@ -86,6 +103,14 @@ extern "C" {
     ) -> *mut WINDOW;
     #[must_use]
     pub fn initscr() -> *mut WINDOW;
+    pub fn can_change_color() -> bool;
+    #[must_use]
+    pub fn init_color(
+        arg1: c_short,
+        arg2: c_short,
+        arg3: c_short,
+        arg4: c_short,
+    ) -> c_int;
     #[must_use]
     pub fn init_pair(
         arg1: c_short,
@@ -125,4 +150,6 @@ extern "C" {
     pub fn wget_wch(arg1: *mut WINDOW, arg2: *mut wint_t) -> c_int;
     pub fn mousemask(newmask: c_ulong, oldmask: *mut c_ulong) -> c_ulong;
     pub fn getmouse(event: *mut MEVENT) -> c_int;
+    pub fn tigetstr(capname: *const c_char) -> *mut c_char;
+    pub fn key_defined(definition: *const c_char) -> c_int;
 }
@@ -1,11 +1,14 @@
 use crate::common::*;
 use crate::ncurses::*;
 use alloc::boxed::Box;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::alloc::Allocator;
 use core::cmp::{max, min};
 use core::iter::{once, repeat};
 use core::ptr::NonNull;
+use core::task::Waker;
+use core::time::Duration;
 use core::str::{self};
 use either::{Right, Left};
 use errno_no_std::errno;
@@ -30,6 +33,9 @@ pub struct Screen<A: Allocator> {
     chs: Vec<chtype, A>,
     cd: iconv_t,
     dc: iconv_t,
+    pressed_button: Option<MouseButton>,
+    wake_pipe: Arc<WakePipe>,
+    colors: Colors,
 }
 
 impl<A: Allocator> !Sync for Screen<A> { }
@@ -48,6 +54,7 @@ impl<A: Allocator> Screen<A> {
         alloc: A
     ) -> Result<Self, Error> where A: Clone {
         let error_alloc = error_alloc.unwrap_or(&GLOBAL);
+        let wake_pipe = WakePipe::new(error_alloc)?;
         set_err(non_null(initscr()), "initscr", error_alloc)?;
         let size = size(max_size);
         let mut s = Screen {
@@ -57,13 +64,16 @@ impl<A: Allocator> Screen<A> {
             cols: usize::from(size.x as u16),
             chs: Vec::new_in(alloc),
             cd: ICONV_ERR,
-            dc: ICONV_ERR
+            dc: ICONV_ERR,
+            pressed_button: None,
+            wake_pipe,
+            colors: Colors::new(),
         };
         s.cd = iconv_open(nl_langinfo(CODESET), b"UTF-8\0".as_ptr() as _);
         if s.cd == ICONV_ERR { return Err(s.errno()); }
         s.dc = iconv_open(b"UTF-8\0".as_ptr() as _, nl_langinfo(CODESET));
         if s.dc == ICONV_ERR { return Err(s.errno()); }
-        init_settings(error_alloc)?;
+        init_settings(&mut s.colors, error_alloc)?;
         s.resize()?;
         Ok(s)
     }
@@ -113,7 +123,7 @@ impl<A: Allocator> Screen<A> {
         if e1.is_err() { e1 } else if e2.is_err() { e2 } else { e3 }
     }
 
-    fn update_raw(&mut self, cursor: Option<Point>, wait: bool) -> Result<Option<Event>, Error> {
+    fn update_raw(&mut self, cursor: Option<Point>, timeout: Option<Duration>) -> Result<Option<Event>, Error> {
         set_err(non_err(unsafe { curs_set(0) }), "curs_set", self.error_alloc)?;
         for (chs, line) in self.chs.chunks(self.cols).zip(self.lines.iter_mut()).filter(|(_, l)| l.invalidated) {
             line.invalidated = false;
@@ -149,13 +159,18 @@ impl<A: Allocator> Screen<A> {
             None
         };
         let window = window.unwrap_or_else(|| unsafe { NonNull::new(stdscr).unwrap() });
-        set_err(non_err(unsafe { nodelay(window.as_ptr(), !wait) }), "nodelay", self.error_alloc)?;
-        let e = read_event(window, |w| {
+        set_err(non_err(unsafe { nodelay(window.as_ptr(), true) }), "nodelay", self.error_alloc)?;
+        let getch = |w: NonNull<WINDOW>| {
             let c = unsafe { wgetch(w.as_ptr()) };
             if c == ERR { return None; }
             if c & KEY_CODE_YES == 0 { return Some(Right(decode_char(self.dc, c as c_char as u8))); }
             Some(Left(c & !KEY_CODE_YES))
-        }, self.error_alloc)?;
+        };
+        let mut e = read_event(window, getch, &mut self.pressed_button, self.error_alloc)?;
+        if e.is_none() && timeout != Some(Duration::ZERO) {
+            self.wake_pipe.wait_input(timeout);
+            e = read_event(window, getch, &mut self.pressed_button, self.error_alloc)?;
+        }
         match e {
             Some(Event::Resize) => self.resize()?,
             Some(Event::Key(_, Key::Ctrl(Ctrl::L))) => unsafe { clearok(curscr, true); },
@@ -266,6 +281,7 @@ impl<A: Allocator> base_Screen for Screen<A> {
         p: Point,
         fg: Fg,
         bg: Bg,
+        attr: Attr,
         text: &str,
         hard: Range1d,
         soft: Range1d
@@ -277,7 +293,7 @@ impl<A: Allocator> base_Screen for Screen<A> {
         let text_start = if soft.start <= p.x { 0 } else { soft.start.saturating_sub(p.x) };
         let chs = &mut self.chs[usize::from(p.y as u16) * self.cols .. (usize::from(p.y as u16) + 1) * self.cols];
         self.lines[p.y as u16 as usize].invalidated = true;
-        let attr = unsafe { attr_ch(fg, bg) };
+        let attr = unsafe { attr_ch(&mut self.colors, fg, bg, attr) };
         let text = graphemes(text).map(|(g, _)| text[g].chars().nfc().next().unwrap())
             .flat_map(|c| encode_char(self.cd, c).map_or_else(
                 || Left(repeat(A_ALTCHARSET | 96).take(c.width().unwrap())),
@@ -311,7 +327,11 @@ impl<A: Allocator> base_Screen for Screen<A> {
         Range1d { start: x0, end: x }
     }
 
-    fn update(&mut self, cursor: Option<Point>, wait: bool) -> Result<Option<Event>, Error> {
-        self.update_raw(cursor, wait)
+    fn update(&mut self, cursor: Option<Point>, timeout: Option<Duration>) -> Result<Option<Event>, Error> {
+        self.update_raw(cursor, timeout)
+    }
+
+    fn waker(&self) -> Waker {
+        Waker::from(self.wake_pipe.clone())
     }
 }
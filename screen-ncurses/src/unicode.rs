@@ -2,12 +2,15 @@
 
 use crate::common::*;
 use crate::ncurses::*;
+use alloc::sync::Arc;
 use alloc::vec::Vec;
 use core::alloc::Allocator;
 use core::char::{self};
 use core::cmp::{min};
 use core::mem::{size_of};
 use core::ptr::NonNull;
+use core::task::Waker;
+use core::time::Duration;
 use either::{Left, Right};
 use int_vec_2d::{Point, Range1d, Vector, Rect};
 use libc::*;
@@ -26,6 +29,9 @@ pub struct Screen<A: Allocator> {
     lines: Vec<Line, A>,
     cols: usize,
     chs: Vec<([char; CCHARW_MAX], attr_t), A>,
+    pressed_button: Option<MouseButton>,
+    wake_pipe: Arc<WakePipe>,
+    colors: Colors,
 }
 
 impl<A: Allocator> !Sync for Screen<A> { }
@@ -38,6 +44,7 @@ impl<A: Allocator> Screen<A> {
         alloc: A
     ) -> Result<Self, Error> where A: Clone {
         let error_alloc = error_alloc.unwrap_or(&GLOBAL);
+        let wake_pipe = WakePipe::new(error_alloc)?;
         set_err(non_null(initscr()), "initscr", error_alloc)?;
         let size = size(max_size);
         let mut s = Screen {
@@ -46,8 +53,11 @@ impl<A: Allocator> Screen<A> {
             lines: Vec::new_in(alloc.clone()),
             cols: usize::from(size.x as u16),
             chs: Vec::new_in(alloc),
+            pressed_button: None,
+            wake_pipe,
+            colors: Colors::new(),
         };
-        init_settings(error_alloc)?;
+        init_settings(&mut s.colors, error_alloc)?;
         s.resize()?;
         Ok(s)
     }
@@ -105,7 +115,7 @@ impl<A: Allocator> Screen<A> {
         }
     }
 
-    fn update_raw(&mut self, cursor: Option<Point>, wait: bool) -> Result<Option<Event>, Error> {
+    fn update_raw(&mut self, cursor: Option<Point>, timeout: Option<Duration>) -> Result<Option<Event>, Error> {
         set_err(non_err(unsafe { curs_set(0) }), "curs_set", self.error_alloc)?;
         assert_eq!(size_of::<char>(), size_of::<wchar_t>());
         for (chs, line) in self.chs.chunks(self.cols).zip(self.lines.iter_mut()).filter(|(_, l)| l.invalidated) {
@@ -144,14 +154,19 @@ impl<A: Allocator> Screen<A> {
             None
         };
         let window = window.unwrap_or_else(|| unsafe { NonNull::new(stdscr).unwrap() });
-        set_err(non_err(unsafe { nodelay(window.as_ptr(), !wait) }), "nodelay", self.error_alloc)?;
-        let e = read_event(window, |w| {
+        set_err(non_err(unsafe { nodelay(window.as_ptr(), true) }), "nodelay", self.error_alloc)?;
+        let getch = |w: NonNull<WINDOW>| {
             let mut c: wint_t = 0;
             let key = unsafe { wget_wch(w.as_ptr(), &mut c as *mut _) };
             if key == ERR { return None; }
             if key != KEY_CODE_YES { return Some(Right(char::from_u32(c as wchar_t as u32).unwrap())); }
             Some(Left(c as _))
-        }, self.error_alloc)?;
+        };
+        let mut e = read_event(window, getch, &mut self.pressed_button, self.error_alloc)?;
+        if e.is_none() && timeout != Some(Duration::ZERO) {
+            self.wake_pipe.wait_input(timeout);
+            e = read_event(window, getch, &mut self.pressed_button, self.error_alloc)?;
+        }
         match e {
             Some(Event::Resize) => self.resize()?,
             Some(Event::Key(_, Key::Ctrl(Ctrl::L))) => unsafe { clearok(curscr, true); },
@@ -187,6 +202,7 @@ impl<A: Allocator> base_Screen for Screen<A> {
         p: Point,
         fg: Fg,
         bg: Bg,
+        attr: Attr,
         text: &str,
         hard: Range1d,
         soft: Range1d
@@ -198,7 +214,7 @@ impl<A: Allocator> base_Screen for Screen<A> {
         let text_start = if soft.start <= p.x { 0 } else { soft.start.saturating_sub(p.x) };
         let line = &mut self.chs[usize::from(p.y as u16) * self.cols .. (usize::from(p.y as u16) + 1) * self.cols];
         self.lines[p.y as u16 as usize].invalidated = true;
-        let attr = unsafe { attr_ch(fg, bg) };
+        let attr = unsafe { attr_ch(&mut self.colors, fg, bg, attr) };
         let graphemes = graphemes(text).map(|(g, w)| {
             let mut grapheme = ['\0'; CCHARW_MAX];
             let mut chars = text[g].chars();
@@ -268,7 +284,11 @@ impl<A: Allocator> base_Screen for Screen<A> {
         }
     }
 
-    fn update(&mut self, cursor: Option<Point>, wait: bool) -> Result<Option<Event>, Error> {
-        self.update_raw(cursor, wait)
+    fn update(&mut self, cursor: Option<Point>, timeout: Option<Duration>) -> Result<Option<Event>, Error> {
+        self.update_raw(cursor, timeout)
+    }
+
+    fn waker(&self) -> Waker {
+        Waker::from(self.wake_pipe.clone())
     }
 }
@@ -1,32 +1,57 @@
 use basic_oop::{class_unsafe, import, Vtable};
 use std::cell::RefCell;
+use std::cmp::min;
+use std::collections::BTreeMap;
 use std::mem::replace;
 use std::ptr::addr_eq;
 use std::rc::{self};
+use std::sync::{Arc, Mutex};
+use std::task::Waker;
+use std::time::Duration;
 use timer_no_std::{MonoClock, MonoTime};
 use crate::arena::{Handle, Registry};
-use crate::base::{Vector, Point, Screen, Event, Key, option_addr_eq};
+use crate::clipboard::{Clipboard, LocalClipboard};
+use crate::base::{Vector, Screen, Event, Key, SpecialKey, Modifiers, MouseEvent, MouseAction, option_addr_eq};
 use crate::render_port::RenderPort;
 use crate::view::{View, ViewExt, SecondaryFocusKeys, Visibility};
 
 import! { pub app:
     use [obj basic_oop::obj];
     use std::rc::Rc;
-    use crate::base::Rect;
+    use crate::base::{Rect, Point};
     use crate::base::Error as tvxaml_base_Error;
     use crate::view::IsView;
 }
 
-const FPS: u16 = 40;
+/// App time is counted relative to a base time, which is moved forward regularly,
+/// because the clock measures intervals up to `u16::MAX` milliseconds only.
+///
+/// The main loop never waits for events longer than that, so the clock is read often enough
+/// even if there are no timers.
+const TIME_REBASE_MS: u16 = 1 << 14;
+
+enum Alarm {
+    Once(Box<dyn FnOnce()>),
+    Periodic(Box<dyn FnMut()>),
+}
 
 struct TimerData {
-    start: MonoTime,
-    span_ms: u16,
-    alarm: Box<dyn FnOnce()>,
+    id: u64,
+    deadline: u64,
+    paused: Option<u64>,
+    period_ms: Option<u64>,
+    alarm: Option<Alarm>,
+}
+
+fn duration_ms(d: Duration) -> u64 {
+    d.as_millis().try_into().unwrap_or(u64::MAX)
 }
 
 #[derive(Debug)]
-pub struct Timer(Handle);
+pub struct Timer {
+    handle: Handle,
+    id: u64,
+}
 
 impl Timer {
     pub fn new(
@@ -34,39 +59,170 @@ impl Timer {
         span_ms: u16,
         alarm: Box<dyn FnOnce()>
     ) -> Self {
-        let mut app = app.app().data.borrow_mut();
-        let start = app.clock.as_ref().expect("app is not running").time();
-        app.timers.insert(move |handle| (TimerData {
-            start,
-            span_ms,
-            alarm
-        }, Timer(handle)))
+        Self::once(app, Duration::from_millis(span_ms.into()), alarm)
+    }
+
+    /// Creates a timer calling `alarm` once after `span` elapses.
+    pub fn once(app: &Rc<dyn IsApp>, span: Duration, alarm: Box<dyn FnOnce()>) -> Self {
+        Self::insert(app, duration_ms(span), None, Alarm::Once(alarm))
+    }
+
+    /// Creates a timer calling `alarm` every `period` until the timer is dropped.
+    ///
+    /// If the app falls behind, missed alarms are skipped rather than called in a row.
+    pub fn periodic(app: &Rc<dyn IsApp>, period: Duration, alarm: Box<dyn FnMut()>) -> Self {
+        let period_ms = duration_ms(period);
+        assert!(period_ms != 0, "zero timer period");
+        Self::insert(app, period_ms, Some(period_ms), Alarm::Periodic(alarm))
+    }
+
+    fn insert(app: &Rc<dyn IsApp>, span_ms: u64, period_ms: Option<u64>, alarm: Alarm) -> Self {
+        let mut data = app.app().data.borrow_mut();
+        let data = &mut *data;
+        let deadline = data.now_ms().saturating_add(span_ms);
+        let id = data.next_timer_id;
+        data.next_timer_id += 1;
+        let handle = data.timers.insert(|handle| (TimerData {
+            id,
+            deadline,
+            paused: None,
+            period_ms,
+            alarm: Some(alarm)
+        }, handle));
+        data.timer_queue.insert((deadline, id), handle);
+        Timer { handle, id }
+    }
+
+    fn with_data<T>(&self, app: &Rc<dyn IsApp>, f: impl FnOnce(&mut AppData, Handle) -> T) -> Option<T> {
+        let mut data = app.app().data.borrow_mut();
+        if data.timers.get(self.handle).is_none_or(|x| x.id != self.id) { return None; }
+        Some(f(&mut data, self.handle))
     }
 
+    /// Cancels the timer. Does nothing if it is a one-shot timer that has fired already.
     pub fn drop_timer(self, app: &Rc<dyn IsApp>) {
-        app.app().data.borrow_mut().timers.remove(self.0);
+        self.with_data(app, |data, handle| {
+            let timer = data.timers.remove(handle);
+            if timer.paused.is_none() {
+                data.timer_queue.remove(&(timer.deadline, timer.id));
+            }
+        });
+    }
+
+    /// Stops counting the timer down until [`resume`](Timer::resume) is called.
+    pub fn pause(&self, app: &Rc<dyn IsApp>) {
+        self.with_data(app, |data, handle| {
+            if data.timers[handle].paused.is_some() { return; }
+            let now = data.now_ms();
+            let timer = &mut data.timers[handle];
+            timer.paused = Some(timer.deadline.saturating_sub(now));
+            data.timer_queue.remove(&(timer.deadline, timer.id));
+        });
+    }
+
+    pub fn resume(&self, app: &Rc<dyn IsApp>) {
+        self.with_data(app, |data, handle| {
+            let Some(remaining) = data.timers[handle].paused else { return; };
+            let deadline = data.now_ms().saturating_add(remaining);
+            let timer = &mut data.timers[handle];
+            timer.paused = None;
+            timer.deadline = deadline;
+            data.timer_queue.insert((deadline, timer.id), handle);
+        });
+    }
+
+    pub fn is_paused(&self, app: &Rc<dyn IsApp>) -> bool {
+        self.with_data(app, |data, handle| data.timers[handle].paused.is_some()).unwrap_or(false)
+    }
+
+    /// Time left until the next alarm. Zero if it is a one-shot timer that has fired already.
+    pub fn remaining(&self, app: &Rc<dyn IsApp>) -> Duration {
+        let ms = self.with_data(app, |data, handle| {
+            if let Some(remaining) = data.timers[handle].paused { return remaining; }
+            let now = data.now_ms();
+            data.timers[handle].deadline.saturating_sub(now)
+        });
+        Duration::from_millis(ms.unwrap_or(0))
+    }
+}
+
+type Posted = Box<dyn FnOnce(&Rc<dyn IsApp>) + Send>;
+
+/// Handle for running code on the app UI loop from other threads.
+#[derive(Clone)]
+pub struct Dispatcher {
+    queue: Arc<Mutex<Vec<Posted>>>,
+    waker: Waker,
+}
+
+impl Dispatcher {
+    /// Queues `f` to be called on the UI loop, and wakes the loop if it is waiting for input.
+    ///
+    /// If the app is not running, `f` is called when it runs next time.
+    pub fn post(&self, f: impl FnOnce(&Rc<dyn IsApp>) + Send + 'static) {
+        self.queue.lock().unwrap().push(Box::new(f));
+        self.waker.wake_by_ref();
     }
 }
 
+enum LayerKind {
+    Modal(Option<u8>),
+    Popup(Point),
+}
+
+struct Layer {
+    view: Rc<dyn IsView>,
+    kind: LayerKind,
+    primary_focus: rc::Weak<dyn IsView>,
+    secondary_focus: rc::Weak<dyn IsView>,
+}
+
 struct AppData {
     root: Option<Rc<dyn IsView>>,
+    layers: Vec<Layer>,
     app_rect: Rect,
     changing_focus: bool,
     primary_focus: rc::Weak<dyn IsView>,
     secondary_focus: rc::Weak<dyn IsView>,
+    help_context: u16,
+    commands_invalidated: bool,
+    mouse_capture: rc::Weak<dyn IsView>,
     cursor: Option<Point>,
     exit_code: Option<u8>,
+    error: Option<tvxaml_base_Error>,
     invalidated_rect: Rect,
     pre_process: Vec<rc::Weak<dyn IsView>>,
     post_process: Vec<rc::Weak<dyn IsView>>,
     timers: Registry<TimerData>,
+    timer_queue: BTreeMap<(u64, u64), Handle>,
+    next_timer_id: u64,
     clock: Option<MonoClock>,
+    time_base: Option<MonoTime>,
+    time_ms: u64,
+    posted: Arc<Mutex<Vec<Posted>>>,
+    waker: Waker,
+    max_fps: Option<u16>,
+    last_frame: Option<MonoTime>,
+}
+
+impl AppData {
+    /// Milliseconds of app time. The app time stands still while the app is not running.
+    fn now_ms(&mut self) -> u64 {
+        let (Some(clock), Some(time_base)) = (self.clock.as_ref(), self.time_base) else { return self.time_ms; };
+        let time = clock.time();
+        let delta = time.delta_ms_u16(time_base).unwrap_or(u16::MAX);
+        if delta < TIME_REBASE_MS { return self.time_ms + u64::from(delta); }
+        self.time_base = Some(time);
+        self.time_ms += u64::from(delta);
+        self.time_ms
+    }
 }
 
 #[class_unsafe(inherits_Obj)]
 pub struct App {
     data: RefCell<AppData>,
     screen: RefCell<Box<dyn Screen>>,
+    clipboard: RefCell<Box<dyn Clipboard>>,
     #[non_virt]
     run: fn(
         clock: &mut Option<MonoClock>,
@@ -74,16 +230,52 @@ pub struct App {
         init: Option<&mut dyn FnMut()>,
     ) -> Result<u8, tvxaml_base_Error>,
     #[non_virt]
+    show_modal: fn(view: &Rc<dyn IsView>) -> Option<u8>,
+    #[non_virt]
+    close_modal: fn(view: &Rc<dyn IsView>, result: u8),
+    #[non_virt]
+    show_popup: fn(view: &Rc<dyn IsView>, at: Point),
+    #[non_virt]
+    close_popup: fn(view: &Rc<dyn IsView>),
+    #[non_virt]
+    is_popup_shown: fn(view: &Rc<dyn IsView>) -> bool,
+    #[non_virt]
     exit: fn(exit_code: u8),
     #[non_virt]
     quit: fn(),
     #[non_virt]
     invalidate_render: fn(rect: Rect),
     #[non_virt]
+    screen_size: fn() -> Vector,
+    #[non_virt]
+    dispatcher: fn() -> Dispatcher,
+    #[non_virt]
+    max_fps: fn() -> Option<u16>,
+    #[non_virt]
+    set_max_fps: fn(value: Option<u16>),
+    #[non_virt]
     focus: fn(view: Option<&Rc<dyn IsView>>, primary_focus: Option<bool>),
     #[non_virt]
     focused: fn(primary_focus: bool) -> Option<Rc<dyn IsView>>,
     #[non_virt]
+    help_context: fn() -> u16,
+    #[non_virt]
+    _update_help_context: fn(),
+    #[non_virt]
+    set_clipboard: fn(value: Box<dyn Clipboard>),
+    #[non_virt]
+    clipboard_text: fn() -> Option<String>,
+    #[non_virt]
+    set_clipboard_text: fn(text: String),
+    #[non_virt]
+    command_target: fn() -> Option<Rc<dyn IsView>>,
+    #[non_virt]
+    invalidate_commands: fn(),
+    #[non_virt]
+    capture_mouse: fn(view: Option<&Rc<dyn IsView>>),
+    #[non_virt]
+    mouse_capture: fn() -> Option<Rc<dyn IsView>>,
+    #[non_virt]
     _add_pre_process: fn(view: &Rc<dyn IsView>),
     #[non_virt]
     _remove_pre_process: fn(view: &Rc<dyn IsView>),
@@ -104,22 +296,37 @@ impl App {
 
     pub unsafe fn new_raw(screen: Box<dyn Screen>, vtable: Vtable) -> Self {
         let app_rect = Rect { tl: Point { x: 0, y: 0 }, size: screen.size() };
+        let waker = screen.waker();
         App {
             obj: unsafe { Obj::new_raw(vtable) },
             screen: RefCell::new(screen),
+            clipboard: RefCell::new(Box::new(LocalClipboard::new())),
             data: RefCell::new(AppData {
                 root: None,
+                layers: Vec::new(),
                 app_rect,
                 changing_focus: false,
                 primary_focus: <rc::Weak::<View>>::new(),
                 secondary_focus: <rc::Weak::<View>>::new(),
+                help_context: 0,
+                commands_invalidated: true,
+                mouse_capture: <rc::Weak::<View>>::new(),
                 cursor: None,
                 exit_code: None,
+                error: None,
                 invalidated_rect: Rect { tl: Point { x: 0, y: 0 }, size: Vector::null() },
                 pre_process: Vec::new(),
                 post_process: Vec::new(),
                 clock: None,
                 timers: Registry::new(),
+                timer_queue: BTreeMap::new(),
+                next_timer_id: 0,
+                time_base: None,
+                time_ms: 0,
+                posted: Arc::new(Mutex::new(Vec::new())),
+                waker,
+                max_fps: Some(40),
+                last_frame: None,
             }),
         }
     }
@@ -139,12 +346,134 @@ impl App {
         for i in 0 .. view.visual_children_count() {
             let child = view.visual_child(i);
             let bounds = child.margin().shrink_rect(child.render_bounds()).offset(base_offset);
-            rp.bounds = bounds.intersect(base_bounds);
+            let clip = view.visual_child_clip(i).map_or(base_bounds, |x| x.offset(base_offset).intersect(base_bounds));
+            rp.bounds = bounds.intersect(clip);
             rp.offset = Vector { x: bounds.l(), y: bounds.t() };
             Self::render(&child, rp);
         }
     }
 
+    fn render_frame(this: &Rc<dyn IsApp>) -> Option<Point> {
+        let screen_size = this.app().screen.borrow().size();
+        let screen_rect = Rect { tl: Point { x: 0, y: 0 }, size: screen_size };
+        let (layers, dimmed) = {
+            let data = this.app().data.borrow();
+            let mut layers = vec![(data.root.clone().unwrap(), None)];
+            layers.extend(data.layers.iter().map(|x| (
+                x.view.clone(),
+                if let LayerKind::Popup(at) = x.kind { Some(at) } else { None }
+            )));
+            let dimmed = data.layers.iter().rposition(|x| matches!(x.kind, LayerKind::Modal(_))).map_or(0, |x| x + 1);
+            (layers, dimmed)
+        };
+        for (layer, at) in &layers {
+            if let &Some(at) = at {
+                layer.measure(None, None);
+                let size = layer.desired_size().min(screen_size);
+                let tl = Point {
+                    x: at.x.min(screen_size.x.wrapping_sub(size.x)).max(0),
+                    y: at.y.min(screen_size.y.wrapping_sub(size.y)).max(0),
+                };
+                layer.arrange(Rect { tl, size });
+            } else {
+                layer.measure(Some(screen_size.x), Some(screen_size.y));
+                layer.arrange(screen_rect);
+            }
+        }
+        let mut screen = this.app().screen.borrow_mut();
+        let (cursor, invalidated_rect) = {
+            let mut data = this.app().data.borrow_mut();
+            (
+                data.cursor,
+                replace(&mut data.invalidated_rect, Rect { tl: Point { x: 0, y: 0 }, size: Vector::null() })
+            )
+        };
+        let mut rp = RenderPort {
+            screen: screen.as_mut(),
+            invalidated_rect,
+            bounds: screen_rect,
+            offset: Vector::null(),
+            cursor,
+            dim: false,
+        };
+        for (i, (layer, _)) in layers.iter().enumerate() {
+            let bounds = layer.margin().shrink_rect(layer.render_bounds());
+            rp.bounds = bounds.intersect(screen_rect);
+            rp.offset = Vector { x: bounds.l(), y: bounds.t() };
+            rp.dim = i < dimmed;
+            Self::render(layer, &mut rp);
+        }
+        let cursor = rp.cursor;
+        this.app().data.borrow_mut().cursor = cursor;
+        cursor
+    }
+
+    fn top_layer(this: &Rc<dyn IsApp>) -> Rc<dyn IsView> {
+        let data = this.app().data.borrow();
+        data.layers.last().map_or_else(|| data.root.clone().expect("app is not running"), |x| x.view.clone())
+    }
+
+    /// Checks if `view` is in the topmost modal layer (or the root if there are no modal layers),
+    /// or in a popup layer above it.
+    fn is_in_input_layers(this: &Rc<dyn IsApp>, view: &Rc<dyn IsView>) -> bool {
+        let layers = {
+            let data = this.app().data.borrow();
+            let modal = data.layers.iter().rposition(|x| matches!(x.kind, LayerKind::Modal(_)));
+            let mut layers = Vec::new();
+            if modal.is_none() { layers.push(data.root.clone().expect("app is not running")); }
+            layers.extend(data.layers[modal.unwrap_or(0) ..].iter().map(|x| x.view.clone()));
+            layers
+        };
+        layers.iter().any(|x| x.is_visual_ancestor_of(view.clone()))
+    }
+
+    fn layer_index(this: &Rc<dyn IsApp>, view: &Rc<dyn IsView>) -> Option<usize> {
+        let layers: Vec<_> = this.app().data.borrow().layers.iter().map(|x| x.view.clone()).collect();
+        layers.iter().rposition(|x| x.is_visual_ancestor_of(view.clone()))
+    }
+
+    fn push_layer(this: &Rc<dyn IsApp>, view: &Rc<dyn IsView>, kind: LayerKind) {
+        assert!(view.app().is_none(), "view already attached to an app");
+        assert!(this.app().data.borrow().root.is_some(), "app is not running");
+        let primary_focus = this.focused(true);
+        let secondary_focus = this.focused(false);
+        this.focus(None, Some(true));
+        this.focus(None, Some(false));
+        this.capture_mouse(None);
+        this.app().data.borrow_mut().layers.push(Layer {
+            view: view.clone(),
+            kind,
+            primary_focus: primary_focus.as_ref().map_or_else(|| <rc::Weak::<View>>::new(), Rc::downgrade),
+            secondary_focus: secondary_focus.as_ref().map_or_else(|| <rc::Weak::<View>>::new(), Rc::downgrade),
+        });
+        view._attach_to_app(this);
+        let app_rect = this.app().data.borrow().app_rect;
+        this.invalidate_render(app_rect);
+        this.focus_next(true);
+    }
+
+    fn pop_layer(this: &Rc<dyn IsApp>) -> Layer {
+        this.focus(None, Some(true));
+        this.focus(None, Some(false));
+        this.capture_mouse(None);
+        let view = this.app().data.borrow().layers.last().unwrap().view.clone();
+        view._detach_from_app();
+        let layer = this.app().data.borrow_mut().layers.pop().unwrap();
+        let app_rect = this.app().data.borrow().app_rect;
+        this.invalidate_render(app_rect);
+        if let Some(focus) = layer.primary_focus.upgrade() && focus.app().is_some() {
+            this.focus(Some(&focus), Some(true));
+        }
+        if let Some(focus) = layer.secondary_focus.upgrade() && focus.app().is_some() {
+            this.focus(Some(&focus), Some(false));
+        }
+        layer
+    }
+
+    fn is_top_popup(this: &Rc<dyn IsApp>) -> bool {
+        this.app().data.borrow().layers.last().is_some_and(|x| matches!(x.kind, LayerKind::Popup(_)))
+    }
+
     pub fn run_impl(
         this: &Rc<dyn IsApp>,
         clock: &mut Option<MonoClock>,
@@ -157,63 +486,129 @@ impl App {
             this.app().data.borrow_mut().root = Some(old_root);
             panic!("app is already running");
         }
-        this.app().data.borrow_mut().clock = Some(clock.take().expect("no clock"));
+        {
+            let mut data = this.app().data.borrow_mut();
+            let clock = clock.take().expect("no clock");
+            data.time_base = Some(clock.time());
+            data.clock = Some(clock);
+        }
         root._attach_to_app(this);
         init.map(|x| x());
-        let mut time = this.app().data.borrow().clock.as_ref().unwrap().time();
-        let res = loop {
-            if let Some(exit_code) = this.app().data.borrow_mut().exit_code.take() {
-                break Ok(exit_code);
+        Self::run_loop(this, None);
+        while Self::is_top_popup(this) {
+            Self::pop_layer(this);
+        }
+        this.focus(None, Some(true));
+        this.focus(None, Some(false));
+        root._detach_from_app();
+        let mut data = this.app().data.borrow_mut();
+        let res = if let Some(e) = data.error.take() {
+            Err(e)
+        } else {
+            Ok(data.exit_code.take().unwrap())
+        };
+        data.time_ms = data.now_ms();
+        data.time_base = None;
+        data.last_frame = None;
+        *clock = Some(data.clock.take().unwrap());
+        data.root = None;
+        res
+    }
+
+    fn run_loop(this: &Rc<dyn IsApp>, modal: Option<&Rc<dyn IsView>>) {
+        loop {
+            {
+                let data = this.app().data.borrow();
+                if data.exit_code.is_some() || data.error.is_some() { break; }
+                if let Some(modal) = modal {
+                    let layer = data.layers.iter().find(|x| addr_eq(Rc::as_ptr(&x.view), Rc::as_ptr(modal))).unwrap();
+                    if let LayerKind::Modal(Some(_)) = layer.kind { break; }
+                }
             }
             loop {
                 let alarm = {
                     let mut data = this.app().data.borrow_mut();
-                    let time = data.clock.as_ref().unwrap().time();
-                    let timer = data.timers.items().iter()
-                        .find(|(_, data)| time.delta_ms_u16(data.start).unwrap_or(u16::MAX) >= data.span_ms)
-                        .map(|(handle, _)| handle);
-                    timer.map(|x| data.timers.remove(x).alarm)
+                    let data = &mut *data;
+                    let now = data.now_ms();
+                    let Some((&(deadline, id), &handle)) = data.timer_queue.first_key_value() else { break; };
+                    if deadline > now { break; }
+                    data.timer_queue.pop_first();
+                    if let Some(period_ms) = data.timers[handle].period_ms {
+                        let next = deadline.saturating_add(period_ms);
+                        let next = if next > now { next } else { now.saturating_add(period_ms) };
+                        data.timers[handle].deadline = next;
+                        data.timer_queue.insert((next, id), handle);
+                        data.timers[handle].alarm.take().map(|x| (handle, id, x))
+                    } else {
+                        data.timers.remove(handle).alarm.map(|x| (handle, id, x))
+                    }
                 };
-                if let Some(alarm) = alarm {
-                    alarm();
-                } else {
-                    break;
+                match alarm {
+                    Some((_, _, Alarm::Once(alarm))) => alarm(),
+                    Some((handle, id, Alarm::Periodic(mut alarm))) => {
+                        alarm();
+                        let mut data = this.app().data.borrow_mut();
+                        if let Some(timer) = data.timers.get_mut(handle) && timer.id == id {
+                            timer.alarm = Some(Alarm::Periodic(alarm));
+                        }
+                    },
+                    None => { },
                 }
             }
-            let has_timers = !this.app().data.borrow().timers.items().is_empty();
-            let screen_size = this.app().screen.borrow().size();
-            root.measure(Some(screen_size.x), Some(screen_size.y));
-            root.arrange(Rect { tl: Point { x: 0, y: 0 }, size: screen_size });
-            let bounds = root.margin().shrink_rect(root.render_bounds());
-            let mut screen = this.app().screen.borrow_mut();
-            let (cursor, invalidated_rect) = {
+            let posted = replace(&mut *this.app().data.borrow().posted.lock().unwrap(), Vec::new());
+            for f in posted {
+                f(this);
+            }
+            if replace(&mut this.app().data.borrow_mut().commands_invalidated, false) {
+                Self::requery_commands(this);
+            }
+            let (timeout, frame_delay) = {
                 let mut data = this.app().data.borrow_mut();
-                (
-                    data.cursor,
-                    replace(&mut data.invalidated_rect, Rect { tl: Point { x: 0, y: 0 }, size: Vector::null() })
-                )
+                let now = data.now_ms();
+                let timeout = data.timer_queue.first_key_value()
+                    .map_or(TIME_REBASE_MS, |(&(deadline, _), _)| {
+                        min(deadline.saturating_sub(now), u64::from(TIME_REBASE_MS)) as u16
+                    });
+                let time = data.clock.as_ref().unwrap().time();
+                let frame_delay = match (data.max_fps, data.last_frame) {
+                    (Some(max_fps), Some(last_frame)) =>
+                        (1000 / max_fps).saturating_sub(time.delta_ms_u16(last_frame).unwrap_or(u16::MAX)),
+                    _ => 0,
+                };
+                (timeout, frame_delay)
             };
-            let mut rp = RenderPort {
-                screen: screen.as_mut(),
-                invalidated_rect,
-                bounds: bounds.intersect(Rect { tl: Point { x: 0, y: 0 }, size: screen_size }),
-                offset: Vector { x: bounds.l(), y: bounds.t() },
-                cursor,
+            let (cursor, timeout) = if frame_delay == 0 {
+                {
+                    let mut data = this.app().data.borrow_mut();
+                    data.last_frame = Some(data.clock.as_ref().unwrap().time());
+                }
+                (Self::render_frame(this), timeout)
+            } else {
+                let cursor = this.app().data.borrow().cursor;
+                (cursor, min(timeout, frame_delay))
             };
-            Self::render(root, &mut rp);
-            let cursor = rp.cursor;
-            this.app().data.borrow_mut().cursor = cursor;
-            match screen.update(cursor, !has_timers) {
-                Err(e) => break Err(e),
+            let event = this.app().screen.borrow_mut().update(cursor, Some(Duration::from_millis(timeout.into())));
+            let is_input = match &event {
+                Ok(Some(Event::Key(..) | Event::Paste(_))) => true,
+                Ok(Some(Event::Mouse(e))) => e.action != MouseAction::Move,
+                _ => false,
+            };
+            if is_input {
+                this.invalidate_commands();
+            }
+            match event {
+                Err(e) => this.app().data.borrow_mut().error = Some(e),
                 Ok(Some(Event::Resize)) => {
                     let app_rect = Rect { tl: Point { x: 0, y: 0 }, size: this.app().screen.borrow().size() };
                     this.app().data.borrow_mut().app_rect = app_rect;
                 },
                 Ok(Some(Event::Key(n, key))) => {
                     'c: for _ in 0 .. n.get() {
+                        let top = Self::top_layer(this);
                         let pre_process = this.app().data.borrow().pre_process.clone();
                         for pre_process in pre_process {
                             let pre_process = pre_process.upgrade().unwrap();
+                            if !Self::is_in_input_layers(this, &pre_process) { continue; }
                             if pre_process.is_enabled() && pre_process.pre_process_key(key) { continue 'c; }
                         }
                         if let Some(focused) = this.focused(true) {
@@ -225,12 +620,14 @@ impl App {
                         let post_process = this.app().data.borrow().post_process.clone();
                         for post_process in post_process {
                             let post_process = post_process.upgrade().unwrap();
+                            if !Self::is_in_input_layers(this, &post_process) { continue; }
                             if post_process.is_enabled() && post_process.post_process_key(key) { continue 'c; }
                         }
                         match key {
                             Key::Tab => this.focus_next(true),
+                            Key::Modified(m, SpecialKey::Tab) if m == Modifiers::SHIFT => this.focus_prev(true),
                             Key::Left | Key::Right | Key::Up | Key::Down => {
-                                let primary_focus = if let Some(sfr) = root._secondary_focus_root() {
+                                let primary_focus = if let Some(sfr) = top._secondary_focus_root() {
                                     match (key, sfr.secondary_focus_keys()) {
                                         (Key::Left, SecondaryFocusKeys::LeftRight) => false,
                                         (Key::Right, SecondaryFocusKeys::LeftRight) => false,
@@ -256,23 +653,122 @@ impl App {
                         }
                     }
                 },
+                Ok(Some(Event::Mouse(event))) => Self::route_mouse(this, event),
+                Ok(Some(Event::Paste(text))) => Self::route_paste(this, &text),
                 _ => { },
             }
-            let data = this.app().data.borrow();
-            let clock = data.clock.as_ref().unwrap();
-            let ms = time.split_ms_u16(clock).unwrap_or(u16::MAX);
-            if has_timers {
-                assert!(FPS != 0 && u16::MAX / FPS > 8);
-                clock.sleep_ms_u16((1000 / FPS).saturating_sub(ms));
+        }
+    }
+
+    pub fn max_fps_impl(this: &Rc<dyn IsApp>) -> Option<u16> {
+        this.app().data.borrow().max_fps
+    }
+
+    pub fn set_max_fps_impl(this: &Rc<dyn IsApp>, value: Option<u16>) {
+        assert!(value != Some(0), "zero fps");
+        this.app().data.borrow_mut().max_fps = value;
+    }
+
+    pub fn set_clipboard_impl(this: &Rc<dyn IsApp>, value: Box<dyn Clipboard>) {
+        *this.app().clipboard.borrow_mut() = value;
+    }
+
+    pub fn clipboard_text_impl(this: &Rc<dyn IsApp>) -> Option<String> {
+        this.app().clipboard.borrow().get()
+    }
+
+    pub fn set_clipboard_text_impl(this: &Rc<dyn IsApp>, text: String) {
+        this.app().clipboard.borrow_mut().set(text);
+    }
+
+    pub fn dispatcher_impl(this: &Rc<dyn IsApp>) -> Dispatcher {
+        let data = this.app().data.borrow();
+        Dispatcher { queue: data.posted.clone(), waker: data.waker.clone() }
+    }
+
+    fn route_paste(this: &Rc<dyn IsApp>, text: &str) {
+        if let Some(focused) = this.focused(true) {
+            if focused.is_enabled() && focused._raise_paste(text) { return; }
+        }
+        if let Some(focused) = this.focused(false) {
+            if focused.is_enabled() { focused._raise_paste(text); }
+        }
+    }
+
+    fn route_mouse(this: &Rc<dyn IsApp>, event: MouseEvent) {
+        let target = if let Some(capture) = this.mouse_capture() {
+            capture
+        } else {
+            loop {
+                let root = Self::top_layer(this);
+                if let Some(target) = root.hit_test(root.screen_to_inner(event.point)) { break target; }
+                if !matches!(event.action, MouseAction::Down(_)) || !Self::is_top_popup(this) { return; }
+                Self::pop_layer(this);
             }
         };
-        this.focus(None, Some(true));
-        this.focus(None, Some(false));
-        root._detach_from_app();
+        if target.is_enabled() {
+            target._raise_mouse(event);
+        }
+    }
+
+    /// Routes all mouse events to `view`, or releases the capture if `view` is `None`.
+    ///
+    /// The call is ignored if `view` is not in the topmost modal layer or a popup above it,
+    /// like [`focus`](AppExt::focus) is.
+    pub fn capture_mouse_impl(this: &Rc<dyn IsApp>, view: Option<&Rc<dyn IsView>>) {
+        if let Some(view) = view && !Self::is_in_input_layers(this, view) { return; }
+        this.app().data.borrow_mut().mouse_capture = view.map_or_else(|| <rc::Weak::<View>>::new(), Rc::downgrade);
+    }
+
+    pub fn mouse_capture_impl(this: &Rc<dyn IsApp>) -> Option<Rc<dyn IsView>> {
+        this.app().data.borrow().mouse_capture.upgrade()
+    }
+
+    /// Shows `view` as a modal layer above everything else and runs a nested loop
+    /// until the layer is closed with [`close_modal`](AppExt::close_modal).
+    ///
+    /// While the layer is shown, focus, keys and mouse are confined to it, and layers below are rendered dimmed.
+    /// Returns `None` if the app is exiting before the layer is closed.
+    pub fn show_modal_impl(this: &Rc<dyn IsApp>, view: &Rc<dyn IsView>) -> Option<u8> {
+        Self::push_layer(this, view, LayerKind::Modal(None));
+        Self::run_loop(this, Some(view));
+        while Self::is_top_popup(this) {
+            Self::pop_layer(this);
+        }
+        let layer = Self::pop_layer(this);
+        debug_assert!(addr_eq(Rc::as_ptr(&layer.view), Rc::as_ptr(view)));
+        let LayerKind::Modal(result) = layer.kind else { panic!() };
+        result
+    }
+
+    /// Closes the modal layer containing `view` with `result`.
+    pub fn close_modal_impl(this: &Rc<dyn IsApp>, view: &Rc<dyn IsView>, result: u8) {
+        let index = Self::layer_index(this, view).expect("view is not in a modal layer");
         let mut data = this.app().data.borrow_mut();
-        *clock = Some(data.clock.take().unwrap());
-        data.root = None;
-        res
+        let LayerKind::Modal(layer_result) = &mut data.layers[index].kind else { panic!("view is not in a modal layer") };
+        *layer_result = Some(result);
+    }
+
+    /// Shows `view` above everything else at `at` (or as close as it fits the screen) without blocking.
+    ///
+    /// A mouse press outside the popup closes it. Pre-process and post-process keys still work under popups.
+    pub fn show_popup_impl(this: &Rc<dyn IsApp>, view: &Rc<dyn IsView>, at: Point) {
+        Self::push_layer(this, view, LayerKind::Popup(at));
+    }
+
+    /// Closes the popup layer containing `view`, and all popups shown above it.
+    pub fn close_popup_impl(this: &Rc<dyn IsApp>, view: &Rc<dyn IsView>) {
+        let Some(index) = Self::layer_index(this, view) else { return; };
+        assert!(matches!(this.app().data.borrow().layers[index].kind, LayerKind::Popup(_)), "view is not in a popup layer");
+        while this.app().data.borrow().layers.len() > index {
+            if !Self::is_top_popup(this) { break; }
+            Self::pop_layer(this);
+        }
+    }
+
+    pub fn is_popup_shown_impl(this: &Rc<dyn IsApp>, view: &Rc<dyn IsView>) -> bool {
+        Self::layer_index(this, view)
+            .is_some_and(|x| matches!(this.app().data.borrow().layers[x].kind, LayerKind::Popup(_)))
     }
 
     pub fn exit_impl(this: &Rc<dyn IsApp>, exit_code: u8) {
@@ -289,17 +785,17 @@ impl App {
         data.invalidated_rect = union;
     }
 
+    pub fn screen_size_impl(this: &Rc<dyn IsApp>) -> Vector {
+        this.app().data.borrow().app_rect.size
+    }
+
     fn move_focus(this: &Rc<dyn IsApp>, primary_focus: bool, forward: bool) {
-        let sfr = {
-            let data = this.app().data.borrow();
-            let root = data.root.as_ref().expect("app is not running");
-            root._secondary_focus_root()
-        };
+        let sfr = Self::top_layer(this)._secondary_focus_root();
         let focused = if let Some(focused) = this.focused(primary_focus) && focused._is_visible_core() {
             focused
         } else {
             let root = if primary_focus {
-                this.app().data.borrow().root.clone().unwrap()
+                Self::top_layer(this)
             } else {
                 let Some(sfr) = sfr.clone() else { return; };
                 sfr
@@ -416,16 +912,16 @@ impl App {
         }
     }
 
+    /// Moves primary or secondary focus to `view`, or clears it if `view` is `None`.
+    ///
+    /// If `primary_focus` is `None`, the focus kind is chosen by the secondary focus root of the top layer.
+    /// The call is ignored if `view` is not in the topmost modal layer or a popup above it.
     pub fn focus_impl(this: &Rc<dyn IsApp>, view: Option<&Rc<dyn IsView>>, primary_focus: Option<bool>) {
         assert!(view.is_some() || primary_focus.is_some(), "incorrect args");
         let primary_focus = if let Some(primary_focus) = primary_focus {
             primary_focus
         } else {
-            let sfr = {
-                let data = this.app().data.borrow();
-                let root = data.root.as_ref().expect("app is not running");
-                root._secondary_focus_root()
-            };
+            let sfr = Self::top_layer(this)._secondary_focus_root();
             if let Some(sfr) = sfr {
                 !sfr.is_visual_ancestor_of(view.unwrap().clone())
             } else {
@@ -433,6 +929,7 @@ impl App {
             }
         };
         view.map(|x| assert!(option_addr_eq(x.app().map(|x| Rc::as_ptr(&x)), Some(Rc::as_ptr(this)))));
+        if let Some(view) = view && !Self::is_in_input_layers(this, view) { return; }
         let prev = {
             let mut data = this.app().data.borrow_mut();
             assert!(!data.changing_focus);
@@ -458,6 +955,69 @@ impl App {
             next._set_is_focused(primary_focus, true);
         }
         this.app().data.borrow_mut().changing_focus = false;
+        this._update_help_context();
+        this.invalidate_commands();
+    }
+
+    /// The view commands from menus and status bar items are executed with:
+    /// the focused view, or the topmost modal or popup if nothing is focused.
+    pub fn command_target_impl(this: &Rc<dyn IsApp>) -> Option<Rc<dyn IsView>> {
+        if this.app().data.borrow().root.is_none() { return None; }
+        this.focused(true).or_else(|| this.focused(false)).or_else(|| Some(Self::top_layer(this)))
+    }
+
+    /// Schedules the command-dependent state update
+    /// (e.g. [`Button`](crate::button::Button) `is_enabled` following its command can-execute)
+    /// before the next frame.
+    ///
+    /// It is done automatically after user input and focus changes.
+    pub fn invalidate_commands_impl(this: &Rc<dyn IsApp>) {
+        this.app().data.borrow_mut().commands_invalidated = true;
+    }
+
+    fn requery_commands(this: &Rc<dyn IsApp>) {
+        fn requery(view: &Rc<dyn IsView>) {
+            view._requery_commands();
+            for i in 0 .. view.visual_children_count() {
+                requery(&view.visual_child(i));
+            }
+        }
+
+        let views = {
+            let data = this.app().data.borrow();
+            data.root.iter().cloned().chain(data.layers.iter().map(|x| x.view.clone())).collect::<Vec<_>>()
+        };
+        for view in views {
+            requery(&view);
+        }
+    }
+
+    /// The help context of the focused view, i.e. the first non-zero
+    /// [`View::help_context`](crate::view::View::help_context_impl) on the way from the focused view to the root.
+    pub fn help_context_impl(this: &Rc<dyn IsApp>) -> u16 {
+        this.app().data.borrow().help_context
+    }
+
+    pub fn _update_help_context_impl(this: &Rc<dyn IsApp>) {
+        let mut help_context = 0;
+        for primary_focus in [true, false] {
+            let mut view = this.focused(primary_focus);
+            while let Some(v) = view {
+                help_context = v.help_context();
+                if help_context != 0 { break; }
+                view = v.visual_parent();
+            }
+            if help_context != 0 { break; }
+        }
+        let views = {
+            let mut data = this.app().data.borrow_mut();
+            if data.help_context == help_context { return; }
+            data.help_context = help_context;
+            data.pre_process.iter().chain(data.post_process.iter()).filter_map(|x| x.upgrade()).collect::<Vec<_>>()
+        };
+        for view in views {
+            view.help_context_changed();
+        }
     }
 
     pub fn _add_pre_process_impl(this: &Rc<dyn IsApp>, view: &Rc<dyn IsView>) {
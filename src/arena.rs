@@ -88,6 +88,14 @@ impl<T> Registry<T> {
         }
     }
 
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        self.items.0.get(handle.0.get()).and_then(|x| x.as_ref().right())
+    }
+
+    pub fn get_mut(&mut self, handle: Handle) -> Option<&mut T> {
+        self.items.0.get_mut(handle.0.get()).and_then(|x| x.as_mut().right())
+    }
+
     pub fn remove(&mut self, handle: Handle) -> T {
         let item = replace(&mut self.items.0[handle.0.get()], Left(self.free_cell)).right().unwrap();
         self.free_cell = Some(handle.0);
@@ -1,5 +1,6 @@
 use iter_identify_first_last::IteratorIdentifyFirstLastExt;
 use serde::{Serialize, Deserialize};
+use std::collections::VecDeque;
 use std::ptr::addr_eq;
 
 pub use int_vec_2d::*;
@@ -38,6 +39,79 @@ pub fn label(text: &str) -> Option<char> {
     None
 }
 
+/// Parses a key name, e.g. `F1`, `Alt-X`, `Ctrl+S` or `Shift-F6`.
+/// A modifier name ends at the first `-` or `+` after it, so the key itself can be a separator,
+/// e.g. `-` or `Alt--`.
+pub fn parse_key(text: &str) -> Option<Key> {
+    let mut modifiers = Modifiers::empty();
+    let mut name = text;
+    while let Some((i, _)) = name.char_indices().skip(1).find(|&(_, c)| c == '-' || c == '+') {
+        modifiers |= match name[.. i].to_ascii_lowercase().as_str() {
+            "shift" => Modifiers::SHIFT,
+            "ctrl" => Modifiers::CTRL,
+            "alt" => Modifiers::ALT,
+            _ => break,
+        };
+        name = &name[i + 1 ..];
+    }
+    let special = match name.to_ascii_lowercase().as_str() {
+        "tab" => Some(SpecialKey::Tab),
+        "down" => Some(SpecialKey::Down),
+        "up" => Some(SpecialKey::Up),
+        "left" => Some(SpecialKey::Left),
+        "right" => Some(SpecialKey::Right),
+        "home" => Some(SpecialKey::Home),
+        "end" => Some(SpecialKey::End),
+        "del" | "delete" => Some(SpecialKey::Delete),
+        "ins" | "insert" => Some(SpecialKey::Insert),
+        "pgdn" | "pagedown" => Some(SpecialKey::PageDown),
+        "pgup" | "pageup" => Some(SpecialKey::PageUp),
+        "f1" => Some(SpecialKey::F1),
+        "f2" => Some(SpecialKey::F2),
+        "f3" => Some(SpecialKey::F3),
+        "f4" => Some(SpecialKey::F4),
+        "f5" => Some(SpecialKey::F5),
+        "f6" => Some(SpecialKey::F6),
+        "f7" => Some(SpecialKey::F7),
+        "f8" => Some(SpecialKey::F8),
+        "f9" => Some(SpecialKey::F9),
+        "f10" => Some(SpecialKey::F10),
+        "f11" => Some(SpecialKey::F11),
+        "f12" => Some(SpecialKey::F12),
+        _ => None,
+    };
+    if let Some(special) = special {
+        return Some(special.with(modifiers));
+    }
+    if modifiers.is_empty() {
+        match name.to_ascii_lowercase().as_str() {
+            "esc" | "escape" => return Some(Key::Escape),
+            "enter" => return Some(Key::Enter),
+            "bksp" | "backspace" => return Some(Key::Backspace),
+            _ => { },
+        }
+    }
+    let mut chars = name.chars();
+    let c = chars.next()?;
+    if chars.next().is_some() { return None; }
+    if modifiers.is_empty() {
+        Some(Key::Char(c))
+    } else if modifiers == Modifiers::ALT {
+        Some(Key::Alt(c.to_lowercase().next().unwrap()))
+    } else if modifiers == Modifiers::CTRL {
+        Some(Key::Ctrl(match c.to_ascii_uppercase() {
+            'A' => Ctrl::A, 'B' => Ctrl::B, 'C' => Ctrl::C, 'D' => Ctrl::D, 'E' => Ctrl::E,
+            'F' => Ctrl::F, 'G' => Ctrl::G, 'J' => Ctrl::J, 'K' => Ctrl::K, 'L' => Ctrl::L,
+            'N' => Ctrl::N, 'O' => Ctrl::O, 'P' => Ctrl::P, 'Q' => Ctrl::Q, 'R' => Ctrl::R,
+            'S' => Ctrl::S, 'T' => Ctrl::T, 'U' => Ctrl::U, 'V' => Ctrl::V, 'W' => Ctrl::W,
+            'X' => Ctrl::X, 'Y' => Ctrl::Y, 'Z' => Ctrl::Z,
+            _ => return None,
+        }))
+    } else {
+        None
+    }
+}
+
 #[derive(Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
 #[derive(Serialize, Deserialize)]
 pub enum TextWrapping {
@@ -78,3 +152,33 @@ pub fn option_addr_eq<T, U>(p: Option<*const T>, q: Option<*const U>) -> bool wh
     let Some(q) = q else { return false; };
     addr_eq(p, q)
 }
+
+/// Appends an item to a bounded history, dropping the oldest one when the history exceeds `limit`.
+pub(crate) fn push_bounded<T>(history: &mut VecDeque<T>, item: T, limit: usize) {
+    history.push_back(item);
+    if history.len() > limit { history.pop_front(); }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_key_modifiers() {
+        assert_eq!(parse_key("F1"), Some(Key::F1));
+        assert_eq!(parse_key("Alt-X"), Some(Key::Alt('x')));
+        assert_eq!(parse_key("ctrl+s"), Some(Key::Ctrl(Ctrl::S)));
+        assert_eq!(parse_key("Shift-F6"), Some(Key::Modified(Modifiers::SHIFT, SpecialKey::F6)));
+        assert_eq!(parse_key("Ctrl-Shift-Tab"), Some(Key::Modified(Modifiers::CTRL | Modifiers::SHIFT, SpecialKey::Tab)));
+        assert_eq!(parse_key("Meta-X"), None);
+    }
+
+    #[test]
+    fn parse_key_separator() {
+        assert_eq!(parse_key("-"), Some(Key::Char('-')));
+        assert_eq!(parse_key("+"), Some(Key::Char('+')));
+        assert_eq!(parse_key("Alt--"), Some(Key::Alt('-')));
+        assert_eq!(parse_key("Alt-+"), Some(Key::Alt('+')));
+        assert_eq!(parse_key("Alt-"), None);
+    }
+}
@@ -3,9 +3,11 @@ use dynamic_cast::dyn_cast_rc;
 use std::cell::RefCell;
 use std::ptr::addr_eq;
 use crate::app::{Timer, AppExt};
-use crate::base::{label_width, HAlign, VAlign, label};
+use crate::base::{label_width, HAlign, VAlign, label, option_addr_eq};
+use crate::command::{IsCommand, CommandExt};
 use crate::event_handler::EventHandler;
 use crate::template::{Template, NameResolver};
+use crate::view::click_on_mouse;
 
 import! { pub button:
     use [view crate::view];
@@ -23,6 +25,7 @@ struct ButtonData {
     press_handler: EventHandler<Option<Box<dyn FnMut()>>>,
     release_handler: EventHandler<Option<Box<dyn FnMut()>>>,
     release_timer: Option<Timer>,
+    command: Option<Rc<dyn IsCommand>>,
 }
 
 #[class_unsafe(inherits_View)]
@@ -52,6 +55,12 @@ pub struct Button {
     color_pressed: fn() -> (Fg, Bg),
     #[non_virt]
     set_color_pressed: fn(value: (Fg, Bg)),
+    #[non_virt]
+    command: fn() -> Option<Rc<dyn IsCommand>>,
+    #[non_virt]
+    set_command: fn(value: Option<Rc<dyn IsCommand>>),
+    #[over]
+    _requery_commands: (),
     #[over]
     is_enabled_changed: (),
     #[over]
@@ -73,6 +82,8 @@ pub struct Button {
     #[over]
     key: (),
     #[over]
+    mouse: (),
+    #[over]
     _init: (),
     #[over]
     _detach_from_app: (),
@@ -103,6 +114,7 @@ impl Button {
                 press_handler: Default::default(),
                 release_handler: Default::default(),
                 release_timer: None,
+                command: None,
             }),
         }
     }
@@ -196,6 +208,29 @@ impl Button {
         this.invalidate_render();
     }
 
+    pub fn command_impl(this: &Rc<dyn IsButton>) -> Option<Rc<dyn IsCommand>> {
+        this.button().data.borrow().command.clone()
+    }
+
+    /// Sets the command executed on click. While the command is set,
+    /// the button is enabled only if the command can be executed with the button as the target
+    /// (in addition to [`set_is_enabled`](ViewExt::set_is_enabled)).
+    pub fn set_command_impl(this: &Rc<dyn IsButton>, value: Option<Rc<dyn IsCommand>>) {
+        {
+            let mut data = this.button().data.borrow_mut();
+            if option_addr_eq(data.command.as_ref().map(Rc::as_ptr), value.as_ref().map(Rc::as_ptr)) { return; }
+            data.command = value;
+        }
+        this._requery_commands();
+    }
+
+    pub fn _requery_commands_impl(this: &Rc<dyn IsView>) {
+        View::_requery_commands_impl(this);
+        let button: Rc<dyn IsButton> = dyn_cast_rc(this.clone()).unwrap();
+        let can_execute = button.command().is_none_or(|x| x.can_execute(this));
+        this._set_is_enabled_by_command(can_execute);
+    }
+
     pub fn is_pressed_impl(this: &Rc<dyn IsButton>) -> bool {
         this.button().data.borrow().release_timer.is_some()
     }
@@ -288,6 +323,10 @@ impl Button {
         let mut invoke = this.button().data.borrow_mut().click_handler.begin_invoke();
         invoke.as_mut().map(|x| x());
         this.button().data.borrow_mut().click_handler.end_invoke(invoke);
+        if let Some(command) = this.command() {
+            let target: Rc<dyn IsView> = this.clone();
+            command.execute(&target);
+        }
     }
 
     pub fn _detach_from_app_impl(this: &Rc<dyn IsView>) {
@@ -309,6 +348,10 @@ impl Button {
         View::key_impl(this, key, original_source)
     }
 
+    pub fn mouse_impl(this: &Rc<dyn IsView>, event: MouseEvent, original_source: &Rc<dyn IsView>) -> bool {
+        click_on_mouse(this, event, original_source, || Self::click(&dyn_cast_rc(this.clone()).unwrap()))
+    }
+
     pub fn pre_post_process_impl(_this: &Rc<dyn IsView>) -> PrePostProcess {
         PrePostProcess::POST_PROCESS
     }
@@ -367,6 +410,9 @@ macro_rules! button_template {
                 #[serde(default)]
                 #[serde(skip_serializing_if="Option::is_none")]
                 pub color_pressed: Option<($crate::base::Fg, $crate::base::Bg)>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="String::is_empty")]
+                pub command: String,
                 $($(
                     $(#[$field_attr])*
                     pub $field_name : $field_ty
@@ -391,6 +437,10 @@ macro_rules! button_apply_template {
             $this.color_focused.map(|x| obj.set_color_focused(x));
             $this.color_disabled.map(|x| obj.set_color_disabled(x));
             $this.color_pressed.map(|x| obj.set_color_pressed(x));
+            $names.resolve(
+                $this.command.clone(),
+                Box::new(move |x| obj.set_command(Some($crate::dynamic_cast_dyn_cast_rc(x).unwrap())))
+            );
         }
     };
 }
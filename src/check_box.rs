@@ -6,6 +6,7 @@ use crate::app::AppExt;
 use crate::base::{label_width, label};
 use crate::event_handler::EventHandler;
 use crate::template::{Template, NameResolver};
+use crate::view::click_on_mouse;
 
 import! { pub check_box:
     use [view crate::view];
@@ -71,6 +72,8 @@ pub struct CheckBox {
     #[over]
     key: (),
     #[over]
+    mouse: (),
+    #[over]
     _init: (),
     #[over]
     pre_post_process: (),
@@ -280,6 +283,10 @@ impl CheckBox {
         View::key_impl(this, key, original_source)
     }
 
+    pub fn mouse_impl(this: &Rc<dyn IsView>, event: MouseEvent, original_source: &Rc<dyn IsView>) -> bool {
+        click_on_mouse(this, event, original_source, || Self::click(&dyn_cast_rc(this.clone()).unwrap()))
+    }
+
     pub fn pre_post_process_impl(_this: &Rc<dyn IsView>) -> PrePostProcess {
         PrePostProcess::POST_PROCESS
     }
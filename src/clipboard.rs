@@ -0,0 +1,30 @@
+/// App-wide clipboard, see [`App::set_clipboard`](crate::app::App::set_clipboard_impl).
+///
+/// Implement it to share the clipboard with the system, e.g. through OSC 52 or an X11 selection.
+pub trait Clipboard {
+    fn get(&self) -> Option<String>;
+
+    fn set(&mut self, text: String);
+}
+
+/// A clipboard visible to the app only. It is the default app clipboard.
+#[derive(Debug, Default, Clone)]
+pub struct LocalClipboard {
+    text: Option<String>,
+}
+
+impl LocalClipboard {
+    pub fn new() -> Self {
+        LocalClipboard { text: None }
+    }
+}
+
+impl Clipboard for LocalClipboard {
+    fn get(&self) -> Option<String> {
+        self.text.clone()
+    }
+
+    fn set(&mut self, text: String) {
+        self.text = Some(text);
+    }
+}
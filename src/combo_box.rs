@@ -0,0 +1,444 @@
+use basic_oop::{class_unsafe, import, Vtable};
+use dynamic_cast::dyn_cast_rc;
+use std::cell::RefCell;
+use std::cmp::min;
+use crate::app::AppExt;
+use crate::base::{text_width, Ctrl, Modifiers, MouseAction, MouseButton, SpecialKey};
+use crate::event_handler::EventHandler;
+use crate::items_control::ItemsControlExt;
+use crate::list_box::{IsListBox, ListBox, ListBoxExt};
+use crate::static_text::{IsStaticText, StaticTextExt, StaticTextTemplate};
+use crate::template::{Template, NameResolver};
+
+import! { pub combo_box:
+    use [input_line crate::input_line];
+    use crate::base::{Fg, Bg};
+}
+
+struct ComboBoxData {
+    items: Vec<String>,
+    selected_index: Option<usize>,
+    is_editable: bool,
+    drop_down_height: i16,
+    drop_down: Option<Rc<dyn IsListBox>>,
+    color_button: (Fg, Bg),
+    selection_change_handler: EventHandler<Option<Box<dyn FnMut()>>>,
+}
+
+/// An input line with a drop-down list of items.
+///
+/// The list opens on Down, Alt+Down or a click on the button, and is shown in a popup layer
+/// under the combo box, or above it if there is no room below.
+/// Enter, Tab or a click on an item commits the list selection, Escape or a click outside cancels it.
+///
+/// An editable combo box accepts any text, and the selected index follows the text.
+/// A read-only one shows the selected item only, and typing a letter selects the next item starting with it.
+#[class_unsafe(inherits_InputLine)]
+pub struct ComboBox {
+    data: RefCell<ComboBoxData>,
+    #[non_virt]
+    items: fn() -> Vec<String>,
+    #[non_virt]
+    set_items: fn(value: Vec<String>),
+    #[non_virt]
+    selected_index: fn() -> Option<usize>,
+    #[non_virt]
+    set_selected_index: fn(value: Option<usize>),
+    #[non_virt]
+    is_editable: fn() -> bool,
+    #[non_virt]
+    set_is_editable: fn(value: bool),
+    #[non_virt]
+    drop_down_height: fn() -> i16,
+    #[non_virt]
+    set_drop_down_height: fn(value: i16),
+    #[non_virt]
+    color_button: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color_button: fn(value: (Fg, Bg)),
+    #[non_virt]
+    is_dropped_down: fn() -> bool,
+    #[non_virt]
+    open_drop_down: fn(),
+    #[non_virt]
+    close_drop_down: fn(commit: bool),
+    #[non_virt]
+    handle_selection_change: fn(handler: Option<Box<dyn FnMut()>>),
+    #[virt]
+    selection_changed: fn(),
+    #[over]
+    text_changed: (),
+    #[over]
+    measure_override: (),
+    #[over]
+    arrange_override: (),
+    #[over]
+    render: (),
+    #[over]
+    key: (),
+    #[over]
+    mouse: (),
+    #[over]
+    paste: (),
+}
+
+impl ComboBox {
+    pub fn new() -> Rc<dyn IsComboBox> {
+        let res: Rc<dyn IsComboBox> = Rc::new(unsafe { Self::new_raw(COMBO_BOX_VTABLE.as_ptr()) });
+        res._init();
+        res
+    }
+
+    pub unsafe fn new_raw(vtable: Vtable) -> Self {
+        ComboBox {
+            input_line: unsafe { InputLine::new_raw(vtable) },
+            data: RefCell::new(ComboBoxData {
+                items: Vec::new(),
+                selected_index: None,
+                is_editable: true,
+                drop_down_height: 8,
+                drop_down: None,
+                color_button: (Fg::Black, Bg::Green),
+                selection_change_handler: Default::default(),
+            }),
+        }
+    }
+
+    pub fn items_impl(this: &Rc<dyn IsComboBox>) -> Vec<String> {
+        this.combo_box().data.borrow().items.clone()
+    }
+
+    /// Sets the items, closing the drop-down list. The selected index is reset to the item matching the text.
+    pub fn set_items_impl(this: &Rc<dyn IsComboBox>, value: Vec<String>) {
+        this.close_drop_down(false);
+        this.combo_box().data.borrow_mut().items = value;
+        Self::sync_selected_index(this);
+        this.invalidate_measure();
+    }
+
+    pub fn selected_index_impl(this: &Rc<dyn IsComboBox>) -> Option<usize> {
+        this.combo_box().data.borrow().selected_index
+    }
+
+    /// Selects the item and sets the text to it. `None` clears the text.
+    pub fn set_selected_index_impl(this: &Rc<dyn IsComboBox>, value: Option<usize>) {
+        let text = {
+            let mut data = this.combo_box().data.borrow_mut();
+            if data.selected_index == value { return; }
+            let text = value.map_or_else(String::new, |x| data.items.get(x).expect("invalid item index").clone());
+            data.selected_index = value;
+            text
+        };
+        this.text().set(text);
+        this.selection_changed();
+    }
+
+    pub fn is_editable_impl(this: &Rc<dyn IsComboBox>) -> bool {
+        this.combo_box().data.borrow().is_editable
+    }
+
+    pub fn set_is_editable_impl(this: &Rc<dyn IsComboBox>, value: bool) {
+        this.combo_box().data.borrow_mut().is_editable = value;
+    }
+
+    /// The maximum number of items visible in the drop-down list.
+    pub fn drop_down_height_impl(this: &Rc<dyn IsComboBox>) -> i16 {
+        this.combo_box().data.borrow().drop_down_height
+    }
+
+    pub fn set_drop_down_height_impl(this: &Rc<dyn IsComboBox>, value: i16) {
+        assert!(value > 0, "invalid drop-down height");
+        this.combo_box().data.borrow_mut().drop_down_height = value;
+    }
+
+    pub fn color_button_impl(this: &Rc<dyn IsComboBox>) -> (Fg, Bg) {
+        this.combo_box().data.borrow().color_button
+    }
+
+    pub fn set_color_button_impl(this: &Rc<dyn IsComboBox>, value: (Fg, Bg)) {
+        {
+            let mut data = this.combo_box().data.borrow_mut();
+            if data.color_button == value { return; }
+            data.color_button = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn is_dropped_down_impl(this: &Rc<dyn IsComboBox>) -> bool {
+        let Some(drop_down) = this.combo_box().data.borrow().drop_down.clone() else { return false; };
+        let drop_down: Rc<dyn IsView> = drop_down;
+        this.app().is_some_and(|x| x.is_popup_shown(&drop_down))
+    }
+
+    pub fn open_drop_down_impl(this: &Rc<dyn IsComboBox>) {
+        if this.is_dropped_down() { return; }
+        let Some(app) = this.app() else { return; };
+        let (count, selected_index, drop_down_height) = {
+            let data = this.combo_box().data.borrow();
+            (data.items.len(), data.selected_index, data.drop_down_height)
+        };
+        if count == 0 { return; }
+        let drop_down = ListBox::new();
+        drop_down.set_item_template(Rc::new(StaticTextTemplate::default()));
+        let combo_box = Rc::downgrade(this);
+        drop_down.handle_bind(Some(Box::new(move |item, index, _| {
+            let Some(combo_box) = combo_box.upgrade() else { return false; };
+            let item: Option<Rc<dyn IsStaticText>> = dyn_cast_rc(item.clone());
+            let Some(item) = item else { return false; };
+            item.set_text(Rc::new(combo_box.combo_box().data.borrow().items[index].clone()));
+            true
+        })));
+        drop_down.set_items_count(count);
+        drop_down.set_current_index(Some(selected_index.unwrap_or(0)));
+        let combo_box = Rc::downgrade(this);
+        drop_down.handle_item_activate(Some(Box::new(move |_| {
+            combo_box.upgrade().map(|x| x.close_drop_down(true));
+        })));
+        let combo_box = Rc::downgrade(this);
+        drop_down.handle_preview_key(Some(Box::new(move |key, _| {
+            let Some(combo_box) = combo_box.upgrade() else { return false; };
+            match key {
+                Key::Escape => combo_box.close_drop_down(false),
+                Key::Tab => combo_box.close_drop_down(true),
+                Key::Modified(m, SpecialKey::Up | SpecialKey::Down) if m == Modifiers::ALT => combo_box.close_drop_down(true),
+                _ => return false,
+            }
+            true
+        })));
+        let combo_box = Rc::downgrade(this);
+        drop_down.handle_preview_mouse(Some(Box::new(move |event, _| {
+            if event.action != MouseAction::Up(MouseButton::Left) { return false; }
+            combo_box.upgrade().map(|x| x.close_drop_down(true));
+            true
+        })));
+        let bounds = this.inner_render_bounds();
+        let at = this.inner_to_screen(bounds.tl);
+        let screen_height = app.screen_size().y;
+        let height = min(i16::try_from(count).unwrap_or(i16::MAX), drop_down_height);
+        let below = screen_height.wrapping_sub(at.y).wrapping_sub(1);
+        let (y, height) = if below >= height || below >= at.y {
+            (at.y.wrapping_add(1), min(height, below.max(1)))
+        } else {
+            let height = min(height, at.y);
+            (at.y.wrapping_sub(height), height)
+        };
+        drop_down.set_width(Some(bounds.w()));
+        drop_down.set_height(Some(height));
+        this.combo_box().data.borrow_mut().drop_down = Some(drop_down.clone());
+        let drop_down: Rc<dyn IsView> = drop_down;
+        app.show_popup(&drop_down, Point { x: at.x, y });
+    }
+
+    /// Closes the drop-down list. If `commit` is set, the current list item gets selected.
+    pub fn close_drop_down_impl(this: &Rc<dyn IsComboBox>, commit: bool) {
+        let Some(drop_down) = this.combo_box().data.borrow_mut().drop_down.take() else { return; };
+        let current_index = drop_down.current_index();
+        let drop_down: Rc<dyn IsView> = drop_down;
+        if let Some(app) = drop_down.app() {
+            app.close_popup(&drop_down);
+        }
+        if commit && current_index.is_some() {
+            this.set_selected_index(current_index);
+            this.select_all();
+        }
+    }
+
+    pub fn handle_selection_change_impl(this: &Rc<dyn IsComboBox>, handler: Option<Box<dyn FnMut()>>) {
+        this.combo_box().data.borrow_mut().selection_change_handler.set(handler);
+    }
+
+    pub fn selection_changed_impl(this: &Rc<dyn IsComboBox>) {
+        let mut invoke = this.combo_box().data.borrow_mut().selection_change_handler.begin_invoke();
+        invoke.as_mut().map(|x| x());
+        this.combo_box().data.borrow_mut().selection_change_handler.end_invoke(invoke);
+    }
+
+    fn sync_selected_index(this: &Rc<dyn IsComboBox>) {
+        let text = this.text().to_string();
+        {
+            let mut data = this.combo_box().data.borrow_mut();
+            if data.selected_index.is_some_and(|x| data.items.get(x) == Some(&text)) { return; }
+            let selected_index = data.items.iter().position(|x| *x == text);
+            if data.selected_index == selected_index { return; }
+            data.selected_index = selected_index;
+        }
+        this.selection_changed();
+    }
+
+    pub fn text_changed_impl(this: &Rc<dyn IsInputLine>) {
+        InputLine::text_changed_impl(this);
+        let this: Rc<dyn IsComboBox> = dyn_cast_rc(this.clone()).unwrap();
+        Self::sync_selected_index(&this);
+    }
+
+    pub fn measure_override_impl(this: &Rc<dyn IsView>, w: Option<i16>, _h: Option<i16>) -> Vector {
+        let this: Rc<dyn IsComboBox> = dyn_cast_rc(this.clone()).unwrap();
+        let width = w.unwrap_or_else(|| {
+            let data = this.combo_box().data.borrow();
+            data.items.iter().map(|x| text_width(x)).max().unwrap_or(0).wrapping_add(4)
+        });
+        Vector { x: width, y: 1 }
+    }
+
+    pub fn arrange_override_impl(this: &Rc<dyn IsView>, bounds: Rect) -> Vector {
+        let text_bounds = Rect { tl: bounds.tl, size: Vector { x: bounds.w().wrapping_sub(1), y: bounds.h() } };
+        InputLine::arrange_override_impl(this, text_bounds);
+        Vector { x: bounds.w(), y: 1 }
+    }
+
+    pub fn render_impl(this: &Rc<dyn IsView>, rp: &mut RenderPort) {
+        InputLine::render_impl(this, rp);
+        let bounds = this.inner_render_bounds();
+        let this: Rc<dyn IsComboBox> = dyn_cast_rc(this.clone()).unwrap();
+        let color = this.combo_box().data.borrow().color_button;
+        rp.text(bounds.tr_inner(), color, "▼");
+    }
+
+    fn select_by_char(this: &Rc<dyn IsComboBox>, c: char) {
+        let c: Vec<char> = c.to_lowercase().collect();
+        let index = {
+            let data = this.combo_box().data.borrow();
+            let count = data.items.len();
+            let start = data.selected_index.map_or(0, |x| x + 1);
+            (0 .. count).map(|x| (start + x) % count).find(|&x| {
+                let mut item = data.items[x].chars().flat_map(|x| x.to_lowercase());
+                c.iter().all(|&c| item.next() == Some(c))
+            })
+        };
+        if index.is_some() {
+            this.set_selected_index(index);
+            this.select_all();
+        }
+    }
+
+    pub fn key_impl(this: &Rc<dyn IsView>, key: Key, original_source: &Rc<dyn IsView>) -> bool {
+        let combo_box: Rc<dyn IsComboBox> = dyn_cast_rc(this.clone()).unwrap();
+        match key {
+            Key::Down => {
+                combo_box.open_drop_down();
+                return true;
+            },
+            Key::Modified(m, SpecialKey::Down) if m == Modifiers::ALT => {
+                combo_box.open_drop_down();
+                return true;
+            },
+            _ => { },
+        }
+        if !combo_box.is_editable() {
+            match key {
+                Key::Char(c) => {
+                    Self::select_by_char(&combo_box, c);
+                    return true;
+                },
+                Key::Backspace | Key::Delete
+                | Key::Ctrl(Ctrl::X | Ctrl::V | Ctrl::Z | Ctrl::Y) => return true,
+                Key::Modified(m, SpecialKey::Delete | SpecialKey::Insert) if m == Modifiers::SHIFT => return true,
+                _ => { },
+            }
+        }
+        InputLine::key_impl(this, key, original_source)
+    }
+
+    pub fn mouse_impl(this: &Rc<dyn IsView>, event: MouseEvent, original_source: &Rc<dyn IsView>) -> bool {
+        let combo_box: Rc<dyn IsComboBox> = dyn_cast_rc(this.clone()).unwrap();
+        if event.action == MouseAction::Down(MouseButton::Left) {
+            let on_button = event.point.x >= this.inner_render_bounds().r().wrapping_sub(1);
+            if on_button || !combo_box.is_editable() {
+                this.app().unwrap().focus(Some(this), None);
+                combo_box.open_drop_down();
+                return true;
+            }
+        }
+        InputLine::mouse_impl(this, event, original_source)
+    }
+
+    pub fn paste_impl(this: &Rc<dyn IsView>, text: &str, original_source: &Rc<dyn IsView>) -> bool {
+        let combo_box: Rc<dyn IsComboBox> = dyn_cast_rc(this.clone()).unwrap();
+        if !combo_box.is_editable() { return true; }
+        InputLine::paste_impl(this, text, original_source)
+    }
+}
+
+#[macro_export]
+macro_rules! combo_box_template {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident in $mod:ident {
+            $(use $path:path as $import:ident;)*
+
+            $($(
+                $(#[$field_attr:meta])*
+                pub $field_name:ident : $field_ty:ty
+            ),+ $(,)?)?
+        }
+    ) => {
+        $crate::input_line_template! {
+            $(#[$attr])*
+            $vis struct $name in $mod {
+                $(use $path as $import;)*
+
+                #[serde(default)]
+                #[serde(skip_serializing_if="Vec::is_empty")]
+                pub items: Vec<String>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub is_editable: Option<bool>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub drop_down_height: Option<i16>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color_button: Option<($crate::base::Fg, $crate::base::Bg)>,
+                $($(
+                    $(#[$field_attr])*
+                    pub $field_name : $field_ty
+                ),+)?
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! combo_box_apply_template {
+    ($this:ident, $instance:ident, $names:ident) => {
+        $crate::input_line_apply_template!($this, $instance, $names);
+        {
+            use $crate::combo_box::ComboBoxExt;
+
+            let obj: $crate::alloc_rc_Rc<dyn $crate::combo_box::IsComboBox>
+                = $crate::dynamic_cast_dyn_cast_rc($instance.clone()).unwrap();
+            if !$this.items.is_empty() {
+                obj.set_items($this.items.clone());
+            }
+            $this.is_editable.map(|x| obj.set_is_editable(x));
+            $this.drop_down_height.map(|x| obj.set_drop_down_height(x));
+            $this.color_button.map(|x| obj.set_color_button(x));
+        }
+    };
+}
+
+combo_box_template! {
+    #[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+    #[serde(rename="ComboBox")]
+    pub struct ComboBoxTemplate in template { }
+}
+
+#[typetag::serde(name="ComboBox")]
+impl Template for ComboBoxTemplate {
+    fn is_name_scope(&self) -> bool {
+        self.is_name_scope
+    }
+
+    fn name(&self) -> Option<&String> {
+        Some(&self.name)
+    }
+
+    fn create_instance(&self) -> Rc<dyn IsObj> {
+        ComboBox::new()
+    }
+
+    fn apply(&self, instance: &Rc<dyn IsObj>, names: &mut NameResolver) {
+        let this = self;
+        combo_box_apply_template!(this, instance, names);
+    }
+}
@@ -0,0 +1,220 @@
+use basic_oop::{class_unsafe, import, Vtable};
+use std::cell::RefCell;
+use crate::event_handler::EventHandler;
+use crate::template::{Template, NameResolver};
+use crate::view::ViewExt;
+
+import! { pub command:
+    use [obj basic_oop::obj];
+    use std::rc::Rc;
+    use crate::base::Key;
+    use crate::view::IsView;
+}
+
+struct CommandData {
+    key: Option<Key>,
+}
+
+/// An action that can be bound to buttons, menu items, status bar items and key shortcuts.
+///
+/// A command does nothing by itself. Executing it routes the request from the target view
+/// up through the visual tree to the first view having a [`CommandBinding`] for the command.
+#[class_unsafe(inherits_Obj)]
+pub struct Command {
+    data: RefCell<CommandData>,
+    #[non_virt]
+    key: fn() -> Option<Key>,
+    #[non_virt]
+    set_key: fn(value: Option<Key>),
+    #[non_virt]
+    can_execute: fn(target: &Rc<dyn IsView>) -> bool,
+    #[non_virt]
+    execute: fn(target: &Rc<dyn IsView>) -> bool,
+}
+
+impl Command {
+    pub fn new() -> Rc<dyn IsCommand> {
+        Rc::new(unsafe { Self::new_raw(COMMAND_VTABLE.as_ptr()) })
+    }
+
+    pub unsafe fn new_raw(vtable: Vtable) -> Self {
+        Command {
+            obj: unsafe { Obj::new_raw(vtable) },
+            data: RefCell::new(CommandData {
+                key: None,
+            }),
+        }
+    }
+
+    /// The key executing the command when pressed in a view having a binding for the command
+    /// (or in one of its visual descendants).
+    pub fn key_impl(this: &Rc<dyn IsCommand>) -> Option<Key> {
+        this.command().data.borrow().key
+    }
+
+    pub fn set_key_impl(this: &Rc<dyn IsCommand>, value: Option<Key>) {
+        this.command().data.borrow_mut().key = value;
+    }
+
+    /// Checks if the command can be executed with `target`.
+    /// A command without a binding on the way from `target` to the root cannot be executed.
+    pub fn can_execute_impl(this: &Rc<dyn IsCommand>, target: &Rc<dyn IsView>) -> bool {
+        target._raise_can_execute(this)
+    }
+
+    /// Executes the command with `target`, returns `false` if the command cannot be executed.
+    pub fn execute_impl(this: &Rc<dyn IsCommand>, target: &Rc<dyn IsView>) -> bool {
+        if !this.can_execute(target) { return false; }
+        target._raise_execute(this)
+    }
+}
+
+#[macro_export]
+macro_rules! command_template {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident in $mod:ident {
+            $(use $path:path as $import:ident;)*
+            $($(
+                $(#[$field_attr:meta])*
+                pub $field_name:ident : $field_ty:ty
+            ),+ $(,)?)?
+        }
+    ) => {
+        $crate::template! {
+            $(#[$attr])*
+            $vis struct $name in $mod {
+                $(use $path as $import;)*
+                #[serde(default)]
+                #[serde(skip_serializing_if="String::is_empty")]
+                pub name: String,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub key: Option<String>,
+                $($(
+                    $(#[$field_attr])*
+                    pub $field_name : $field_ty
+                ),+)?
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! command_apply_template {
+    ($this:ident, $instance:ident, $names:ident) => {
+        {
+            use $crate::command::CommandExt;
+
+            let _ = $names;
+            let obj: $crate::alloc_rc_Rc<dyn $crate::command::IsCommand>
+                = $crate::dynamic_cast_dyn_cast_rc($instance.clone()).unwrap();
+            if let Some(key) = $this.key.as_ref() {
+                match $crate::base::parse_key(key) {
+                    Some(x) => obj.set_key(Some(x)),
+                    None => eprintln!("Warning: invalid key ('{key}')"),
+                }
+            }
+        }
+    };
+}
+
+command_template! {
+    #[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+    #[serde(rename="Command")]
+    pub struct CommandTemplate in command_template { }
+}
+
+#[typetag::serde(name="Command")]
+impl Template for CommandTemplate {
+    fn name(&self) -> Option<&String> {
+        Some(&self.name)
+    }
+
+    fn create_instance(&self) -> Rc<dyn IsObj> {
+        Command::new()
+    }
+
+    fn apply(&self, instance: &Rc<dyn IsObj>, names: &mut NameResolver) {
+        let this = self;
+        command_apply_template!(this, instance, names);
+    }
+}
+
+import! { pub command_binding:
+    use [obj basic_oop::obj];
+    use std::rc::Rc;
+    use crate::view::IsView;
+}
+
+struct CommandBindingData {
+    execute_handler: EventHandler<Option<Box<dyn FnMut(&Rc<dyn IsView>)>>>,
+    can_execute_handler: EventHandler<Option<Box<dyn FnMut(&Rc<dyn IsView>) -> bool>>>,
+}
+
+/// Connects a [`Command`] with its implementation in a view,
+/// see [`View::add_command_binding`](crate::view::View::add_command_binding_impl).
+#[class_unsafe(inherits_Obj)]
+pub struct CommandBinding {
+    command: Rc<dyn IsCommand>,
+    data: RefCell<CommandBindingData>,
+    #[non_virt]
+    command: fn() -> Rc<dyn IsCommand>,
+    #[non_virt]
+    handle_execute: fn(handler: Option<Box<dyn FnMut(&Rc<dyn IsView>)>>),
+    #[non_virt]
+    handle_can_execute: fn(handler: Option<Box<dyn FnMut(&Rc<dyn IsView>) -> bool>>),
+    #[virt]
+    execute: fn(target: &Rc<dyn IsView>),
+    #[virt]
+    can_execute: fn(target: &Rc<dyn IsView>) -> bool,
+}
+
+impl CommandBinding {
+    pub fn new(command: &Rc<dyn IsCommand>) -> Rc<dyn IsCommandBinding> {
+        Rc::new(unsafe { Self::new_raw(command, COMMAND_BINDING_VTABLE.as_ptr()) })
+    }
+
+    pub unsafe fn new_raw(command: &Rc<dyn IsCommand>, vtable: Vtable) -> Self {
+        CommandBinding {
+            obj: unsafe { Obj::new_raw(vtable) },
+            command: command.clone(),
+            data: RefCell::new(CommandBindingData {
+                execute_handler: Default::default(),
+                can_execute_handler: Default::default(),
+            }),
+        }
+    }
+
+    pub fn command_impl(this: &Rc<dyn IsCommandBinding>) -> Rc<dyn IsCommand> {
+        this.command_binding().command.clone()
+    }
+
+    pub fn handle_execute_impl(this: &Rc<dyn IsCommandBinding>, handler: Option<Box<dyn FnMut(&Rc<dyn IsView>)>>) {
+        this.command_binding().data.borrow_mut().execute_handler.set(handler);
+    }
+
+    /// Sets the can-execute handler. Without a handler, the command can always be executed.
+    ///
+    /// Call [`App::invalidate_commands`](crate::app::App::invalidate_commands_impl)
+    /// when the handler result changes not in response to user input.
+    pub fn handle_can_execute_impl(
+        this: &Rc<dyn IsCommandBinding>,
+        handler: Option<Box<dyn FnMut(&Rc<dyn IsView>) -> bool>>
+    ) {
+        this.command_binding().data.borrow_mut().can_execute_handler.set(handler);
+    }
+
+    pub fn execute_impl(this: &Rc<dyn IsCommandBinding>, target: &Rc<dyn IsView>) {
+        let mut invoke = this.command_binding().data.borrow_mut().execute_handler.begin_invoke();
+        invoke.as_mut().map(|x| x(target));
+        this.command_binding().data.borrow_mut().execute_handler.end_invoke(invoke);
+    }
+
+    pub fn can_execute_impl(this: &Rc<dyn IsCommandBinding>, target: &Rc<dyn IsView>) -> bool {
+        let mut invoke = this.command_binding().data.borrow_mut().can_execute_handler.begin_invoke();
+        let can_execute = invoke.as_mut().map_or(true, |x| x(target));
+        this.command_binding().data.borrow_mut().can_execute_handler.end_invoke(invoke);
+        can_execute
+    }
+}
@@ -0,0 +1,364 @@
+use basic_oop::{class_unsafe, import, Vtable};
+use dynamic_cast::dyn_cast_rc;
+use std::cell::RefCell;
+use std::ptr::addr_eq;
+use crate::app::AppExt;
+use crate::background::{Background, BackgroundExt, IsBackground};
+use crate::base::{Modifiers, SpecialKey};
+use crate::canvas::{CanvasLayout, IsCanvasLayout, CanvasLayoutExt};
+use crate::template::{Template, NameResolver};
+use crate::view_vec::ViewVecExt;
+use crate::window::{IsWindow, WindowExt};
+
+import! { pub desktop:
+    use [canvas crate::canvas];
+    use crate::base::{Fg, Bg};
+}
+
+struct DesktopData {
+    background: Rc<dyn IsBackground>,
+}
+
+/// Overlapping windows over a [`Background`] filled with a pattern.
+
+#[class_unsafe(inherits_Canvas)]
+pub struct Desktop {
+    data: RefCell<DesktopData>,
+    #[non_virt]
+    background: fn() -> Rc<dyn IsBackground>,
+    #[non_virt]
+    pattern: fn() -> Rc<String>,
+    #[non_virt]
+    set_pattern: fn(value: Rc<String>),
+    #[non_virt]
+    color: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color: fn(value: (Fg, Bg)),
+    #[non_virt]
+    windows: fn() -> Vec<Rc<dyn IsWindow>>,
+    #[non_virt]
+    active_window: fn() -> Option<Rc<dyn IsWindow>>,
+    #[non_virt]
+    add_window: fn(window: Rc<dyn IsWindow>),
+    #[non_virt]
+    remove_window: fn(window: &Rc<dyn IsWindow>),
+    #[non_virt]
+    activate: fn(window: &Rc<dyn IsWindow>),
+    #[non_virt]
+    next_window: fn(),
+    #[non_virt]
+    prev_window: fn(),
+    #[non_virt]
+    tile: fn(),
+    #[non_virt]
+    cascade: fn(),
+    #[over]
+    _init: (),
+    #[over]
+    visual_children_count: (),
+    #[over]
+    visual_child: (),
+    #[over]
+    measure_override: (),
+    #[over]
+    arrange_override: (),
+    #[over]
+    pre_post_process: (),
+    #[over]
+    post_process_key: (),
+}
+
+impl Desktop {
+    pub fn new() -> Rc<dyn IsDesktop> {
+        let res: Rc<dyn IsDesktop> = Rc::new(unsafe { Self::new_raw(DESKTOP_VTABLE.as_ptr()) });
+        res._init();
+        res
+    }
+
+    pub unsafe fn new_raw(vtable: Vtable) -> Self {
+        let background = Background::new();
+        background.set_color((Fg::Blue, Bg::LightGray));
+        Desktop {
+            canvas: unsafe { Canvas::new_raw(vtable) },
+            data: RefCell::new(DesktopData {
+                background,
+            }),
+        }
+    }
+
+    pub fn _init_impl(this: &Rc<dyn IsView>) {
+        Panel::_init_impl(this);
+        let desktop: Rc<dyn IsDesktop> = dyn_cast_rc(this.clone()).unwrap();
+        let background: Rc<dyn IsView> = desktop.background();
+        background._set_layout_parent(Some(this));
+        background._set_visual_parent(Some(this));
+        this.add_visual_child(&background);
+    }
+
+    /// The view filling the desktop under the windows.
+    pub fn background_impl(this: &Rc<dyn IsDesktop>) -> Rc<dyn IsBackground> {
+        this.desktop().data.borrow().background.clone()
+    }
+
+    pub fn pattern_impl(this: &Rc<dyn IsDesktop>) -> Rc<String> {
+        this.background().pattern()
+    }
+
+    pub fn set_pattern_impl(this: &Rc<dyn IsDesktop>, value: Rc<String>) {
+        this.background().set_pattern(value);
+    }
+
+    pub fn color_impl(this: &Rc<dyn IsDesktop>) -> (Fg, Bg) {
+        this.background().color()
+    }
+
+    pub fn set_color_impl(this: &Rc<dyn IsDesktop>, value: (Fg, Bg)) {
+        this.background().set_color(value);
+    }
+
+    pub fn visual_children_count_impl(this: &Rc<dyn IsView>) -> usize {
+        Panel::visual_children_count_impl(this) + 1
+    }
+
+    pub fn visual_child_impl(this: &Rc<dyn IsView>, index: usize) -> Rc<dyn IsView> {
+        if index == 0 {
+            let desktop: Rc<dyn IsDesktop> = dyn_cast_rc(this.clone()).unwrap();
+            return desktop.background();
+        }
+        Panel::visual_child_impl(this, index - 1)
+    }
+
+    /// Windows in z-order, the bottom one first.
+    pub fn windows_impl(this: &Rc<dyn IsDesktop>) -> Vec<Rc<dyn IsWindow>> {
+        this.children().iter().filter_map(|x| dyn_cast_rc(x)).collect()
+    }
+
+    pub fn active_window_impl(this: &Rc<dyn IsDesktop>) -> Option<Rc<dyn IsWindow>> {
+        this.children().iter().rev().find_map(|x| dyn_cast_rc(x))
+    }
+
+    pub fn add_window_impl(this: &Rc<dyn IsDesktop>, window: Rc<dyn IsWindow>) {
+        let layout: Option<Rc<dyn IsCanvasLayout>> = dyn_cast_rc(window.layout());
+        if layout.is_none() {
+            window.set_layout(CanvasLayout::new());
+        }
+        let old_active = this.active_window();
+        this.children().push(window.clone());
+        old_active.map(|x| x.invalidate_render());
+        Self::focus_window(this, &window, None);
+    }
+
+    pub fn remove_window_impl(this: &Rc<dyn IsDesktop>, window: &Rc<dyn IsWindow>) {
+        let Some(index) = Self::index_of(this, window) else { return; };
+        this.children().remove(index);
+        if let Some(active) = this.active_window() {
+            active.invalidate_render();
+            Self::focus_window(this, &active, None);
+        }
+    }
+
+    pub fn activate_impl(this: &Rc<dyn IsDesktop>, window: &Rc<dyn IsWindow>) {
+        let index = Self::index_of(this, window).expect("window is not on the desktop");
+        let focused = this.app().and_then(|x| x.focused(true));
+        let old_active = this.active_window();
+        if index + 1 != this.children().len() {
+            let window = this.children().remove(index);
+            this.children().push(window);
+        }
+        if let Some(old_active) = old_active && !addr_eq(Rc::as_ptr(&old_active), Rc::as_ptr(window)) {
+            old_active.invalidate_render();
+        }
+        Self::focus_window(this, window, focused);
+    }
+
+    /// Activates the bottom window, so that repeated calls cycle through all windows.
+    pub fn next_window_impl(this: &Rc<dyn IsDesktop>) {
+        let windows = this.windows();
+        if windows.len() < 2 { return; }
+        this.activate(&windows[0]);
+    }
+
+    /// Sends the active window to the bottom and activates the one below it.
+    pub fn prev_window_impl(this: &Rc<dyn IsDesktop>) {
+        let windows = this.windows();
+        if windows.len() < 2 { return; }
+        let index = Self::index_of(this, windows.last().unwrap()).unwrap();
+        let window = this.children().remove(index);
+        this.children().insert(0, window);
+        let active = this.active_window().unwrap();
+        Self::focus_window(this, &active, None);
+    }
+
+    pub fn tile_impl(this: &Rc<dyn IsDesktop>) {
+        let windows = this.windows();
+        if windows.is_empty() { return; }
+        let size = this.inner_render_bounds().size;
+        let count = windows.len() as i32;
+        let cols = (1 ..).find(|x| x * x >= count).unwrap();
+        let rows = (count + cols - 1) / cols;
+        for (i, window) in windows.iter().enumerate() {
+            let i = i as i32;
+            let (col, row) = (i % cols, i / cols);
+            let row_cols = if row == rows - 1 { count - row * cols } else { cols };
+            let l = i32::from(size.x) * col / row_cols;
+            let r = i32::from(size.x) * (col + 1) / row_cols;
+            let t = i32::from(size.y) * row / rows;
+            let b = i32::from(size.y) * (row + 1) / rows;
+            Self::place(window, Point { x: l as i16, y: t as i16 }, Vector { x: (r - l) as i16, y: (b - t) as i16 });
+        }
+    }
+
+    pub fn cascade_impl(this: &Rc<dyn IsDesktop>) {
+        let windows = this.windows();
+        if windows.is_empty() { return; }
+        let size = this.inner_render_bounds().size;
+        let shift = (windows.len() - 1).min(i16::MAX as usize) as i16;
+        let window_size = Vector { x: size.x.saturating_sub(shift), y: size.y.saturating_sub(shift) };
+        for (i, window) in windows.iter().enumerate() {
+            let i = i as i16;
+            Self::place(window, Point { x: i, y: i }, window_size);
+        }
+    }
+
+    fn place(window: &Rc<dyn IsWindow>, tl: Point, size: Vector) {
+        window.set_is_zoomed(false);
+        let layout: Option<Rc<dyn IsCanvasLayout>> = dyn_cast_rc(window.layout());
+        layout.map(|x| x.set_tl(tl));
+        window.resize_to(size);
+    }
+
+    fn index_of(this: &Rc<dyn IsDesktop>, window: &Rc<dyn IsWindow>) -> Option<usize> {
+        this.children().iter().position(|x| addr_eq(Rc::as_ptr(&x), Rc::as_ptr(window)))
+    }
+
+    fn focus_window(this: &Rc<dyn IsDesktop>, window: &Rc<dyn IsWindow>, focused: Option<Rc<dyn IsView>>) {
+        window.invalidate_render();
+        let Some(app) = this.app() else { return; };
+        let window: Rc<dyn IsView> = window.clone();
+        if app.focused(true).is_some_and(|x| window.is_visual_ancestor_of(x)) { return; }
+        if let Some(focused) = focused && focused.app().is_some() && window.is_visual_ancestor_of(focused.clone()) {
+            app.focus(Some(&focused), Some(true));
+        } else {
+            app.focus(Some(&window), Some(true));
+        }
+    }
+
+    pub fn measure_override_impl(this: &Rc<dyn IsView>, w: Option<i16>, h: Option<i16>) -> Vector {
+        let this: Rc<dyn IsDesktop> = dyn_cast_rc(this.clone()).unwrap();
+        this.background().measure(w, h);
+        for child in this.children().iter() {
+            let window: Option<Rc<dyn IsWindow>> = dyn_cast_rc(child.clone());
+            if window.is_some_and(|x| x.is_zoomed()) {
+                child.measure(w, h);
+            } else {
+                child.measure(None, None);
+            }
+        }
+        Vector { x: w.unwrap_or(1), y: h.unwrap_or(1) }
+    }
+
+    pub fn arrange_override_impl(this: &Rc<dyn IsView>, bounds: Rect) -> Vector {
+        let this: Rc<dyn IsDesktop> = dyn_cast_rc(this.clone()).unwrap();
+        this.background().arrange(Rect { tl: Point { x: 0, y: 0 }, size: bounds.size });
+        for child in this.children().iter() {
+            let window: Option<Rc<dyn IsWindow>> = dyn_cast_rc(child.clone());
+            if window.is_some_and(|x| x.is_zoomed()) {
+                child.arrange(Rect { tl: Point { x: 0, y: 0 }, size: bounds.size });
+            } else {
+                let child_size = child.desired_size();
+                let layout: Option<Rc<dyn IsCanvasLayout>> = dyn_cast_rc(child.layout());
+                let tl = layout.map_or(Point { x: 0, y: 0 }, |x| x.tl());
+                child.arrange(Rect { tl, size: child_size });
+            }
+        }
+        bounds.size
+    }
+
+    pub fn pre_post_process_impl(_this: &Rc<dyn IsView>) -> PrePostProcess {
+        PrePostProcess::POST_PROCESS
+    }
+
+    pub fn post_process_key_impl(this: &Rc<dyn IsView>, key: Key) -> bool {
+        let this: Rc<dyn IsDesktop> = dyn_cast_rc(this.clone()).unwrap();
+        match key {
+            Key::F6 => this.next_window(),
+            Key::Modified(m, SpecialKey::F6) if m == Modifiers::SHIFT => this.prev_window(),
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[macro_export]
+macro_rules! desktop_template {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident in $mod:ident {
+            $(use $path:path as $import:ident;)*
+
+            $($(
+                $(#[$field_attr:meta])*
+                pub $field_name:ident : $field_ty:ty
+            ),+ $(,)?)?
+        }
+    ) => {
+        $crate::canvas_template! {
+            $(#[$attr])*
+            $vis struct $name in $mod {
+                $(use $path as $import;)*
+
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub pattern: Option<String>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color: Option<($crate::base::Fg, $crate::base::Bg)>,
+                $($(
+                    $(#[$field_attr])*
+                    pub $field_name : $field_ty
+                ),+)?
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! desktop_apply_template {
+    ($this:ident, $instance:ident, $names:ident) => {
+        $crate::canvas_apply_template!($this, $instance, $names);
+        {
+            use $crate::desktop::DesktopExt;
+
+            let obj: $crate::alloc_rc_Rc<dyn $crate::desktop::IsDesktop>
+                = $crate::dynamic_cast_dyn_cast_rc($instance.clone()).unwrap();
+            $this.pattern.as_ref().map(|x| obj.set_pattern($crate::alloc_rc_Rc::new(x.clone())));
+            $this.color.map(|x| obj.set_color(x));
+        }
+    };
+}
+
+desktop_template! {
+    #[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+    #[serde(rename="Desktop@Children")]
+    pub struct DesktopTemplate in desktop_template { }
+}
+
+#[typetag::serde(name="Desktop")]
+impl Template for DesktopTemplate {
+    fn is_name_scope(&self) -> bool {
+        self.is_name_scope
+    }
+
+    fn name(&self) -> Option<&String> {
+        Some(&self.name)
+    }
+
+    fn create_instance(&self) -> Rc<dyn IsObj> {
+        Desktop::new()
+    }
+
+    fn apply(&self, instance: &Rc<dyn IsObj>, names: &mut NameResolver) {
+        let this = self;
+        desktop_apply_template!(this, instance, names);
+    }
+}
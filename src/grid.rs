@@ -0,0 +1,582 @@
+use basic_oop::{class_unsafe, import, Vtable};
+use dynamic_cast::dyn_cast_rc;
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+use serde::de::Unexpected;
+use serde::de::Error as de_Error;
+use std::cell::{Cell, RefCell};
+use std::cmp::min;
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+use crate::template::{Template, NameResolver};
+use crate::view_vec::ViewVecExt;
+
+import! { pub grid_layout:
+    use [layout crate::view];
+}
+
+#[class_unsafe(inherits_Layout)]
+pub struct GridLayout {
+    row: Cell<u16>,
+    column: Cell<u16>,
+    row_span: Cell<u16>,
+    column_span: Cell<u16>,
+    #[non_virt]
+    row: fn() -> u16,
+    #[non_virt]
+    set_row: fn(value: u16),
+    #[non_virt]
+    column: fn() -> u16,
+    #[non_virt]
+    set_column: fn(value: u16),
+    #[non_virt]
+    row_span: fn() -> u16,
+    #[non_virt]
+    set_row_span: fn(value: u16),
+    #[non_virt]
+    column_span: fn() -> u16,
+    #[non_virt]
+    set_column_span: fn(value: u16),
+}
+
+impl GridLayout {
+    pub fn new() -> Rc<dyn IsGridLayout> {
+        Rc::new(unsafe { Self::new_raw(GRID_LAYOUT_VTABLE.as_ptr()) })
+    }
+
+    pub unsafe fn new_raw(vtable: Vtable) -> Self {
+        GridLayout {
+            layout: unsafe { Layout::new_raw(vtable) },
+            row: Cell::new(0),
+            column: Cell::new(0),
+            row_span: Cell::new(1),
+            column_span: Cell::new(1),
+        }
+    }
+
+    fn invalidate_layout_parent(this: &Rc<dyn IsGridLayout>) {
+        this.owner().and_then(|x| x.layout_parent()).map(|x| x.invalidate_measure());
+    }
+
+    pub fn row_impl(this: &Rc<dyn IsGridLayout>) -> u16 {
+        this.grid_layout().row.get()
+    }
+
+    pub fn set_row_impl(this: &Rc<dyn IsGridLayout>, value: u16) {
+        let old = this.grid_layout().row.replace(value);
+        if old == value { return; }
+        Self::invalidate_layout_parent(this);
+    }
+
+    pub fn column_impl(this: &Rc<dyn IsGridLayout>) -> u16 {
+        this.grid_layout().column.get()
+    }
+
+    pub fn set_column_impl(this: &Rc<dyn IsGridLayout>, value: u16) {
+        let old = this.grid_layout().column.replace(value);
+        if old == value { return; }
+        Self::invalidate_layout_parent(this);
+    }
+
+    pub fn row_span_impl(this: &Rc<dyn IsGridLayout>) -> u16 {
+        this.grid_layout().row_span.get()
+    }
+
+    /// Sets the number of rows the view occupies. Zero is treated as one.
+    pub fn set_row_span_impl(this: &Rc<dyn IsGridLayout>, value: u16) {
+        let old = this.grid_layout().row_span.replace(value);
+        if old == value { return; }
+        Self::invalidate_layout_parent(this);
+    }
+
+    pub fn column_span_impl(this: &Rc<dyn IsGridLayout>) -> u16 {
+        this.grid_layout().column_span.get()
+    }
+
+    /// Sets the number of columns the view occupies. Zero is treated as one.
+    pub fn set_column_span_impl(this: &Rc<dyn IsGridLayout>, value: u16) {
+        let old = this.grid_layout().column_span.replace(value);
+        if old == value { return; }
+        Self::invalidate_layout_parent(this);
+    }
+}
+
+#[macro_export]
+macro_rules! grid_layout_template {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident in $mod:ident {
+            $(use $path:path as $import:ident;)*
+
+            $($(
+                $(#[$field_attr:meta])*
+                pub $field_name:ident : $field_ty:ty
+            ),+ $(,)?)?
+        }
+    ) => {
+        $crate::layout_template! {
+            $(#[$attr])*
+            $vis struct $name in $mod {
+                $(use $path as $import;)*
+
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub row: Option<u16>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub column: Option<u16>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub row_span: Option<u16>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub column_span: Option<u16>,
+                $($(
+                    $(#[$field_attr])*
+                    pub $field_name : $field_ty
+                ),+)?
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! grid_layout_apply_template {
+    ($this:ident, $instance:ident, $names:ident) => {
+        $crate::layout_apply_template!($this, $instance, $names);
+        {
+            use $crate::grid::GridLayoutExt;
+
+            let obj: $crate::alloc_rc_Rc<dyn $crate::grid::IsGridLayout>
+                = $crate::dynamic_cast_dyn_cast_rc($instance.clone()).unwrap();
+            $this.row.map(|x| obj.set_row(x));
+            $this.column.map(|x| obj.set_column(x));
+            $this.row_span.map(|x| obj.set_row_span(x));
+            $this.column_span.map(|x| obj.set_column_span(x));
+        }
+    };
+}
+
+grid_layout_template! {
+    #[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+    #[serde(rename="GridLayout")]
+    pub struct GridLayoutTemplate in grid_layout_template { }
+}
+
+#[typetag::serde(name="GridLayout")]
+impl Template for GridLayoutTemplate {
+    fn name(&self) -> Option<&String> {
+        Some(&self.name)
+    }
+
+    fn create_instance(&self) -> Rc<dyn IsObj> {
+        GridLayout::new()
+    }
+
+    fn apply(&self, instance: &Rc<dyn IsObj>, names: &mut NameResolver) {
+        let this = self;
+        grid_layout_apply_template!(this, instance, names);
+    }
+}
+
+/// The size of a grid row or column.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize)]
+pub enum GridLength {
+    /// Fits the largest view placed in the row or column.
+    Auto,
+    Fixed(i16),
+    /// A weighted share of the space left after auto and fixed rows or columns.
+    Star(u16),
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParseGridLengthError;
+
+impl Display for ParseGridLengthError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "invalid grid length")
+    }
+}
+
+impl std::error::Error for ParseGridLengthError { }
+
+impl FromStr for GridLength {
+    type Err = ParseGridLengthError;
+
+    fn from_str(s: &str) -> Result<GridLength, ParseGridLengthError> {
+        let s = s.trim();
+        if s == "Auto" { return Ok(GridLength::Auto); }
+        if let Some(weight) = s.strip_suffix('*') {
+            if weight.is_empty() { return Ok(GridLength::Star(1)); }
+            return u16::from_str(weight).map(GridLength::Star).map_err(|_| ParseGridLengthError);
+        }
+        i16::from_str(s).ok().filter(|&x| x >= 0).map(GridLength::Fixed).ok_or(ParseGridLengthError)
+    }
+}
+
+impl Display for GridLength {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            GridLength::Auto => write!(f, "Auto"),
+            GridLength::Fixed(size) => write!(f, "{size}"),
+            GridLength::Star(1) => write!(f, "*"),
+            GridLength::Star(weight) => write!(f, "{weight}*"),
+        }
+    }
+}
+
+#[doc(hidden)]
+pub fn serialize_grid_lengths<S>(
+    value: &Option<Vec<GridLength>>, serializer: S
+) -> Result<S::Ok, S::Error> where S: Serializer {
+    if serializer.is_human_readable() {
+        let s = value.as_ref().map(|x| x.iter().map(|x| x.to_string()).collect::<Vec<_>>().join(","));
+        s.serialize(serializer)
+    } else {
+        value.serialize(serializer)
+    }
+}
+
+#[doc(hidden)]
+pub fn deserialize_grid_lengths<'de, D>(
+    deserializer: D
+) -> Result<Option<Vec<GridLength>>, D::Error> where D: Deserializer<'de> {
+    if deserializer.is_human_readable() {
+        let s = <Option<String>>::deserialize(deserializer)?;
+        let Some(s) = s else { return Ok(None); };
+        if s.trim().is_empty() { return Ok(Some(Vec::new())); }
+        let lengths = s.split(',')
+            .map(|x| GridLength::from_str(x).map_err(|_| D::Error::invalid_value(Unexpected::Str(x), &"grid length")))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(lengths))
+    } else {
+        <Option<Vec<GridLength>>>::deserialize(deserializer)
+    }
+}
+
+import! { pub grid:
+    use [panel crate::panel];
+}
+
+struct GridData {
+    rows: Vec<GridLength>,
+    columns: Vec<GridLength>,
+}
+
+#[class_unsafe(inherits_Panel)]
+pub struct Grid {
+    data: RefCell<GridData>,
+    #[non_virt]
+    rows: fn() -> Vec<GridLength>,
+    #[non_virt]
+    set_rows: fn(value: Vec<GridLength>),
+    #[non_virt]
+    columns: fn() -> Vec<GridLength>,
+    #[non_virt]
+    set_columns: fn(value: Vec<GridLength>),
+    #[over]
+    measure_override: (),
+    #[over]
+    arrange_override: (),
+}
+
+struct GridCell {
+    row: usize,
+    column: usize,
+    row_span: usize,
+    column_span: usize,
+}
+
+impl Grid {
+    pub fn new() -> Rc<dyn IsGrid> {
+        let res: Rc<dyn IsGrid> = Rc::new(unsafe { Self::new_raw(GRID_VTABLE.as_ptr()) });
+        res._init();
+        res
+    }
+
+    pub unsafe fn new_raw(vtable: Vtable) -> Self {
+        Grid {
+            panel: unsafe { Panel::new_raw(vtable) },
+            data: RefCell::new(GridData {
+                rows: Vec::new(),
+                columns: Vec::new(),
+            }),
+        }
+    }
+
+    pub fn rows_impl(this: &Rc<dyn IsGrid>) -> Vec<GridLength> {
+        this.grid().data.borrow().rows.clone()
+    }
+
+    /// Sets the row definitions. An empty list means a single star-sized row.
+    pub fn set_rows_impl(this: &Rc<dyn IsGrid>, value: Vec<GridLength>) {
+        {
+            let mut data = this.grid().data.borrow_mut();
+            if data.rows == value { return; }
+            data.rows = value;
+        }
+        this.invalidate_measure();
+    }
+
+    pub fn columns_impl(this: &Rc<dyn IsGrid>) -> Vec<GridLength> {
+        this.grid().data.borrow().columns.clone()
+    }
+
+    /// Sets the column definitions. An empty list means a single star-sized column.
+    pub fn set_columns_impl(this: &Rc<dyn IsGrid>, value: Vec<GridLength>) {
+        {
+            let mut data = this.grid().data.borrow_mut();
+            if data.columns == value { return; }
+            data.columns = value;
+        }
+        this.invalidate_measure();
+    }
+
+    fn definitions(lengths: Vec<GridLength>) -> Vec<GridLength> {
+        if lengths.is_empty() { vec![GridLength::Star(1)] } else { lengths }
+    }
+
+    fn cell(child: &Rc<dyn IsView>, rows: usize, columns: usize) -> GridCell {
+        let grid_layout: Option<Rc<dyn IsGridLayout>> = dyn_cast_rc(child.layout());
+        let (row, column, row_span, column_span) = grid_layout.map_or(
+            (0, 0, 1, 1),
+            |x| (x.row(), x.column(), x.row_span(), x.column_span())
+        );
+        let row = min(usize::from(row), rows - 1);
+        let column = min(usize::from(column), columns - 1);
+        GridCell {
+            row,
+            column,
+            row_span: usize::from(row_span).clamp(1, rows - row),
+            column_span: usize::from(column_span).clamp(1, columns - column),
+        }
+    }
+
+    fn has_star(lengths: &[GridLength]) -> bool {
+        lengths.iter().any(|x| matches!(x, GridLength::Star(_)))
+    }
+
+    fn span_constraint(lengths: &[GridLength], sizes: &[i16], available: Option<i16>) -> Option<i16> {
+        let sum = || sizes.iter().map(|&x| i32::from(x)).sum::<i32>().clamp(0, i32::from(i16::MAX)) as i16;
+        if Self::has_star(lengths) {
+            available.map(|_| sum())
+        } else if lengths.iter().all(|x| matches!(x, GridLength::Fixed(_))) {
+            Some(sum())
+        } else {
+            None
+        }
+    }
+
+    /// Computes row or column sizes from the definitions and the views' `(start, span, desired size)`.
+    ///
+    /// Auto sizes are taken from views not spanning star rows or columns.
+    /// With known available space, star rows or columns share what is left;
+    /// otherwise they grow to fit the views placed in them.
+    fn track_sizes(lengths: &[GridLength], available: Option<i16>, items: &[(usize, usize, i16)]) -> Vec<i16> {
+        let mut sizes: Vec<i16> = lengths.iter().map(|x| match x {
+            &GridLength::Fixed(size) => size.max(0),
+            _ => 0,
+        }).collect();
+        let mut auto_items: Vec<_> = items.iter().copied()
+            .filter(|&(start, span, _)| !Self::has_star(&lengths[start .. start + span]))
+            .collect();
+        auto_items.sort_by_key(|x| x.1);
+        for (start, span, desired) in auto_items {
+            let Some(last_auto) = (start .. start + span).rev().find(|&i| lengths[i] == GridLength::Auto) else {
+                continue;
+            };
+            let current: i32 = sizes[start .. start + span].iter().map(|&x| i32::from(x)).sum();
+            let excess = i32::from(desired) - current;
+            if excess > 0 {
+                sizes[last_auto] = (i32::from(sizes[last_auto]) + excess).min(i32::from(i16::MAX)) as i16;
+            }
+        }
+        let weight = |x: &GridLength| if let &GridLength::Star(weight) = x { i32::from(weight) } else { 0 };
+        if let Some(available) = available {
+            let used: i32 = lengths.iter().zip(sizes.iter())
+                .filter(|(x, _)| !matches!(x, GridLength::Star(_)))
+                .map(|(_, &size)| i32::from(size))
+                .sum();
+            let mut remaining = (i32::from(available) - used).max(0);
+            let mut weights: i32 = lengths.iter().map(weight).sum();
+            for (i, length) in lengths.iter().enumerate() {
+                if !matches!(length, GridLength::Star(_)) { continue; }
+                let w = weight(length);
+                let size = if weights == 0 { 0 } else { (i64::from(remaining) * i64::from(w) / i64::from(weights)) as i32 };
+                sizes[i] = size as i16;
+                remaining -= size;
+                weights -= w;
+            }
+        } else {
+            let mut unit = 0i32;
+            for &(start, span, desired) in items {
+                let span_lengths = &lengths[start .. start + span];
+                let weights: i32 = span_lengths.iter().map(weight).sum();
+                if weights == 0 { continue; }
+                let used: i32 = span_lengths.iter().zip(sizes[start .. start + span].iter())
+                    .filter(|(x, _)| !matches!(x, GridLength::Star(_)))
+                    .map(|(_, &size)| i32::from(size))
+                    .sum();
+                let needed = (i32::from(desired) - used).max(0);
+                unit = unit.max((needed + weights - 1) / weights);
+            }
+            for (i, length) in lengths.iter().enumerate() {
+                if !matches!(length, GridLength::Star(_)) { continue; }
+                sizes[i] = (unit * weight(length)).min(i32::from(i16::MAX)) as i16;
+            }
+        }
+        sizes
+    }
+
+    fn sum(sizes: &[i16]) -> i16 {
+        sizes.iter().map(|&x| i32::from(x)).sum::<i32>().min(i32::from(i16::MAX)) as i16
+    }
+
+    pub fn measure_override_impl(this: &Rc<dyn IsView>, w: Option<i16>, h: Option<i16>) -> Vector {
+        let this: Rc<dyn IsGrid> = dyn_cast_rc(this.clone()).unwrap();
+        let rows = Self::definitions(this.rows());
+        let columns = Self::definitions(this.columns());
+        let children: Vec<_> = this.children().iter().map(|x| {
+            let cell = Self::cell(&x, rows.len(), columns.len());
+            (x, cell)
+        }).collect();
+        let mut row_sizes = Self::track_sizes(&rows, h, &[]);
+        let mut column_sizes = Self::track_sizes(&columns, w, &[]);
+        let mut row_items = Vec::new();
+        let mut column_items = Vec::new();
+        for star_pass in [false, true] {
+            for (child, cell) in &children {
+                let row_lengths = &rows[cell.row .. cell.row + cell.row_span];
+                let column_lengths = &columns[cell.column .. cell.column + cell.column_span];
+                let star = Self::has_star(row_lengths) || Self::has_star(column_lengths);
+                if star != star_pass { continue; }
+                child.measure(
+                    Self::span_constraint(
+                        column_lengths,
+                        &column_sizes[cell.column .. cell.column + cell.column_span],
+                        w
+                    ),
+                    Self::span_constraint(
+                        row_lengths,
+                        &row_sizes[cell.row .. cell.row + cell.row_span],
+                        h
+                    ),
+                );
+                let desired_size = child.desired_size();
+                row_items.push((cell.row, cell.row_span, desired_size.y));
+                column_items.push((cell.column, cell.column_span, desired_size.x));
+            }
+            row_sizes = Self::track_sizes(&rows, h, &row_items);
+            column_sizes = Self::track_sizes(&columns, w, &column_items);
+        }
+        Vector { x: Self::sum(&column_sizes), y: Self::sum(&row_sizes) }
+    }
+
+    pub fn arrange_override_impl(this: &Rc<dyn IsView>, bounds: Rect) -> Vector {
+        let this: Rc<dyn IsGrid> = dyn_cast_rc(this.clone()).unwrap();
+        let rows = Self::definitions(this.rows());
+        let columns = Self::definitions(this.columns());
+        let children: Vec<_> = this.children().iter().map(|x| {
+            let cell = Self::cell(&x, rows.len(), columns.len());
+            (x, cell)
+        }).collect();
+        let row_items: Vec<_> = children.iter()
+            .map(|(child, cell)| (cell.row, cell.row_span, child.desired_size().y))
+            .collect();
+        let column_items: Vec<_> = children.iter()
+            .map(|(child, cell)| (cell.column, cell.column_span, child.desired_size().x))
+            .collect();
+        let row_sizes = Self::track_sizes(&rows, Some(bounds.h()), &row_items);
+        let column_sizes = Self::track_sizes(&columns, Some(bounds.w()), &column_items);
+        for (child, cell) in &children {
+            let x = Self::sum(&column_sizes[.. cell.column]);
+            let y = Self::sum(&row_sizes[.. cell.row]);
+            let size = Vector {
+                x: Self::sum(&column_sizes[cell.column .. cell.column + cell.column_span]),
+                y: Self::sum(&row_sizes[cell.row .. cell.row + cell.row_span]),
+            };
+            child.arrange(Rect { tl: bounds.tl.offset(Vector { x, y }), size });
+        }
+        Vector { x: Self::sum(&column_sizes), y: Self::sum(&row_sizes) }
+    }
+}
+
+#[macro_export]
+macro_rules! grid_template {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident in $mod:ident {
+            $(use $path:path as $import:ident;)*
+
+            $($(
+                $(#[$field_attr:meta])*
+                pub $field_name:ident : $field_ty:ty
+            ),+ $(,)?)?
+        }
+    ) => {
+        $crate::panel_template! {
+            $(#[$attr])*
+            $vis struct $name in $mod {
+                use $crate::grid::serialize_grid_lengths as tvxaml_grid_serialize_grid_lengths;
+                use $crate::grid::deserialize_grid_lengths as tvxaml_grid_deserialize_grid_lengths;
+                $(use $path as $import;)*
+
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                #[serde(serialize_with="tvxaml_grid_serialize_grid_lengths")]
+                #[serde(deserialize_with="tvxaml_grid_deserialize_grid_lengths")]
+                pub rows: Option<Vec<$crate::grid::GridLength>>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                #[serde(serialize_with="tvxaml_grid_serialize_grid_lengths")]
+                #[serde(deserialize_with="tvxaml_grid_deserialize_grid_lengths")]
+                pub columns: Option<Vec<$crate::grid::GridLength>>,
+                $($(
+                    $(#[$field_attr])*
+                    pub $field_name : $field_ty
+                ),+)?
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! grid_apply_template {
+    ($this:ident, $instance:ident, $names:ident) => {
+        $crate::panel_apply_template!($this, $instance, $names);
+        {
+            use $crate::grid::GridExt;
+
+            let obj: $crate::alloc_rc_Rc<dyn $crate::grid::IsGrid>
+                = $crate::dynamic_cast_dyn_cast_rc($instance.clone()).unwrap();
+            $this.rows.as_ref().map(|x| obj.set_rows(x.clone()));
+            $this.columns.as_ref().map(|x| obj.set_columns(x.clone()));
+        }
+    };
+}
+
+grid_template! {
+    #[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+    #[serde(rename="Grid@Children")]
+    pub struct GridTemplate in grid_template { }
+}
+
+#[typetag::serde(name="Grid")]
+impl Template for GridTemplate {
+    fn is_name_scope(&self) -> bool {
+        self.is_name_scope
+    }
+
+    fn name(&self) -> Option<&String> {
+        Some(&self.name)
+    }
+
+    fn create_instance(&self) -> Rc<dyn IsObj> {
+        Grid::new()
+    }
+
+    fn apply(&self, instance: &Rc<dyn IsObj>, names: &mut NameResolver) {
+        let this = self;
+        grid_apply_template!(this, instance, names);
+    }
+}
@@ -1,33 +1,141 @@
 use basic_oop::{class_unsafe, import, Vtable};
 use dynamic_cast::dyn_cast_rc;
+use std::borrow::Cow;
 use std::cell::RefCell;
-use std::iter::once;
+use std::cmp::{max, min};
+use std::collections::VecDeque;
+use std::iter::{once, repeat_n};
 use std::mem::replace;
 use std::ops::{Range, RangeInclusive};
+use std::ptr::addr_eq;
 use std::rc::{self};
 use unicode_width::UnicodeWidthChar;
-use crate::base::{text_width, VAlign, graphemes, HAlign, char_width};
+use crate::app::AppExt;
+use crate::base::{text_width, VAlign, graphemes, HAlign, char_width, MouseAction, MouseButton};
+use crate::base::{push_bounded, Ctrl, Modifiers, SpecialKey};
 use crate::event_handler::EventHandler;
 use crate::template::{Template, NameResolver};
+use crate::validator::Validator;
 
 import! { pub input_line:
     use [view crate::view];
     use crate::base::{Fg, Bg};
 }
 
+const UNDO_LIMIT: usize = 100;
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn grapheme_start_before(text: &str, index: usize) -> usize {
+    graphemes(text).take_while(|(g, _)| g.start <= index).last().map_or(index, |(g, _)| g.start)
+}
+
+fn grapheme_start_after(text: &str, index: usize) -> usize {
+    graphemes(text).find(|(g, _)| g.start >= index).map_or(text.len(), |(g, _)| g.start)
+}
+
+fn word_left(text: &str, index: usize) -> usize {
+    let start = text[.. index].trim_end_matches(|c| !is_word_char(c)).trim_end_matches(is_word_char).len();
+    grapheme_start_before(text, start)
+}
+
+fn word_right(text: &str, index: usize) -> usize {
+    let tail = text[index ..].trim_start_matches(is_word_char).trim_start_matches(|c| !is_word_char(c));
+    grapheme_start_after(text, text.len() - tail.len())
+}
+
+fn word_at(text: &str, index: usize) -> Range<usize> {
+    let start = text[.. index].trim_end_matches(is_word_char).len();
+    let end = text.len() - text[index ..].trim_start_matches(is_word_char).len();
+    grapheme_start_before(text, start) .. grapheme_start_after(text, end)
+}
+
 struct InputLineData {
     text: String,
     color: (Fg, Bg),
     color_focused: (Fg, Bg),
     color_disabled: (Fg, Bg),
+    color_selected: (Fg, Bg),
+    color_invalid: (Fg, Bg),
     view: Option<(i16, RangeInclusive<usize>, i16)>,
     cursor: usize,
+    anchor: Option<usize>,
     delete_char: bool,
     width: i16,
     is_numeric: bool,
+    max_length: Option<usize>,
+    password_char: Option<char>,
+    validator: Option<Rc<dyn Validator>>,
+    is_invalid: bool,
+    undo: VecDeque<(String, usize)>,
+    redo: Vec<(String, usize)>,
+    undo_merge: bool,
     text_change_handler: EventHandler<Option<Box<dyn FnMut()>>>,
 }
 
+impl InputLineData {
+    fn selection(&self) -> Option<Range<usize>> {
+        let anchor = self.anchor?;
+        if anchor < self.cursor {
+            Some(anchor .. self.cursor)
+        } else if anchor > self.cursor {
+            Some(self.cursor .. anchor)
+        } else {
+            None
+        }
+    }
+
+    fn grapheme_end(&self, index: usize) -> usize {
+        index + graphemes(&self.text[index ..]).next().map_or(0, |(g, _)| g.end - 1)
+    }
+
+    /// Saves the current text for undo. A merged edit (typing) continues the previous merged one.
+    fn save_undo(&mut self, merge: bool) {
+        if !(merge && self.undo_merge) {
+            let state = (self.text.clone(), self.cursor);
+            push_bounded(&mut self.undo, state, UNDO_LIMIT);
+        }
+        self.undo_merge = merge;
+        self.redo.clear();
+        self.is_invalid = false;
+    }
+
+    /// Checks an edited text against the max length and the validator.
+    /// Returns the text to set, or `None` if the edit is rejected.
+    fn check_edit(&self, mut text: String, auto_fill: bool) -> Option<String> {
+        if let Some(max_length) = self.max_length {
+            let len = text.chars().count();
+            if len > max_length && len > self.text.chars().count() { return None; }
+        }
+        if let Some(validator) = self.validator.as_ref() && !validator.is_valid_input(&mut text, auto_fill) {
+            return None;
+        }
+        Some(text)
+    }
+
+    fn shown_width(&self, width: i16) -> i16 {
+        self.password_char.map_or(width, char_width)
+    }
+
+    fn shown_text_width(&self, text: &str) -> i16 {
+        if let Some(c) = self.password_char {
+            graphemes(text).fold(0, |w, _| w.wrapping_add(char_width(c)))
+        } else {
+            text_width(text)
+        }
+    }
+
+    fn shown_text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        if let Some(c) = self.password_char {
+            Cow::Owned(repeat_n(c, graphemes(text).count()).collect())
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+}
+
 #[derive(Clone)]
 pub struct TextBuf {
     owner: rc::Weak<dyn IsInputLine>,
@@ -40,11 +148,17 @@ impl ToString for TextBuf {
 }
 
 impl TextBuf {
+    /// Changes the text. It resets the cursor and the selection, and clears the undo history.
     pub fn change<T>(&self, f: impl FnOnce(&mut String) -> T) -> T {
         let res = {
             let owner = self.owner.upgrade().unwrap();
             let mut data = owner.input_line().data.borrow_mut();
-            f(&mut data.text)
+            let res = f(&mut data.text);
+            data.undo.clear();
+            data.redo.clear();
+            data.undo_merge = false;
+            data.is_invalid = false;
+            res
         };
         let owner = self.owner.upgrade().unwrap();
         InputLine::reset_view(&owner);
@@ -82,6 +196,50 @@ pub struct InputLine {
     color_disabled: fn() -> (Fg, Bg),
     #[non_virt]
     set_color_disabled: fn(value: (Fg, Bg)),
+    #[non_virt]
+    color_selected: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color_selected: fn(value: (Fg, Bg)),
+    #[non_virt]
+    color_invalid: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color_invalid: fn(value: (Fg, Bg)),
+    #[non_virt]
+    max_length: fn() -> Option<usize>,
+    #[non_virt]
+    set_max_length: fn(value: Option<usize>),
+    #[non_virt]
+    password_char: fn() -> Option<char>,
+    #[non_virt]
+    set_password_char: fn(value: Option<char>),
+    #[non_virt]
+    validator: fn() -> Option<Rc<dyn Validator>>,
+    #[non_virt]
+    set_validator: fn(value: Option<Rc<dyn Validator>>),
+    #[non_virt]
+    validate: fn() -> bool,
+    #[non_virt]
+    is_valid: fn() -> bool,
+    #[non_virt]
+    selection: fn() -> Option<Range<usize>>,
+    #[non_virt]
+    selected_text: fn() -> String,
+    #[non_virt]
+    select_all: fn(),
+    #[non_virt]
+    can_undo: fn() -> bool,
+    #[non_virt]
+    undo: fn() -> bool,
+    #[non_virt]
+    can_redo: fn() -> bool,
+    #[non_virt]
+    redo: fn() -> bool,
+    #[non_virt]
+    cut_to_clipboard: fn(),
+    #[non_virt]
+    copy_to_clipboard: fn(),
+    #[non_virt]
+    paste_from_clipboard: fn(),
     #[over]
     is_enabled_changed: (),
     #[over]
@@ -100,6 +258,10 @@ pub struct InputLine {
     text_changed: fn(),
     #[over]
     key: (),
+    #[over]
+    mouse: (),
+    #[over]
+    paste: (),
 }
 
 impl InputLine {
@@ -117,12 +279,22 @@ impl InputLine {
                 color: (Fg::LightGray, Bg::Blue),
                 color_focused: (Fg::LightGray, Bg::Blue),
                 color_disabled: (Fg::DarkGray, Bg::Blue),
+                color_selected: (Fg::Blue, Bg::LightGray),
+                color_invalid: (Fg::Yellow, Bg::Red),
                 text_change_handler: Default::default(),
                 cursor: 0,
+                anchor: None,
                 view: None,
                 delete_char: false,
                 is_numeric: false,
+                max_length: None,
+                password_char: None,
+                validator: None,
+                is_invalid: false,
                 width: 0,
+                undo: VecDeque::new(),
+                redo: Vec::new(),
+                undo_merge: false,
             }),
         }
     }
@@ -199,6 +371,166 @@ impl InputLine {
         this.invalidate_render();
     }
 
+    pub fn color_selected_impl(this: &Rc<dyn IsInputLine>) -> (Fg, Bg) {
+        this.input_line().data.borrow().color_selected
+    }
+
+    pub fn set_color_selected_impl(this: &Rc<dyn IsInputLine>, value: (Fg, Bg)) {
+        {
+            let mut data = this.input_line().data.borrow_mut();
+            if data.color_selected == value { return; }
+            data.color_selected = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn color_invalid_impl(this: &Rc<dyn IsInputLine>) -> (Fg, Bg) {
+        this.input_line().data.borrow().color_invalid
+    }
+
+    pub fn set_color_invalid_impl(this: &Rc<dyn IsInputLine>, value: (Fg, Bg)) {
+        {
+            let mut data = this.input_line().data.borrow_mut();
+            if data.color_invalid == value { return; }
+            data.color_invalid = value;
+        }
+        this.invalidate_render();
+    }
+
+    /// The maximum text length in chars. Typing and pasting cannot make the text longer.
+    pub fn max_length_impl(this: &Rc<dyn IsInputLine>) -> Option<usize> {
+        this.input_line().data.borrow().max_length
+    }
+
+    pub fn set_max_length_impl(this: &Rc<dyn IsInputLine>, value: Option<usize>) {
+        this.input_line().data.borrow_mut().max_length = value;
+    }
+
+    /// The char shown instead of each text grapheme. The text cannot be copied while it is set.
+    pub fn password_char_impl(this: &Rc<dyn IsInputLine>) -> Option<char> {
+        this.input_line().data.borrow().password_char
+    }
+
+    pub fn set_password_char_impl(this: &Rc<dyn IsInputLine>, value: Option<char>) {
+        assert!(value.is_none_or(|x| char_width(x) > 0), "invalid password char");
+        {
+            let mut data = this.input_line().data.borrow_mut();
+            if data.password_char == value { return; }
+            data.password_char = value;
+        }
+        Self::reset_view(this);
+    }
+
+    pub fn validator_impl(this: &Rc<dyn IsInputLine>) -> Option<Rc<dyn Validator>> {
+        this.input_line().data.borrow().validator.clone()
+    }
+
+    /// Sets the validator checking each edit and, on focus loss, the whole text.
+    pub fn set_validator_impl(this: &Rc<dyn IsInputLine>, value: Option<Rc<dyn Validator>>) {
+        {
+            let mut data = this.input_line().data.borrow_mut();
+            data.validator = value;
+            data.is_invalid = false;
+        }
+        this.invalidate_render();
+    }
+
+    /// Checks the text with the validator. Invalid text is shown
+    /// in [`color_invalid`](InputLine::color_invalid_impl) until it is edited.
+    pub fn validate_impl(this: &Rc<dyn IsInputLine>) -> bool {
+        let is_valid = {
+            let mut data = this.input_line().data.borrow_mut();
+            let is_valid = data.validator.as_ref().is_none_or(|x| x.is_valid(&data.text));
+            if data.is_invalid == !is_valid { return is_valid; }
+            data.is_invalid = !is_valid;
+            is_valid
+        };
+        this.invalidate_render();
+        is_valid
+    }
+
+    /// Returns `false` if the last [`validate`](InputLine::validate_impl) call failed
+    /// and the text has not been edited since.
+    pub fn is_valid_impl(this: &Rc<dyn IsInputLine>) -> bool {
+        !this.input_line().data.borrow().is_invalid
+    }
+
+    /// The selected byte range of the text.
+    pub fn selection_impl(this: &Rc<dyn IsInputLine>) -> Option<Range<usize>> {
+        this.input_line().data.borrow().selection()
+    }
+
+    pub fn selected_text_impl(this: &Rc<dyn IsInputLine>) -> String {
+        let data = this.input_line().data.borrow();
+        data.selection().map_or_else(String::new, |x| data.text[x].to_string())
+    }
+
+    pub fn select_all_impl(this: &Rc<dyn IsInputLine>) {
+        this.input_line().data.borrow_mut().anchor = Some(0);
+        Self::cursor_end(this);
+    }
+
+    pub fn can_undo_impl(this: &Rc<dyn IsInputLine>) -> bool {
+        !this.input_line().data.borrow().undo.is_empty()
+    }
+
+    pub fn undo_impl(this: &Rc<dyn IsInputLine>) -> bool {
+        {
+            let mut data = this.input_line().data.borrow_mut();
+            let Some((text, cursor)) = data.undo.pop_back() else { return false; };
+            let state = (replace(&mut data.text, text), data.cursor);
+            data.redo.push(state);
+            data.cursor = cursor;
+            data.anchor = None;
+            data.delete_char = false;
+            data.undo_merge = false;
+            data.is_invalid = false;
+        }
+        Self::update_view(this, 0);
+        this.text_changed();
+        true
+    }
+
+    pub fn can_redo_impl(this: &Rc<dyn IsInputLine>) -> bool {
+        !this.input_line().data.borrow().redo.is_empty()
+    }
+
+    pub fn redo_impl(this: &Rc<dyn IsInputLine>) -> bool {
+        {
+            let mut data = this.input_line().data.borrow_mut();
+            let Some((text, cursor)) = data.redo.pop() else { return false; };
+            let state = (replace(&mut data.text, text), data.cursor);
+            push_bounded(&mut data.undo, state, UNDO_LIMIT);
+            data.cursor = cursor;
+            data.anchor = None;
+            data.delete_char = false;
+            data.undo_merge = false;
+            data.is_invalid = false;
+        }
+        Self::update_view(this, 0);
+        this.text_changed();
+        true
+    }
+
+    pub fn cut_to_clipboard_impl(this: &Rc<dyn IsInputLine>) {
+        if this.password_char().is_some() { return; }
+        this.copy_to_clipboard();
+        Self::delete_selection(this, false);
+    }
+
+    pub fn copy_to_clipboard_impl(this: &Rc<dyn IsInputLine>) {
+        if this.password_char().is_some() { return; }
+        let text = this.selected_text();
+        if text.is_empty() { return; }
+        let Some(app) = this.app() else { return; };
+        app.set_clipboard_text(text);
+    }
+
+    pub fn paste_from_clipboard_impl(this: &Rc<dyn IsInputLine>) {
+        let Some(text) = this.app().and_then(|x| x.clipboard_text()) else { return; };
+        Self::insert(this, &text);
+    }
+
     pub fn measure_override_impl(_this: &Rc<dyn IsView>, w: Option<i16>, _h: Option<i16>) -> Vector {
         Vector { x: w.unwrap_or(1), y: 1 }
     }
@@ -216,6 +548,7 @@ impl InputLine {
             let mut data = this.input_line().data.borrow_mut();
             data.delete_char = false;
             data.cursor = data.text.len();
+            data.anchor = None;
             (data.is_numeric, data.text.len())
         };
         if is_focused {
@@ -252,6 +585,7 @@ impl InputLine {
             let mut w = if with_end { 1i16 } else { 0i16 };
             let mut prev_g: Option<Range<usize>> = None;
             for (g, g_w) in graphemes(text).rev() {
+                let g_w = data.shown_width(g_w);
                 if w.wrapping_add(g_w) as u16 > data.width as u16 {
                     break 'r prev_g.map(|x| (data.width.wrapping_sub(w), x.start ..= view_end));
                 }
@@ -272,7 +606,7 @@ impl InputLine {
                 (g, g_w)
             in
                 graphemes(&data.text[view_start .. ])
-                    .map(|(g, g_w)| (view_start + g.end - 1, g_w))
+                    .map(|(g, g_w)| (view_start + g.end - 1, data.shown_width(g_w)))
                     .chain(once((data.text.len(), 1)))
             {
                 if w.wrapping_add(g_w) as u16 > data.width as u16 {
@@ -286,6 +620,32 @@ impl InputLine {
         data.view = view.map(|(x, p)| (left_p, x, p));
     }
 
+    fn text_start(data: &InputLineData, bounds: Rect) -> Option<Point> {
+        let (left_p, view, right_p) = data.view.clone()?;
+        let show_text_end = view.contains(&data.text.len());
+        let text = if show_text_end {
+            &data.text[*view.start() .. *view.end()]
+        } else {
+            &data.text[view.clone()]
+        };
+        let the_text_width = data.shown_text_width(text)
+            .wrapping_add(left_p)
+            .wrapping_add(right_p)
+            .wrapping_add(if show_text_end { 1 } else { 0 })
+        ;
+        let align = Thickness::align(
+            Vector { x: the_text_width, y: 1 },
+            Vector { x: data.width, y: 1 },
+            if data.is_numeric { HAlign::Right } else { HAlign::Left },
+            VAlign::Top
+        );
+        let text_start = align.shrink_rect(Thickness::new(1, 0, 1, 0).shrink_rect(bounds))
+            .tl
+            .offset(Vector { x: left_p, y: 0 })
+        ;
+        Some(text_start)
+    }
+
     pub fn render_impl(this: &Rc<dyn IsView>, rp: &mut RenderPort) {
         let bounds = this.inner_render_bounds();
         let is_enabled = this.is_enabled();
@@ -299,38 +659,34 @@ impl InputLine {
             (false, true) => (data.color_disabled.0, data.color_focused.1),
             (false, false) => data.color_disabled
         };
+        let color = if is_enabled && data.is_invalid { data.color_invalid } else { color };
         rp.fill_bg(color);
-        if let Some((left_p, view, right_p)) = data.view.clone() {
+        if let Some((_, view, _)) = data.view.clone() {
             let show_text_end = view.contains(&data.text.len());
             let text = if show_text_end {
                 &data.text[*view.start() .. *view.end()]
             } else {
                 &data.text[view.clone()]
             };
-            let the_text_width = text_width(text)
-                .wrapping_add(left_p)
-                .wrapping_add(right_p)
-                .wrapping_add(if show_text_end { 1 } else { 0 })
-            ;
-            let align = Thickness::align(
-                Vector { x: the_text_width, y: 1 },
-                Vector { x: data.width, y: 1 },
-                if data.is_numeric { HAlign::Right } else { HAlign::Left },
-                VAlign::Top
-            );
-            let text_start = align.shrink_rect(Thickness::new(1, 0, 1, 0).shrink_rect(bounds))
-                .tl
-                .offset(Vector { x: left_p, y: 0 })
-            ;
-            rp.text(text_start, color, text);
+            let text_start = Self::text_start(&data, bounds).unwrap();
+            rp.text(text_start, color, &data.shown_text(text));
+            if is_focused && let Some(selection) = data.selection() {
+                let start = max(selection.start, *view.start());
+                let end = min(selection.end, *view.start() + text.len());
+                if start < end {
+                    let x = data.shown_text_width(&data.text[*view.start() .. start]);
+                    let selected = data.shown_text(&data.text[start .. end]);
+                    rp.text(text_start.offset(Vector { x, y: 0 }), data.color_selected, &selected);
+                }
+            }
             if graphemes(&data.text[.. *view.start()]).next_back().is_some() {
                 rp.text(Point { x: 0, y: 0 }, color, "◄");
             }
             if !show_text_end && graphemes(&data.text[*view.end() + 1 .. ]).next().is_some() {
-                rp.text(bounds.tr_inner(), color, "►");
+                rp.text(Point { x: data.width.wrapping_add(1), y: 0 }, color, "►");
             }
             if is_focused_primary && view.contains(&data.cursor) {
-                let cursor_x = text_width(&data.text[*view.start() .. data.cursor]);
+                let cursor_x = data.shown_text_width(&data.text[*view.start() .. data.cursor]);
                 rp.cursor(text_start.offset(Vector { x: cursor_x, y: 0 }));
             }
         }
@@ -338,8 +694,12 @@ impl InputLine {
 
     pub fn is_focused_changed_impl(this: &Rc<dyn IsView>, primary_focus: bool) {
         View::is_focused_changed_impl(this, primary_focus);
+        let is_focused = this.is_focused(None);
         let this: Rc<dyn IsInputLine> = dyn_cast_rc(this.clone()).unwrap();
         Self::reset_view(&this);
+        if !is_focused {
+            this.validate();
+        }
     }
 
     pub fn handle_text_change_impl(this: &Rc<dyn IsInputLine>, handler: Option<Box<dyn FnMut()>>) {
@@ -426,9 +786,23 @@ impl InputLine {
             } else {
                 None
             };
-            data.delete_char = true;
             let cursor = data.cursor;
-            data.text.insert(cursor, c);
+            let mut text = data.text.clone();
+            text.insert(cursor, c);
+            let Some(checked_text) = data.check_edit(text.clone(), true) else { return; };
+            data.save_undo(true);
+            if checked_text != text {
+                let at_end = cursor == data.text.len();
+                data.text = checked_text;
+                data.cursor = if at_end { data.text.len() } else { grapheme_start_after(&data.text, cursor + c.len_utf8()) };
+                data.delete_char = false;
+                drop(data);
+                Self::update_view(this, 0);
+                this.text_changed();
+                return;
+            }
+            data.delete_char = true;
+            data.text = text;
             data.cursor += c.len_utf8();
             if let Some((left_p, view, _)) = data.view.clone() && *view.start() < cursor {
                 (*view.start(), left_p) 
@@ -461,20 +835,20 @@ impl InputLine {
     fn delete_before_cursor(this: &Rc<dyn IsInputLine>) {
         let (view_start, left_p) = {
             let mut data = this.input_line().data.borrow_mut();
-            if data.delete_char {
+            let (range, delete_char) = if data.delete_char {
                 let c = data.text[.. data.cursor].chars().next_back().unwrap();
-                data.cursor -= c.len_utf8();
-                let cursor = data.cursor;
-                data.text.remove(cursor);
-                if char_width(c) != 0 {
-                    data.delete_char = false;
-                }
+                (data.cursor - c.len_utf8() .. data.cursor, char_width(c) == 0)
             } else {
                 let Some((g, _)) = graphemes(&data.text[.. data.cursor]).next_back() else { return; };
-                let cursor = data.cursor;
-                data.text.replace_range(g.start .. cursor, "");
-                data.cursor = g.start;
+                (g.start .. data.cursor, false)
             };
+            let mut text = data.text.clone();
+            text.replace_range(range.clone(), "");
+            let Some(text) = data.check_edit(text, false) else { return; };
+            data.save_undo(false);
+            data.text = text;
+            data.cursor = range.start;
+            data.delete_char = delete_char;
             if let Some((left_p, view, _)) = data.view.clone() && *view.start() <= data.cursor {
                 (*view.start(), left_p)
             } else {
@@ -488,7 +862,7 @@ impl InputLine {
                 let with_end = view.contains(&data.text.len());
                 if with_end {
                     let text = &data.text[*view.start() .. *view.end()];
-                    let text_width = text_width(text).wrapping_add(1).wrapping_add(left_p).wrapping_add(right_p);
+                    let text_width = data.shown_text_width(text).wrapping_add(1).wrapping_add(left_p).wrapping_add(right_p);
                     if text_width < data.width {
                         Some(data.text.len())
                     } else {
@@ -502,37 +876,323 @@ impl InputLine {
             }
         };
         view_end.map(|x| Self::calc_view_start(this, x));
+        this.text_changed();
         this.invalidate_render();
     }
 
+    fn cursor_at(this: &Rc<dyn IsInputLine>, p: Point) {
+        let bounds = this.inner_render_bounds();
+        {
+            let mut data = this.input_line().data.borrow_mut();
+            let Some(text_start) = Self::text_start(&data, bounds) else { return; };
+            let view = data.view.clone().unwrap().1;
+            let with_end = view.contains(&data.text.len());
+            let text = if with_end {
+                &data.text[*view.start() .. *view.end()]
+            } else {
+                &data.text[view.clone()]
+            };
+            let mut x = text_start.x;
+            let mut cursor = None;
+            let mut last = *view.start();
+            for (g, g_w) in graphemes(text) {
+                last = *view.start() + g.start;
+                x = x.wrapping_add(data.shown_width(g_w));
+                if cursor.is_none() && p.x < x {
+                    cursor = Some(last);
+                }
+            }
+            data.cursor = cursor.unwrap_or(if with_end { data.text.len() } else { last });
+            data.delete_char = false;
+        }
+        this.invalidate_render();
+    }
+
+    fn cursor_to(this: &Rc<dyn IsInputLine>, cursor: usize) {
+        let (view_start, view_end) = {
+            let mut data = this.input_line().data.borrow_mut();
+            data.delete_char = false;
+            data.cursor = cursor;
+            match data.view.clone() {
+                Some((_, view, _)) if view.contains(&cursor) => (None, None),
+                Some((_, view, _)) if cursor > *view.end() => (None, Some(data.grapheme_end(cursor))),
+                _ => (Some(cursor), None),
+            }
+        };
+        view_start.map(|x| Self::calc_view_end(this, x, 0));
+        view_end.map(|x| Self::calc_view_start(this, x));
+        this.invalidate_render();
+    }
+
+    /// Starts or drops the selection before a cursor move.
+    fn start_move(this: &Rc<dyn IsInputLine>, select: bool) {
+        let deselected = {
+            let mut data = this.input_line().data.borrow_mut();
+            if select {
+                if data.anchor.is_none() { data.anchor = Some(data.cursor); }
+                false
+            } else {
+                data.anchor.take().is_some()
+            }
+        };
+        if deselected {
+            this.invalidate_render();
+        }
+    }
+
+    /// Recalculates the view after the text starting at `edit_start` changed.
+    fn update_view(this: &Rc<dyn IsInputLine>, edit_start: usize) {
+        let (view_start, left_p) = {
+            let data = this.input_line().data.borrow();
+            if let Some((left_p, view, _)) = data.view.clone() && *view.start() <= edit_start {
+                (*view.start(), left_p)
+            } else {
+                (min(edit_start, data.cursor), 0)
+            }
+        };
+        Self::calc_view_end(this, view_start, left_p);
+        let view_end = {
+            let data = this.input_line().data.borrow();
+            if let Some((left_p, view, right_p)) = data.view.clone() {
+                if !view.contains(&data.cursor) {
+                    Some(data.grapheme_end(data.cursor))
+                } else if view.contains(&data.text.len()) {
+                    let text = &data.text[*view.start() .. *view.end()];
+                    let text_width = data.shown_text_width(text).wrapping_add(1).wrapping_add(left_p).wrapping_add(right_p);
+                    if text_width < data.width { Some(data.text.len()) } else { None }
+                } else {
+                    None
+                }
+            } else {
+                None
+            }
+        };
+        view_end.map(|x| Self::calc_view_start(this, x));
+        this.invalidate_render();
+    }
+
+    fn edit(this: &Rc<dyn IsInputLine>, range: Range<usize>, text: &str, merge_undo: bool) {
+        {
+            let mut data = this.input_line().data.borrow_mut();
+            let mut new_text = data.text.clone();
+            new_text.replace_range(range.clone(), text);
+            let Some(new_text) = data.check_edit(new_text, !text.is_empty()) else { return; };
+            data.save_undo(merge_undo);
+            let at_end = range.end == data.text.len();
+            data.text = new_text;
+            data.cursor = if at_end {
+                data.text.len()
+            } else {
+                grapheme_start_after(&data.text, range.start + text.len())
+            };
+            data.anchor = None;
+            data.delete_char = false;
+        }
+        Self::update_view(this, range.start);
+        this.text_changed();
+    }
+
+    fn delete_selection(this: &Rc<dyn IsInputLine>, merge_undo: bool) -> bool {
+        let Some(selection) = this.input_line().data.borrow().selection() else { return false; };
+        Self::edit(this, selection, "", merge_undo);
+        true
+    }
+
+    fn delete_after_cursor(this: &Rc<dyn IsInputLine>) {
+        let range = {
+            let data = this.input_line().data.borrow();
+            let Some((g, _)) = graphemes(&data.text[data.cursor ..]).next() else { return; };
+            data.cursor .. data.cursor + g.end
+        };
+        Self::edit(this, range, "", false);
+    }
+
+    fn insert(this: &Rc<dyn IsInputLine>, text: &str) {
+        let mut text: String = text.chars()
+            .map(|c| if c == '\n' || c == '\t' { ' ' } else { c })
+            .filter(|&c| c != '\0' && c.width().is_some())
+            .collect();
+        let range = {
+            let data = this.input_line().data.borrow();
+            let range = data.selection().unwrap_or(data.cursor .. data.cursor);
+            if let Some(max_length) = data.max_length {
+                let kept = data.text.chars().count() - data.text[range.clone()].chars().count();
+                if let Some((end, _)) = text.char_indices().nth(max_length.saturating_sub(kept)) {
+                    text.truncate(end);
+                }
+            }
+            range
+        };
+        if text.is_empty() && range.is_empty() { return; }
+        Self::edit(this, range, &text, false);
+    }
+
+    fn drag_cursor(this: &Rc<dyn IsInputLine>, p: Point) {
+        let width = this.input_line().data.borrow().width;
+        if p.x < 1 {
+            Self::cursor_left(this);
+        } else if p.x >= width.wrapping_add(1) {
+            Self::cursor_right(this);
+        } else {
+            Self::cursor_at(this, p);
+        }
+    }
+
+    pub fn mouse_impl(this: &Rc<dyn IsView>, event: MouseEvent, original_source: &Rc<dyn IsView>) -> bool {
+        let app = this.app().unwrap();
+        let input_line: Rc<dyn IsInputLine> = dyn_cast_rc(this.clone()).unwrap();
+        match event.action {
+            MouseAction::Down(MouseButton::Left) => {
+                app.focus(Some(this), None);
+                app.capture_mouse(Some(this));
+                input_line.input_line().data.borrow_mut().undo_merge = false;
+                Self::start_move(&input_line, event.modifiers.contains(Modifiers::SHIFT));
+                Self::cursor_at(&input_line, event.point);
+                true
+            },
+            MouseAction::Drag(MouseButton::Left) => {
+                let captured = app.mouse_capture().is_some_and(|x| addr_eq(Rc::as_ptr(&x), Rc::as_ptr(this)));
+                if !captured { return View::mouse_impl(this, event, original_source); }
+                Self::start_move(&input_line, true);
+                Self::drag_cursor(&input_line, event.point);
+                true
+            },
+            MouseAction::Up(MouseButton::Left) => {
+                let captured = app.mouse_capture().is_some_and(|x| addr_eq(Rc::as_ptr(&x), Rc::as_ptr(this)));
+                if !captured { return View::mouse_impl(this, event, original_source); }
+                app.capture_mouse(None);
+                true
+            },
+            MouseAction::DoubleClick(MouseButton::Left) => {
+                let word = {
+                    let data = input_line.input_line().data.borrow();
+                    word_at(&data.text, data.cursor)
+                };
+                Self::start_move(&input_line, false);
+                input_line.input_line().data.borrow_mut().anchor = Some(word.start);
+                Self::cursor_to(&input_line, word.end);
+                true
+            },
+            _ => View::mouse_impl(this, event, original_source)
+        }
+    }
+
+    pub fn paste_impl(this: &Rc<dyn IsView>, text: &str, _original_source: &Rc<dyn IsView>) -> bool {
+        let this: Rc<dyn IsInputLine> = dyn_cast_rc(this.clone()).unwrap();
+        Self::insert(&this, text);
+        true
+    }
+
     pub fn key_impl(this: &Rc<dyn IsView>, key: Key, original_source: &Rc<dyn IsView>) -> bool {
+        let input_line: Rc<dyn IsInputLine> = dyn_cast_rc(this.clone()).unwrap();
+        if !matches!(key, Key::Char(_)) {
+            input_line.input_line().data.borrow_mut().undo_merge = false;
+        }
         match key {
             Key::Left => {
-                let this: Rc<dyn IsInputLine> = dyn_cast_rc(this.clone()).unwrap();
-                if Self::cursor_left(&this) { return true; }
+                Self::start_move(&input_line, false);
+                if Self::cursor_left(&input_line) { return true; }
             },
             Key::Right => {
-                let this: Rc<dyn IsInputLine> = dyn_cast_rc(this.clone()).unwrap();
-                if Self::cursor_right(&this) { return true; }
-            }, 
+                Self::start_move(&input_line, false);
+                if Self::cursor_right(&input_line) { return true; }
+            },
             Key::Home => {
-                let this: Rc<dyn IsInputLine> = dyn_cast_rc(this.clone()).unwrap();
-                Self::cursor_home(&this);
+                Self::start_move(&input_line, false);
+                Self::cursor_home(&input_line);
                 return true;
             },
             Key::End => {
-                let this: Rc<dyn IsInputLine> = dyn_cast_rc(this.clone()).unwrap();
-                Self::cursor_end(&this);
+                Self::start_move(&input_line, false);
+                Self::cursor_end(&input_line);
+                return true;
+            },
+            Key::Modified(m, SpecialKey::Left) if m == Modifiers::SHIFT => {
+                Self::start_move(&input_line, true);
+                Self::cursor_left(&input_line);
+                return true;
+            },
+            Key::Modified(m, SpecialKey::Right) if m == Modifiers::SHIFT => {
+                Self::start_move(&input_line, true);
+                Self::cursor_right(&input_line);
+                return true;
+            },
+            Key::Modified(m, SpecialKey::Home) if m == Modifiers::SHIFT => {
+                Self::start_move(&input_line, true);
+                Self::cursor_home(&input_line);
+                return true;
+            },
+            Key::Modified(m, SpecialKey::End) if m == Modifiers::SHIFT => {
+                Self::start_move(&input_line, true);
+                Self::cursor_end(&input_line);
+                return true;
+            },
+            Key::Modified(m, k @ (SpecialKey::Left | SpecialKey::Right)) if m - Modifiers::SHIFT == Modifiers::CTRL => {
+                Self::start_move(&input_line, m.contains(Modifiers::SHIFT));
+                let cursor = {
+                    let data = input_line.input_line().data.borrow();
+                    if k == SpecialKey::Left { word_left(&data.text, data.cursor) } else { word_right(&data.text, data.cursor) }
+                };
+                Self::cursor_to(&input_line, cursor);
                 return true;
             },
             Key::Char(c) => {
-                let this: Rc<dyn IsInputLine> = dyn_cast_rc(this.clone()).unwrap();
-                Self::type_char(&this, c);
+                let selection = input_line.input_line().data.borrow().selection();
+                if let Some(selection) = selection {
+                    if c != '\0' && c.width().is_some() {
+                        Self::edit(&input_line, selection, c.encode_utf8(&mut [0; 4]), true);
+                    }
+                } else {
+                    Self::type_char(&input_line, c);
+                }
                 return true;
             },
             Key::Backspace => {
-                let this: Rc<dyn IsInputLine> = dyn_cast_rc(this.clone()).unwrap();
-                Self::delete_before_cursor(&this);
+                if !Self::delete_selection(&input_line, false) {
+                    Self::delete_before_cursor(&input_line);
+                }
+                return true;
+            },
+            Key::Delete => {
+                if !Self::delete_selection(&input_line, false) {
+                    Self::delete_after_cursor(&input_line);
+                }
+                return true;
+            },
+            Key::Ctrl(Ctrl::A) => {
+                input_line.select_all();
+                return true;
+            },
+            Key::Ctrl(Ctrl::C) => {
+                input_line.copy_to_clipboard();
+                return true;
+            },
+            Key::Modified(m, SpecialKey::Insert) if m == Modifiers::CTRL => {
+                input_line.copy_to_clipboard();
+                return true;
+            },
+            Key::Ctrl(Ctrl::X) => {
+                input_line.cut_to_clipboard();
+                return true;
+            },
+            Key::Modified(m, SpecialKey::Delete) if m == Modifiers::SHIFT => {
+                input_line.cut_to_clipboard();
+                return true;
+            },
+            Key::Ctrl(Ctrl::V) => {
+                input_line.paste_from_clipboard();
+                return true;
+            },
+            Key::Modified(m, SpecialKey::Insert) if m == Modifiers::SHIFT => {
+                input_line.paste_from_clipboard();
+                return true;
+            },
+            Key::Ctrl(Ctrl::Z) => {
+                input_line.undo();
+                return true;
+            },
+            Key::Ctrl(Ctrl::Y) => {
+                input_line.redo();
                 return true;
             },
             _ => { },
@@ -564,6 +1224,18 @@ macro_rules! input_line_template {
                 pub is_numeric: Option<bool>,
                 #[serde(default)]
                 #[serde(skip_serializing_if="Option::is_none")]
+                pub max_length: Option<usize>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub password_char: Option<char>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub picture: Option<String>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub picture_auto_fill: Option<bool>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
                 pub color: Option<($crate::base::Fg, $crate::base::Bg)>,
                 #[serde(default)]
                 #[serde(skip_serializing_if="Option::is_none")]
@@ -574,6 +1246,12 @@ macro_rules! input_line_template {
                 #[serde(default)]
                 #[serde(skip_serializing_if="Option::is_none")]
                 pub color_disabled: Option<($crate::base::Fg, $crate::base::Bg)>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color_selected: Option<($crate::base::Fg, $crate::base::Bg)>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color_invalid: Option<($crate::base::Fg, $crate::base::Bg)>,
                 $($(
                     $(#[$field_attr])*
                     pub $field_name : $field_ty
@@ -593,9 +1271,24 @@ macro_rules! input_line_apply_template {
             let obj: $crate::alloc_rc_Rc<dyn $crate::input_line::IsInputLine>
                 = $crate::dynamic_cast_dyn_cast_rc($instance.clone()).unwrap();
             $this.is_numeric.map(|x| obj.set_is_numeric(x));
+            $this.max_length.map(|x| obj.set_max_length(Some(x)));
+            $this.password_char.map(|x| obj.set_password_char(Some(x)));
+            if let Some(picture) = $this.picture.as_ref() {
+                let auto_fill = $this.picture_auto_fill.unwrap_or(false);
+                match $crate::validator::PictureValidator::try_new(picture, auto_fill) {
+                    Ok(validator) => {
+                        let validator: $crate::alloc_rc_Rc<dyn $crate::validator::Validator>
+                            = $crate::alloc_rc_Rc::new(validator);
+                        obj.set_validator(Some(validator));
+                    },
+                    Err(_) => eprintln!("Warning: invalid picture ('{picture}')"),
+                }
+            }
             $this.color.map(|x| obj.set_color(x));
             $this.color_focused.map(|x| obj.set_color_focused(x));
             $this.color_disabled.map(|x| obj.set_color_disabled(x));
+            $this.color_selected.map(|x| obj.set_color_selected(x));
+            $this.color_invalid.map(|x| obj.set_color_invalid(x));
         }
     };
 }
@@ -2,12 +2,14 @@ use basic_oop::{class_unsafe, import, Vtable};
 use dynamic_cast::dyn_cast_rc;
 use std::cell::RefCell;
 use std::mem::replace;
+use std::ops::Range;
 use std::ptr::addr_eq;
 use crate::decorator::{IsDecorator, DecoratorExt, DecoratorTemplate};
 use crate::panel::{IsPanel, PanelExt};
 use crate::stack_panel::StackPanelTemplate;
 use crate::template::{NameResolver, Names};
 use crate::view_vec::ViewVecExt;
+use crate::virtualizing_stack_panel::{IsVirtualizingStackPanel, VirtualizingStackPanelExt};
 
 import! { pub items_control:
     use [control crate::control];
@@ -18,8 +20,16 @@ struct ItemsControlData {
     item_template: Rc<dyn Template>,
     loaded_item_templates: Vec<Rc<dyn IsView>>,
     panel_template: Rc<dyn Template>,
+    panel: Option<Rc<dyn IsPanel>>,
 }
 
+/// A control showing `items_count` views loaded from the item template in a panel loaded from the panel template.
+///
+/// Item views are bound to their indices with the `bind` event, and the `unbind` event is raised
+/// before a view is dropped. Changing the items count binds or unbinds only the added or removed items.
+///
+/// With a [`VirtualizingStackPanel`](crate::virtualizing_stack_panel::VirtualizingStackPanel)
+/// panel template views are realized only for the shown items, and reused for other items on scrolling.
 #[class_unsafe(inherits_Control)]
 pub struct ItemsControl {
     data: RefCell<ItemsControlData>,
@@ -28,6 +38,14 @@ pub struct ItemsControl {
     #[non_virt]
     set_items_count: fn(value: usize),
     #[non_virt]
+    item_view: fn(index: usize) -> Option<Rc<dyn IsView>>,
+    #[non_virt]
+    realized_items: fn() -> Range<usize>,
+    #[non_virt]
+    items_panel: fn() -> Option<Rc<dyn IsPanel>>,
+    #[virt]
+    items_count_changed: fn(),
+    #[non_virt]
     item_template: fn() -> Rc<dyn Template>,
     #[non_virt]
     set_item_template: fn(value: Rc<dyn Template>),
@@ -57,6 +75,7 @@ impl ItemsControl {
                 item_template: Rc::new(ViewTemplate::default()),
                 loaded_item_templates: Vec::new(),
                 panel_template: Rc::new(StackPanelTemplate::default()),
+                panel: None,
             }),
         }
     }
@@ -67,26 +86,34 @@ impl ItemsControl {
             = dyn_cast_rc(
                 template.find("PART_ItemsPresenter").expect("PART_ItemsPresenter").clone()
             ).expect("PART_ItemsPresenter: Decorator");
-        let (panel, old_loaded_item_templates, new_loaded_item_templates) = {
+        let (panel, old_panel, old_loaded_item_templates, new_loaded_item_templates) = {
             let mut data = this.items_control().data.borrow_mut();
             let panel: Rc<dyn IsPanel> = dyn_cast_rc(data.panel_template.load_root().0).expect("Panel");
-            let new_loaded_item_templates: Vec<Rc<dyn IsView>>
-                = (0 .. data.items_count)
+            let virtualizing: Option<Rc<dyn IsVirtualizingStackPanel>> = dyn_cast_rc(panel.clone());
+            let new_loaded_item_templates: Vec<Rc<dyn IsView>> = if virtualizing.is_some() {
+                Vec::new()
+            } else {
+                (0 .. data.items_count)
                     .map(|_| dyn_cast_rc(data.item_template.load_root().0).expect("View")).collect()
-                ;
+            };
             let old_loaded_item_templates
                 = replace(&mut data.loaded_item_templates, new_loaded_item_templates.clone());
-            (panel, old_loaded_item_templates, new_loaded_item_templates)
+            let old_panel = replace(&mut data.panel, Some(panel.clone()));
+            (panel, old_panel, old_loaded_item_templates, new_loaded_item_templates)
         };
+        let old_virtualizing: Option<Rc<dyn IsVirtualizingStackPanel>> = old_panel.and_then(dyn_cast_rc);
+        old_virtualizing.map(|x| x._set_items_control(None));
         for (i, old_loaded_item_template) in old_loaded_item_templates.into_iter().enumerate() {
             this._raise_unbind(&old_loaded_item_template, i);
         }
         let panel_children = panel.children();
-        part_items_presenter.set_child(Some(panel));
+        part_items_presenter.set_child(Some(panel.clone()));
         for (i, new_loaded_item_template) in new_loaded_item_templates.into_iter().enumerate() {
             panel_children.push(new_loaded_item_template.clone());
             this._raise_bind(&new_loaded_item_template, i);
         }
+        let virtualizing: Option<Rc<dyn IsVirtualizingStackPanel>> = dyn_cast_rc(panel);
+        virtualizing.map(|x| x._set_items_control(Some(&this)));
     }
 
     pub fn items_count_impl(this: &Rc<dyn IsItemsControl>) -> usize {
@@ -94,12 +121,69 @@ impl ItemsControl {
     }
 
     pub fn set_items_count_impl(this: &Rc<dyn IsItemsControl>, value: usize) {
-        {
+        let (old_value, panel) = {
             let mut data = this.items_control().data.borrow_mut();
             if data.items_count == value { return; }
-            data.items_count = value;
+            (replace(&mut data.items_count, value), data.panel.clone())
         };
-        this.update();
+        let Some(panel) = panel else {
+            this.update();
+            return;
+        };
+        let virtualizing: Option<Rc<dyn IsVirtualizingStackPanel>> = dyn_cast_rc(panel.clone());
+        if let Some(virtualizing) = virtualizing {
+            virtualizing._items_count_changed();
+        } else if value > old_value {
+            let panel_children = panel.children();
+            for index in old_value .. value {
+                let item: Rc<dyn IsView> = {
+                    let mut data = this.items_control().data.borrow_mut();
+                    let item: Rc<dyn IsView> = dyn_cast_rc(data.item_template.load_root().0).expect("View");
+                    data.loaded_item_templates.push(item.clone());
+                    item
+                };
+                panel_children.push(item.clone());
+                this._raise_bind(&item, index);
+            }
+        } else {
+            let panel_children = panel.children();
+            for index in (value .. old_value).rev() {
+                let item = this.items_control().data.borrow_mut().loaded_item_templates.pop().unwrap();
+                this._raise_unbind(&item, index);
+                panel_children.pop();
+            }
+        }
+        this.items_count_changed();
+    }
+
+    /// Called after the items count is changed with the item template already applied.
+    pub fn items_count_changed_impl(_this: &Rc<dyn IsItemsControl>) { }
+
+    /// The view loaded from the item template for the item with the specified index.
+    ///
+    /// Returns `None` for items not realized by a virtualizing panel.
+    pub fn item_view_impl(this: &Rc<dyn IsItemsControl>, index: usize) -> Option<Rc<dyn IsView>> {
+        if let Some(virtualizing) = Self::virtualizing_panel(this) {
+            return virtualizing.realized_item(index);
+        }
+        this.items_control().data.borrow().loaded_item_templates.get(index).cloned()
+    }
+
+    /// The indices of the items having views.
+    pub fn realized_items_impl(this: &Rc<dyn IsItemsControl>) -> Range<usize> {
+        if let Some(virtualizing) = Self::virtualizing_panel(this) {
+            return virtualizing.realized_items();
+        }
+        0 .. this.items_control().data.borrow().loaded_item_templates.len()
+    }
+
+    /// The panel loaded from the panel template, if the control template is applied.
+    pub fn items_panel_impl(this: &Rc<dyn IsItemsControl>) -> Option<Rc<dyn IsPanel>> {
+        this.items_control().data.borrow().panel.clone()
+    }
+
+    fn virtualizing_panel(this: &Rc<dyn IsItemsControl>) -> Option<Rc<dyn IsVirtualizingStackPanel>> {
+        this.items_panel().and_then(dyn_cast_rc)
     }
 
     pub fn item_template_impl(this: &Rc<dyn IsItemsControl>) -> Rc<dyn Template> {
@@ -16,14 +16,22 @@ pub mod obj_col;
 pub mod template;
 pub mod render_port;
 pub mod app;
+pub mod clipboard;
 pub mod event_handler;
+pub mod command;
 pub mod view;
 pub mod view_vec;
 pub mod panel;
 pub mod decorator;
+pub mod scroll_bar;
+pub mod scroll_viewer;
 pub mod stack_panel;
+pub mod wrap_panel;
+pub mod uniform_grid;
+pub mod virtualizing_stack_panel;
 pub mod canvas;
 pub mod dock_panel;
+pub mod grid;
 pub mod static_text;
 pub mod check_box;
 pub mod radio_button;
@@ -38,5 +46,14 @@ pub mod border;
 pub mod pile_panel;
 pub mod adorners_panel;
 pub mod padding;
+pub mod validator;
 pub mod input_line;
+pub mod text_editor;
 pub mod items_control;
+pub mod list_box;
+pub mod combo_box;
+pub mod window;
+pub mod desktop;
+pub mod menu;
+pub mod menu_bar;
+pub mod status_bar;
@@ -0,0 +1,737 @@
+use basic_oop::{class_unsafe, import, Vtable};
+use dynamic_cast::dyn_cast_rc;
+use serde::{Serialize, Deserialize};
+use std::cell::RefCell;
+use std::cmp::{max, min};
+use std::collections::BTreeSet;
+use std::ptr::addr_eq;
+use crate::app::AppExt;
+use crate::base::{Modifiers, MouseAction, MouseButton};
+use crate::event_handler::EventHandler;
+use crate::static_text::{IsStaticText, StaticTextExt};
+use crate::template::{NameResolver, Names};
+use crate::view::Visibility;
+use crate::virtualizing_stack_panel::{IsVirtualizingStackPanel, VirtualizingStackPanelExt};
+
+import! { pub list_box:
+    use [items_control crate::items_control];
+    use crate::base::{Fg, Bg};
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Serialize, Deserialize)]
+pub enum SelectionMode { Single, Multiple }
+
+const WHEEL_ITEMS: usize = 3;
+
+struct ListBoxData {
+    selection_mode: SelectionMode,
+    current: Option<usize>,
+    selected: BTreeSet<usize>,
+    top: usize,
+    height: i16,
+    search: String,
+    color: (Fg, Bg),
+    color_selected: (Fg, Bg),
+    color_focused: (Fg, Bg),
+    color_disabled: (Fg, Bg),
+    selection_change_handler: EventHandler<Option<Box<dyn FnMut()>>>,
+    item_activate_handler: EventHandler<Option<Box<dyn FnMut(usize)>>>,
+    item_text_handler: EventHandler<Option<Box<dyn FnMut(usize) -> Option<String>>>>,
+}
+
+/// A list of items loaded from the item template, with the current item and selected items.
+///
+/// In the `Single` selection mode the current item is the only selected one.
+/// In the `Multiple` mode Space and Ctrl+click toggle the current item selection.
+///
+/// Item views are colored with [`set_item_color`](ListBox::set_item_color_impl),
+/// and searched by [`item_text`](ListBox::item_text_impl) typing the text start.
+/// Both support `StaticText` items out of the box; for a virtualizing panel,
+/// supply the item text from the data with [`handle_item_text`](ListBox::handle_item_text_impl).
+///
+/// The list scrolls by collapsing items above the top one,
+/// or by changing the offset of a `VirtualizingStackPanel` panel.
+/// A `VirtualizingStackPanel` realizes items only within a limited height, so it can not be placed
+/// in a `ScrollViewer`; a `ScrollBar` can follow its scroll state instead,
+/// see [`VirtualizingStackPanel`](crate::virtualizing_stack_panel::VirtualizingStackPanel).
+#[class_unsafe(inherits_ItemsControl)]
+pub struct ListBox {
+    data: RefCell<ListBoxData>,
+    #[non_virt]
+    selection_mode: fn() -> SelectionMode,
+    #[non_virt]
+    set_selection_mode: fn(value: SelectionMode),
+    #[non_virt]
+    current_index: fn() -> Option<usize>,
+    #[non_virt]
+    set_current_index: fn(value: Option<usize>),
+    #[non_virt]
+    selected_index: fn() -> Option<usize>,
+    #[non_virt]
+    selected_indices: fn() -> Vec<usize>,
+    #[non_virt]
+    is_selected: fn(index: usize) -> bool,
+    #[non_virt]
+    set_is_selected: fn(index: usize, value: bool),
+    #[non_virt]
+    color: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color: fn(value: (Fg, Bg)),
+    #[non_virt]
+    color_selected: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color_selected: fn(value: (Fg, Bg)),
+    #[non_virt]
+    color_focused: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color_focused: fn(value: (Fg, Bg)),
+    #[non_virt]
+    color_disabled: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color_disabled: fn(value: (Fg, Bg)),
+    #[non_virt]
+    handle_selection_change: fn(handler: Option<Box<dyn FnMut()>>),
+    #[virt]
+    selection_changed: fn(),
+    #[non_virt]
+    handle_item_activate: fn(handler: Option<Box<dyn FnMut(usize)>>),
+    #[virt]
+    item_activated: fn(index: usize),
+    #[non_virt]
+    handle_item_text: fn(handler: Option<Box<dyn FnMut(usize) -> Option<String>>>),
+    #[virt]
+    item_text: fn(index: usize) -> Option<String>,
+    #[virt]
+    set_item_color: fn(item: &Rc<dyn IsView>, color: (Fg, Bg)),
+    #[over]
+    _init: (),
+    #[over]
+    update_override: (),
+    #[over]
+    items_count_changed: (),
+    #[over]
+    bind: (),
+    #[over]
+    is_enabled_changed: (),
+    #[over]
+    is_focused_changed: (),
+    #[over]
+    arrange_override: (),
+    #[over]
+    key: (),
+    #[over]
+    mouse: (),
+}
+
+impl ListBox {
+    pub fn new() -> Rc<dyn IsListBox> {
+        let res: Rc<dyn IsListBox> = Rc::new(unsafe { Self::new_raw(LIST_BOX_VTABLE.as_ptr()) });
+        res._init();
+        res
+    }
+
+    pub unsafe fn new_raw(vtable: Vtable) -> Self {
+        ListBox {
+            items_control: unsafe { ItemsControl::new_raw(vtable) },
+            data: RefCell::new(ListBoxData {
+                selection_mode: SelectionMode::Single,
+                current: None,
+                selected: BTreeSet::new(),
+                top: 0,
+                height: 0,
+                search: String::new(),
+                color: (Fg::Black, Bg::Cyan),
+                color_selected: (Fg::Yellow, Bg::Cyan),
+                color_focused: (Fg::White, Bg::Green),
+                color_disabled: (Fg::DarkGray, Bg::Cyan),
+                selection_change_handler: Default::default(),
+                item_activate_handler: Default::default(),
+                item_text_handler: Default::default(),
+            }),
+        }
+    }
+
+    pub fn _init_impl(this: &Rc<dyn IsView>) {
+        View::_init_impl(this);
+        this.set_allow_focus(true);
+    }
+
+    pub fn update_override_impl(this: &Rc<dyn IsControl>, template: &Names) {
+        ItemsControl::update_override_impl(this, template);
+        let this: Rc<dyn IsListBox> = dyn_cast_rc(this.clone()).unwrap();
+        Self::items_changed(&this);
+    }
+
+    pub fn items_count_changed_impl(this: &Rc<dyn IsItemsControl>) {
+        ItemsControl::items_count_changed_impl(this);
+        let this: Rc<dyn IsListBox> = dyn_cast_rc(this.clone()).unwrap();
+        let top = Self::top(&this);
+        this.list_box().data.borrow_mut().top = top;
+        Self::items_changed(&this);
+    }
+
+    fn items_changed(this: &Rc<dyn IsListBox>) {
+        let count = this.items_count();
+        let selection_changed = {
+            let mut data = this.list_box().data.borrow_mut();
+            let old_selected_count = data.selected.len();
+            data.selected.retain(|&x| x < count);
+            let old_current = data.current;
+            data.current = data.current.and_then(|x| if count == 0 { None } else { Some(min(x, count - 1)) });
+            if data.selection_mode == SelectionMode::Single {
+                data.selected = data.current.into_iter().collect();
+            }
+            data.top = min(data.top, count.saturating_sub(1));
+            data.current != old_current || data.selected.len() != old_selected_count
+        };
+        Self::update_top(this);
+        Self::update_colors(this);
+        if selection_changed {
+            this.selection_changed();
+        }
+    }
+
+    pub fn bind_impl(
+        this: &Rc<dyn IsView>,
+        item: &Rc<dyn IsView>,
+        index: usize,
+        original_source: &Rc<dyn IsView>
+    ) -> bool {
+        let handled = View::bind_impl(this, item, index, original_source);
+        if addr_eq(Rc::as_ptr(original_source), Rc::as_ptr(this)) {
+            let this: Rc<dyn IsListBox> = dyn_cast_rc(this.clone()).unwrap();
+            this.set_item_color(item, Self::item_color(&this, index));
+        }
+        handled
+    }
+
+    pub fn is_enabled_changed_impl(this: &Rc<dyn IsView>) {
+        View::is_enabled_changed_impl(this);
+        let this: Rc<dyn IsListBox> = dyn_cast_rc(this.clone()).unwrap();
+        Self::update_colors(&this);
+    }
+
+    pub fn is_focused_changed_impl(this: &Rc<dyn IsView>, primary_focus: bool) {
+        View::is_focused_changed_impl(this, primary_focus);
+        let this: Rc<dyn IsListBox> = dyn_cast_rc(this.clone()).unwrap();
+        this.list_box().data.borrow_mut().search.clear();
+        Self::update_colors(&this);
+    }
+
+    pub fn arrange_override_impl(this: &Rc<dyn IsView>, bounds: Rect) -> Vector {
+        let size = Control::arrange_override_impl(this, bounds);
+        let this: Rc<dyn IsListBox> = dyn_cast_rc(this.clone()).unwrap();
+        let height_changed = {
+            let mut data = this.list_box().data.borrow_mut();
+            let height_changed = data.height != bounds.h();
+            data.height = bounds.h();
+            height_changed
+        };
+        if height_changed && let Some(current) = this.current_index() {
+            Self::scroll_into_view(&this, current);
+        }
+        size
+    }
+
+    pub fn selection_mode_impl(this: &Rc<dyn IsListBox>) -> SelectionMode {
+        this.list_box().data.borrow().selection_mode
+    }
+
+    pub fn set_selection_mode_impl(this: &Rc<dyn IsListBox>, value: SelectionMode) {
+        let selection_changed = {
+            let mut data = this.list_box().data.borrow_mut();
+            if data.selection_mode == value { return; }
+            data.selection_mode = value;
+            if value == SelectionMode::Single {
+                let selected: BTreeSet<usize> = data.current.into_iter().collect();
+                let selection_changed = selected != data.selected;
+                data.selected = selected;
+                selection_changed
+            } else {
+                false
+            }
+        };
+        if selection_changed {
+            Self::update_colors(this);
+            this.selection_changed();
+        }
+    }
+
+    pub fn current_index_impl(this: &Rc<dyn IsListBox>) -> Option<usize> {
+        this.list_box().data.borrow().current
+    }
+
+    /// Sets the current item, and scrolls the list to show it.
+    /// In the `Single` selection mode it selects the item as well.
+    pub fn set_current_index_impl(this: &Rc<dyn IsListBox>, value: Option<usize>) {
+        assert!(value.is_none_or(|x| x < this.items_count()), "invalid item index");
+        let selection_changed = {
+            let mut data = this.list_box().data.borrow_mut();
+            if data.current == value { return; }
+            data.current = value;
+            data.selection_mode == SelectionMode::Single
+        };
+        if selection_changed {
+            this.list_box().data.borrow_mut().selected = value.into_iter().collect();
+        }
+        if let Some(value) = value {
+            Self::scroll_into_view(this, value);
+        }
+        Self::update_colors(this);
+        if selection_changed {
+            this.selection_changed();
+        }
+    }
+
+    /// The first selected item.
+    pub fn selected_index_impl(this: &Rc<dyn IsListBox>) -> Option<usize> {
+        this.list_box().data.borrow().selected.first().copied()
+    }
+
+    pub fn selected_indices_impl(this: &Rc<dyn IsListBox>) -> Vec<usize> {
+        this.list_box().data.borrow().selected.iter().copied().collect()
+    }
+
+    pub fn is_selected_impl(this: &Rc<dyn IsListBox>, index: usize) -> bool {
+        this.list_box().data.borrow().selected.contains(&index)
+    }
+
+    /// Selects or deselects the item. In the `Single` selection mode selecting the item makes it current.
+    pub fn set_is_selected_impl(this: &Rc<dyn IsListBox>, index: usize, value: bool) {
+        assert!(index < this.items_count(), "invalid item index");
+        if this.selection_mode() == SelectionMode::Single {
+            if value {
+                this.set_current_index(Some(index));
+            } else if this.current_index() == Some(index) {
+                this.set_current_index(None);
+            }
+            return;
+        }
+        {
+            let mut data = this.list_box().data.borrow_mut();
+            let changed = if value { data.selected.insert(index) } else { data.selected.remove(&index) };
+            if !changed { return; }
+        }
+        Self::update_colors(this);
+        this.selection_changed();
+    }
+
+    pub fn color_impl(this: &Rc<dyn IsListBox>) -> (Fg, Bg) {
+        this.list_box().data.borrow().color
+    }
+
+    pub fn set_color_impl(this: &Rc<dyn IsListBox>, value: (Fg, Bg)) {
+        {
+            let mut data = this.list_box().data.borrow_mut();
+            if data.color == value { return; }
+            data.color = value;
+        }
+        Self::update_colors(this);
+    }
+
+    pub fn color_selected_impl(this: &Rc<dyn IsListBox>) -> (Fg, Bg) {
+        this.list_box().data.borrow().color_selected
+    }
+
+    pub fn set_color_selected_impl(this: &Rc<dyn IsListBox>, value: (Fg, Bg)) {
+        {
+            let mut data = this.list_box().data.borrow_mut();
+            if data.color_selected == value { return; }
+            data.color_selected = value;
+        }
+        Self::update_colors(this);
+    }
+
+    /// The current item color when the list box is focused.
+    pub fn color_focused_impl(this: &Rc<dyn IsListBox>) -> (Fg, Bg) {
+        this.list_box().data.borrow().color_focused
+    }
+
+    pub fn set_color_focused_impl(this: &Rc<dyn IsListBox>, value: (Fg, Bg)) {
+        {
+            let mut data = this.list_box().data.borrow_mut();
+            if data.color_focused == value { return; }
+            data.color_focused = value;
+        }
+        Self::update_colors(this);
+    }
+
+    pub fn color_disabled_impl(this: &Rc<dyn IsListBox>) -> (Fg, Bg) {
+        this.list_box().data.borrow().color_disabled
+    }
+
+    pub fn set_color_disabled_impl(this: &Rc<dyn IsListBox>, value: (Fg, Bg)) {
+        {
+            let mut data = this.list_box().data.borrow_mut();
+            if data.color_disabled == value { return; }
+            data.color_disabled = value;
+        }
+        Self::update_colors(this);
+    }
+
+    pub fn handle_selection_change_impl(this: &Rc<dyn IsListBox>, handler: Option<Box<dyn FnMut()>>) {
+        this.list_box().data.borrow_mut().selection_change_handler.set(handler);
+    }
+
+    pub fn selection_changed_impl(this: &Rc<dyn IsListBox>) {
+        let mut invoke = this.list_box().data.borrow_mut().selection_change_handler.begin_invoke();
+        invoke.as_mut().map(|x| x());
+        this.list_box().data.borrow_mut().selection_change_handler.end_invoke(invoke);
+    }
+
+    pub fn handle_item_activate_impl(this: &Rc<dyn IsListBox>, handler: Option<Box<dyn FnMut(usize)>>) {
+        this.list_box().data.borrow_mut().item_activate_handler.set(handler);
+    }
+
+    /// Called on Enter or double click.
+    pub fn item_activated_impl(this: &Rc<dyn IsListBox>, index: usize) {
+        let mut invoke = this.list_box().data.borrow_mut().item_activate_handler.begin_invoke();
+        invoke.as_mut().map(|x| x(index));
+        this.list_box().data.borrow_mut().item_activate_handler.end_invoke(invoke);
+    }
+
+    /// Sets the handler returning the item text by the item index, used instead of the item views,
+    /// which do not exist for items not realized by a virtualizing panel.
+    pub fn handle_item_text_impl(this: &Rc<dyn IsListBox>, handler: Option<Box<dyn FnMut(usize) -> Option<String>>>) {
+        this.list_box().data.borrow_mut().item_text_handler.set(handler);
+    }
+
+    /// The item text used by the type-ahead search. Returns the item text handler result if the handler is set,
+    /// or `StaticText` text for realized `StaticText` items.
+    pub fn item_text_impl(this: &Rc<dyn IsListBox>, index: usize) -> Option<String> {
+        let mut invoke = this.list_box().data.borrow_mut().item_text_handler.begin_invoke();
+        let text = invoke.as_mut().map(|x| x(index));
+        this.list_box().data.borrow_mut().item_text_handler.end_invoke(invoke);
+        if let Some(text) = text { return text; }
+        let item: Rc<dyn IsStaticText> = dyn_cast_rc(this.item_view(index)?)?;
+        Some(item.text().to_string())
+    }
+
+    /// Applies the item color depending on its state. Sets `StaticText` color for `StaticText` items.
+    pub fn set_item_color_impl(_this: &Rc<dyn IsListBox>, item: &Rc<dyn IsView>, color: (Fg, Bg)) {
+        let item: Option<Rc<dyn IsStaticText>> = dyn_cast_rc(item.clone());
+        if let Some(item) = item {
+            item.set_color(color);
+        }
+    }
+
+    fn item_color(this: &Rc<dyn IsListBox>, index: usize) -> (Fg, Bg) {
+        let is_enabled = this.is_enabled();
+        let is_focused = this.is_focused(None);
+        let data = this.list_box().data.borrow();
+        if !is_enabled {
+            data.color_disabled
+        } else if is_focused && data.current == Some(index) {
+            data.color_focused
+        } else if data.selected.contains(&index) {
+            data.color_selected
+        } else {
+            data.color
+        }
+    }
+
+    fn update_colors(this: &Rc<dyn IsListBox>) {
+        for index in this.realized_items() {
+            let Some(item) = this.item_view(index) else { break; };
+            this.set_item_color(&item, Self::item_color(this, index));
+        }
+    }
+
+    /// Without a virtualizing panel all items are realized, so their heights are known.
+    fn item_height(this: &Rc<dyn IsListBox>, index: usize) -> i16 {
+        this.item_view(index).map_or(1, |x| max(x.desired_size().y, 1))
+    }
+
+    fn virtualizing_panel(this: &Rc<dyn IsListBox>) -> Option<Rc<dyn IsVirtualizingStackPanel>> {
+        this.items_panel().and_then(dyn_cast_rc)
+    }
+
+    fn top(this: &Rc<dyn IsListBox>) -> usize {
+        Self::virtualizing_panel(this).map_or_else(|| this.list_box().data.borrow().top, |x| x.offset())
+    }
+
+    fn update_top(this: &Rc<dyn IsListBox>) {
+        let top = this.list_box().data.borrow().top;
+        if let Some(panel) = Self::virtualizing_panel(this) {
+            panel.set_offset(top);
+            return;
+        }
+        for index in this.realized_items() {
+            let Some(item) = this.item_view(index) else { break; };
+            item.set_visibility(if index < top { Visibility::Collapsed } else { Visibility::Visible });
+        }
+    }
+
+    fn scroll_into_view(this: &Rc<dyn IsListBox>, index: usize) {
+        if let Some(panel) = Self::virtualizing_panel(this) {
+            panel.scroll_into_view(index);
+            this.list_box().data.borrow_mut().top = panel.offset();
+            return;
+        }
+        let mut top = Self::top(this);
+        let height = this.list_box().data.borrow().height;
+        if index < top {
+            top = index;
+        } else if height > 0 {
+            let mut rows = (top ..= index).fold(0i16, |h, x| h.saturating_add(Self::item_height(this, x)));
+            while rows > height && top < index {
+                rows -= Self::item_height(this, top);
+                top += 1;
+            }
+        }
+        Self::set_top(this, top);
+    }
+
+    fn set_top(this: &Rc<dyn IsListBox>, top: usize) {
+        if Self::top(this) == top { return; }
+        this.list_box().data.borrow_mut().top = top;
+        Self::update_top(this);
+    }
+
+    /// The number of items fitting in the list box starting from `top`.
+    fn page_size(this: &Rc<dyn IsListBox>, top: usize) -> usize {
+        if let Some(panel) = Self::virtualizing_panel(this) {
+            return panel.viewport();
+        }
+        let height = this.list_box().data.borrow().height;
+        let mut rows = 0i16;
+        let mut count = 0;
+        for index in top .. this.items_count() {
+            rows = rows.saturating_add(Self::item_height(this, index));
+            if rows > height { break; }
+            count += 1;
+        }
+        max(count, 1)
+    }
+
+    fn move_current(this: &Rc<dyn IsListBox>, f: impl FnOnce(Option<usize>, usize) -> usize) {
+        let count = this.items_count();
+        if count == 0 { return; }
+        let current = this.current_index();
+        let index = min(f(current, count), count - 1);
+        this.set_current_index(Some(index));
+    }
+
+    fn search(this: &Rc<dyn IsListBox>, c: char) -> bool {
+        let count = this.items_count();
+        if count == 0 { return false; }
+        let current = this.current_index().unwrap_or(0);
+        let mut search = this.list_box().data.borrow().search.clone();
+        search.push(c);
+        let matches = |index: usize, search: &str| this.item_text(index).is_some_and(|text| {
+            let mut text = text.chars().flat_map(|x| x.to_lowercase());
+            search.chars().flat_map(|x| x.to_lowercase()).all(|x| text.next() == Some(x))
+        });
+        let found = (0 .. count).map(|x| (current + x) % count).find(|&x| matches(x, &search)).or_else(|| {
+            search = c.to_string();
+            (1 ..= count).map(|x| (current + x) % count).find(|&x| matches(x, &search))
+        });
+        let Some(found) = found else { return false; };
+        this.list_box().data.borrow_mut().search = search;
+        this.set_current_index(Some(found));
+        true
+    }
+
+    fn activate_current(this: &Rc<dyn IsListBox>) -> bool {
+        let Some(current) = this.current_index() else { return false; };
+        this.item_activated(current);
+        true
+    }
+
+    fn item_at(this: &Rc<dyn IsListBox>, p: Point) -> Option<usize> {
+        let p = this.inner_to_screen(p);
+        let realized = this.realized_items();
+        (max(Self::top(this), realized.start) .. realized.end).find(|&index| {
+            this.item_view(index).is_some_and(|item| item.inner_render_bounds().contains(item.screen_to_inner(p)))
+        })
+    }
+
+    pub fn key_impl(this: &Rc<dyn IsView>, key: Key, original_source: &Rc<dyn IsView>) -> bool {
+        let list_box: Rc<dyn IsListBox> = dyn_cast_rc(this.clone()).unwrap();
+        if !matches!(key, Key::Char(_)) {
+            list_box.list_box().data.borrow_mut().search.clear();
+        }
+        match key {
+            Key::Up => {
+                Self::move_current(&list_box, |current, _| current.map_or(0, |x| x.saturating_sub(1)));
+                return true;
+            },
+            Key::Down => {
+                Self::move_current(&list_box, |current, _| current.map_or(0, |x| x + 1));
+                return true;
+            },
+            Key::PageUp => {
+                let page = Self::page_size(&list_box, Self::top(&list_box));
+                Self::move_current(&list_box, |current, _| current.map_or(0, |x| x.saturating_sub(page - 1)));
+                return true;
+            },
+            Key::PageDown => {
+                let page = Self::page_size(&list_box, Self::top(&list_box));
+                Self::move_current(&list_box, |current, _| current.map_or(0, |x| x + (page - 1)));
+                return true;
+            },
+            Key::Home => {
+                Self::move_current(&list_box, |_, _| 0);
+                return true;
+            },
+            Key::End => {
+                Self::move_current(&list_box, |_, count| count - 1);
+                return true;
+            },
+            Key::Enter => {
+                if Self::activate_current(&list_box) { return true; }
+            },
+            Key::Char(' ') if list_box.selection_mode() == SelectionMode::Multiple => {
+                if let Some(current) = list_box.current_index() {
+                    list_box.set_is_selected(current, !list_box.is_selected(current));
+                }
+                return true;
+            },
+            Key::Char(c) => {
+                if Self::search(&list_box, c) { return true; }
+            },
+            _ => { },
+        }
+        View::key_impl(this, key, original_source)
+    }
+
+    pub fn mouse_impl(this: &Rc<dyn IsView>, event: MouseEvent, original_source: &Rc<dyn IsView>) -> bool {
+        let list_box: Rc<dyn IsListBox> = dyn_cast_rc(this.clone()).unwrap();
+        match event.action {
+            MouseAction::Down(MouseButton::Left) => {
+                this.app().unwrap().focus(Some(this), None);
+                let Some(index) = Self::item_at(&list_box, event.point) else { return true; };
+                list_box.list_box().data.borrow_mut().search.clear();
+                let toggle = event.modifiers.contains(Modifiers::CTRL)
+                    && list_box.selection_mode() == SelectionMode::Multiple;
+                if toggle {
+                    list_box.set_current_index(Some(index));
+                    list_box.set_is_selected(index, !list_box.is_selected(index));
+                } else {
+                    Self::select_only(&list_box, index);
+                }
+                true
+            },
+            MouseAction::DoubleClick(MouseButton::Left) => {
+                if Self::item_at(&list_box, event.point) != list_box.current_index() { return true; }
+                Self::activate_current(&list_box);
+                true
+            },
+            MouseAction::WheelUp => {
+                let top = Self::top(&list_box);
+                Self::set_top(&list_box, top.saturating_sub(WHEEL_ITEMS));
+                true
+            },
+            MouseAction::WheelDown => {
+                let top = Self::top(&list_box);
+                let max_top = list_box.items_count().saturating_sub(Self::page_size(&list_box, top));
+                Self::set_top(&list_box, max(top, min(top + WHEEL_ITEMS, max_top)));
+                true
+            },
+            _ => View::mouse_impl(this, event, original_source)
+        }
+    }
+
+    fn select_only(this: &Rc<dyn IsListBox>, index: usize) {
+        this.set_current_index(Some(index));
+        if this.selection_mode() == SelectionMode::Single { return; }
+        let selection_changed = {
+            let mut data = this.list_box().data.borrow_mut();
+            let selected = BTreeSet::from([index]);
+            let selection_changed = data.selected != selected;
+            data.selected = selected;
+            selection_changed
+        };
+        if selection_changed {
+            Self::update_colors(this);
+            this.selection_changed();
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! list_box_template {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident in $mod:ident {
+            $(use $path:path as $import:ident;)*
+
+            $($(
+                $(#[$field_attr:meta])*
+                pub $field_name:ident : $field_ty:ty
+            ),+ $(,)?)?
+        }
+    ) => {
+        $crate::items_control_template! {
+            $(#[$attr])*
+            $vis struct $name in $mod {
+                $(use $path as $import;)*
+
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub selection_mode: Option<$crate::list_box::SelectionMode>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color: Option<($crate::base::Fg, $crate::base::Bg)>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color_selected: Option<($crate::base::Fg, $crate::base::Bg)>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color_focused: Option<($crate::base::Fg, $crate::base::Bg)>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color_disabled: Option<($crate::base::Fg, $crate::base::Bg)>,
+                $($(
+                    $(#[$field_attr])*
+                    pub $field_name : $field_ty
+                ),+)?
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! list_box_apply_template {
+    ($this:ident, $instance:ident, $names:ident) => {
+        $crate::items_control_apply_template!($this, $instance, $names);
+        {
+            use $crate::list_box::ListBoxExt;
+
+            let obj: $crate::alloc_rc_Rc<dyn $crate::list_box::IsListBox>
+                = $crate::dynamic_cast_dyn_cast_rc($instance.clone()).unwrap();
+            $this.selection_mode.map(|x| obj.set_selection_mode(x));
+            $this.color.map(|x| obj.set_color(x));
+            $this.color_selected.map(|x| obj.set_color_selected(x));
+            $this.color_focused.map(|x| obj.set_color_focused(x));
+            $this.color_disabled.map(|x| obj.set_color_disabled(x));
+        }
+    };
+}
+
+list_box_template! {
+    #[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+    #[serde(rename="ListBox@ItemTemplate")]
+    pub struct ListBoxTemplate in template { }
+}
+
+#[typetag::serde(name="ListBox")]
+impl Template for ListBoxTemplate {
+    fn is_name_scope(&self) -> bool {
+        self.is_name_scope
+    }
+
+    fn name(&self) -> Option<&String> {
+        Some(&self.name)
+    }
+
+    fn create_instance(&self) -> Rc<dyn IsObj> {
+        ListBox::new()
+    }
+
+    fn apply(&self, instance: &Rc<dyn IsObj>, names: &mut NameResolver) {
+        let this = self;
+        list_box_apply_template!(this, instance, names);
+    }
+}
@@ -0,0 +1,786 @@
+use basic_oop::{class_unsafe, import, Vtable};
+use dynamic_cast::dyn_cast_rc;
+use std::cell::RefCell;
+use std::ptr::addr_eq;
+use std::rc::{self};
+use crate::app::AppExt;
+use crate::base::{label, label_width, text_width, MouseAction, MouseButton};
+use crate::command::{IsCommand, CommandExt};
+use crate::event_handler::EventHandler;
+use crate::menu_bar::{IsMenuBar, MenuBarExt};
+use crate::template::{Template, NameResolver};
+
+import! { pub menu_item:
+    use [obj basic_oop::obj];
+    use std::rc::Rc;
+}
+
+struct MenuItemData {
+    text: Rc<String>,
+    shortcut_text: Rc<String>,
+    is_separator: bool,
+    is_checkable: bool,
+    is_checked: bool,
+    is_enabled: bool,
+    command_enabled: bool,
+    items: Vec<Rc<dyn IsMenuItem>>,
+    command: Option<Rc<dyn IsCommand>>,
+    click_handler: EventHandler<Option<Box<dyn FnMut()>>>,
+    owner: rc::Weak<dyn IsView>,
+}
+
+#[class_unsafe(inherits_Obj)]
+pub struct MenuItem {
+    data: RefCell<MenuItemData>,
+    #[non_virt]
+    text: fn() -> Rc<String>,
+    #[non_virt]
+    set_text: fn(value: Rc<String>),
+    #[non_virt]
+    shortcut_text: fn() -> Rc<String>,
+    #[non_virt]
+    set_shortcut_text: fn(value: Rc<String>),
+    #[non_virt]
+    is_separator: fn() -> bool,
+    #[non_virt]
+    set_is_separator: fn(value: bool),
+    #[non_virt]
+    is_checkable: fn() -> bool,
+    #[non_virt]
+    set_is_checkable: fn(value: bool),
+    #[non_virt]
+    is_checked: fn() -> bool,
+    #[non_virt]
+    set_is_checked: fn(value: bool),
+    #[non_virt]
+    is_enabled: fn() -> bool,
+    #[non_virt]
+    set_is_enabled: fn(value: bool),
+    #[non_virt]
+    items: fn() -> Vec<Rc<dyn IsMenuItem>>,
+    #[non_virt]
+    set_items: fn(value: Vec<Rc<dyn IsMenuItem>>),
+    #[non_virt]
+    command: fn() -> Option<Rc<dyn IsCommand>>,
+    #[non_virt]
+    set_command: fn(value: Option<Rc<dyn IsCommand>>),
+    #[non_virt]
+    handle_click: fn(handler: Option<Box<dyn FnMut()>>),
+    #[virt]
+    click: fn(),
+}
+
+impl MenuItem {
+    pub fn new() -> Rc<dyn IsMenuItem> {
+        Rc::new(unsafe { Self::new_raw(MENU_ITEM_VTABLE.as_ptr()) })
+    }
+
+    pub unsafe fn new_raw(vtable: Vtable) -> Self {
+        MenuItem {
+            obj: unsafe { Obj::new_raw(vtable) },
+            data: RefCell::new(MenuItemData {
+                text: Rc::new(String::new()),
+                shortcut_text: Rc::new(String::new()),
+                is_separator: false,
+                is_checkable: false,
+                is_checked: false,
+                is_enabled: true,
+                command_enabled: true,
+                items: Vec::new(),
+                command: None,
+                click_handler: Default::default(),
+                owner: <rc::Weak::<View>>::new(),
+            }),
+        }
+    }
+
+    pub fn text_impl(this: &Rc<dyn IsMenuItem>) -> Rc<String> {
+        this.menu_item().data.borrow().text.clone()
+    }
+
+    pub fn set_text_impl(this: &Rc<dyn IsMenuItem>, value: Rc<String>) {
+        {
+            let mut data = this.menu_item().data.borrow_mut();
+            if addr_eq(Rc::as_ptr(&data.text), Rc::as_ptr(&value)) { return; }
+            data.text = value;
+        }
+        Self::invalidate_owner(this, true);
+    }
+
+    pub fn shortcut_text_impl(this: &Rc<dyn IsMenuItem>) -> Rc<String> {
+        this.menu_item().data.borrow().shortcut_text.clone()
+    }
+
+    pub fn set_shortcut_text_impl(this: &Rc<dyn IsMenuItem>, value: Rc<String>) {
+        {
+            let mut data = this.menu_item().data.borrow_mut();
+            if addr_eq(Rc::as_ptr(&data.shortcut_text), Rc::as_ptr(&value)) { return; }
+            data.shortcut_text = value;
+        }
+        Self::invalidate_owner(this, true);
+    }
+
+    pub fn is_separator_impl(this: &Rc<dyn IsMenuItem>) -> bool {
+        this.menu_item().data.borrow().is_separator
+    }
+
+    pub fn set_is_separator_impl(this: &Rc<dyn IsMenuItem>, value: bool) {
+        {
+            let mut data = this.menu_item().data.borrow_mut();
+            if data.is_separator == value { return; }
+            data.is_separator = value;
+        }
+        Self::invalidate_owner(this, true);
+    }
+
+    pub fn is_checkable_impl(this: &Rc<dyn IsMenuItem>) -> bool {
+        this.menu_item().data.borrow().is_checkable
+    }
+
+    pub fn set_is_checkable_impl(this: &Rc<dyn IsMenuItem>, value: bool) {
+        {
+            let mut data = this.menu_item().data.borrow_mut();
+            if data.is_checkable == value { return; }
+            data.is_checkable = value;
+        }
+        Self::invalidate_owner(this, false);
+    }
+
+    pub fn is_checked_impl(this: &Rc<dyn IsMenuItem>) -> bool {
+        this.menu_item().data.borrow().is_checked
+    }
+
+    pub fn set_is_checked_impl(this: &Rc<dyn IsMenuItem>, value: bool) {
+        {
+            let mut data = this.menu_item().data.borrow_mut();
+            if data.is_checked == value { return; }
+            data.is_checked = value;
+        }
+        Self::invalidate_owner(this, false);
+    }
+
+    /// The item is enabled if it is not disabled with [`set_is_enabled`](MenuItem::set_is_enabled_impl),
+    /// and its command, if any, can be executed.
+    pub fn is_enabled_impl(this: &Rc<dyn IsMenuItem>) -> bool {
+        let data = this.menu_item().data.borrow();
+        data.is_enabled && data.command_enabled
+    }
+
+    pub fn set_is_enabled_impl(this: &Rc<dyn IsMenuItem>, value: bool) {
+        {
+            let mut data = this.menu_item().data.borrow_mut();
+            if data.is_enabled == value { return; }
+            data.is_enabled = value;
+        }
+        Self::invalidate_owner(this, false);
+    }
+
+    pub fn items_impl(this: &Rc<dyn IsMenuItem>) -> Vec<Rc<dyn IsMenuItem>> {
+        this.menu_item().data.borrow().items.clone()
+    }
+
+    pub fn set_items_impl(this: &Rc<dyn IsMenuItem>, value: Vec<Rc<dyn IsMenuItem>>) {
+        this.menu_item().data.borrow_mut().items = value;
+        Self::invalidate_owner(this, true);
+    }
+
+    pub fn command_impl(this: &Rc<dyn IsMenuItem>) -> Option<Rc<dyn IsCommand>> {
+        this.menu_item().data.borrow().command.clone()
+    }
+
+    /// Sets the command executed on click with the
+    /// [command target](crate::app::App::command_target_impl) (or, for context menus, the menu owner).
+    /// While the command is set, the item is enabled only if the command can be executed.
+    pub fn set_command_impl(this: &Rc<dyn IsMenuItem>, value: Option<Rc<dyn IsCommand>>) {
+        this.menu_item().data.borrow_mut().command = value;
+    }
+
+    pub fn handle_click_impl(this: &Rc<dyn IsMenuItem>, handler: Option<Box<dyn FnMut()>>) {
+        this.menu_item().data.borrow_mut().click_handler.set(handler);
+    }
+
+    pub fn click_impl(this: &Rc<dyn IsMenuItem>) {
+        if this.is_checkable() {
+            this.set_is_checked(!this.is_checked());
+        }
+        let mut invoke = this.menu_item().data.borrow_mut().click_handler.begin_invoke();
+        invoke.as_mut().map(|x| x());
+        this.menu_item().data.borrow_mut().click_handler.end_invoke(invoke);
+    }
+
+    pub(crate) fn requery_command(this: &Rc<dyn IsMenuItem>, target: Option<&Rc<dyn IsView>>) {
+        let command_enabled = this.command().is_none_or(|x| target.is_some_and(|target| x.can_execute(target)));
+        {
+            let mut data = this.menu_item().data.borrow_mut();
+            if data.command_enabled == command_enabled { return; }
+            data.command_enabled = command_enabled;
+        }
+        Self::invalidate_owner(this, false);
+    }
+
+    pub(crate) fn invoke(this: &Rc<dyn IsMenuItem>, target: Option<&Rc<dyn IsView>>) {
+        this.click();
+        if let Some(command) = this.command() && let Some(target) = target {
+            command.execute(target);
+        }
+    }
+
+    /// Sets the view showing the item, which is repainted when the item changes.
+    pub(crate) fn set_owner(this: &Rc<dyn IsMenuItem>, owner: &Rc<dyn IsView>) {
+        this.menu_item().data.borrow_mut().owner = Rc::downgrade(owner);
+    }
+
+    fn invalidate_owner(this: &Rc<dyn IsMenuItem>, measure: bool) {
+        let Some(owner) = this.menu_item().data.borrow().owner.upgrade() else { return; };
+        if measure {
+            owner.invalidate_measure();
+        }
+        owner.invalidate_render();
+    }
+
+    fn is_selectable(this: &Rc<dyn IsMenuItem>) -> bool {
+        let data = this.menu_item().data.borrow();
+        !data.is_separator && data.is_enabled && data.command_enabled
+    }
+}
+
+#[macro_export]
+macro_rules! menu_item_template {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident in $mod:ident {
+            $(use $path:path as $import:ident;)*
+            $($(
+                $(#[$field_attr:meta])*
+                pub $field_name:ident : $field_ty:ty
+            ),+ $(,)?)?
+        }
+    ) => {
+        $crate::template! {
+            $(#[$attr])*
+            $vis struct $name in $mod {
+                $(use $path as $import;)*
+                #[serde(default)]
+                #[serde(skip_serializing_if="String::is_empty")]
+                pub name: String,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub text: Option<String>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub shortcut_text: Option<String>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub is_separator: Option<bool>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub is_checkable: Option<bool>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub is_checked: Option<bool>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub is_enabled: Option<bool>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Vec::is_empty")]
+                pub items: Vec<Box<dyn $crate::template::Template>>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="String::is_empty")]
+                pub command: String,
+                $($(
+                    $(#[$field_attr])*
+                    pub $field_name : $field_ty
+                ),+)?
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! menu_item_apply_template {
+    ($this:ident, $instance:ident, $names:ident) => {
+        {
+            use $crate::menu::MenuItemExt;
+
+            let obj: $crate::alloc_rc_Rc<dyn $crate::menu::IsMenuItem>
+                = $crate::dynamic_cast_dyn_cast_rc($instance.clone()).unwrap();
+            $this.text.as_ref().map(|x| obj.set_text($crate::alloc_rc_Rc::new(x.clone())));
+            $this.shortcut_text.as_ref().map(|x| obj.set_shortcut_text($crate::alloc_rc_Rc::new(x.clone())));
+            $this.is_separator.map(|x| obj.set_is_separator(x));
+            $this.is_checkable.map(|x| obj.set_is_checkable(x));
+            $this.is_checked.map(|x| obj.set_is_checked(x));
+            $this.is_enabled.map(|x| obj.set_is_enabled(x));
+            if !$this.items.is_empty() {
+                obj.set_items($this.items.iter().map(|x|
+                    $crate::dynamic_cast_dyn_cast_rc(x.load_content($names)).unwrap()
+                ).collect());
+            }
+            $names.resolve(
+                $this.command.clone(),
+                Box::new(move |x| obj.set_command(Some($crate::dynamic_cast_dyn_cast_rc(x).unwrap())))
+            );
+        }
+    };
+}
+
+menu_item_template! {
+    #[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+    #[serde(rename="MenuItem@Items")]
+    pub struct MenuItemTemplate in menu_item_template { }
+}
+
+#[typetag::serde(name="MenuItem")]
+impl Template for MenuItemTemplate {
+    fn name(&self) -> Option<&String> {
+        Some(&self.name)
+    }
+
+    fn create_instance(&self) -> Rc<dyn IsObj> {
+        MenuItem::new()
+    }
+
+    fn apply(&self, instance: &Rc<dyn IsObj>, names: &mut NameResolver) {
+        let this = self;
+        menu_item_apply_template!(this, instance, names);
+    }
+}
+
+import! { pub menu_popup:
+    use [view crate::view];
+    use crate::base::{Fg, Bg};
+}
+
+struct MenuPopupData {
+    items: Vec<Rc<dyn IsMenuItem>>,
+    selected: Option<usize>,
+    parent: rc::Weak<dyn IsView>,
+    target: rc::Weak<dyn IsView>,
+    submenu: Option<Rc<dyn IsMenuPopup>>,
+    color: (Fg, Bg),
+    color_hotkey: (Fg, Bg),
+    color_selected: (Fg, Bg),
+    color_disabled: (Fg, Bg),
+}
+
+/// A drop-down list of menu items, shown in a popup layer.
+///
+/// The parent is a [`MenuBar`](crate::menu_bar::MenuBar) for drop-down menus,
+/// another `MenuPopup` for submenus, and nothing for context menus.
+#[class_unsafe(inherits_View)]
+pub struct MenuPopup {
+    data: RefCell<MenuPopupData>,
+    #[non_virt]
+    items: fn() -> Vec<Rc<dyn IsMenuItem>>,
+    #[non_virt]
+    parent: fn() -> Option<Rc<dyn IsView>>,
+    #[non_virt]
+    target: fn() -> Option<Rc<dyn IsView>>,
+    #[non_virt]
+    color: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color: fn(value: (Fg, Bg)),
+    #[non_virt]
+    color_hotkey: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color_hotkey: fn(value: (Fg, Bg)),
+    #[non_virt]
+    color_selected: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color_selected: fn(value: (Fg, Bg)),
+    #[non_virt]
+    color_disabled: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color_disabled: fn(value: (Fg, Bg)),
+    #[non_virt]
+    close_menu: fn(),
+    #[over]
+    _init: (),
+    #[over]
+    _detach_from_app: (),
+    #[over]
+    measure_override: (),
+    #[over]
+    arrange_override: (),
+    #[over]
+    render: (),
+    #[over]
+    key: (),
+    #[over]
+    mouse: (),
+}
+
+impl MenuPopup {
+    /// Creates a popup with `items`. Item commands are executed with `target`.
+    pub fn new(
+        items: Vec<Rc<dyn IsMenuItem>>,
+        parent: Option<&Rc<dyn IsView>>,
+        target: Option<&Rc<dyn IsView>>,
+    ) -> Rc<dyn IsMenuPopup> {
+        let res: Rc<dyn IsMenuPopup> = Rc::new(unsafe { Self::new_raw(items, parent, target, MENU_POPUP_VTABLE.as_ptr()) });
+        res._init();
+        let owner: Rc<dyn IsView> = res.clone();
+        for item in res.items() {
+            MenuItem::set_owner(&item, &owner);
+        }
+        res
+    }
+
+    pub unsafe fn new_raw(
+        items: Vec<Rc<dyn IsMenuItem>>,
+        parent: Option<&Rc<dyn IsView>>,
+        target: Option<&Rc<dyn IsView>>,
+        vtable: Vtable,
+    ) -> Self {
+        for item in &items {
+            MenuItem::requery_command(item, target);
+        }
+        let selected = items.iter().position(MenuItem::is_selectable);
+        MenuPopup {
+            view: unsafe { View::new_raw(vtable) },
+            data: RefCell::new(MenuPopupData {
+                items,
+                selected,
+                parent: parent.map_or_else(|| <rc::Weak::<View>>::new(), Rc::downgrade),
+                target: target.map_or_else(|| <rc::Weak::<View>>::new(), Rc::downgrade),
+                submenu: None,
+                color: (Fg::Black, Bg::LightGray),
+                color_hotkey: (Fg::Red, Bg::LightGray),
+                color_selected: (Fg::Black, Bg::Green),
+                color_disabled: (Fg::DarkGray, Bg::LightGray),
+            }),
+        }
+    }
+
+    /// Shows a context menu with `items`.
+    ///
+    /// The menu is shown at `at` in `view` coordinates, e.g. at the mouse cursor,
+    /// or right under the top left corner of `view` if `at` is `None`.
+    pub fn show_context(view: &Rc<dyn IsView>, items: Vec<Rc<dyn IsMenuItem>>, at: Option<Point>) {
+        let app = view.app().expect("view is not attached to an app");
+        let at = view.inner_to_screen(at.unwrap_or(Point { x: 0, y: 1 }));
+        let popup: Rc<dyn IsView> = MenuPopup::new(items, None, Some(view));
+        app.show_popup(&popup, at);
+    }
+
+    pub fn _init_impl(this: &Rc<dyn IsView>) {
+        View::_init_impl(this);
+        this.set_allow_focus(true);
+    }
+
+    pub fn items_impl(this: &Rc<dyn IsMenuPopup>) -> Vec<Rc<dyn IsMenuItem>> {
+        this.menu_popup().data.borrow().items.clone()
+    }
+
+    pub fn parent_impl(this: &Rc<dyn IsMenuPopup>) -> Option<Rc<dyn IsView>> {
+        this.menu_popup().data.borrow().parent.upgrade()
+    }
+
+    pub fn target_impl(this: &Rc<dyn IsMenuPopup>) -> Option<Rc<dyn IsView>> {
+        this.menu_popup().data.borrow().target.upgrade()
+    }
+
+    pub fn color_impl(this: &Rc<dyn IsMenuPopup>) -> (Fg, Bg) {
+        this.menu_popup().data.borrow().color
+    }
+
+    pub fn set_color_impl(this: &Rc<dyn IsMenuPopup>, value: (Fg, Bg)) {
+        {
+            let mut data = this.menu_popup().data.borrow_mut();
+            if data.color == value { return; }
+            data.color = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn color_hotkey_impl(this: &Rc<dyn IsMenuPopup>) -> (Fg, Bg) {
+        this.menu_popup().data.borrow().color_hotkey
+    }
+
+    pub fn set_color_hotkey_impl(this: &Rc<dyn IsMenuPopup>, value: (Fg, Bg)) {
+        {
+            let mut data = this.menu_popup().data.borrow_mut();
+            if data.color_hotkey == value { return; }
+            data.color_hotkey = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn color_selected_impl(this: &Rc<dyn IsMenuPopup>) -> (Fg, Bg) {
+        this.menu_popup().data.borrow().color_selected
+    }
+
+    pub fn set_color_selected_impl(this: &Rc<dyn IsMenuPopup>, value: (Fg, Bg)) {
+        {
+            let mut data = this.menu_popup().data.borrow_mut();
+            if data.color_selected == value { return; }
+            data.color_selected = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn color_disabled_impl(this: &Rc<dyn IsMenuPopup>) -> (Fg, Bg) {
+        this.menu_popup().data.borrow().color_disabled
+    }
+
+    pub fn set_color_disabled_impl(this: &Rc<dyn IsMenuPopup>, value: (Fg, Bg)) {
+        {
+            let mut data = this.menu_popup().data.borrow_mut();
+            if data.color_disabled == value { return; }
+            data.color_disabled = value;
+        }
+        this.invalidate_render();
+    }
+
+    /// Closes the whole menu this popup belongs to, including all its submenus.
+    pub fn close_menu_impl(this: &Rc<dyn IsMenuPopup>) {
+        let mut root = this.clone();
+        while let Some(parent) = root.parent() && let Some(parent) = dyn_cast_rc(parent) {
+            root = parent;
+        }
+        let Some(app) = root.app() else { return; };
+        let root: Rc<dyn IsView> = root;
+        app.close_popup(&root);
+    }
+
+    pub fn _detach_from_app_impl(this: &Rc<dyn IsView>) {
+        View::_detach_from_app_impl(this);
+        let this: Rc<dyn IsMenuPopup> = dyn_cast_rc(this.clone()).unwrap();
+        this.menu_popup().data.borrow_mut().submenu = None;
+        let Some(parent) = this.parent() else { return; };
+        if let Some(parent) = dyn_cast_rc::<dyn IsMenuPopup>(parent.clone()) {
+            let mut parent_data = parent.menu_popup().data.borrow_mut();
+            if parent_data.submenu.as_ref().is_some_and(|x| addr_eq(Rc::as_ptr(x), Rc::as_ptr(&this))) {
+                parent_data.submenu = None;
+            }
+        } else if let Some(menu_bar) = dyn_cast_rc::<dyn IsMenuBar>(parent) {
+            menu_bar._menu_closed(&this);
+        }
+    }
+
+    fn columns(items: &[Rc<dyn IsMenuItem>]) -> (i16, i16, bool) {
+        let mut label = 0i16;
+        let mut shortcut = 0i16;
+        let mut submenu = false;
+        for item in items {
+            let data = item.menu_item().data.borrow();
+            if data.is_separator { continue; }
+            label = label.max(label_width(&data.text));
+            shortcut = shortcut.max(text_width(&data.shortcut_text));
+            submenu |= !data.items.is_empty();
+        }
+        (label, shortcut, submenu)
+    }
+
+    pub fn measure_override_impl(this: &Rc<dyn IsView>, _w: Option<i16>, _h: Option<i16>) -> Vector {
+        let this: Rc<dyn IsMenuPopup> = dyn_cast_rc(this.clone()).unwrap();
+        let data = this.menu_popup().data.borrow();
+        let (label, shortcut, submenu) = Self::columns(&data.items);
+        let mut width = 4i16.wrapping_add(label).wrapping_add(2);
+        if shortcut != 0 { width = width.wrapping_add(2).wrapping_add(shortcut); }
+        if submenu { width = width.wrapping_add(2); }
+        Vector { x: width, y: i16::try_from(data.items.len()).unwrap_or(i16::MAX).saturating_add(2) }
+    }
+
+    pub fn arrange_override_impl(_this: &Rc<dyn IsView>, bounds: Rect) -> Vector {
+        bounds.size
+    }
+
+    pub fn render_impl(this: &Rc<dyn IsView>, rp: &mut RenderPort) {
+        let this: Rc<dyn IsMenuPopup> = dyn_cast_rc(this.clone()).unwrap();
+        let bounds = this.inner_render_bounds();
+        let data = this.menu_popup().data.borrow();
+        rp.fill_bg(data.color);
+        rp.h_line(bounds.tl, bounds.w(), false, data.color);
+        rp.h_line(bounds.bl_inner(), bounds.w(), false, data.color);
+        rp.v_line(bounds.tl, bounds.h(), false, data.color);
+        rp.v_line(bounds.tr_inner(), bounds.h(), false, data.color);
+        rp.tl_edge(bounds.tl, false, data.color);
+        rp.tr_edge(bounds.tr_inner(), false, data.color);
+        rp.br_edge(bounds.br_inner(), false, data.color);
+        rp.bl_edge(bounds.bl_inner(), false, data.color);
+        for (i, item) in data.items.iter().enumerate() {
+            let y = i16::try_from(i).unwrap_or(i16::MAX).saturating_add(1);
+            let item = item.menu_item().data.borrow();
+            if item.is_separator {
+                rp.h_line(Point { x: 1, y }, bounds.w().wrapping_sub(2), false, data.color);
+                rp.text(Point { x: 0, y }, data.color, "├");
+                rp.text(Point { x: bounds.w().wrapping_sub(1), y }, data.color, "┤");
+                continue;
+            }
+            let (color, color_hotkey) = if !(item.is_enabled && item.command_enabled) {
+                (data.color_disabled, data.color_disabled)
+            } else if data.selected == Some(i) {
+                (data.color_selected, (data.color_hotkey.0, data.color_selected.1))
+            } else {
+                (data.color, data.color_hotkey)
+            };
+            rp.fill_bg_rect(Rect { tl: Point { x: 1, y }, size: Vector { x: bounds.w().wrapping_sub(2), y: 1 } }, color);
+            if item.is_checked {
+                rp.text(Point { x: 2, y }, color, "√");
+            }
+            rp.label(Point { x: 4, y }, color, color_hotkey, &item.text);
+            let mut r = bounds.w().wrapping_sub(2);
+            if !item.items.is_empty() {
+                rp.text(Point { x: r.wrapping_sub(1), y }, color, "►");
+                r = r.wrapping_sub(2);
+            }
+            let shortcut_width = text_width(&item.shortcut_text);
+            if shortcut_width != 0 {
+                rp.text(Point { x: r.wrapping_sub(shortcut_width), y }, color, &item.shortcut_text);
+            }
+        }
+    }
+
+    fn select(this: &Rc<dyn IsMenuPopup>, index: Option<usize>) {
+        {
+            let mut data = this.menu_popup().data.borrow_mut();
+            if data.selected == index { return; }
+            data.selected = index;
+        }
+        this.invalidate_render();
+    }
+
+    fn select_next(this: &Rc<dyn IsMenuPopup>, forward: bool) {
+        let (items, selected) = {
+            let data = this.menu_popup().data.borrow();
+            (data.items.clone(), data.selected)
+        };
+        let len = items.len();
+        if len == 0 { return; }
+        let mut index = selected.unwrap_or(if forward { len - 1 } else { 0 });
+        for _ in 0 .. len {
+            index = if forward { (index + 1) % len } else { (index + len - 1) % len };
+            if MenuItem::is_selectable(&items[index]) {
+                Self::select(this, Some(index));
+                return;
+            }
+        }
+    }
+
+    fn open_submenu(this: &Rc<dyn IsMenuPopup>, index: usize) {
+        let item = this.menu_popup().data.borrow().items[index].clone();
+        let items = item.items();
+        if items.is_empty() { return; }
+        let app = this.app().unwrap();
+        if let Some(submenu) = this.menu_popup().data.borrow_mut().submenu.take() {
+            let submenu: Rc<dyn IsView> = submenu;
+            app.close_popup(&submenu);
+        }
+        let parent: Rc<dyn IsView> = this.clone();
+        let submenu = MenuPopup::new(items, Some(&parent), this.target().as_ref());
+        {
+            let data = this.menu_popup().data.borrow();
+            submenu.set_color(data.color);
+            submenu.set_color_hotkey(data.color_hotkey);
+            submenu.set_color_selected(data.color_selected);
+            submenu.set_color_disabled(data.color_disabled);
+        }
+        this.menu_popup().data.borrow_mut().submenu = Some(submenu.clone());
+        let y = i16::try_from(index).unwrap_or(i16::MAX).saturating_add(1);
+        let at = this.inner_to_screen(Point { x: this.inner_render_bounds().w().wrapping_sub(2), y });
+        let submenu: Rc<dyn IsView> = submenu;
+        app.show_popup(&submenu, at);
+    }
+
+    fn activate(this: &Rc<dyn IsMenuPopup>, index: usize) {
+        let item = this.menu_popup().data.borrow().items[index].clone();
+        if !MenuItem::is_selectable(&item) { return; }
+        Self::select(this, Some(index));
+        if !item.items().is_empty() {
+            Self::open_submenu(this, index);
+            return;
+        }
+        let target = this.target();
+        this.close_menu();
+        MenuItem::invoke(&item, target.as_ref());
+    }
+
+    fn item_at(this: &Rc<dyn IsMenuPopup>, p: Point) -> Option<usize> {
+        let bounds = this.inner_render_bounds();
+        if p.x <= 0 || p.x >= bounds.w().wrapping_sub(1) || p.y <= 0 { return None; }
+        let index = usize::try_from(p.y - 1).ok()?;
+        if index >= this.menu_popup().data.borrow().items.len() { return None; }
+        Some(index)
+    }
+
+    pub fn key_impl(this: &Rc<dyn IsView>, key: Key, original_source: &Rc<dyn IsView>) -> bool {
+        let popup: Rc<dyn IsMenuPopup> = dyn_cast_rc(this.clone()).unwrap();
+        let parent = popup.parent();
+        let menu_bar = parent.clone().and_then(|x| dyn_cast_rc::<dyn IsMenuBar>(x));
+        let is_submenu = parent.and_then(|x| dyn_cast_rc::<dyn IsMenuPopup>(x)).is_some();
+        match key {
+            Key::Up => Self::select_next(&popup, false),
+            Key::Down => Self::select_next(&popup, true),
+            Key::Home => {
+                Self::select(&popup, None);
+                Self::select_next(&popup, true);
+            },
+            Key::End => {
+                Self::select(&popup, None);
+                Self::select_next(&popup, false);
+            },
+            Key::Enter => {
+                let selected = popup.menu_popup().data.borrow().selected;
+                selected.map(|x| Self::activate(&popup, x));
+            },
+            Key::Right => {
+                let selected = popup.menu_popup().data.borrow().selected;
+                let has_submenu = selected.is_some_and(|x| !popup.menu_popup().data.borrow().items[x].items().is_empty());
+                if has_submenu {
+                    Self::open_submenu(&popup, selected.unwrap());
+                } else if let Some(menu_bar) = menu_bar {
+                    menu_bar.open_next(true);
+                }
+            },
+            Key::Left => {
+                if is_submenu {
+                    let this: Rc<dyn IsView> = popup.clone();
+                    this.app().unwrap().close_popup(&this);
+                } else if let Some(menu_bar) = menu_bar {
+                    menu_bar.open_next(false);
+                }
+            },
+            Key::Escape => {
+                let this: Rc<dyn IsView> = popup.clone();
+                this.app().unwrap().close_popup(&this);
+            },
+            Key::F10 => popup.close_menu(),
+            Key::Char(c) | Key::Alt(c) => {
+                let c = c.to_lowercase().next().unwrap();
+                let items = popup.items();
+                if let Some(index) = items.iter().position(|x| MenuItem::is_selectable(x) && label(&x.text()) == Some(c)) {
+                    Self::activate(&popup, index);
+                } else if let Key::Alt(_) = key && let Some(menu_bar) = menu_bar {
+                    return menu_bar.pre_process_key(key);
+                }
+            },
+            _ => return View::key_impl(this, key, original_source),
+        }
+        true
+    }
+
+    pub fn mouse_impl(this: &Rc<dyn IsView>, event: MouseEvent, original_source: &Rc<dyn IsView>) -> bool {
+        let popup: Rc<dyn IsMenuPopup> = dyn_cast_rc(this.clone()).unwrap();
+        let index = Self::item_at(&popup, event.point);
+        let selectable = index.is_some_and(|x| MenuItem::is_selectable(&popup.menu_popup().data.borrow().items[x]));
+        match event.action {
+            MouseAction::Move | MouseAction::Drag(MouseButton::Left) => {
+                if selectable { Self::select(&popup, index); }
+                true
+            },
+            MouseAction::Down(MouseButton::Left) => {
+                if selectable {
+                    let index = index.unwrap();
+                    Self::select(&popup, Some(index));
+                    Self::open_submenu(&popup, index);
+                }
+                true
+            },
+            MouseAction::Up(MouseButton::Left) => {
+                if selectable { Self::activate(&popup, index.unwrap()); }
+                true
+            },
+            _ => View::mouse_impl(this, event, original_source)
+        }
+    }
+}
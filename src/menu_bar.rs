@@ -0,0 +1,418 @@
+use basic_oop::{class_unsafe, import, Vtable};
+use dynamic_cast::dyn_cast_rc;
+use std::cell::RefCell;
+use std::ptr::addr_eq;
+use crate::app::AppExt;
+use crate::base::{label, label_width, MouseAction, MouseButton};
+use crate::menu::{IsMenuItem, MenuItem, MenuItemExt, MenuPopup, MenuPopupExt};
+use crate::template::{Template, NameResolver};
+
+import! { pub menu_bar:
+    use [view crate::view];
+    use crate::base::{Fg, Bg};
+    use crate::menu::IsMenuPopup;
+}
+
+struct MenuBarData {
+    items: Vec<Rc<dyn IsMenuItem>>,
+    opened: Option<(usize, Rc<dyn IsMenuPopup>)>,
+    just_closed: Option<usize>,
+    color: (Fg, Bg),
+    color_hotkey: (Fg, Bg),
+    color_selected: (Fg, Bg),
+    color_disabled: (Fg, Bg),
+}
+
+#[class_unsafe(inherits_View)]
+pub struct MenuBar {
+    data: RefCell<MenuBarData>,
+    #[non_virt]
+    items: fn() -> Vec<Rc<dyn IsMenuItem>>,
+    #[non_virt]
+    set_items: fn(value: Vec<Rc<dyn IsMenuItem>>),
+    #[non_virt]
+    color: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color: fn(value: (Fg, Bg)),
+    #[non_virt]
+    color_hotkey: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color_hotkey: fn(value: (Fg, Bg)),
+    #[non_virt]
+    color_selected: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color_selected: fn(value: (Fg, Bg)),
+    #[non_virt]
+    color_disabled: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color_disabled: fn(value: (Fg, Bg)),
+    #[non_virt]
+    open: fn(index: usize),
+    #[non_virt]
+    open_next: fn(forward: bool),
+    #[non_virt]
+    close: fn(),
+    #[non_virt]
+    _menu_closed: fn(popup: &Rc<dyn IsMenuPopup>),
+    #[over]
+    _requery_commands: (),
+    #[over]
+    measure_override: (),
+    #[over]
+    arrange_override: (),
+    #[over]
+    render: (),
+    #[over]
+    pre_post_process: (),
+    #[over]
+    pre_process_key: (),
+    #[over]
+    mouse: (),
+}
+
+impl MenuBar {
+    pub fn new() -> Rc<dyn IsMenuBar> {
+        let res: Rc<dyn IsMenuBar> = Rc::new(unsafe { Self::new_raw(MENU_BAR_VTABLE.as_ptr()) });
+        res._init();
+        res
+    }
+
+    pub unsafe fn new_raw(vtable: Vtable) -> Self {
+        MenuBar {
+            view: unsafe { View::new_raw(vtable) },
+            data: RefCell::new(MenuBarData {
+                items: Vec::new(),
+                opened: None,
+                just_closed: None,
+                color: (Fg::Black, Bg::LightGray),
+                color_hotkey: (Fg::Red, Bg::LightGray),
+                color_selected: (Fg::Black, Bg::Green),
+                color_disabled: (Fg::DarkGray, Bg::LightGray),
+            }),
+        }
+    }
+
+    pub fn items_impl(this: &Rc<dyn IsMenuBar>) -> Vec<Rc<dyn IsMenuItem>> {
+        this.menu_bar().data.borrow().items.clone()
+    }
+
+    pub fn set_items_impl(this: &Rc<dyn IsMenuBar>, value: Vec<Rc<dyn IsMenuItem>>) {
+        this.close();
+        let owner: Rc<dyn IsView> = this.clone();
+        for item in &value {
+            MenuItem::set_owner(item, &owner);
+        }
+        this.menu_bar().data.borrow_mut().items = value;
+        this.invalidate_measure();
+        this.invalidate_render();
+    }
+
+    pub fn color_impl(this: &Rc<dyn IsMenuBar>) -> (Fg, Bg) {
+        this.menu_bar().data.borrow().color
+    }
+
+    pub fn set_color_impl(this: &Rc<dyn IsMenuBar>, value: (Fg, Bg)) {
+        {
+            let mut data = this.menu_bar().data.borrow_mut();
+            if data.color == value { return; }
+            data.color = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn color_hotkey_impl(this: &Rc<dyn IsMenuBar>) -> (Fg, Bg) {
+        this.menu_bar().data.borrow().color_hotkey
+    }
+
+    pub fn set_color_hotkey_impl(this: &Rc<dyn IsMenuBar>, value: (Fg, Bg)) {
+        {
+            let mut data = this.menu_bar().data.borrow_mut();
+            if data.color_hotkey == value { return; }
+            data.color_hotkey = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn color_selected_impl(this: &Rc<dyn IsMenuBar>) -> (Fg, Bg) {
+        this.menu_bar().data.borrow().color_selected
+    }
+
+    pub fn set_color_selected_impl(this: &Rc<dyn IsMenuBar>, value: (Fg, Bg)) {
+        {
+            let mut data = this.menu_bar().data.borrow_mut();
+            if data.color_selected == value { return; }
+            data.color_selected = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn color_disabled_impl(this: &Rc<dyn IsMenuBar>) -> (Fg, Bg) {
+        this.menu_bar().data.borrow().color_disabled
+    }
+
+    pub fn set_color_disabled_impl(this: &Rc<dyn IsMenuBar>, value: (Fg, Bg)) {
+        {
+            let mut data = this.menu_bar().data.borrow_mut();
+            if data.color_disabled == value { return; }
+            data.color_disabled = value;
+        }
+        this.invalidate_render();
+    }
+
+    fn item_x(items: &[Rc<dyn IsMenuItem>], index: usize) -> i16 {
+        items[.. index].iter().fold(1i16, |x, item| x.wrapping_add(label_width(&item.text())).wrapping_add(2))
+    }
+
+    fn item_at(items: &[Rc<dyn IsMenuItem>], x: i16) -> Option<usize> {
+        let mut start = 1i16;
+        for (i, item) in items.iter().enumerate() {
+            let end = start.wrapping_add(label_width(&item.text())).wrapping_add(2);
+            if x >= start && x < end { return Some(i); }
+            start = end;
+        }
+        None
+    }
+
+    /// Opens the drop-down menu of the top-level item at `index`,
+    /// or clicks the item if it has no subitems.
+    pub fn open_impl(this: &Rc<dyn IsMenuBar>, index: usize) {
+        this.close();
+        let app = this.app().expect("menu bar is not attached to an app");
+        let target = app.command_target();
+        let item = this.menu_bar().data.borrow().items[index].clone();
+        MenuItem::requery_command(&item, target.as_ref());
+        if !item.is_enabled() { return; }
+        let subitems = item.items();
+        if subitems.is_empty() {
+            MenuItem::invoke(&item, target.as_ref());
+            return;
+        }
+        let parent: Rc<dyn IsView> = this.clone();
+        let popup = MenuPopup::new(subitems, Some(&parent), target.as_ref());
+        let x = {
+            let data = this.menu_bar().data.borrow();
+            popup.set_color(data.color);
+            popup.set_color_hotkey(data.color_hotkey);
+            popup.set_color_selected(data.color_selected);
+            popup.set_color_disabled(data.color_disabled);
+            Self::item_x(&data.items, index)
+        };
+        this.menu_bar().data.borrow_mut().opened = Some((index, popup.clone()));
+        this.invalidate_render();
+        let popup: Rc<dyn IsView> = popup;
+        app.show_popup(&popup, this.inner_to_screen(Point { x, y: 1 }));
+    }
+
+    /// Moves the opened drop-down menu to the next (or previous) top-level item.
+    pub fn open_next_impl(this: &Rc<dyn IsMenuBar>, forward: bool) {
+        let (len, opened) = {
+            let data = this.menu_bar().data.borrow();
+            (data.items.len(), data.opened.as_ref().map(|x| x.0))
+        };
+        if len == 0 { return; }
+        let index = match opened {
+            None => 0,
+            Some(index) if forward => (index + 1) % len,
+            Some(index) => (index + len - 1) % len,
+        };
+        this.open(index);
+    }
+
+    pub fn close_impl(this: &Rc<dyn IsMenuBar>) {
+        let popup = this.menu_bar().data.borrow().opened.as_ref().map(|x| x.1.clone());
+        if let Some(popup) = popup {
+            popup.close_menu();
+        }
+    }
+
+    /// Called when the drop-down menu is closed.
+    ///
+    /// Closing the menu by a click outside it is followed by the click itself,
+    /// so the closed menu index is remembered until the next commands requery to not reopen it
+    /// if its title is clicked.
+    pub fn _menu_closed_impl(this: &Rc<dyn IsMenuBar>, popup: &Rc<dyn IsMenuPopup>) {
+        {
+            let mut data = this.menu_bar().data.borrow_mut();
+            if !data.opened.as_ref().is_some_and(|x| addr_eq(Rc::as_ptr(&x.1), Rc::as_ptr(popup))) { return; }
+            data.just_closed = data.opened.take().map(|x| x.0);
+        }
+        if let Some(app) = this.app() {
+            app.invalidate_commands();
+        }
+        this.invalidate_render();
+    }
+
+    pub fn _requery_commands_impl(this: &Rc<dyn IsView>) {
+        View::_requery_commands_impl(this);
+        let target = this.app().and_then(|x| x.command_target());
+        let menu_bar: Rc<dyn IsMenuBar> = dyn_cast_rc(this.clone()).unwrap();
+        menu_bar.menu_bar().data.borrow_mut().just_closed = None;
+        for item in menu_bar.items() {
+            MenuItem::requery_command(&item, target.as_ref());
+        }
+    }
+
+    pub fn measure_override_impl(this: &Rc<dyn IsView>, w: Option<i16>, _h: Option<i16>) -> Vector {
+        let this: Rc<dyn IsMenuBar> = dyn_cast_rc(this.clone()).unwrap();
+        let data = this.menu_bar().data.borrow();
+        let width = Self::item_x(&data.items, data.items.len()).wrapping_add(1);
+        Vector { x: w.unwrap_or(width), y: 1 }
+    }
+
+    pub fn arrange_override_impl(_this: &Rc<dyn IsView>, bounds: Rect) -> Vector {
+        Vector { x: bounds.w(), y: 1 }
+    }
+
+    pub fn render_impl(this: &Rc<dyn IsView>, rp: &mut RenderPort) {
+        let this: Rc<dyn IsMenuBar> = dyn_cast_rc(this.clone()).unwrap();
+        let data = this.menu_bar().data.borrow();
+        rp.fill_bg(data.color);
+        let mut x = 1i16;
+        for (i, item) in data.items.iter().enumerate() {
+            let text = item.text();
+            let (color, color_hotkey) = if !item.is_enabled() {
+                (data.color_disabled, data.color_disabled)
+            } else if data.opened.as_ref().is_some_and(|x| x.0 == i) {
+                (data.color_selected, (data.color_hotkey.0, data.color_selected.1))
+            } else {
+                (data.color, data.color_hotkey)
+            };
+            let width = label_width(&text);
+            rp.text(Point { x, y: 0 }, color, " ");
+            rp.label(Point { x: x.wrapping_add(1), y: 0 }, color, color_hotkey, &text);
+            rp.text(Point { x: x.wrapping_add(1).wrapping_add(width), y: 0 }, color, " ");
+            x = x.wrapping_add(width).wrapping_add(2);
+        }
+    }
+
+    pub fn pre_post_process_impl(_this: &Rc<dyn IsView>) -> PrePostProcess {
+        PrePostProcess::PRE_PROCESS
+    }
+
+    pub fn pre_process_key_impl(this: &Rc<dyn IsView>, key: Key) -> bool {
+        let this: Rc<dyn IsMenuBar> = dyn_cast_rc(this.clone()).unwrap();
+        match key {
+            Key::F10 => {
+                if this.menu_bar().data.borrow().opened.is_some() {
+                    this.close();
+                } else {
+                    this.open_next(true);
+                }
+                true
+            },
+            Key::Alt(c) => {
+                let c = c.to_lowercase().next().unwrap();
+                let items = this.items();
+                let Some(index) = items.iter().position(|x| x.is_enabled() && label(&x.text()) == Some(c)) else {
+                    return false;
+                };
+                this.open(index);
+                true
+            },
+            _ => false
+        }
+    }
+
+    pub fn mouse_impl(this: &Rc<dyn IsView>, event: MouseEvent, original_source: &Rc<dyn IsView>) -> bool {
+        let menu_bar: Rc<dyn IsMenuBar> = dyn_cast_rc(this.clone()).unwrap();
+        match event.action {
+            MouseAction::Down(MouseButton::Left) => {
+                let items = menu_bar.items();
+                let just_closed = menu_bar.menu_bar().data.borrow_mut().just_closed.take();
+                if let Some(index) = Self::item_at(&items, event.point.x) && just_closed != Some(index) {
+                    menu_bar.open(index);
+                }
+                true
+            },
+            _ => View::mouse_impl(this, event, original_source)
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! menu_bar_template {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident in $mod:ident {
+            $(use $path:path as $import:ident;)*
+            $($(
+                $(#[$field_attr:meta])*
+                pub $field_name:ident : $field_ty:ty
+            ),+ $(,)?)?
+        }
+    ) => {
+        $crate::view_template! {
+            $(#[$attr])*
+            $vis struct $name in $mod {
+                $(use $path as $import;)*
+                #[serde(default)]
+                #[serde(skip_serializing_if="Vec::is_empty")]
+                pub items: Vec<Box<dyn $crate::template::Template>>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color: Option<($crate::base::Fg, $crate::base::Bg)>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color_hotkey: Option<($crate::base::Fg, $crate::base::Bg)>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color_selected: Option<($crate::base::Fg, $crate::base::Bg)>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color_disabled: Option<($crate::base::Fg, $crate::base::Bg)>,
+                $($(
+                    $(#[$field_attr])*
+                    pub $field_name : $field_ty
+                ),+)?
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! menu_bar_apply_template {
+    ($this:ident, $instance:ident, $names:ident) => {
+        $crate::view_apply_template!($this, $instance, $names);
+        {
+            use $crate::menu_bar::MenuBarExt;
+
+            let obj: $crate::alloc_rc_Rc<dyn $crate::menu_bar::IsMenuBar>
+                = $crate::dynamic_cast_dyn_cast_rc($instance.clone()).unwrap();
+            if !$this.items.is_empty() {
+                obj.set_items($this.items.iter().map(|x|
+                    $crate::dynamic_cast_dyn_cast_rc(x.load_content($names)).unwrap()
+                ).collect());
+            }
+            $this.color.map(|x| obj.set_color(x));
+            $this.color_hotkey.map(|x| obj.set_color_hotkey(x));
+            $this.color_selected.map(|x| obj.set_color_selected(x));
+            $this.color_disabled.map(|x| obj.set_color_disabled(x));
+        }
+    };
+}
+
+menu_bar_template! {
+    #[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+    #[serde(rename="MenuBar@Items")]
+    pub struct MenuBarTemplate in template { }
+}
+
+#[typetag::serde(name="MenuBar")]
+impl Template for MenuBarTemplate {
+    fn is_name_scope(&self) -> bool {
+        self.is_name_scope
+    }
+
+    fn name(&self) -> Option<&String> {
+        Some(&self.name)
+    }
+
+    fn create_instance(&self) -> Rc<dyn IsObj> {
+        MenuBar::new()
+    }
+
+    fn apply(&self, instance: &Rc<dyn IsObj>, names: &mut NameResolver) {
+        let this = self;
+        menu_bar_apply_template!(this, instance, names);
+    }
+}
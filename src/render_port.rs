@@ -1,5 +1,5 @@
 use iter_identify_first_last::IteratorIdentifyFirstLastExt;
-use crate::base::{Vector, Rect, Point, Range1d, Screen, Fg, Bg, text_width};
+use crate::base::{Vector, Rect, Point, Range1d, Screen, Fg, Bg, Attr, text_width};
 
 pub struct RenderPort<'a> {
     pub(crate) screen: &'a mut dyn Screen,
@@ -7,6 +7,7 @@ pub struct RenderPort<'a> {
     pub(crate) offset: Vector,
     pub(crate) invalidated_rect: Rect,
     pub(crate) cursor: Option<Point>,
+    pub(crate) dim: bool,
 }
 
 impl<'a> RenderPort<'a> {
@@ -18,8 +19,9 @@ impl<'a> RenderPort<'a> {
         if !self.invalidated_rect.v_range().contains(p.y) { return; }
         if p.y < 0 || p.y >= screen_size.y { return; }
         if p.x >= bounds.r() || p.x >= self.invalidated_rect.r() { return; } // don't screen do same check?
+        let attr = if self.dim { Attr::DIM } else { Attr::empty() };
         let rendered = self.screen.out(
-            p, color.0, color.1, text, bounds.h_range(), self.invalidated_rect.h_range()
+            p, color.0, color.1, attr, text, bounds.h_range(), self.invalidated_rect.h_range()
         );
         self.invalidated_rect = self.invalidated_rect.union_intersect(
             Rect::from_h_v_ranges(rendered, Range1d { start: p.y, end: p.y.wrapping_add(1) }),
@@ -33,14 +35,19 @@ impl<'a> RenderPort<'a> {
     }
 
     pub fn text(&mut self, p: Point, color: (Fg, Bg), text: &str) {
+        self.text_attr(p, color, Attr::empty(), text);
+    }
+
+    pub fn text_attr(&mut self, p: Point, color: (Fg, Bg), attr: Attr, text: &str) {
         let screen_size = self.screen.size();
         let p = p.offset(self.offset);
         if !self.bounds.v_range().contains(p.y) { return; }
         if !self.invalidated_rect.v_range().contains(p.y) { return; }
         if p.y < 0 || p.y >= screen_size.y { return; }
         if p.x >= self.bounds.r() || p.x >= self.invalidated_rect.r() { return; } // don't screen do same check?
+        let attr = if self.dim { attr | Attr::DIM } else { attr };
         let rendered = self.screen.out(
-            p, color.0, color.1, text, self.bounds.h_range(), self.invalidated_rect.h_range()
+            p, color.0, color.1, attr, text, self.bounds.h_range(), self.invalidated_rect.h_range()
         );
         self.invalidated_rect = self.invalidated_rect.union_intersect(
             Rect::from_h_v_ranges(rendered, Range1d { start: p.y, end: p.y.wrapping_add(1) }),
@@ -77,14 +84,30 @@ impl<'a> RenderPort<'a> {
         }
     }
 
-    pub fn label(&mut self, mut p: Point, color: (Fg, Bg), color_hotkey: (Fg, Bg), text: &str) {
+    pub fn label(&mut self, p: Point, color: (Fg, Bg), color_hotkey: (Fg, Bg), text: &str) {
+        self.label_attr(p, color, color_hotkey, Attr::empty(), Attr::empty(), text);
+    }
+
+    pub fn label_attr(
+        &mut self,
+        mut p: Point,
+        color: (Fg, Bg),
+        color_hotkey: (Fg, Bg),
+        attr: Attr,
+        attr_hotkey: Attr,
+        text: &str
+    ) {
         let mut hotkey = false;
         for (first, last, text) in text.split('~').identify_first_last() {
             if !first && !text.is_empty() {
                 hotkey = !hotkey;
             }
             let actual_text = if !first && !last && text.is_empty() { "~" } else { text };
-            self.text(p, if hotkey { color_hotkey } else { color }, actual_text);
+            if hotkey {
+                self.text_attr(p, color_hotkey, attr_hotkey, actual_text);
+            } else {
+                self.text_attr(p, color, attr, actual_text);
+            }
             p = p.offset(Vector { x: text_width(actual_text), y: 0 });
             if !first && text.is_empty() {
                 hotkey = !hotkey;
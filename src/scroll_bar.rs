@@ -0,0 +1,411 @@
+use basic_oop::{class_unsafe, import, Vtable};
+use dynamic_cast::dyn_cast_rc;
+use std::cell::RefCell;
+use std::cmp::{max, min};
+use std::ptr::addr_eq;
+use crate::app::AppExt;
+use crate::base::{MouseAction, MouseButton};
+use crate::event_handler::EventHandler;
+use crate::template::{Template, NameResolver};
+
+import! { pub scroll_bar:
+    use [view crate::view];
+    use crate::base::{Fg, Bg};
+}
+
+const WHEEL_LINES: i16 = 3;
+
+struct ScrollBarData {
+    vertical: bool,
+    value: i16,
+    maximum: i16,
+    viewport_size: i16,
+    color: (Fg, Bg),
+    color_thumb: (Fg, Bg),
+    thumb_grip: Option<i16>,
+    value_change_handler: EventHandler<Option<Box<dyn FnMut()>>>,
+}
+
+/// A bar with arrows at both ends and a thumb showing the value in the `0 ..= maximum` range.
+///
+/// The thumb length is proportional to the viewport size, which is also the page the bar scrolls by
+/// on a click on the track.
+#[class_unsafe(inherits_View)]
+pub struct ScrollBar {
+    data: RefCell<ScrollBarData>,
+    #[non_virt]
+    vertical: fn() -> bool,
+    #[non_virt]
+    set_vertical: fn(value: bool),
+    #[non_virt]
+    value: fn() -> i16,
+    #[non_virt]
+    set_value: fn(value: i16),
+    #[non_virt]
+    maximum: fn() -> i16,
+    #[non_virt]
+    set_maximum: fn(value: i16),
+    #[non_virt]
+    viewport_size: fn() -> i16,
+    #[non_virt]
+    set_viewport_size: fn(value: i16),
+    #[non_virt]
+    color: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color: fn(value: (Fg, Bg)),
+    #[non_virt]
+    color_thumb: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color_thumb: fn(value: (Fg, Bg)),
+    #[non_virt]
+    handle_value_change: fn(handler: Option<Box<dyn FnMut()>>),
+    #[virt]
+    value_changed: fn(),
+    #[over]
+    measure_override: (),
+    #[over]
+    arrange_override: (),
+    #[over]
+    render: (),
+    #[over]
+    mouse: (),
+}
+
+impl ScrollBar {
+    pub fn new() -> Rc<dyn IsScrollBar> {
+        let res: Rc<dyn IsScrollBar> = Rc::new(unsafe { Self::new_raw(SCROLL_BAR_VTABLE.as_ptr()) });
+        res._init();
+        res
+    }
+
+    pub unsafe fn new_raw(vtable: Vtable) -> Self {
+        ScrollBar {
+            view: unsafe { View::new_raw(vtable) },
+            data: RefCell::new(ScrollBarData {
+                vertical: true,
+                value: 0,
+                maximum: 0,
+                viewport_size: 1,
+                color: (Fg::Blue, Bg::Cyan),
+                color_thumb: (Fg::Blue, Bg::Cyan),
+                thumb_grip: None,
+                value_change_handler: Default::default(),
+            }),
+        }
+    }
+
+    pub fn vertical_impl(this: &Rc<dyn IsScrollBar>) -> bool {
+        this.scroll_bar().data.borrow().vertical
+    }
+
+    pub fn set_vertical_impl(this: &Rc<dyn IsScrollBar>, value: bool) {
+        {
+            let mut data = this.scroll_bar().data.borrow_mut();
+            if data.vertical == value { return; }
+            data.vertical = value;
+        }
+        this.invalidate_measure();
+    }
+
+    pub fn value_impl(this: &Rc<dyn IsScrollBar>) -> i16 {
+        this.scroll_bar().data.borrow().value
+    }
+
+    /// Sets the value, clamped to the `0 ..= maximum` range.
+    pub fn set_value_impl(this: &Rc<dyn IsScrollBar>, value: i16) {
+        {
+            let mut data = this.scroll_bar().data.borrow_mut();
+            let value = value.clamp(0, data.maximum);
+            if data.value == value { return; }
+            data.value = value;
+        }
+        this.invalidate_render();
+        this.value_changed();
+    }
+
+    pub fn maximum_impl(this: &Rc<dyn IsScrollBar>) -> i16 {
+        this.scroll_bar().data.borrow().maximum
+    }
+
+    pub fn set_maximum_impl(this: &Rc<dyn IsScrollBar>, value: i16) {
+        let value_changed = {
+            let mut data = this.scroll_bar().data.borrow_mut();
+            let value = max(value, 0);
+            if data.maximum == value { return; }
+            data.maximum = value;
+            let old_value = data.value;
+            data.value = min(data.value, value);
+            data.value != old_value
+        };
+        this.invalidate_render();
+        if value_changed {
+            this.value_changed();
+        }
+    }
+
+    pub fn viewport_size_impl(this: &Rc<dyn IsScrollBar>) -> i16 {
+        this.scroll_bar().data.borrow().viewport_size
+    }
+
+    pub fn set_viewport_size_impl(this: &Rc<dyn IsScrollBar>, value: i16) {
+        {
+            let mut data = this.scroll_bar().data.borrow_mut();
+            let value = max(value, 1);
+            if data.viewport_size == value { return; }
+            data.viewport_size = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn color_impl(this: &Rc<dyn IsScrollBar>) -> (Fg, Bg) {
+        this.scroll_bar().data.borrow().color
+    }
+
+    pub fn set_color_impl(this: &Rc<dyn IsScrollBar>, value: (Fg, Bg)) {
+        {
+            let mut data = this.scroll_bar().data.borrow_mut();
+            if data.color == value { return; }
+            data.color = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn color_thumb_impl(this: &Rc<dyn IsScrollBar>) -> (Fg, Bg) {
+        this.scroll_bar().data.borrow().color_thumb
+    }
+
+    pub fn set_color_thumb_impl(this: &Rc<dyn IsScrollBar>, value: (Fg, Bg)) {
+        {
+            let mut data = this.scroll_bar().data.borrow_mut();
+            if data.color_thumb == value { return; }
+            data.color_thumb = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn handle_value_change_impl(this: &Rc<dyn IsScrollBar>, handler: Option<Box<dyn FnMut()>>) {
+        this.scroll_bar().data.borrow_mut().value_change_handler.set(handler);
+    }
+
+    pub fn value_changed_impl(this: &Rc<dyn IsScrollBar>) {
+        let mut invoke = this.scroll_bar().data.borrow_mut().value_change_handler.begin_invoke();
+        invoke.as_mut().map(|x| x());
+        this.scroll_bar().data.borrow_mut().value_change_handler.end_invoke(invoke);
+    }
+
+    pub fn measure_override_impl(this: &Rc<dyn IsView>, w: Option<i16>, h: Option<i16>) -> Vector {
+        let this: Rc<dyn IsScrollBar> = dyn_cast_rc(this.clone()).unwrap();
+        if this.vertical() {
+            Vector { x: 1, y: h.unwrap_or(2) }
+        } else {
+            Vector { x: w.unwrap_or(2), y: 1 }
+        }
+    }
+
+    pub fn arrange_override_impl(this: &Rc<dyn IsView>, bounds: Rect) -> Vector {
+        let this: Rc<dyn IsScrollBar> = dyn_cast_rc(this.clone()).unwrap();
+        if this.vertical() {
+            Vector { x: min(bounds.w(), 1), y: bounds.h() }
+        } else {
+            Vector { x: bounds.w(), y: min(bounds.h(), 1) }
+        }
+    }
+
+    fn length(this: &Rc<dyn IsScrollBar>) -> i16 {
+        let size = this.inner_render_bounds().size;
+        if this.scroll_bar().data.borrow().vertical { size.y } else { size.x }
+    }
+
+    /// The thumb start and length along the track, which starts right after the first arrow.
+    fn thumb(data: &ScrollBarData, length: i16) -> Option<(i16, i16)> {
+        let track = i32::from(length) - 2;
+        if track < 1 || data.maximum == 0 { return None; }
+        let total = i32::from(data.maximum) + i32::from(data.viewport_size);
+        let thumb = (track * i32::from(data.viewport_size) / total).clamp(1, track);
+        let start = (track - thumb) * i32::from(data.value) / i32::from(data.maximum);
+        Some((start as i16, thumb as i16))
+    }
+
+    fn value_at_thumb_start(data: &ScrollBarData, length: i16, start: i16) -> i16 {
+        let Some((_, thumb)) = Self::thumb(data, length) else { return 0; };
+        let range = i32::from(length) - 2 - i32::from(thumb);
+        if range <= 0 { return 0; }
+        let start = i32::from(start).clamp(0, range);
+        ((start * i32::from(data.maximum) + range / 2) / range) as i16
+    }
+
+    pub fn render_impl(this: &Rc<dyn IsView>, rp: &mut RenderPort) {
+        let this: Rc<dyn IsScrollBar> = dyn_cast_rc(this.clone()).unwrap();
+        let length = Self::length(&this);
+        let data = this.scroll_bar().data.borrow();
+        let at = |i: i16| if data.vertical { Point { x: 0, y: i } } else { Point { x: i, y: 0 } };
+        rp.fill_bg(data.color);
+        for i in 1 .. length.wrapping_sub(1) {
+            rp.text(at(i), data.color, "░");
+        }
+        if let Some((start, thumb)) = Self::thumb(&data, length) {
+            for i in start .. start + thumb {
+                rp.text(at(i + 1), data.color_thumb, "█");
+            }
+        }
+        let (first, last) = if data.vertical { ("▲", "▼") } else { ("◄", "►") };
+        rp.text(at(0), data.color, first);
+        if length > 1 {
+            rp.text(at(length - 1), data.color, last);
+        }
+    }
+
+    fn scroll_by(this: &Rc<dyn IsScrollBar>, delta: i16) {
+        let value = this.value();
+        this.set_value(value.saturating_add(delta));
+    }
+
+    pub fn mouse_impl(this: &Rc<dyn IsView>, event: MouseEvent, original_source: &Rc<dyn IsView>) -> bool {
+        let app = this.app().unwrap();
+        let scroll_bar: Rc<dyn IsScrollBar> = dyn_cast_rc(this.clone()).unwrap();
+        let length = Self::length(&scroll_bar);
+        let (i, page) = {
+            let data = scroll_bar.scroll_bar().data.borrow();
+            (if data.vertical { event.point.y } else { event.point.x }, data.viewport_size)
+        };
+        match event.action {
+            MouseAction::Down(MouseButton::Left) => {
+                if i <= 0 {
+                    Self::scroll_by(&scroll_bar, -1);
+                } else if i >= length - 1 {
+                    Self::scroll_by(&scroll_bar, 1);
+                } else {
+                    let thumb = Self::thumb(&scroll_bar.scroll_bar().data.borrow(), length);
+                    match thumb {
+                        Some((start, _)) if i - 1 < start => Self::scroll_by(&scroll_bar, -page),
+                        Some((start, thumb)) if i - 1 >= start + thumb => Self::scroll_by(&scroll_bar, page),
+                        Some((start, _)) => {
+                            scroll_bar.scroll_bar().data.borrow_mut().thumb_grip = Some(i - 1 - start);
+                            app.capture_mouse(Some(this));
+                        },
+                        None => { },
+                    }
+                }
+                true
+            },
+            MouseAction::Drag(MouseButton::Left) => {
+                let is_captured = app.mouse_capture().is_some_and(|x| addr_eq(Rc::as_ptr(&x), Rc::as_ptr(this)));
+                if !is_captured { return true; }
+                let value = {
+                    let data = scroll_bar.scroll_bar().data.borrow();
+                    let Some(thumb_grip) = data.thumb_grip else { return true; };
+                    Self::value_at_thumb_start(&data, length, i - 1 - thumb_grip)
+                };
+                scroll_bar.set_value(value);
+                true
+            },
+            MouseAction::Up(MouseButton::Left) => {
+                scroll_bar.scroll_bar().data.borrow_mut().thumb_grip = None;
+                let is_captured = app.mouse_capture().is_some_and(|x| addr_eq(Rc::as_ptr(&x), Rc::as_ptr(this)));
+                if is_captured {
+                    app.capture_mouse(None);
+                }
+                true
+            },
+            MouseAction::WheelUp => {
+                Self::scroll_by(&scroll_bar, -WHEEL_LINES);
+                true
+            },
+            MouseAction::WheelDown => {
+                Self::scroll_by(&scroll_bar, WHEEL_LINES);
+                true
+            },
+            _ => View::mouse_impl(this, event, original_source)
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! scroll_bar_template {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident in $mod:ident {
+            $(use $path:path as $import:ident;)*
+
+            $($(
+                $(#[$field_attr:meta])*
+                pub $field_name:ident : $field_ty:ty
+            ),+ $(,)?)?
+        }
+    ) => {
+        $crate::view_template! {
+            $(#[$attr])*
+            $vis struct $name in $mod {
+                $(use $path as $import;)*
+
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub vertical: Option<bool>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub maximum: Option<i16>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub value: Option<i16>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub viewport_size: Option<i16>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color: Option<($crate::base::Fg, $crate::base::Bg)>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color_thumb: Option<($crate::base::Fg, $crate::base::Bg)>,
+                $($(
+                    $(#[$field_attr])*
+                    pub $field_name : $field_ty
+                ),+)?
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! scroll_bar_apply_template {
+    ($this:ident, $instance:ident, $names:ident) => {
+        $crate::view_apply_template!($this, $instance, $names);
+        {
+            use $crate::scroll_bar::ScrollBarExt;
+
+            let obj: $crate::alloc_rc_Rc<dyn $crate::scroll_bar::IsScrollBar>
+                = $crate::dynamic_cast_dyn_cast_rc($instance.clone()).unwrap();
+            $this.vertical.map(|x| obj.set_vertical(x));
+            $this.maximum.map(|x| obj.set_maximum(x));
+            $this.value.map(|x| obj.set_value(x));
+            $this.viewport_size.map(|x| obj.set_viewport_size(x));
+            $this.color.map(|x| obj.set_color(x));
+            $this.color_thumb.map(|x| obj.set_color_thumb(x));
+        }
+    };
+}
+
+scroll_bar_template! {
+    #[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+    #[serde(rename="ScrollBar")]
+    pub struct ScrollBarTemplate in template { }
+}
+
+#[typetag::serde(name="ScrollBar")]
+impl Template for ScrollBarTemplate {
+    fn is_name_scope(&self) -> bool {
+        self.is_name_scope
+    }
+
+    fn name(&self) -> Option<&String> {
+        Some(&self.name)
+    }
+
+    fn create_instance(&self) -> Rc<dyn IsObj> {
+        ScrollBar::new()
+    }
+
+    fn apply(&self, instance: &Rc<dyn IsObj>, names: &mut NameResolver) {
+        let this = self;
+        scroll_bar_apply_template!(this, instance, names);
+    }
+}
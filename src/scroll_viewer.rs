@@ -0,0 +1,523 @@
+use basic_oop::{class_unsafe, import, Vtable};
+use dynamic_cast::dyn_cast_rc;
+use serde::{Serialize, Deserialize};
+use std::cell::RefCell;
+use std::cmp::{max, min};
+use std::ptr::addr_eq;
+use std::rc::{self};
+use crate::app::AppExt;
+use crate::base::{Modifiers, MouseAction, MouseButton, SpecialKey};
+use crate::scroll_bar::{IsScrollBar, ScrollBar, ScrollBarExt};
+use crate::template::{Template, NameResolver};
+use crate::view::Visibility;
+
+import! { pub scroll_viewer:
+    use [decorator crate::decorator];
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+#[derive(Serialize, Deserialize)]
+pub enum ScrollBarVisibility {
+    /// The scroll bar is shown if the content does not fit.
+    Auto,
+    Visible,
+    /// The content scrolls, but the scroll bar is not shown.
+    Hidden,
+    /// The content does not scroll, it is measured with the viewer size.
+    Disabled,
+}
+
+const WHEEL_LINES: i16 = 3;
+
+struct ScrollViewerData {
+    h_scroll_bar: Rc<dyn IsScrollBar>,
+    v_scroll_bar: Rc<dyn IsScrollBar>,
+    h_scroll_bar_visibility: ScrollBarVisibility,
+    v_scroll_bar_visibility: ScrollBarVisibility,
+    offset: Vector,
+    extent: Vector,
+    viewport: Vector,
+    bring_into_view: Option<rc::Weak<dyn IsView>>,
+    updating_scroll_bars: bool,
+}
+
+/// Shows a part of its child, which is measured with unbounded size
+/// and arranged at the scroll offset, clipped to the viewport.
+///
+/// Scrolls with the scroll bars, the mouse wheel (Shift+wheel scrolls horizontally),
+/// Page Up and Page Down, and arrows, Home and End if the viewer itself is focused.
+/// A focused descendant is scrolled into view automatically.
+#[class_unsafe(inherits_Decorator)]
+pub struct ScrollViewer {
+    data: RefCell<ScrollViewerData>,
+    #[non_virt]
+    h_scroll_bar_visibility: fn() -> ScrollBarVisibility,
+    #[non_virt]
+    set_h_scroll_bar_visibility: fn(value: ScrollBarVisibility),
+    #[non_virt]
+    v_scroll_bar_visibility: fn() -> ScrollBarVisibility,
+    #[non_virt]
+    set_v_scroll_bar_visibility: fn(value: ScrollBarVisibility),
+    #[non_virt]
+    h_scroll_bar: fn() -> Rc<dyn IsScrollBar>,
+    #[non_virt]
+    v_scroll_bar: fn() -> Rc<dyn IsScrollBar>,
+    #[non_virt]
+    offset: fn() -> Vector,
+    #[non_virt]
+    set_offset: fn(value: Vector),
+    #[non_virt]
+    extent: fn() -> Vector,
+    #[non_virt]
+    viewport: fn() -> Vector,
+    #[non_virt]
+    bring_into_view: fn(descendant: &Rc<dyn IsView>),
+    #[over]
+    _init: (),
+    #[over]
+    visual_children_count: (),
+    #[over]
+    visual_child: (),
+    #[over]
+    visual_child_clip: (),
+    #[over]
+    measure_override: (),
+    #[over]
+    arrange_override: (),
+    #[over]
+    render: (),
+    #[over]
+    descendant_focused: (),
+    #[over]
+    key: (),
+    #[over]
+    mouse: (),
+}
+
+impl ScrollViewer {
+    pub fn new() -> Rc<dyn IsScrollViewer> {
+        let res: Rc<dyn IsScrollViewer> = Rc::new(unsafe { Self::new_raw(SCROLL_VIEWER_VTABLE.as_ptr()) });
+        res._init();
+        res
+    }
+
+    pub unsafe fn new_raw(vtable: Vtable) -> Self {
+        let h_scroll_bar = ScrollBar::new();
+        h_scroll_bar.set_vertical(false);
+        ScrollViewer {
+            decorator: unsafe { Decorator::new_raw(vtable) },
+            data: RefCell::new(ScrollViewerData {
+                h_scroll_bar,
+                v_scroll_bar: ScrollBar::new(),
+                h_scroll_bar_visibility: ScrollBarVisibility::Auto,
+                v_scroll_bar_visibility: ScrollBarVisibility::Auto,
+                offset: Vector::null(),
+                extent: Vector::null(),
+                viewport: Vector::null(),
+                bring_into_view: None,
+                updating_scroll_bars: false,
+            }),
+        }
+    }
+
+    pub fn _init_impl(this: &Rc<dyn IsView>) {
+        View::_init_impl(this);
+        this.set_allow_focus(true);
+        let scroll_viewer: Rc<dyn IsScrollViewer> = dyn_cast_rc(this.clone()).unwrap();
+        let (h_scroll_bar, v_scroll_bar) = {
+            let data = scroll_viewer.scroll_viewer().data.borrow();
+            (data.h_scroll_bar.clone(), data.v_scroll_bar.clone())
+        };
+        for scroll_bar in [h_scroll_bar, v_scroll_bar] {
+            let viewer = Rc::downgrade(&scroll_viewer);
+            let bar = Rc::downgrade(&scroll_bar);
+            let vertical = scroll_bar.vertical();
+            scroll_bar.handle_value_change(Some(Box::new(move || {
+                let (Some(viewer), Some(bar)) = (viewer.upgrade(), bar.upgrade()) else { return; };
+                if viewer.scroll_viewer().data.borrow().updating_scroll_bars { return; }
+                let offset = viewer.offset();
+                let value = bar.value();
+                viewer.set_offset(if vertical { Vector { x: offset.x, y: value } } else { Vector { x: value, y: offset.y } });
+            })));
+            scroll_bar.set_visibility(Visibility::Collapsed);
+            let scroll_bar: Rc<dyn IsView> = scroll_bar;
+            scroll_bar._set_layout_parent(Some(this));
+            scroll_bar._set_visual_parent(Some(this));
+            this.add_visual_child(&scroll_bar);
+        }
+    }
+
+    pub fn h_scroll_bar_visibility_impl(this: &Rc<dyn IsScrollViewer>) -> ScrollBarVisibility {
+        this.scroll_viewer().data.borrow().h_scroll_bar_visibility
+    }
+
+    pub fn set_h_scroll_bar_visibility_impl(this: &Rc<dyn IsScrollViewer>, value: ScrollBarVisibility) {
+        {
+            let mut data = this.scroll_viewer().data.borrow_mut();
+            if data.h_scroll_bar_visibility == value { return; }
+            data.h_scroll_bar_visibility = value;
+        }
+        this.invalidate_measure();
+    }
+
+    pub fn v_scroll_bar_visibility_impl(this: &Rc<dyn IsScrollViewer>) -> ScrollBarVisibility {
+        this.scroll_viewer().data.borrow().v_scroll_bar_visibility
+    }
+
+    pub fn set_v_scroll_bar_visibility_impl(this: &Rc<dyn IsScrollViewer>, value: ScrollBarVisibility) {
+        {
+            let mut data = this.scroll_viewer().data.borrow_mut();
+            if data.v_scroll_bar_visibility == value { return; }
+            data.v_scroll_bar_visibility = value;
+        }
+        this.invalidate_measure();
+    }
+
+    pub fn h_scroll_bar_impl(this: &Rc<dyn IsScrollViewer>) -> Rc<dyn IsScrollBar> {
+        this.scroll_viewer().data.borrow().h_scroll_bar.clone()
+    }
+
+    pub fn v_scroll_bar_impl(this: &Rc<dyn IsScrollViewer>) -> Rc<dyn IsScrollBar> {
+        this.scroll_viewer().data.borrow().v_scroll_bar.clone()
+    }
+
+    /// The content point shown at the viewport top left corner.
+    pub fn offset_impl(this: &Rc<dyn IsScrollViewer>) -> Vector {
+        this.scroll_viewer().data.borrow().offset
+    }
+
+    /// Scrolls the content. The offset is clamped so that the viewport stays inside the content.
+    pub fn set_offset_impl(this: &Rc<dyn IsScrollViewer>, value: Vector) {
+        {
+            let mut data = this.scroll_viewer().data.borrow_mut();
+            let value = Self::clamp_offset(value, data.extent, data.viewport);
+            if data.offset == value { return; }
+            data.offset = value;
+        }
+        this.invalidate_arrange();
+    }
+
+    /// The arranged child size.
+    pub fn extent_impl(this: &Rc<dyn IsScrollViewer>) -> Vector {
+        this.scroll_viewer().data.borrow().extent
+    }
+
+    /// The size of the part of the viewer showing the child, i.e. without scroll bars.
+    pub fn viewport_impl(this: &Rc<dyn IsScrollViewer>) -> Vector {
+        this.scroll_viewer().data.borrow().viewport
+    }
+
+    /// Scrolls `descendant` into view on the next arrange.
+    pub fn bring_into_view_impl(this: &Rc<dyn IsScrollViewer>, descendant: &Rc<dyn IsView>) {
+        this.scroll_viewer().data.borrow_mut().bring_into_view = Some(Rc::downgrade(descendant));
+        this.invalidate_arrange();
+    }
+
+    fn clamp_offset(offset: Vector, extent: Vector, viewport: Vector) -> Vector {
+        Vector {
+            x: offset.x.clamp(0, max(extent.x.wrapping_sub(viewport.x), 0)),
+            y: offset.y.clamp(0, max(extent.y.wrapping_sub(viewport.y), 0)),
+        }
+    }
+
+    pub fn visual_children_count_impl(this: &Rc<dyn IsView>) -> usize {
+        Decorator::visual_children_count_impl(this) + 2
+    }
+
+    pub fn visual_child_impl(this: &Rc<dyn IsView>, index: usize) -> Rc<dyn IsView> {
+        let scroll_viewer: Rc<dyn IsScrollViewer> = dyn_cast_rc(this.clone()).unwrap();
+        let child_count = Decorator::visual_children_count_impl(this);
+        if index < child_count { return Decorator::visual_child_impl(this, index); }
+        let data = scroll_viewer.scroll_viewer().data.borrow();
+        match index - child_count {
+            0 => data.v_scroll_bar.clone(),
+            1 => data.h_scroll_bar.clone(),
+            _ => panic!("visual child index out of bounds")
+        }
+    }
+
+    pub fn visual_child_clip_impl(this: &Rc<dyn IsView>, index: usize) -> Option<Rect> {
+        if index >= Decorator::visual_children_count_impl(this) { return None; }
+        let this: Rc<dyn IsScrollViewer> = dyn_cast_rc(this.clone()).unwrap();
+        Some(Rect { tl: Point { x: 0, y: 0 }, size: this.viewport() })
+    }
+
+    pub fn measure_override_impl(this: &Rc<dyn IsView>, w: Option<i16>, h: Option<i16>) -> Vector {
+        let this: Rc<dyn IsScrollViewer> = dyn_cast_rc(this.clone()).unwrap();
+        let (h_visibility, v_visibility, h_scroll_bar, v_scroll_bar) = {
+            let data = this.scroll_viewer().data.borrow();
+            (data.h_scroll_bar_visibility, data.v_scroll_bar_visibility, data.h_scroll_bar.clone(), data.v_scroll_bar.clone())
+        };
+        h_scroll_bar.measure(w, Some(1));
+        v_scroll_bar.measure(Some(1), h);
+        let h_bar = if h_visibility == ScrollBarVisibility::Visible { 1i16 } else { 0 };
+        let v_bar = if v_visibility == ScrollBarVisibility::Visible { 1i16 } else { 0 };
+        let extent = if let Some(child) = this.child() {
+            child.measure(
+                if h_visibility == ScrollBarVisibility::Disabled { w.map(|w| w.wrapping_sub(v_bar)) } else { None },
+                if v_visibility == ScrollBarVisibility::Disabled { h.map(|h| h.wrapping_sub(h_bar)) } else { None },
+            );
+            child.desired_size()
+        } else {
+            Vector::null()
+        };
+        Vector { x: extent.x.wrapping_add(v_bar), y: extent.y.wrapping_add(h_bar) }
+    }
+
+    fn arrange_child(this: &Rc<dyn IsScrollViewer>, child: &Rc<dyn IsView>) {
+        let (offset, extent) = {
+            let data = this.scroll_viewer().data.borrow();
+            (data.offset, data.extent)
+        };
+        child.arrange(Rect { tl: Point { x: offset.x.wrapping_neg(), y: offset.y.wrapping_neg() }, size: extent });
+    }
+
+    fn scroll_to_descendant(this: &Rc<dyn IsScrollViewer>, child: &Rc<dyn IsView>) -> bool {
+        let Some(descendant) = this.scroll_viewer().data.borrow_mut().bring_into_view.take() else { return false; };
+        let Some(descendant) = descendant.upgrade() else { return false; };
+        if !child.is_visual_ancestor_of(descendant.clone()) { return false; }
+        let tl = child.screen_to_inner(descendant.inner_to_screen(Point { x: 0, y: 0 }));
+        let size = descendant.inner_render_bounds().size;
+        let mut data = this.scroll_viewer().data.borrow_mut();
+        let scroll = |offset: i16, start: i16, len: i16, viewport: i16| {
+            if start < offset {
+                start
+            } else if start.wrapping_add(len) > offset.wrapping_add(viewport) {
+                min(start, start.wrapping_add(len).wrapping_sub(viewport))
+            } else {
+                offset
+            }
+        };
+        let offset = Vector {
+            x: scroll(data.offset.x, tl.x, size.x, data.viewport.x),
+            y: scroll(data.offset.y, tl.y, size.y, data.viewport.y),
+        };
+        let offset = Self::clamp_offset(offset, data.extent, data.viewport);
+        if data.offset == offset { return false; }
+        data.offset = offset;
+        true
+    }
+
+    pub fn arrange_override_impl(this: &Rc<dyn IsView>, bounds: Rect) -> Vector {
+        let this: Rc<dyn IsScrollViewer> = dyn_cast_rc(this.clone()).unwrap();
+        let child = this.child();
+        let desired_size = child.as_ref().map_or(Vector::null(), |x| x.desired_size());
+        let (h_scroll_bar, v_scroll_bar, h_bar, v_bar, viewport) = {
+            let mut data = this.scroll_viewer().data.borrow_mut();
+            let h_visibility = data.h_scroll_bar_visibility;
+            let v_visibility = data.v_scroll_bar_visibility;
+            let mut h_bar = h_visibility == ScrollBarVisibility::Visible;
+            let mut v_bar = v_visibility == ScrollBarVisibility::Visible;
+            for _ in 0 .. 2 {
+                let viewport_h = bounds.h().wrapping_sub(if h_bar { 1 } else { 0 });
+                v_bar |= v_visibility == ScrollBarVisibility::Auto && desired_size.y > viewport_h;
+                let viewport_w = bounds.w().wrapping_sub(if v_bar { 1 } else { 0 });
+                h_bar |= h_visibility == ScrollBarVisibility::Auto && desired_size.x > viewport_w;
+            }
+            let viewport = Vector {
+                x: max(bounds.w().wrapping_sub(if v_bar { 1 } else { 0 }), 0),
+                y: max(bounds.h().wrapping_sub(if h_bar { 1 } else { 0 }), 0),
+            };
+            let extent = Vector {
+                x: if h_visibility == ScrollBarVisibility::Disabled { viewport.x } else { max(desired_size.x, viewport.x) },
+                y: if v_visibility == ScrollBarVisibility::Disabled { viewport.y } else { max(desired_size.y, viewport.y) },
+            };
+            data.viewport = viewport;
+            data.extent = extent;
+            data.offset = Self::clamp_offset(data.offset, extent, viewport);
+            (data.h_scroll_bar.clone(), data.v_scroll_bar.clone(), h_bar, v_bar, viewport)
+        };
+        h_scroll_bar.set_visibility(if h_bar { Visibility::Visible } else { Visibility::Collapsed });
+        v_scroll_bar.set_visibility(if v_bar { Visibility::Visible } else { Visibility::Collapsed });
+        if let Some(child) = &child {
+            Self::arrange_child(&this, child);
+            if Self::scroll_to_descendant(&this, child) {
+                Self::arrange_child(&this, child);
+            }
+        }
+        let extent = this.extent();
+        let offset = this.offset();
+        this.scroll_viewer().data.borrow_mut().updating_scroll_bars = true;
+        h_scroll_bar.set_maximum(extent.x.wrapping_sub(viewport.x));
+        h_scroll_bar.set_viewport_size(viewport.x);
+        h_scroll_bar.set_value(offset.x);
+        h_scroll_bar.arrange(Rect { tl: Point { x: 0, y: viewport.y }, size: Vector { x: viewport.x, y: 1 } });
+        v_scroll_bar.set_maximum(extent.y.wrapping_sub(viewport.y));
+        v_scroll_bar.set_viewport_size(viewport.y);
+        v_scroll_bar.set_value(offset.y);
+        this.scroll_viewer().data.borrow_mut().updating_scroll_bars = false;
+        v_scroll_bar.arrange(Rect { tl: Point { x: viewport.x, y: 0 }, size: Vector { x: 1, y: viewport.y } });
+        bounds.size
+    }
+
+    pub fn render_impl(this: &Rc<dyn IsView>, rp: &mut RenderPort) {
+        let this: Rc<dyn IsScrollViewer> = dyn_cast_rc(this.clone()).unwrap();
+        let data = this.scroll_viewer().data.borrow();
+        let h_bar = data.h_scroll_bar.visibility() == Visibility::Visible;
+        let v_bar = data.v_scroll_bar.visibility() == Visibility::Visible;
+        if h_bar && v_bar {
+            rp.text(Point { x: data.viewport.x, y: data.viewport.y }, data.v_scroll_bar.color(), " ");
+        }
+    }
+
+    pub fn descendant_focused_impl(this: &Rc<dyn IsView>, descendant: &Rc<dyn IsView>, primary_focus: bool) {
+        View::descendant_focused_impl(this, descendant, primary_focus);
+        let this: Rc<dyn IsScrollViewer> = dyn_cast_rc(this.clone()).unwrap();
+        let is_scroll_bar = {
+            let data = this.scroll_viewer().data.borrow();
+            [&data.h_scroll_bar, &data.v_scroll_bar].into_iter().any(|x| x.is_visual_ancestor_of(descendant.clone()))
+        };
+        if is_scroll_bar { return; }
+        this.bring_into_view(descendant);
+    }
+
+    /// Scrolls by `delta`, returns `false` if the offset did not change.
+    fn scroll_by(this: &Rc<dyn IsScrollViewer>, delta: Vector) -> bool {
+        let offset = this.offset();
+        this.set_offset(Vector { x: offset.x.saturating_add(delta.x), y: offset.y.saturating_add(delta.y) });
+        this.offset() != offset
+    }
+
+    pub fn key_impl(this: &Rc<dyn IsView>, key: Key, original_source: &Rc<dyn IsView>) -> bool {
+        let scroll_viewer: Rc<dyn IsScrollViewer> = dyn_cast_rc(this.clone()).unwrap();
+        let viewport = scroll_viewer.viewport();
+        let page = max(viewport.y.wrapping_sub(1), 1);
+        match key {
+            Key::PageUp => {
+                Self::scroll_by(&scroll_viewer, Vector { x: 0, y: -page });
+                return true;
+            },
+            Key::PageDown => {
+                Self::scroll_by(&scroll_viewer, Vector { x: 0, y: page });
+                return true;
+            },
+            Key::Modified(m, SpecialKey::Home) if m == Modifiers::CTRL => {
+                scroll_viewer.set_offset(Vector::null());
+                return true;
+            },
+            Key::Modified(m, SpecialKey::End) if m == Modifiers::CTRL => {
+                let offset = scroll_viewer.offset();
+                scroll_viewer.set_offset(Vector { x: offset.x, y: i16::MAX });
+                return true;
+            },
+            _ => { },
+        }
+        let is_source = addr_eq(Rc::as_ptr(original_source), Rc::as_ptr(this));
+        if is_source {
+            let delta = match key {
+                Key::Up => Some(Vector { x: 0, y: -1 }),
+                Key::Down => Some(Vector { x: 0, y: 1 }),
+                Key::Left => Some(Vector { x: -1, y: 0 }),
+                Key::Right => Some(Vector { x: 1, y: 0 }),
+                _ => None,
+            };
+            if let Some(delta) = delta {
+                Self::scroll_by(&scroll_viewer, delta);
+                return true;
+            }
+            match key {
+                Key::Home => {
+                    let offset = scroll_viewer.offset();
+                    scroll_viewer.set_offset(Vector { x: 0, y: offset.y });
+                    return true;
+                },
+                Key::End => {
+                    let offset = scroll_viewer.offset();
+                    scroll_viewer.set_offset(Vector { x: i16::MAX, y: offset.y });
+                    return true;
+                },
+                _ => { },
+            }
+        }
+        View::key_impl(this, key, original_source)
+    }
+
+    pub fn mouse_impl(this: &Rc<dyn IsView>, event: MouseEvent, original_source: &Rc<dyn IsView>) -> bool {
+        let scroll_viewer: Rc<dyn IsScrollViewer> = dyn_cast_rc(this.clone()).unwrap();
+        let horizontal = event.modifiers.contains(Modifiers::SHIFT);
+        let delta = |lines: i16| if horizontal { Vector { x: lines, y: 0 } } else { Vector { x: 0, y: lines } };
+        match event.action {
+            MouseAction::WheelUp => Self::scroll_by(&scroll_viewer, delta(-WHEEL_LINES)),
+            MouseAction::WheelDown => Self::scroll_by(&scroll_viewer, delta(WHEEL_LINES)),
+            MouseAction::Down(MouseButton::Left) if addr_eq(Rc::as_ptr(original_source), Rc::as_ptr(this)) => {
+                this.app().unwrap().focus(Some(this), None);
+                true
+            },
+            _ => View::mouse_impl(this, event, original_source)
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! scroll_viewer_template {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident in $mod:ident {
+            $(use $path:path as $import:ident;)*
+
+            $($(
+                $(#[$field_attr:meta])*
+                pub $field_name:ident : $field_ty:ty
+            ),+ $(,)?)?
+        }
+    ) => {
+        $crate::decorator_template! {
+            $(#[$attr])*
+            $vis struct $name in $mod {
+                $(use $path as $import;)*
+
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub h_scroll_bar_visibility: Option<$crate::scroll_viewer::ScrollBarVisibility>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub v_scroll_bar_visibility: Option<$crate::scroll_viewer::ScrollBarVisibility>,
+                $($(
+                    $(#[$field_attr])*
+                    pub $field_name : $field_ty
+                ),+)?
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! scroll_viewer_apply_template {
+    ($this:ident, $instance:ident, $names:ident) => {
+        $crate::decorator_apply_template!($this, $instance, $names);
+        {
+            use $crate::scroll_viewer::ScrollViewerExt;
+
+            let obj: $crate::alloc_rc_Rc<dyn $crate::scroll_viewer::IsScrollViewer>
+                = $crate::dynamic_cast_dyn_cast_rc($instance.clone()).unwrap();
+            $this.h_scroll_bar_visibility.map(|x| obj.set_h_scroll_bar_visibility(x));
+            $this.v_scroll_bar_visibility.map(|x| obj.set_v_scroll_bar_visibility(x));
+        }
+    };
+}
+
+scroll_viewer_template! {
+    #[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+    #[serde(rename="ScrollViewer@Child")]
+    pub struct ScrollViewerTemplate in template { }
+}
+
+#[typetag::serde(name="ScrollViewer")]
+impl Template for ScrollViewerTemplate {
+    fn is_name_scope(&self) -> bool {
+        self.is_name_scope
+    }
+
+    fn name(&self) -> Option<&String> {
+        Some(&self.name)
+    }
+
+    fn create_instance(&self) -> Rc<dyn IsObj> {
+        ScrollViewer::new()
+    }
+
+    fn apply(&self, instance: &Rc<dyn IsObj>, names: &mut NameResolver) {
+        let this = self;
+        scroll_viewer_apply_template!(this, instance, names);
+    }
+}
@@ -0,0 +1,557 @@
+use basic_oop::{class_unsafe, import, Vtable};
+use dynamic_cast::dyn_cast_rc;
+use std::cell::RefCell;
+use std::ptr::addr_eq;
+use std::rc::{self};
+use crate::app::AppExt;
+use crate::base::{label_width, parse_key, MouseAction, MouseButton};
+use crate::command::{IsCommand, CommandExt};
+use crate::event_handler::EventHandler;
+use crate::template::{Template, NameResolver};
+
+import! { pub status_item:
+    use [obj basic_oop::obj];
+    use std::rc::Rc;
+    use crate::base::Key;
+}
+
+struct StatusItemData {
+    text: Rc<String>,
+    key: Option<Key>,
+    help_context: Option<u16>,
+    command: Option<Rc<dyn IsCommand>>,
+    is_enabled: bool,
+    click_handler: EventHandler<Option<Box<dyn FnMut()>>>,
+    owner: rc::Weak<dyn IsView>,
+}
+
+/// A key hint shown in a [`StatusBar`], e.g. `"~F1~ Help"`.
+#[class_unsafe(inherits_Obj)]
+pub struct StatusItem {
+    data: RefCell<StatusItemData>,
+    #[non_virt]
+    text: fn() -> Rc<String>,
+    #[non_virt]
+    set_text: fn(value: Rc<String>),
+    #[non_virt]
+    key: fn() -> Option<Key>,
+    #[non_virt]
+    set_key: fn(value: Option<Key>),
+    #[non_virt]
+    help_context: fn() -> Option<u16>,
+    #[non_virt]
+    set_help_context: fn(value: Option<u16>),
+    #[non_virt]
+    command: fn() -> Option<Rc<dyn IsCommand>>,
+    #[non_virt]
+    set_command: fn(value: Option<Rc<dyn IsCommand>>),
+    #[non_virt]
+    is_enabled: fn() -> bool,
+    #[non_virt]
+    handle_click: fn(handler: Option<Box<dyn FnMut()>>),
+    #[virt]
+    click: fn(),
+}
+
+impl StatusItem {
+    pub fn new() -> Rc<dyn IsStatusItem> {
+        Rc::new(unsafe { Self::new_raw(STATUS_ITEM_VTABLE.as_ptr()) })
+    }
+
+    pub unsafe fn new_raw(vtable: Vtable) -> Self {
+        StatusItem {
+            obj: unsafe { Obj::new_raw(vtable) },
+            data: RefCell::new(StatusItemData {
+                text: Rc::new(String::new()),
+                key: None,
+                help_context: None,
+                command: None,
+                is_enabled: true,
+                click_handler: Default::default(),
+                owner: <rc::Weak::<View>>::new(),
+            }),
+        }
+    }
+
+    pub fn text_impl(this: &Rc<dyn IsStatusItem>) -> Rc<String> {
+        this.status_item().data.borrow().text.clone()
+    }
+
+    pub fn set_text_impl(this: &Rc<dyn IsStatusItem>, value: Rc<String>) {
+        {
+            let mut data = this.status_item().data.borrow_mut();
+            if addr_eq(Rc::as_ptr(&data.text), Rc::as_ptr(&value)) { return; }
+            data.text = value;
+        }
+        Self::invalidate_owner(this);
+    }
+
+    /// The key firing the item. If not set, it is parsed from the highlighted part of the text,
+    /// e.g. `~F1~`, `~Alt-X~`, `~Ctrl-S~` or `~Shift-F6~`.
+    pub fn key_impl(this: &Rc<dyn IsStatusItem>) -> Option<Key> {
+        let data = this.status_item().data.borrow();
+        data.key.or_else(|| hotkey(&data.text).and_then(parse_key))
+    }
+
+    pub fn set_key_impl(this: &Rc<dyn IsStatusItem>, value: Option<Key>) {
+        {
+            let mut data = this.status_item().data.borrow_mut();
+            if data.key == value { return; }
+            data.key = value;
+        }
+        Self::invalidate_owner(this);
+    }
+
+    /// The help context the item is shown in, or `None` if the item is always shown.
+    pub fn help_context_impl(this: &Rc<dyn IsStatusItem>) -> Option<u16> {
+        this.status_item().data.borrow().help_context
+    }
+
+    pub fn set_help_context_impl(this: &Rc<dyn IsStatusItem>, value: Option<u16>) {
+        {
+            let mut data = this.status_item().data.borrow_mut();
+            if data.help_context == value { return; }
+            data.help_context = value;
+        }
+        Self::invalidate_owner(this);
+    }
+
+    pub fn command_impl(this: &Rc<dyn IsStatusItem>) -> Option<Rc<dyn IsCommand>> {
+        this.status_item().data.borrow().command.clone()
+    }
+
+    /// Sets the command executed on click with the [command target](crate::app::App::command_target_impl).
+    pub fn set_command_impl(this: &Rc<dyn IsStatusItem>, value: Option<Rc<dyn IsCommand>>) {
+        this.status_item().data.borrow_mut().command = value;
+    }
+
+    /// Checks if the item can be clicked, i.e. its command can be executed.
+    pub fn is_enabled_impl(this: &Rc<dyn IsStatusItem>) -> bool {
+        this.status_item().data.borrow().is_enabled
+    }
+
+    pub fn handle_click_impl(this: &Rc<dyn IsStatusItem>, handler: Option<Box<dyn FnMut()>>) {
+        this.status_item().data.borrow_mut().click_handler.set(handler);
+    }
+
+    pub fn click_impl(this: &Rc<dyn IsStatusItem>) {
+        let mut invoke = this.status_item().data.borrow_mut().click_handler.begin_invoke();
+        invoke.as_mut().map(|x| x());
+        this.status_item().data.borrow_mut().click_handler.end_invoke(invoke);
+    }
+
+    /// Updates the item state from its command. Returns `true` if the state has changed.
+    fn requery_command(this: &Rc<dyn IsStatusItem>, target: Option<&Rc<dyn IsView>>) -> bool {
+        let is_enabled = this.command().is_none_or(|x| target.is_some_and(|target| x.can_execute(target)));
+        let mut data = this.status_item().data.borrow_mut();
+        if data.is_enabled == is_enabled { return false; }
+        data.is_enabled = is_enabled;
+        true
+    }
+
+    /// Sets the status bar showing the item, which is repainted when the item changes.
+    pub(crate) fn set_owner(this: &Rc<dyn IsStatusItem>, owner: &Rc<dyn IsView>) {
+        this.status_item().data.borrow_mut().owner = Rc::downgrade(owner);
+    }
+
+    fn invalidate_owner(this: &Rc<dyn IsStatusItem>) {
+        let Some(owner) = this.status_item().data.borrow().owner.upgrade() else { return; };
+        owner.invalidate_render();
+    }
+}
+
+fn hotkey(text: &str) -> Option<&str> {
+    let start = text.find('~')? + 1;
+    let len = text[start ..].find('~')?;
+    if len == 0 { return None; }
+    Some(&text[start .. start + len])
+}
+
+#[macro_export]
+macro_rules! status_item_template {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident in $mod:ident {
+            $(use $path:path as $import:ident;)*
+            $($(
+                $(#[$field_attr:meta])*
+                pub $field_name:ident : $field_ty:ty
+            ),+ $(,)?)?
+        }
+    ) => {
+        $crate::template! {
+            $(#[$attr])*
+            $vis struct $name in $mod {
+                $(use $path as $import;)*
+                #[serde(default)]
+                #[serde(skip_serializing_if="String::is_empty")]
+                pub name: String,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub text: Option<String>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub key: Option<String>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub help_context: Option<u16>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="String::is_empty")]
+                pub command: String,
+                $($(
+                    $(#[$field_attr])*
+                    pub $field_name : $field_ty
+                ),+)?
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! status_item_apply_template {
+    ($this:ident, $instance:ident, $names:ident) => {
+        {
+            use $crate::status_bar::StatusItemExt;
+
+            let obj: $crate::alloc_rc_Rc<dyn $crate::status_bar::IsStatusItem>
+                = $crate::dynamic_cast_dyn_cast_rc($instance.clone()).unwrap();
+            $this.text.as_ref().map(|x| obj.set_text($crate::alloc_rc_Rc::new(x.clone())));
+            if let Some(key) = $this.key.as_ref() {
+                match $crate::base::parse_key(key) {
+                    Some(x) => obj.set_key(Some(x)),
+                    None => eprintln!("Warning: invalid key ('{key}')"),
+                }
+            }
+            $this.help_context.map(|x| obj.set_help_context(Some(x)));
+            $names.resolve(
+                $this.command.clone(),
+                Box::new(move |x| obj.set_command(Some($crate::dynamic_cast_dyn_cast_rc(x).unwrap())))
+            );
+        }
+    };
+}
+
+status_item_template! {
+    #[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+    #[serde(rename="StatusItem")]
+    pub struct StatusItemTemplate in status_item_template { }
+}
+
+#[typetag::serde(name="StatusItem")]
+impl Template for StatusItemTemplate {
+    fn name(&self) -> Option<&String> {
+        Some(&self.name)
+    }
+
+    fn create_instance(&self) -> Rc<dyn IsObj> {
+        StatusItem::new()
+    }
+
+    fn apply(&self, instance: &Rc<dyn IsObj>, names: &mut NameResolver) {
+        let this = self;
+        status_item_apply_template!(this, instance, names);
+    }
+}
+
+import! { pub status_bar:
+    use [view crate::view];
+    use crate::base::{Fg, Bg};
+}
+
+struct StatusBarData {
+    items: Vec<Rc<dyn IsStatusItem>>,
+    color: (Fg, Bg),
+    color_hotkey: (Fg, Bg),
+    color_disabled: (Fg, Bg),
+}
+
+/// A status line with key hints. Only the items matching
+/// the current [`App::help_context`](crate::app::App::help_context_impl) are shown.
+#[class_unsafe(inherits_View)]
+pub struct StatusBar {
+    data: RefCell<StatusBarData>,
+    #[non_virt]
+    items: fn() -> Vec<Rc<dyn IsStatusItem>>,
+    #[non_virt]
+    set_items: fn(value: Vec<Rc<dyn IsStatusItem>>),
+    #[non_virt]
+    visible_items: fn() -> Vec<Rc<dyn IsStatusItem>>,
+    #[non_virt]
+    color: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color: fn(value: (Fg, Bg)),
+    #[non_virt]
+    color_hotkey: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color_hotkey: fn(value: (Fg, Bg)),
+    #[non_virt]
+    color_disabled: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color_disabled: fn(value: (Fg, Bg)),
+    #[over]
+    _requery_commands: (),
+    #[over]
+    measure_override: (),
+    #[over]
+    arrange_override: (),
+    #[over]
+    render: (),
+    #[over]
+    help_context_changed: (),
+    #[over]
+    pre_post_process: (),
+    #[over]
+    post_process_key: (),
+    #[over]
+    mouse: (),
+}
+
+impl StatusBar {
+    pub fn new() -> Rc<dyn IsStatusBar> {
+        let res: Rc<dyn IsStatusBar> = Rc::new(unsafe { Self::new_raw(STATUS_BAR_VTABLE.as_ptr()) });
+        res._init();
+        res
+    }
+
+    pub unsafe fn new_raw(vtable: Vtable) -> Self {
+        StatusBar {
+            view: unsafe { View::new_raw(vtable) },
+            data: RefCell::new(StatusBarData {
+                items: Vec::new(),
+                color: (Fg::Black, Bg::LightGray),
+                color_hotkey: (Fg::Red, Bg::LightGray),
+                color_disabled: (Fg::DarkGray, Bg::LightGray),
+            }),
+        }
+    }
+
+    pub fn items_impl(this: &Rc<dyn IsStatusBar>) -> Vec<Rc<dyn IsStatusItem>> {
+        this.status_bar().data.borrow().items.clone()
+    }
+
+    pub fn set_items_impl(this: &Rc<dyn IsStatusBar>, value: Vec<Rc<dyn IsStatusItem>>) {
+        let owner: Rc<dyn IsView> = this.clone();
+        for item in &value {
+            StatusItem::set_owner(item, &owner);
+        }
+        this.status_bar().data.borrow_mut().items = value;
+        this.invalidate_render();
+    }
+
+    pub fn visible_items_impl(this: &Rc<dyn IsStatusBar>) -> Vec<Rc<dyn IsStatusItem>> {
+        let help_context = this.app().map_or(0, |x| x.help_context());
+        this.status_bar().data.borrow().items.iter()
+            .filter(|x| x.help_context().is_none_or(|x| x == help_context))
+            .cloned()
+            .collect()
+    }
+
+    pub fn color_impl(this: &Rc<dyn IsStatusBar>) -> (Fg, Bg) {
+        this.status_bar().data.borrow().color
+    }
+
+    pub fn set_color_impl(this: &Rc<dyn IsStatusBar>, value: (Fg, Bg)) {
+        {
+            let mut data = this.status_bar().data.borrow_mut();
+            if data.color == value { return; }
+            data.color = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn color_hotkey_impl(this: &Rc<dyn IsStatusBar>) -> (Fg, Bg) {
+        this.status_bar().data.borrow().color_hotkey
+    }
+
+    pub fn set_color_hotkey_impl(this: &Rc<dyn IsStatusBar>, value: (Fg, Bg)) {
+        {
+            let mut data = this.status_bar().data.borrow_mut();
+            if data.color_hotkey == value { return; }
+            data.color_hotkey = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn color_disabled_impl(this: &Rc<dyn IsStatusBar>) -> (Fg, Bg) {
+        this.status_bar().data.borrow().color_disabled
+    }
+
+    pub fn set_color_disabled_impl(this: &Rc<dyn IsStatusBar>, value: (Fg, Bg)) {
+        {
+            let mut data = this.status_bar().data.borrow_mut();
+            if data.color_disabled == value { return; }
+            data.color_disabled = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn _requery_commands_impl(this: &Rc<dyn IsView>) {
+        View::_requery_commands_impl(this);
+        let target = this.app().and_then(|x| x.command_target());
+        let status_bar: Rc<dyn IsStatusBar> = dyn_cast_rc(this.clone()).unwrap();
+        let mut changed = false;
+        for item in status_bar.items() {
+            changed |= StatusItem::requery_command(&item, target.as_ref());
+        }
+        if changed {
+            this.invalidate_render();
+        }
+    }
+
+    pub fn measure_override_impl(_this: &Rc<dyn IsView>, w: Option<i16>, _h: Option<i16>) -> Vector {
+        Vector { x: w.unwrap_or(0), y: 1 }
+    }
+
+    pub fn arrange_override_impl(_this: &Rc<dyn IsView>, bounds: Rect) -> Vector {
+        Vector { x: bounds.w(), y: 1 }
+    }
+
+    pub fn render_impl(this: &Rc<dyn IsView>, rp: &mut RenderPort) {
+        let this: Rc<dyn IsStatusBar> = dyn_cast_rc(this.clone()).unwrap();
+        let items = this.visible_items();
+        let data = this.status_bar().data.borrow();
+        rp.fill_bg(data.color);
+        let mut x = 1i16;
+        for item in items {
+            let text = item.text();
+            let (color, color_hotkey) = if item.is_enabled() {
+                (data.color, data.color_hotkey)
+            } else {
+                (data.color_disabled, data.color_disabled)
+            };
+            rp.label(Point { x, y: 0 }, color, color_hotkey, &text);
+            x = x.wrapping_add(label_width(&text)).wrapping_add(2);
+        }
+    }
+
+    pub fn help_context_changed_impl(this: &Rc<dyn IsView>) {
+        this.invalidate_render();
+    }
+
+    pub fn pre_post_process_impl(_this: &Rc<dyn IsView>) -> PrePostProcess {
+        PrePostProcess::POST_PROCESS
+    }
+
+    pub fn post_process_key_impl(this: &Rc<dyn IsView>, key: Key) -> bool {
+        let this: Rc<dyn IsStatusBar> = dyn_cast_rc(this.clone()).unwrap();
+        let Some(item) = this.visible_items().into_iter().find(|x| x.key() == Some(key)) else { return false; };
+        Self::invoke(&this, &item)
+    }
+
+    /// Clicks the item, and executes its command. Does nothing if the command can not be executed.
+    fn invoke(this: &Rc<dyn IsStatusBar>, item: &Rc<dyn IsStatusItem>) -> bool {
+        let target = this.app().and_then(|x| x.command_target());
+        if StatusItem::requery_command(item, target.as_ref()) {
+            this.invalidate_render();
+        }
+        if !item.is_enabled() { return false; }
+        item.click();
+        if let Some(command) = item.command() && let Some(target) = target {
+            command.execute(&target);
+        }
+        true
+    }
+
+    pub fn mouse_impl(this: &Rc<dyn IsView>, event: MouseEvent, original_source: &Rc<dyn IsView>) -> bool {
+        let status_bar: Rc<dyn IsStatusBar> = dyn_cast_rc(this.clone()).unwrap();
+        match event.action {
+            MouseAction::Down(MouseButton::Left) => {
+                let mut start = 1i16;
+                for item in status_bar.visible_items() {
+                    let end = start.wrapping_add(label_width(&item.text()));
+                    if event.point.x >= start && event.point.x < end {
+                        Self::invoke(&status_bar, &item);
+                        break;
+                    }
+                    start = end.wrapping_add(2);
+                }
+                true
+            },
+            _ => View::mouse_impl(this, event, original_source)
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! status_bar_template {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident in $mod:ident {
+            $(use $path:path as $import:ident;)*
+            $($(
+                $(#[$field_attr:meta])*
+                pub $field_name:ident : $field_ty:ty
+            ),+ $(,)?)?
+        }
+    ) => {
+        $crate::view_template! {
+            $(#[$attr])*
+            $vis struct $name in $mod {
+                $(use $path as $import;)*
+                #[serde(default)]
+                #[serde(skip_serializing_if="Vec::is_empty")]
+                pub items: Vec<Box<dyn $crate::template::Template>>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color: Option<($crate::base::Fg, $crate::base::Bg)>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color_hotkey: Option<($crate::base::Fg, $crate::base::Bg)>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color_disabled: Option<($crate::base::Fg, $crate::base::Bg)>,
+                $($(
+                    $(#[$field_attr])*
+                    pub $field_name : $field_ty
+                ),+)?
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! status_bar_apply_template {
+    ($this:ident, $instance:ident, $names:ident) => {
+        $crate::view_apply_template!($this, $instance, $names);
+        {
+            use $crate::status_bar::StatusBarExt;
+
+            let obj: $crate::alloc_rc_Rc<dyn $crate::status_bar::IsStatusBar>
+                = $crate::dynamic_cast_dyn_cast_rc($instance.clone()).unwrap();
+            if !$this.items.is_empty() {
+                obj.set_items($this.items.iter().map(|x|
+                    $crate::dynamic_cast_dyn_cast_rc(x.load_content($names)).unwrap()
+                ).collect());
+            }
+            $this.color.map(|x| obj.set_color(x));
+            $this.color_hotkey.map(|x| obj.set_color_hotkey(x));
+            $this.color_disabled.map(|x| obj.set_color_disabled(x));
+        }
+    };
+}
+
+status_bar_template! {
+    #[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+    #[serde(rename="StatusBar@Items")]
+    pub struct StatusBarTemplate in template { }
+}
+
+#[typetag::serde(name="StatusBar")]
+impl Template for StatusBarTemplate {
+    fn is_name_scope(&self) -> bool {
+        self.is_name_scope
+    }
+
+    fn name(&self) -> Option<&String> {
+        Some(&self.name)
+    }
+
+    fn create_instance(&self) -> Rc<dyn IsObj> {
+        StatusBar::new()
+    }
+
+    fn apply(&self, instance: &Rc<dyn IsObj>, names: &mut NameResolver) {
+        let this = self;
+        status_bar_apply_template!(this, instance, names);
+    }
+}
@@ -0,0 +1,1237 @@
+use basic_oop::{class_unsafe, import, Vtable};
+use dynamic_cast::dyn_cast_rc;
+use std::cell::RefCell;
+use std::cmp::max;
+use std::collections::VecDeque;
+use std::ops::Range;
+use std::ptr::addr_eq;
+use unicode_width::UnicodeWidthChar;
+use crate::app::AppExt;
+use crate::base::{graphemes, push_bounded, Ctrl, Modifiers, MouseAction, MouseButton, SpecialKey};
+use crate::event_handler::EventHandler;
+use crate::template::{Template, NameResolver};
+
+import! { pub text_editor:
+    use [view crate::view];
+    use crate::base::{Fg, Bg, TextWrapping};
+}
+
+const UNDO_LIMIT: usize = 1000;
+
+const WHEEL_ROWS: isize = 3;
+
+/// A position in [`TextEditor`] text: the line index and the byte index in the line.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash, Default)]
+pub struct TextPos {
+    pub line: usize,
+    pub index: usize,
+}
+
+impl TextPos {
+    fn after(self, text: &str) -> TextPos {
+        if let Some(last_line_start) = text.rfind('\n') {
+            TextPos {
+                line: self.line + text.matches('\n').count(),
+                index: text.len() - last_line_start - 1,
+            }
+        } else {
+            TextPos { line: self.line, index: self.index + text.len() }
+        }
+    }
+}
+
+struct Edit {
+    start: TextPos,
+    removed: String,
+    inserted: String,
+    cursor: TextPos,
+    anchor: Option<TextPos>,
+}
+
+/// Graphemes and tabs with their positions and widths, tabs expanded.
+fn cells(s: &str, tab_width: i16) -> Vec<(Range<usize>, i16, i16)> {
+    let mut res = Vec::new();
+    let mut x = 0i16;
+    let mut start = 0;
+    for part in s.split_inclusive('\t') {
+        let text = part.strip_suffix('\t').unwrap_or(part);
+        for (g, w) in graphemes(text) {
+            res.push((start + g.start .. start + g.end, x, w));
+            x = x.wrapping_add(w);
+        }
+        if text.len() < part.len() {
+            let w = tab_width - x.rem_euclid(tab_width);
+            res.push((start + text.len() .. start + part.len(), x, w));
+            x = x.wrapping_add(w);
+        }
+        start += part.len();
+    }
+    res
+}
+
+fn span_width(s: &str, tab_width: i16) -> i16 {
+    cells(s, tab_width).last().map_or(0, |(_, x, w)| x.wrapping_add(*w))
+}
+
+/// Returns the start indices of the visual rows the line is wrapped into.
+fn line_rows(line: &str, width: i16, wrapping: TextWrapping, tab_width: i16) -> Vec<usize> {
+    let mut starts = vec![0];
+    if wrapping == TextWrapping::NoWrap || width <= 0 { return starts; }
+    // Graphemes with their widths, `None` for tabs, which width depends on the row start.
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for part in line.split_inclusive('\t') {
+        let text = part.strip_suffix('\t').unwrap_or(part);
+        parts.extend(graphemes(text).map(|(g, w)| (start + g.start .. start + g.end, Some(w))));
+        if text.len() < part.len() {
+            parts.push((start + text.len() .. start + part.len(), None));
+        }
+        start += part.len();
+    }
+    let mut row_start = 0;
+    let mut word_break = None;
+    let mut x = 0i16;
+    let mut i = 0;
+    while i < parts.len() {
+        let (g, w) = &parts[i];
+        let w = w.unwrap_or_else(|| tab_width - x.rem_euclid(tab_width));
+        if x.wrapping_add(w) > width && i > row_start {
+            row_start = word_break.unwrap_or(i);
+            starts.push(parts[row_start].0.start);
+            word_break = None;
+            x = 0;
+            i = row_start;
+            continue;
+        }
+        if &line[g.clone()] == " " {
+            word_break = Some(i + 1);
+        }
+        x = x.wrapping_add(w);
+        i += 1;
+    }
+    starts
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+fn sanitize(text: &str, accepts_tab: bool) -> String {
+    text.chars()
+        .map(|c| if c == '\t' && !accepts_tab { ' ' } else { c })
+        .filter(|&c| c == '\n' || c == '\t' || c != '\0' && c.width().is_some())
+        .collect()
+}
+
+struct TextEditorData {
+    lines: Vec<String>,
+    cursor: TextPos,
+    anchor: Option<TextPos>,
+    desired_x: Option<i16>,
+    top: (usize, usize),
+    left: i16,
+    size: Vector,
+    text_wrapping: TextWrapping,
+    tab_width: i16,
+    accepts_tab: bool,
+    is_read_only: bool,
+    undo: VecDeque<Edit>,
+    redo: Vec<Edit>,
+    merge_typing: bool,
+    color: (Fg, Bg),
+    color_focused: (Fg, Bg),
+    color_disabled: (Fg, Bg),
+    color_selected: (Fg, Bg),
+    text_change_handler: EventHandler<Option<Box<dyn FnMut()>>>,
+    cursor_move_handler: EventHandler<Option<Box<dyn FnMut()>>>,
+}
+
+impl TextEditorData {
+    fn rows(&self, line: usize) -> Vec<usize> {
+        line_rows(&self.lines[line], self.size.x, self.text_wrapping, self.tab_width)
+    }
+
+    fn row_range(&self, line: usize, rows: &[usize], row: usize) -> Range<usize> {
+        rows[row] .. rows.get(row + 1).copied().unwrap_or(self.lines[line].len())
+    }
+
+    fn row_and_x(&self, pos: TextPos) -> (usize, i16) {
+        let rows = self.rows(pos.line);
+        let row = rows.partition_point(|&x| x <= pos.index) - 1;
+        (row, span_width(&self.lines[pos.line][rows[row] .. pos.index], self.tab_width))
+    }
+
+    fn pos_at(&self, line: usize, row: usize, x: i16) -> TextPos {
+        let rows = self.rows(line);
+        let range = self.row_range(line, &rows, row);
+        let text = &self.lines[line][range.clone()];
+        let mut last = None;
+        for (g, g_x, g_w) in cells(text, self.tab_width) {
+            if x < g_x.wrapping_add(g_w) {
+                return TextPos { line, index: range.start + g.start };
+            }
+            last = Some(range.start + g.start);
+        }
+        let index = if row + 1 == rows.len() { range.end } else { last.unwrap_or(range.start) };
+        TextPos { line, index }
+    }
+
+    fn row_offset(&self, (mut line, mut row): (usize, usize), delta: isize) -> (usize, usize) {
+        if delta >= 0 {
+            let mut rows_count = self.rows(line).len();
+            for _ in 0 .. delta {
+                if row + 1 < rows_count {
+                    row += 1;
+                } else if line + 1 < self.lines.len() {
+                    line += 1;
+                    row = 0;
+                    rows_count = self.rows(line).len();
+                } else {
+                    break;
+                }
+            }
+        } else {
+            for _ in 0 .. delta.unsigned_abs() {
+                if row > 0 {
+                    row -= 1;
+                } else if line > 0 {
+                    line -= 1;
+                    row = self.rows(line).len() - 1;
+                } else {
+                    break;
+                }
+            }
+        }
+        (line, row)
+    }
+
+    fn rows_between(&self, from: (usize, usize), to: (usize, usize)) -> usize {
+        if from.0 == to.0 { return to.1 - from.1; }
+        let mut n = self.rows(from.0).len() - from.1;
+        for line in from.0 + 1 .. to.0 {
+            n += self.rows(line).len();
+        }
+        n + to.1
+    }
+
+    fn fix_top(&mut self) {
+        if self.top.0 >= self.lines.len() {
+            self.top = (self.lines.len() - 1, 0);
+        }
+        let rows_count = self.rows(self.top.0).len();
+        if self.top.1 >= rows_count {
+            self.top.1 = rows_count - 1;
+        }
+    }
+
+    fn ensure_cursor_visible(&mut self) {
+        self.fix_top();
+        let (row, x) = self.row_and_x(self.cursor);
+        let cursor = (self.cursor.line, row);
+        let height = max(self.size.y, 1);
+        if cursor < self.top {
+            self.top = cursor;
+        } else if self.rows_between(self.top, cursor) >= height as u16 as usize {
+            self.top = self.row_offset(cursor, 1 - isize::from(height));
+        }
+        if self.text_wrapping != TextWrapping::NoWrap {
+            self.left = 0;
+            return;
+        }
+        let width = max(self.size.x, 1);
+        if x < self.left {
+            self.left = x;
+        } else if x >= self.left.wrapping_add(width) {
+            self.left = x.wrapping_sub(width).wrapping_add(1);
+        }
+    }
+
+    fn selection(&self) -> Option<Range<TextPos>> {
+        let anchor = self.anchor?;
+        if anchor == self.cursor { return None; }
+        Some(if anchor < self.cursor { anchor .. self.cursor } else { self.cursor .. anchor })
+    }
+
+    fn text_in(&self, range: Range<TextPos>) -> String {
+        if range.start.line == range.end.line {
+            return self.lines[range.start.line][range.start.index .. range.end.index].to_string();
+        }
+        let mut text = self.lines[range.start.line][range.start.index ..].to_string();
+        for line in &self.lines[range.start.line + 1 .. range.end.line] {
+            text.push('\n');
+            text.push_str(line);
+        }
+        text.push('\n');
+        text.push_str(&self.lines[range.end.line][.. range.end.index]);
+        text
+    }
+
+    /// Replaces the text in `range` with `text`, returns the end of the inserted text.
+    fn replace(&mut self, range: Range<TextPos>, text: &str) -> TextPos {
+        let tail = self.lines[range.end.line][range.end.index ..].to_string();
+        let line = range.start.line;
+        self.lines[line].truncate(range.start.index);
+        self.lines.drain(line + 1 ..= range.end.line);
+        let mut parts = text.split('\n');
+        self.lines[line].push_str(parts.next().unwrap());
+        self.lines.splice(line + 1 .. line + 1, parts.map(|x| x.to_string()));
+        let end = range.start.after(text);
+        self.lines[end.line].push_str(&tail);
+        end
+    }
+
+    fn left(&self, pos: TextPos) -> TextPos {
+        if let Some((g, _, _)) = cells(&self.lines[pos.line][.. pos.index], 1).pop() {
+            TextPos { line: pos.line, index: g.start }
+        } else if pos.line > 0 {
+            TextPos { line: pos.line - 1, index: self.lines[pos.line - 1].len() }
+        } else {
+            pos
+        }
+    }
+
+    fn right(&self, pos: TextPos) -> TextPos {
+        if let Some((g, _, _)) = cells(&self.lines[pos.line][pos.index ..], 1).into_iter().next() {
+            TextPos { line: pos.line, index: pos.index + g.end }
+        } else if pos.line + 1 < self.lines.len() {
+            TextPos { line: pos.line + 1, index: 0 }
+        } else {
+            pos
+        }
+    }
+
+    fn snap(&self, pos: TextPos) -> TextPos {
+        let line = &self.lines[pos.line];
+        let index = cells(line, 1).into_iter()
+            .find(|(g, _, _)| g.end > pos.index)
+            .map_or(line.len(), |(g, _, _)| g.start);
+        TextPos { line: pos.line, index }
+    }
+
+    fn word_left(&self, pos: TextPos) -> TextPos {
+        if pos.index == 0 { return self.left(pos); }
+        let text = &self.lines[pos.line][.. pos.index];
+        let text = text.trim_end_matches(|c| !is_word_char(c)).trim_end_matches(is_word_char);
+        self.snap(TextPos { line: pos.line, index: text.len() })
+    }
+
+    fn word_right(&self, pos: TextPos) -> TextPos {
+        let line = &self.lines[pos.line];
+        if pos.index == line.len() { return self.right(pos); }
+        let text = line[pos.index ..].trim_start_matches(is_word_char).trim_start_matches(|c| !is_word_char(c));
+        self.snap(TextPos { line: pos.line, index: line.len() - text.len() })
+    }
+
+    fn word_at(&self, pos: TextPos) -> Range<TextPos> {
+        let line = &self.lines[pos.line];
+        let start = line[.. pos.index].trim_end_matches(is_word_char).len();
+        let end = line.len() - line[pos.index ..].trim_start_matches(is_word_char).len();
+        self.snap(TextPos { line: pos.line, index: start }) .. self.snap(TextPos { line: pos.line, index: end })
+    }
+
+    fn vertical(&self, pos: TextPos, delta: isize) -> TextPos {
+        let (row, x) = self.row_and_x(pos);
+        let x = self.desired_x.unwrap_or(x);
+        let (line, row) = self.row_offset((pos.line, row), delta);
+        self.pos_at(line, row, x)
+    }
+
+    fn row_start(&self, pos: TextPos) -> TextPos {
+        let (row, _) = self.row_and_x(pos);
+        TextPos { line: pos.line, index: self.rows(pos.line)[row] }
+    }
+
+    fn row_end(&self, pos: TextPos) -> TextPos {
+        let (row, _) = self.row_and_x(pos);
+        self.pos_at(pos.line, row, i16::MAX)
+    }
+
+    fn end(&self) -> TextPos {
+        let line = self.lines.len() - 1;
+        TextPos { line, index: self.lines[line].len() }
+    }
+}
+
+/// A multi-line text editor.
+///
+/// Tabs are expanded to [`tab_width`](TextEditor::tab_width_impl) columns.
+/// `TextWrapping::Wrap` and `TextWrapping::WrapWithOverflow` both wrap lines at word boundaries,
+/// breaking words longer than the view width.
+#[class_unsafe(inherits_View)]
+pub struct TextEditor {
+    data: RefCell<TextEditorData>,
+    #[non_virt]
+    text: fn() -> String,
+    #[non_virt]
+    set_text: fn(value: &str),
+    #[non_virt]
+    line_count: fn() -> usize,
+    #[non_virt]
+    line: fn(index: usize) -> String,
+    #[non_virt]
+    cursor: fn() -> TextPos,
+    #[non_virt]
+    set_cursor: fn(value: TextPos),
+    #[non_virt]
+    cursor_column: fn() -> usize,
+    #[non_virt]
+    selection: fn() -> Option<Range<TextPos>>,
+    #[non_virt]
+    select: fn(range: Range<TextPos>),
+    #[non_virt]
+    select_all: fn(),
+    #[non_virt]
+    selected_text: fn() -> String,
+    #[non_virt]
+    text_wrapping: fn() -> TextWrapping,
+    #[non_virt]
+    set_text_wrapping: fn(value: TextWrapping),
+    #[non_virt]
+    tab_width: fn() -> i16,
+    #[non_virt]
+    set_tab_width: fn(value: i16),
+    #[non_virt]
+    accepts_tab: fn() -> bool,
+    #[non_virt]
+    set_accepts_tab: fn(value: bool),
+    #[non_virt]
+    is_read_only: fn() -> bool,
+    #[non_virt]
+    set_is_read_only: fn(value: bool),
+    #[non_virt]
+    color: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color: fn(value: (Fg, Bg)),
+    #[non_virt]
+    color_focused: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color_focused: fn(value: (Fg, Bg)),
+    #[non_virt]
+    color_disabled: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color_disabled: fn(value: (Fg, Bg)),
+    #[non_virt]
+    color_selected: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color_selected: fn(value: (Fg, Bg)),
+    #[non_virt]
+    insert: fn(text: &str),
+    #[non_virt]
+    can_undo: fn() -> bool,
+    #[non_virt]
+    undo: fn() -> bool,
+    #[non_virt]
+    can_redo: fn() -> bool,
+    #[non_virt]
+    redo: fn() -> bool,
+    #[non_virt]
+    cut_to_clipboard: fn(),
+    #[non_virt]
+    copy_to_clipboard: fn(),
+    #[non_virt]
+    paste_from_clipboard: fn(),
+    #[non_virt]
+    handle_text_change: fn(handler: Option<Box<dyn FnMut()>>),
+    #[virt]
+    text_changed: fn(),
+    #[non_virt]
+    handle_cursor_move: fn(handler: Option<Box<dyn FnMut()>>),
+    #[virt]
+    cursor_moved: fn(),
+    #[over]
+    _init: (),
+    #[over]
+    is_enabled_changed: (),
+    #[over]
+    is_focused_changed: (),
+    #[over]
+    measure_override: (),
+    #[over]
+    arrange_override: (),
+    #[over]
+    render: (),
+    #[over]
+    key: (),
+    #[over]
+    mouse: (),
+    #[over]
+    paste: (),
+}
+
+impl TextEditor {
+    pub fn new() -> Rc<dyn IsTextEditor> {
+        let res: Rc<dyn IsTextEditor> = Rc::new(unsafe { Self::new_raw(TEXT_EDITOR_VTABLE.as_ptr()) });
+        res._init();
+        res
+    }
+
+    pub unsafe fn new_raw(vtable: Vtable) -> Self {
+        TextEditor {
+            view: unsafe { View::new_raw(vtable) },
+            data: RefCell::new(TextEditorData {
+                lines: vec![String::new()],
+                cursor: TextPos::default(),
+                anchor: None,
+                desired_x: None,
+                top: (0, 0),
+                left: 0,
+                size: Vector::null(),
+                text_wrapping: TextWrapping::NoWrap,
+                tab_width: 4,
+                accepts_tab: true,
+                is_read_only: false,
+                undo: VecDeque::new(),
+                redo: Vec::new(),
+                merge_typing: false,
+                color: (Fg::LightGray, Bg::Blue),
+                color_focused: (Fg::LightGray, Bg::Blue),
+                color_disabled: (Fg::DarkGray, Bg::Blue),
+                color_selected: (Fg::Blue, Bg::LightGray),
+                text_change_handler: Default::default(),
+                cursor_move_handler: Default::default(),
+            }),
+        }
+    }
+
+    pub fn _init_impl(this: &Rc<dyn IsView>) {
+        View::_init_impl(this);
+        this.set_allow_focus(true);
+    }
+
+    pub fn is_enabled_changed_impl(this: &Rc<dyn IsView>) {
+        View::is_enabled_changed_impl(this);
+        this.invalidate_render();
+    }
+
+    pub fn is_focused_changed_impl(this: &Rc<dyn IsView>, primary_focus: bool) {
+        View::is_focused_changed_impl(this, primary_focus);
+        this.invalidate_render();
+    }
+
+    pub fn text_impl(this: &Rc<dyn IsTextEditor>) -> String {
+        this.text_editor().data.borrow().lines.join("\n")
+    }
+
+    /// Replaces the whole text, moves the cursor to the text start, and clears the undo history.
+    pub fn set_text_impl(this: &Rc<dyn IsTextEditor>, value: &str) {
+        {
+            let mut data = this.text_editor().data.borrow_mut();
+            let text = sanitize(value, true);
+            data.lines = text.split('\n').map(|x| x.to_string()).collect();
+            data.cursor = TextPos::default();
+            data.anchor = None;
+            data.desired_x = None;
+            data.top = (0, 0);
+            data.left = 0;
+            data.undo.clear();
+            data.redo.clear();
+            data.merge_typing = false;
+        }
+        this.text_changed();
+        this.cursor_moved();
+        this.invalidate_render();
+    }
+
+    pub fn line_count_impl(this: &Rc<dyn IsTextEditor>) -> usize {
+        this.text_editor().data.borrow().lines.len()
+    }
+
+    pub fn line_impl(this: &Rc<dyn IsTextEditor>, index: usize) -> String {
+        this.text_editor().data.borrow().lines[index].clone()
+    }
+
+    pub fn cursor_impl(this: &Rc<dyn IsTextEditor>) -> TextPos {
+        this.text_editor().data.borrow().cursor
+    }
+
+    pub fn set_cursor_impl(this: &Rc<dyn IsTextEditor>, value: TextPos) {
+        Self::move_cursor(this, false, false, |data| {
+            let line = value.line.min(data.lines.len() - 1);
+            data.snap(TextPos { line, index: value.index.min(data.lines[line].len()) })
+        });
+    }
+
+    /// The cursor column as shown on screen with tabs expanded and lines not wrapped.
+    /// Together with the cursor line it makes a line/column indicator,
+    /// see [`handle_cursor_move`](TextEditor::handle_cursor_move_impl).
+    pub fn cursor_column_impl(this: &Rc<dyn IsTextEditor>) -> usize {
+        let data = this.text_editor().data.borrow();
+        span_width(&data.lines[data.cursor.line][.. data.cursor.index], data.tab_width) as u16 as usize
+    }
+
+    pub fn selection_impl(this: &Rc<dyn IsTextEditor>) -> Option<Range<TextPos>> {
+        this.text_editor().data.borrow().selection()
+    }
+
+    pub fn select_impl(this: &Rc<dyn IsTextEditor>, range: Range<TextPos>) {
+        this.set_cursor(range.start);
+        Self::move_cursor(this, true, false, |data| {
+            let line = range.end.line.min(data.lines.len() - 1);
+            data.snap(TextPos { line, index: range.end.index.min(data.lines[line].len()) })
+        });
+    }
+
+    pub fn select_all_impl(this: &Rc<dyn IsTextEditor>) {
+        Self::move_cursor(this, false, false, |_| TextPos::default());
+        Self::move_cursor(this, true, false, |data| data.end());
+    }
+
+    pub fn selected_text_impl(this: &Rc<dyn IsTextEditor>) -> String {
+        let data = this.text_editor().data.borrow();
+        data.selection().map_or_else(String::new, |x| data.text_in(x))
+    }
+
+    pub fn text_wrapping_impl(this: &Rc<dyn IsTextEditor>) -> TextWrapping {
+        this.text_editor().data.borrow().text_wrapping
+    }
+
+    pub fn set_text_wrapping_impl(this: &Rc<dyn IsTextEditor>, value: TextWrapping) {
+        {
+            let mut data = this.text_editor().data.borrow_mut();
+            if data.text_wrapping == value { return; }
+            data.text_wrapping = value;
+            data.top.1 = 0;
+            data.ensure_cursor_visible();
+        }
+        this.invalidate_render();
+    }
+
+    pub fn tab_width_impl(this: &Rc<dyn IsTextEditor>) -> i16 {
+        this.text_editor().data.borrow().tab_width
+    }
+
+    pub fn set_tab_width_impl(this: &Rc<dyn IsTextEditor>, value: i16) {
+        assert!(value > 0, "invalid tab width");
+        {
+            let mut data = this.text_editor().data.borrow_mut();
+            if data.tab_width == value { return; }
+            data.tab_width = value;
+            data.ensure_cursor_visible();
+        }
+        this.invalidate_render();
+    }
+
+    /// If set (the default), the Tab key types a tab, and it does not move the focus.
+    pub fn accepts_tab_impl(this: &Rc<dyn IsTextEditor>) -> bool {
+        this.text_editor().data.borrow().accepts_tab
+    }
+
+    pub fn set_accepts_tab_impl(this: &Rc<dyn IsTextEditor>, value: bool) {
+        this.text_editor().data.borrow_mut().accepts_tab = value;
+    }
+
+    pub fn is_read_only_impl(this: &Rc<dyn IsTextEditor>) -> bool {
+        this.text_editor().data.borrow().is_read_only
+    }
+
+    pub fn set_is_read_only_impl(this: &Rc<dyn IsTextEditor>, value: bool) {
+        this.text_editor().data.borrow_mut().is_read_only = value;
+    }
+
+    pub fn color_impl(this: &Rc<dyn IsTextEditor>) -> (Fg, Bg) {
+        this.text_editor().data.borrow().color
+    }
+
+    pub fn set_color_impl(this: &Rc<dyn IsTextEditor>, value: (Fg, Bg)) {
+        {
+            let mut data = this.text_editor().data.borrow_mut();
+            if data.color == value { return; }
+            data.color = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn color_focused_impl(this: &Rc<dyn IsTextEditor>) -> (Fg, Bg) {
+        this.text_editor().data.borrow().color_focused
+    }
+
+    pub fn set_color_focused_impl(this: &Rc<dyn IsTextEditor>, value: (Fg, Bg)) {
+        {
+            let mut data = this.text_editor().data.borrow_mut();
+            if data.color_focused == value { return; }
+            data.color_focused = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn color_disabled_impl(this: &Rc<dyn IsTextEditor>) -> (Fg, Bg) {
+        this.text_editor().data.borrow().color_disabled
+    }
+
+    pub fn set_color_disabled_impl(this: &Rc<dyn IsTextEditor>, value: (Fg, Bg)) {
+        {
+            let mut data = this.text_editor().data.borrow_mut();
+            if data.color_disabled == value { return; }
+            data.color_disabled = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn color_selected_impl(this: &Rc<dyn IsTextEditor>) -> (Fg, Bg) {
+        this.text_editor().data.borrow().color_selected
+    }
+
+    pub fn set_color_selected_impl(this: &Rc<dyn IsTextEditor>, value: (Fg, Bg)) {
+        {
+            let mut data = this.text_editor().data.borrow_mut();
+            if data.color_selected == value { return; }
+            data.color_selected = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn handle_text_change_impl(this: &Rc<dyn IsTextEditor>, handler: Option<Box<dyn FnMut()>>) {
+        this.text_editor().data.borrow_mut().text_change_handler.set(handler);
+    }
+
+    pub fn text_changed_impl(this: &Rc<dyn IsTextEditor>) {
+        let mut invoke = this.text_editor().data.borrow_mut().text_change_handler.begin_invoke();
+        invoke.as_mut().map(|x| x());
+        this.text_editor().data.borrow_mut().text_change_handler.end_invoke(invoke);
+    }
+
+    pub fn handle_cursor_move_impl(this: &Rc<dyn IsTextEditor>, handler: Option<Box<dyn FnMut()>>) {
+        this.text_editor().data.borrow_mut().cursor_move_handler.set(handler);
+    }
+
+    pub fn cursor_moved_impl(this: &Rc<dyn IsTextEditor>) {
+        let mut invoke = this.text_editor().data.borrow_mut().cursor_move_handler.begin_invoke();
+        invoke.as_mut().map(|x| x());
+        this.text_editor().data.borrow_mut().cursor_move_handler.end_invoke(invoke);
+    }
+
+    fn move_cursor(
+        this: &Rc<dyn IsTextEditor>,
+        select: bool,
+        keep_x: bool,
+        f: impl FnOnce(&TextEditorData) -> TextPos
+    ) {
+        {
+            let mut data = this.text_editor().data.borrow_mut();
+            let cursor = f(&data);
+            if select {
+                if data.anchor.is_none() { data.anchor = Some(data.cursor); }
+            } else {
+                data.anchor = None;
+            }
+            if keep_x {
+                if data.desired_x.is_none() { data.desired_x = Some(data.row_and_x(data.cursor).1); }
+            } else {
+                data.desired_x = None;
+            }
+            data.cursor = cursor;
+            data.merge_typing = false;
+            data.ensure_cursor_visible();
+        }
+        this.cursor_moved();
+        this.invalidate_render();
+    }
+
+    fn edit(this: &Rc<dyn IsTextEditor>, range: Range<TextPos>, text: &str, typing: bool) {
+        {
+            let mut data = this.text_editor().data.borrow_mut();
+            let data = &mut *data;
+            let removed = data.text_in(range.clone());
+            let merge = typing && removed.is_empty() && data.merge_typing && data.undo.back().is_some_and(|x|
+                x.start.after(&x.inserted) == range.start
+            );
+            let (cursor, anchor) = (data.cursor, data.anchor);
+            data.cursor = data.replace(range.clone(), text);
+            data.anchor = None;
+            data.desired_x = None;
+            if merge {
+                data.undo.back_mut().unwrap().inserted.push_str(text);
+            } else {
+                push_bounded(&mut data.undo, Edit { start: range.start, removed, inserted: text.to_string(), cursor, anchor }, UNDO_LIMIT);
+            }
+            data.redo.clear();
+            data.merge_typing = typing && !text.contains('\n');
+            data.ensure_cursor_visible();
+        }
+        this.text_changed();
+        this.cursor_moved();
+        this.invalidate_render();
+    }
+
+    fn type_text(this: &Rc<dyn IsTextEditor>, text: &str, typing: bool) {
+        let (range, text) = {
+            let data = this.text_editor().data.borrow();
+            if data.is_read_only { return; }
+            (data.selection().unwrap_or(data.cursor .. data.cursor), sanitize(text, data.accepts_tab))
+        };
+        if text.is_empty() && range.start == range.end { return; }
+        Self::edit(this, range, &text, typing);
+    }
+
+    /// Replaces the selection (or inserts at the cursor) with `text`.
+    pub fn insert_impl(this: &Rc<dyn IsTextEditor>, text: &str) {
+        Self::type_text(this, text, false);
+    }
+
+    fn delete(this: &Rc<dyn IsTextEditor>, f: impl FnOnce(&TextEditorData) -> TextPos) {
+        let range = {
+            let data = this.text_editor().data.borrow();
+            if data.is_read_only { return; }
+            data.selection().unwrap_or_else(|| {
+                let pos = f(&data);
+                if pos < data.cursor { pos .. data.cursor } else { data.cursor .. pos }
+            })
+        };
+        if range.start == range.end { return; }
+        Self::edit(this, range, "", false);
+    }
+
+    pub fn can_undo_impl(this: &Rc<dyn IsTextEditor>) -> bool {
+        !this.text_editor().data.borrow().undo.is_empty()
+    }
+
+    pub fn undo_impl(this: &Rc<dyn IsTextEditor>) -> bool {
+        {
+            let mut data = this.text_editor().data.borrow_mut();
+            let Some(edit) = data.undo.pop_back() else { return false; };
+            data.replace(edit.start .. edit.start.after(&edit.inserted), &edit.removed);
+            data.cursor = edit.cursor;
+            data.anchor = edit.anchor;
+            data.desired_x = None;
+            data.merge_typing = false;
+            data.redo.push(edit);
+            data.ensure_cursor_visible();
+        }
+        this.text_changed();
+        this.cursor_moved();
+        this.invalidate_render();
+        true
+    }
+
+    pub fn can_redo_impl(this: &Rc<dyn IsTextEditor>) -> bool {
+        !this.text_editor().data.borrow().redo.is_empty()
+    }
+
+    pub fn redo_impl(this: &Rc<dyn IsTextEditor>) -> bool {
+        {
+            let mut data = this.text_editor().data.borrow_mut();
+            let Some(edit) = data.redo.pop() else { return false; };
+            data.cursor = data.replace(edit.start .. edit.start.after(&edit.removed), &edit.inserted);
+            data.anchor = None;
+            data.desired_x = None;
+            data.merge_typing = false;
+            push_bounded(&mut data.undo, edit, UNDO_LIMIT);
+            data.ensure_cursor_visible();
+        }
+        this.text_changed();
+        this.cursor_moved();
+        this.invalidate_render();
+        true
+    }
+
+    pub fn cut_to_clipboard_impl(this: &Rc<dyn IsTextEditor>) {
+        if this.is_read_only() { return; }
+        this.copy_to_clipboard();
+        Self::delete(this, |data| data.cursor);
+    }
+
+    pub fn copy_to_clipboard_impl(this: &Rc<dyn IsTextEditor>) {
+        let text = this.selected_text();
+        if text.is_empty() { return; }
+        let Some(app) = this.app() else { return; };
+        app.set_clipboard_text(text);
+    }
+
+    pub fn paste_from_clipboard_impl(this: &Rc<dyn IsTextEditor>) {
+        let Some(text) = this.app().and_then(|x| x.clipboard_text()) else { return; };
+        Self::type_text(this, &text, false);
+    }
+
+    pub fn measure_override_impl(_this: &Rc<dyn IsView>, w: Option<i16>, h: Option<i16>) -> Vector {
+        Vector { x: w.unwrap_or(1), y: h.unwrap_or(1) }
+    }
+
+    pub fn arrange_override_impl(this: &Rc<dyn IsView>, bounds: Rect) -> Vector {
+        let editor: Rc<dyn IsTextEditor> = dyn_cast_rc(this.clone()).unwrap();
+        {
+            let mut data = editor.text_editor().data.borrow_mut();
+            if data.size != bounds.size {
+                data.size = bounds.size;
+                data.ensure_cursor_visible();
+            }
+        }
+        bounds.size
+    }
+
+    pub fn render_impl(this: &Rc<dyn IsView>, rp: &mut RenderPort) {
+        let is_enabled = this.is_enabled();
+        let is_focused = this.is_focused(None);
+        let is_focused_primary = this.is_focused(Some(true));
+        let editor: Rc<dyn IsTextEditor> = dyn_cast_rc(this.clone()).unwrap();
+        let data = editor.text_editor().data.borrow();
+        let color = match (is_enabled, is_focused) {
+            (true, true) => data.color_focused,
+            (true, false) => data.color,
+            (false, true) => (data.color_disabled.0, data.color_focused.1),
+            (false, false) => data.color_disabled
+        };
+        rp.fill_bg(color);
+        let selection = data.selection();
+        let is_selected = |pos: TextPos| selection.as_ref().is_some_and(|x| x.contains(&pos));
+        let (mut line, mut row) = data.top;
+        let mut rows = data.rows(line);
+        for y in 0 .. data.size.y {
+            let range = data.row_range(line, &rows, row);
+            let text = &data.lines[line][range.clone()];
+            let mut end_x = 0i16;
+            for (g, x, w) in cells(text, data.tab_width) {
+                end_x = x.wrapping_add(w);
+                let x = x.wrapping_sub(data.left);
+                if x.wrapping_add(w) <= 0 { continue; }
+                if x >= data.size.x { break; }
+                let cell_color = if is_selected(TextPos { line, index: range.start + g.start }) {
+                    data.color_selected
+                } else {
+                    color
+                };
+                let g = &text[g];
+                if g == "\t" {
+                    for i in 0 .. w {
+                        rp.text(Point { x: x.wrapping_add(i), y }, cell_color, " ");
+                    }
+                } else {
+                    rp.text(Point { x, y }, cell_color, g);
+                }
+            }
+            if row + 1 == rows.len() && is_selected(TextPos { line, index: range.end }) {
+                rp.text(Point { x: end_x.wrapping_sub(data.left), y }, data.color_selected, " ");
+            }
+            if row + 1 < rows.len() {
+                row += 1;
+            } else if line + 1 < data.lines.len() {
+                line += 1;
+                row = 0;
+                rows = data.rows(line);
+            } else {
+                break;
+            }
+        }
+        if is_focused_primary {
+            let (row, x) = data.row_and_x(data.cursor);
+            let cursor = (data.cursor.line, row);
+            if cursor >= data.top {
+                let y = data.rows_between(data.top, cursor);
+                if y < data.size.y as u16 as usize {
+                    rp.cursor(Point { x: x.wrapping_sub(data.left), y: y as i16 });
+                }
+            }
+        }
+    }
+
+    fn pos_at_point(data: &TextEditorData, p: Point) -> TextPos {
+        let (line, row) = data.row_offset(data.top, isize::from(max(p.y, 0)));
+        data.pos_at(line, row, p.x.wrapping_add(data.left))
+    }
+
+    fn scroll(this: &Rc<dyn IsTextEditor>, delta: isize) {
+        {
+            let mut data = this.text_editor().data.borrow_mut();
+            data.top = data.row_offset(data.top, delta);
+        }
+        this.invalidate_render();
+    }
+
+    pub fn mouse_impl(this: &Rc<dyn IsView>, event: MouseEvent, original_source: &Rc<dyn IsView>) -> bool {
+        let app = this.app().unwrap();
+        let editor: Rc<dyn IsTextEditor> = dyn_cast_rc(this.clone()).unwrap();
+        match event.action {
+            MouseAction::Down(MouseButton::Left) => {
+                app.focus(Some(this), None);
+                app.capture_mouse(Some(this));
+                let select = event.modifiers.contains(Modifiers::SHIFT);
+                Self::move_cursor(&editor, select, false, |data| Self::pos_at_point(data, event.point));
+                true
+            },
+            MouseAction::Drag(MouseButton::Left) => {
+                let captured = app.mouse_capture().is_some_and(|x| addr_eq(Rc::as_ptr(&x), Rc::as_ptr(this)));
+                if !captured { return View::mouse_impl(this, event, original_source); }
+                Self::move_cursor(&editor, true, false, |data| Self::pos_at_point(data, event.point));
+                true
+            },
+            MouseAction::Up(MouseButton::Left) => {
+                let captured = app.mouse_capture().is_some_and(|x| addr_eq(Rc::as_ptr(&x), Rc::as_ptr(this)));
+                if !captured { return View::mouse_impl(this, event, original_source); }
+                app.capture_mouse(None);
+                true
+            },
+            MouseAction::DoubleClick(MouseButton::Left) => {
+                let word = {
+                    let data = editor.text_editor().data.borrow();
+                    data.word_at(Self::pos_at_point(&data, event.point))
+                };
+                editor.select(word);
+                true
+            },
+            MouseAction::WheelUp => {
+                Self::scroll(&editor, -WHEEL_ROWS);
+                true
+            },
+            MouseAction::WheelDown => {
+                Self::scroll(&editor, WHEEL_ROWS);
+                true
+            },
+            _ => View::mouse_impl(this, event, original_source)
+        }
+    }
+
+    pub fn paste_impl(this: &Rc<dyn IsView>, text: &str, _original_source: &Rc<dyn IsView>) -> bool {
+        let editor: Rc<dyn IsTextEditor> = dyn_cast_rc(this.clone()).unwrap();
+        Self::type_text(&editor, text, false);
+        true
+    }
+
+    pub fn key_impl(this: &Rc<dyn IsView>, key: Key, original_source: &Rc<dyn IsView>) -> bool {
+        let editor: Rc<dyn IsTextEditor> = dyn_cast_rc(this.clone()).unwrap();
+        let page = isize::from(max(editor.text_editor().data.borrow().size.y, 1));
+        let navigation = match key {
+            Key::Left => Some((SpecialKey::Left, Modifiers::empty())),
+            Key::Right => Some((SpecialKey::Right, Modifiers::empty())),
+            Key::Up => Some((SpecialKey::Up, Modifiers::empty())),
+            Key::Down => Some((SpecialKey::Down, Modifiers::empty())),
+            Key::Home => Some((SpecialKey::Home, Modifiers::empty())),
+            Key::End => Some((SpecialKey::End, Modifiers::empty())),
+            Key::PageUp => Some((SpecialKey::PageUp, Modifiers::empty())),
+            Key::PageDown => Some((SpecialKey::PageDown, Modifiers::empty())),
+            Key::Modified(m, k) if (m - Modifiers::SHIFT - Modifiers::CTRL).is_empty() => Some((k, m)),
+            _ => None,
+        };
+        if let Some((special, modifiers)) = navigation {
+            let select = modifiers.contains(Modifiers::SHIFT);
+            let ctrl = modifiers.contains(Modifiers::CTRL);
+            let collapse = |data: &TextEditorData, forward: bool| data.selection()
+                .filter(|_| !select)
+                .map(|x| if forward { x.end } else { x.start });
+            match (special, ctrl) {
+                (SpecialKey::Left, false) => {
+                    Self::move_cursor(&editor, select, false, |data|
+                        collapse(data, false).unwrap_or_else(|| data.left(data.cursor))
+                    );
+                    return true;
+                },
+                (SpecialKey::Right, false) => {
+                    Self::move_cursor(&editor, select, false, |data|
+                        collapse(data, true).unwrap_or_else(|| data.right(data.cursor))
+                    );
+                    return true;
+                },
+                (SpecialKey::Left, true) => {
+                    Self::move_cursor(&editor, select, false, |data| data.word_left(data.cursor));
+                    return true;
+                },
+                (SpecialKey::Right, true) => {
+                    Self::move_cursor(&editor, select, false, |data| data.word_right(data.cursor));
+                    return true;
+                },
+                (SpecialKey::Up, false) => {
+                    Self::move_cursor(&editor, select, true, |data| data.vertical(data.cursor, -1));
+                    return true;
+                },
+                (SpecialKey::Down, false) => {
+                    Self::move_cursor(&editor, select, true, |data| data.vertical(data.cursor, 1));
+                    return true;
+                },
+                (SpecialKey::Up, true) => {
+                    Self::scroll(&editor, -1);
+                    return true;
+                },
+                (SpecialKey::Down, true) => {
+                    Self::scroll(&editor, 1);
+                    return true;
+                },
+                (SpecialKey::PageUp, false) => {
+                    Self::move_cursor(&editor, select, true, |data| data.vertical(data.cursor, -page));
+                    return true;
+                },
+                (SpecialKey::PageDown, false) => {
+                    Self::move_cursor(&editor, select, true, |data| data.vertical(data.cursor, page));
+                    return true;
+                },
+                (SpecialKey::Home, false) => {
+                    Self::move_cursor(&editor, select, false, |data| data.row_start(data.cursor));
+                    return true;
+                },
+                (SpecialKey::End, false) => {
+                    Self::move_cursor(&editor, select, false, |data| data.row_end(data.cursor));
+                    return true;
+                },
+                (SpecialKey::Home, true) => {
+                    Self::move_cursor(&editor, select, false, |_| TextPos::default());
+                    return true;
+                },
+                (SpecialKey::End, true) => {
+                    Self::move_cursor(&editor, select, false, |data| data.end());
+                    return true;
+                },
+                _ => { },
+            }
+        }
+        match key {
+            Key::Char(c) => {
+                let mut buf = [0; 4];
+                Self::type_text(&editor, c.encode_utf8(&mut buf), true);
+                return true;
+            },
+            Key::Enter => {
+                Self::type_text(&editor, "\n", false);
+                return true;
+            },
+            Key::Tab if editor.accepts_tab() => {
+                Self::type_text(&editor, "\t", true);
+                return true;
+            },
+            Key::Backspace => {
+                Self::delete(&editor, |data| data.left(data.cursor));
+                return true;
+            },
+            Key::Delete => {
+                Self::delete(&editor, |data| data.right(data.cursor));
+                return true;
+            },
+            Key::Ctrl(Ctrl::A) => {
+                editor.select_all();
+                return true;
+            },
+            Key::Ctrl(Ctrl::C) => {
+                editor.copy_to_clipboard();
+                return true;
+            },
+            Key::Modified(m, SpecialKey::Insert) if m == Modifiers::CTRL => {
+                editor.copy_to_clipboard();
+                return true;
+            },
+            Key::Ctrl(Ctrl::X) => {
+                editor.cut_to_clipboard();
+                return true;
+            },
+            Key::Modified(m, SpecialKey::Delete) if m == Modifiers::SHIFT => {
+                editor.cut_to_clipboard();
+                return true;
+            },
+            Key::Ctrl(Ctrl::V) => {
+                editor.paste_from_clipboard();
+                return true;
+            },
+            Key::Modified(m, SpecialKey::Insert) if m == Modifiers::SHIFT => {
+                editor.paste_from_clipboard();
+                return true;
+            },
+            Key::Ctrl(Ctrl::Z) => {
+                if !editor.is_read_only() { editor.undo(); }
+                return true;
+            },
+            Key::Ctrl(Ctrl::Y) => {
+                if !editor.is_read_only() { editor.redo(); }
+                return true;
+            },
+            _ => { },
+        }
+        View::key_impl(this, key, original_source)
+    }
+}
+
+#[macro_export]
+macro_rules! text_editor_template {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident in $mod:ident {
+            $(use $path:path as $import:ident;)*
+
+            $($(
+                $(#[$field_attr:meta])*
+                pub $field_name:ident : $field_ty:ty
+            ),+ $(,)?)?
+        }
+    ) => {
+        $crate::view_template! {
+            $(#[$attr])*
+            $vis struct $name in $mod {
+                $(use $path as $import;)*
+
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub text: Option<String>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub text_wrapping: Option<$crate::base::TextWrapping>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub tab_width: Option<i16>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub accepts_tab: Option<bool>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub is_read_only: Option<bool>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color: Option<($crate::base::Fg, $crate::base::Bg)>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color_focused: Option<($crate::base::Fg, $crate::base::Bg)>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color_disabled: Option<($crate::base::Fg, $crate::base::Bg)>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color_selected: Option<($crate::base::Fg, $crate::base::Bg)>,
+                $($(
+                    $(#[$field_attr])*
+                    pub $field_name : $field_ty
+                ),+)?
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! text_editor_apply_template {
+    ($this:ident, $instance:ident, $names:ident) => {
+        $crate::view_apply_template!($this, $instance, $names);
+        {
+            use $crate::text_editor::TextEditorExt;
+
+            let obj: $crate::alloc_rc_Rc<dyn $crate::text_editor::IsTextEditor>
+                = $crate::dynamic_cast_dyn_cast_rc($instance.clone()).unwrap();
+            $this.text.as_ref().map(|x| obj.set_text(x));
+            $this.text_wrapping.map(|x| obj.set_text_wrapping(x));
+            $this.tab_width.map(|x| obj.set_tab_width(x));
+            $this.accepts_tab.map(|x| obj.set_accepts_tab(x));
+            $this.is_read_only.map(|x| obj.set_is_read_only(x));
+            $this.color.map(|x| obj.set_color(x));
+            $this.color_focused.map(|x| obj.set_color_focused(x));
+            $this.color_disabled.map(|x| obj.set_color_disabled(x));
+            $this.color_selected.map(|x| obj.set_color_selected(x));
+        }
+    };
+}
+
+text_editor_template! {
+    #[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+    #[serde(rename="TextEditor@Text")]
+    pub struct TextEditorTemplate in template { }
+}
+
+#[typetag::serde(name="TextEditor")]
+impl Template for TextEditorTemplate {
+    fn is_name_scope(&self) -> bool {
+        self.is_name_scope
+    }
+
+    fn name(&self) -> Option<&String> {
+        Some(&self.name)
+    }
+
+    fn create_instance(&self) -> Rc<dyn IsObj> {
+        TextEditor::new()
+    }
+
+    fn apply(&self, instance: &Rc<dyn IsObj>, names: &mut NameResolver) {
+        let this = self;
+        text_editor_apply_template!(this, instance, names);
+    }
+}
@@ -0,0 +1,194 @@
+use basic_oop::{class_unsafe, import, Vtable};
+use dynamic_cast::dyn_cast_rc;
+use std::cell::Cell;
+use crate::template::{Template, NameResolver};
+use crate::view::Visibility;
+use crate::view_vec::ViewVecExt;
+
+import! { pub uniform_grid:
+    use [panel crate::panel];
+}
+
+#[class_unsafe(inherits_Panel)]
+pub struct UniformGrid {
+    rows: Cell<u16>,
+    columns: Cell<u16>,
+    #[non_virt]
+    rows: fn() -> u16,
+    #[non_virt]
+    set_rows: fn(value: u16),
+    #[non_virt]
+    columns: fn() -> u16,
+    #[non_virt]
+    set_columns: fn(value: u16),
+    #[over]
+    measure_override: (),
+    #[over]
+    arrange_override: (),
+}
+
+impl UniformGrid {
+    pub fn new() -> Rc<dyn IsUniformGrid> {
+        let res: Rc<dyn IsUniformGrid> = Rc::new(unsafe { Self::new_raw(UNIFORM_GRID_VTABLE.as_ptr()) });
+        res._init();
+        res
+    }
+
+    pub unsafe fn new_raw(vtable: Vtable) -> Self {
+        UniformGrid {
+            panel: unsafe { Panel::new_raw(vtable) },
+            rows: Cell::new(0),
+            columns: Cell::new(0),
+        }
+    }
+
+    pub fn rows_impl(this: &Rc<dyn IsUniformGrid>) -> u16 {
+        this.uniform_grid().rows.get()
+    }
+
+    /// Sets the number of rows. Zero means the count is derived from the number of children.
+    pub fn set_rows_impl(this: &Rc<dyn IsUniformGrid>, value: u16) {
+        let old = this.uniform_grid().rows.replace(value);
+        if old == value { return; }
+        this.invalidate_measure();
+    }
+
+    pub fn columns_impl(this: &Rc<dyn IsUniformGrid>) -> u16 {
+        this.uniform_grid().columns.get()
+    }
+
+    /// Sets the number of columns. Zero means the count is derived from the number of children.
+    pub fn set_columns_impl(this: &Rc<dyn IsUniformGrid>, value: u16) {
+        let old = this.uniform_grid().columns.replace(value);
+        if old == value { return; }
+        this.invalidate_measure();
+    }
+
+    fn dimensions(this: &Rc<dyn IsUniformGrid>) -> (i32, i32) {
+        let count = this.children().iter().filter(|x| x.visibility() != Visibility::Collapsed).count();
+        let count = i32::try_from(count).unwrap_or(i32::MAX).max(1);
+        let rows = i32::from(this.uniform_grid().rows.get());
+        let columns = i32::from(this.uniform_grid().columns.get());
+        match (rows, columns) {
+            (0, 0) => {
+                let mut columns = 1;
+                while columns * columns < count { columns += 1; }
+                ((count + columns - 1) / columns, columns)
+            },
+            (0, columns) => ((count + columns - 1) / columns, columns),
+            (rows, 0) => (rows, (count + rows - 1) / rows),
+            (rows, columns) => (rows, columns),
+        }
+    }
+
+    pub fn measure_override_impl(this: &Rc<dyn IsView>, w: Option<i16>, h: Option<i16>) -> Vector {
+        let this: Rc<dyn IsUniformGrid> = dyn_cast_rc(this.clone()).unwrap();
+        let (rows, columns) = Self::dimensions(&this);
+        let cell_w = w.map(|w| (i32::from(w) / columns) as i16);
+        let cell_h = h.map(|h| (i32::from(h) / rows) as i16);
+        let mut cell_size = Vector::null();
+        for child in this.children().iter() {
+            child.measure(cell_w, cell_h);
+            cell_size = cell_size.max(child.desired_size());
+        }
+        Vector {
+            x: (i32::from(cell_size.x) * columns).min(i32::from(i16::MAX)) as i16,
+            y: (i32::from(cell_size.y) * rows).min(i32::from(i16::MAX)) as i16,
+        }
+    }
+
+    pub fn arrange_override_impl(this: &Rc<dyn IsView>, bounds: Rect) -> Vector {
+        let this: Rc<dyn IsUniformGrid> = dyn_cast_rc(this.clone()).unwrap();
+        let (rows, columns) = Self::dimensions(&this);
+        let x = |column: i32| (i32::from(bounds.w()) * column / columns) as i16;
+        let y = |row: i32| (i32::from(bounds.h()) * row / rows) as i16;
+        let mut index = 0;
+        for child in this.children().iter() {
+            if child.visibility() == Visibility::Collapsed { continue; }
+            let (row, column) = (index / columns, index % columns);
+            index += 1;
+            if row >= rows {
+                child.arrange(Rect { tl: bounds.tl, size: Vector::null() });
+                continue;
+            }
+            let tl = bounds.tl.offset(Vector { x: x(column), y: y(row) });
+            let size = Vector { x: x(column + 1) - x(column), y: y(row + 1) - y(row) };
+            child.arrange(Rect { tl, size });
+        }
+        bounds.size
+    }
+}
+
+#[macro_export]
+macro_rules! uniform_grid_template {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident in $mod:ident {
+            $(use $path:path as $import:ident;)*
+
+            $($(
+                $(#[$field_attr:meta])*
+                pub $field_name:ident : $field_ty:ty
+            ),+ $(,)?)?
+        }
+    ) => {
+        $crate::panel_template! {
+            $(#[$attr])*
+            $vis struct $name in $mod {
+                $(use $path as $import;)*
+
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub rows: Option<u16>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub columns: Option<u16>,
+                $($(
+                    $(#[$field_attr])*
+                    pub $field_name : $field_ty
+                ),+)?
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! uniform_grid_apply_template {
+    ($this:ident, $instance:ident, $names:ident) => {
+        $crate::panel_apply_template!($this, $instance, $names);
+        {
+            use $crate::uniform_grid::UniformGridExt;
+
+            let obj: $crate::alloc_rc_Rc<dyn $crate::uniform_grid::IsUniformGrid>
+                = $crate::dynamic_cast_dyn_cast_rc($instance.clone()).unwrap();
+            $this.rows.map(|x| obj.set_rows(x));
+            $this.columns.map(|x| obj.set_columns(x));
+        }
+    };
+}
+
+uniform_grid_template! {
+    #[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+    #[serde(rename="UniformGrid@Children")]
+    pub struct UniformGridTemplate in template { }
+}
+
+#[typetag::serde(name="UniformGrid")]
+impl Template for UniformGridTemplate {
+    fn is_name_scope(&self) -> bool {
+        self.is_name_scope
+    }
+
+    fn name(&self) -> Option<&String> {
+        Some(&self.name)
+    }
+
+    fn create_instance(&self) -> Rc<dyn IsObj> {
+        UniformGrid::new()
+    }
+
+    fn apply(&self, instance: &Rc<dyn IsObj>, names: &mut NameResolver) {
+        let this = self;
+        uniform_grid_apply_template!(this, instance, names);
+    }
+}
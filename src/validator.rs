@@ -0,0 +1,433 @@
+use std::cell::Cell;
+use std::fmt::{self, Display, Formatter};
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// Input validator, see [`InputLine::set_validator`](crate::input_line::InputLine::set_validator_impl).
+pub trait Validator {
+    /// Checks the text after an edit. Returning `false` rejects the edit.
+    ///
+    /// The text being typed is not complete yet, so the check should accept any text
+    /// which can become valid after more input.
+    /// If `auto_fill` is set, the validator can complete the text, e.g. append mask literals.
+    fn is_valid_input(&self, text: &mut String, auto_fill: bool) -> bool {
+        let _ = (text, auto_fill);
+        true
+    }
+
+    /// Checks the complete text. It is called when the input line loses focus.
+    fn is_valid(&self, text: &str) -> bool;
+}
+
+/// Accepts integers in the inclusive range `min ..= max`.
+#[derive(Debug, Clone)]
+pub struct RangeValidator {
+    min: i64,
+    max: i64,
+}
+
+impl RangeValidator {
+    pub fn new(min: i64, max: i64) -> Self {
+        assert!(min <= max, "invalid range");
+        RangeValidator { min, max }
+    }
+
+    pub fn min(&self) -> i64 { self.min }
+
+    pub fn max(&self) -> i64 { self.max }
+}
+
+impl Validator for RangeValidator {
+    fn is_valid_input(&self, text: &mut String, _auto_fill: bool) -> bool {
+        let text = text.as_str();
+        let digits = if self.min < 0 { text.strip_prefix('-').unwrap_or(text) } else { text };
+        digits.chars().all(|c| c.is_ascii_digit())
+    }
+
+    fn is_valid(&self, text: &str) -> bool {
+        text.parse::<i64>().is_ok_and(|x| self.min <= x && x <= self.max)
+    }
+}
+
+/// Accepts the strings from the list, and while typing, their prefixes.
+#[derive(Debug, Clone)]
+pub struct ListValidator {
+    items: Vec<String>,
+    ignore_case: bool,
+}
+
+impl ListValidator {
+    pub fn new(items: Vec<String>, ignore_case: bool) -> Self {
+        ListValidator { items, ignore_case }
+    }
+
+    pub fn items(&self) -> &[String] { &self.items }
+
+    fn eq(&self, a: char, b: char) -> bool {
+        if self.ignore_case { a.to_lowercase().eq(b.to_lowercase()) } else { a == b }
+    }
+
+    fn is_prefix(&self, prefix: &str, item: &str) -> bool {
+        let mut item = item.chars();
+        prefix.chars().all(|c| item.next().is_some_and(|x| self.eq(c, x)))
+    }
+}
+
+impl Validator for ListValidator {
+    fn is_valid_input(&self, text: &mut String, _auto_fill: bool) -> bool {
+        self.items.iter().any(|x| self.is_prefix(text, x))
+    }
+
+    fn is_valid(&self, text: &str) -> bool {
+        self.items.iter().any(|x| x.chars().count() == text.chars().count() && self.is_prefix(text, x))
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Digit,
+    Letter,
+    LetterUpper,
+    Any,
+    AnyUpper,
+    Literal(char),
+    Group(Vec<Vec<Node>>),
+    Optional(Vec<Vec<Node>>),
+    Repeat(Option<usize>, Box<Node>),
+}
+
+fn to_upper(c: char) -> char {
+    let mut upper = c.to_uppercase();
+    match (upper.next(), upper.next()) {
+        (Some(u), None) => u,
+        _ => c
+    }
+}
+
+#[derive(Debug)]
+struct Match {
+    text: String,
+    complete: bool,
+}
+
+fn best(a: Option<Match>, b: impl FnOnce() -> Option<Match>) -> Option<Match> {
+    if a.as_ref().is_some_and(|x| x.complete) { return a; }
+    match (a, b()) {
+        (Some(a), Some(b)) => Some(if b.complete { b } else { a }),
+        (a, b) => a.or(b)
+    }
+}
+
+/// Turbo Vision style picture mask.
+///
+/// | Picture | Matches                                     |
+/// |---------|---------------------------------------------|
+/// | `#`     | a digit                                     |
+/// | `?`     | a letter                                    |
+/// | `&`     | a letter, converted to uppercase            |
+/// | `@`     | any character                               |
+/// | `!`     | any character, converted to uppercase       |
+/// | `;c`    | the character `c` literally                 |
+/// | `*n`    | the next item `n` times, or any number of times without `n` |
+/// | `[...]` | optional items                              |
+/// | `{...}` | grouped items                               |
+/// | `,`     | separates alternatives in a group, an optional part, or the whole picture |
+///
+/// Other characters match themselves ignoring case. For example, `"{##:##,#:##}[ {AM,PM}]"`
+/// matches time like `"9:30 PM"`. With `auto_fill`, literals following the typed text
+/// are appended automatically.
+#[derive(Debug, Clone)]
+pub struct PictureValidator {
+    picture: Vec<Vec<Node>>,
+    auto_fill: bool,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ParsePictureError;
+
+impl Display for ParsePictureError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "invalid picture")
+    }
+}
+
+impl std::error::Error for ParsePictureError { }
+
+impl PictureValidator {
+    /// Creates the validator, panics if the picture syntax is invalid.
+    pub fn new(picture: &str, auto_fill: bool) -> Self {
+        Self::try_new(picture, auto_fill).expect("invalid picture")
+    }
+
+    /// Creates the validator, fails if the picture syntax is invalid.
+    pub fn try_new(picture: &str, auto_fill: bool) -> Result<Self, ParsePictureError> {
+        let mut chars = picture.chars().peekable();
+        let picture = Self::parse_alternatives(&mut chars, None).ok_or(ParsePictureError)?;
+        Ok(PictureValidator { picture, auto_fill })
+    }
+
+    fn parse_alternatives(chars: &mut Peekable<Chars>, end: Option<char>) -> Option<Vec<Vec<Node>>> {
+        let mut alternatives = vec![Vec::new()];
+        loop {
+            let c = chars.next();
+            if c == end { return Some(alternatives); }
+            match c? {
+                ',' => alternatives.push(Vec::new()),
+                c => {
+                    let node = Self::parse_node(chars, c)?;
+                    alternatives.last_mut().unwrap().push(node);
+                },
+            }
+        }
+    }
+
+    fn parse_node(chars: &mut Peekable<Chars>, c: char) -> Option<Node> {
+        Some(match c {
+            '#' => Node::Digit,
+            '?' => Node::Letter,
+            '&' => Node::LetterUpper,
+            '@' => Node::Any,
+            '!' => Node::AnyUpper,
+            ';' => Node::Literal(chars.next()?),
+            '{' => Node::Group(Self::parse_alternatives(chars, Some('}'))?),
+            '[' => Node::Optional(Self::parse_alternatives(chars, Some(']'))?),
+            '*' => {
+                let mut count = None;
+                while let Some(d) = chars.peek().and_then(|x| x.to_digit(10)) {
+                    chars.next();
+                    count = Some(count.unwrap_or(0usize).checked_mul(10)?.checked_add(d as usize)?);
+                }
+                let c = chars.next()?;
+                if c == ',' { return None; }
+                Node::Repeat(count, Box::new(Self::parse_node(chars, c)?))
+            },
+            '}' | ']' => return None,
+            c => Node::Literal(c),
+        })
+    }
+
+    fn match_text(&self, input: &[char], auto_fill: bool) -> Option<Match> {
+        let end = |pos: usize, text: String| {
+            if pos == input.len() { Some(Match { text, complete: true }) } else { None }
+        };
+        self.match_alternatives(&self.picture, input, 0, String::new(), auto_fill, &end)
+    }
+
+    fn match_alternatives(
+        &self,
+        alternatives: &[Vec<Node>],
+        input: &[char],
+        pos: usize,
+        text: String,
+        auto_fill: bool,
+        k: &dyn Fn(usize, String) -> Option<Match>,
+    ) -> Option<Match> {
+        let mut res = None;
+        for items in alternatives {
+            res = best(res, || self.match_items(items, input, pos, text.clone(), auto_fill, k));
+        }
+        res
+    }
+
+    fn match_items(
+        &self,
+        items: &[Node],
+        input: &[char],
+        pos: usize,
+        text: String,
+        auto_fill: bool,
+        k: &dyn Fn(usize, String) -> Option<Match>,
+    ) -> Option<Match> {
+        let Some((item, items)) = items.split_first() else { return k(pos, text); };
+        self.match_node(item, input, pos, text, auto_fill, &|pos, text|
+            self.match_items(items, input, pos, text, auto_fill, k)
+        )
+    }
+
+    fn match_node(
+        &self,
+        node: &Node,
+        input: &[char],
+        pos: usize,
+        mut text: String,
+        auto_fill: bool,
+        k: &dyn Fn(usize, String) -> Option<Match>,
+    ) -> Option<Match> {
+        match node {
+            Node::Group(alternatives) => self.match_alternatives(alternatives, input, pos, text, auto_fill, k),
+            Node::Optional(alternatives) => {
+                let skipped = k(pos, text.clone());
+                best(skipped, || self.match_alternatives(alternatives, input, pos, text, false, k))
+            },
+            Node::Repeat(Some(count), node) => {
+                if *count == 0 { return k(pos, text); }
+                let rest = Node::Repeat(Some(count - 1), node.clone());
+                self.match_node(node, input, pos, text, auto_fill, &|pos, text|
+                    self.match_node(&rest, input, pos, text, auto_fill, k)
+                )
+            },
+            Node::Repeat(None, item) => {
+                // Greedy: repeats the item while it matches, without backtracking into the repetitions.
+                let (mut pos, mut text) = (pos, text);
+                loop {
+                    let step = Cell::new(None);
+                    let m = self.match_node(item, input, pos, text.clone(), false, &|next, text| {
+                        if next == pos { return None; }
+                        step.set(Some((next, text)));
+                        Some(Match { text: String::new(), complete: true })
+                    });
+                    let Some((next, next_text)) = step.take() else {
+                        return best(k(pos, text), || m);
+                    };
+                    pos = next;
+                    text = next_text;
+                }
+            },
+            Node::Literal(c) => {
+                let Some(&input_c) = input.get(pos) else {
+                    if !auto_fill { return Some(Match { text, complete: false }); }
+                    text.push(*c);
+                    return k(pos, text);
+                };
+                if input_c != *c && to_upper(input_c) != to_upper(*c) { return None; }
+                text.push(*c);
+                k(pos + 1, text)
+            },
+            class => {
+                let Some(&c) = input.get(pos) else { return Some(Match { text, complete: false }); };
+                let c = match class {
+                    Node::Digit if c.is_ascii_digit() => c,
+                    Node::Letter if c.is_alphabetic() => c,
+                    Node::LetterUpper if c.is_alphabetic() => to_upper(c),
+                    Node::Any => c,
+                    Node::AnyUpper => to_upper(c),
+                    _ => return None,
+                };
+                text.push(c);
+                k(pos + 1, text)
+            },
+        }
+    }
+}
+
+impl Validator for PictureValidator {
+    fn is_valid_input(&self, text: &mut String, auto_fill: bool) -> bool {
+        let input: Vec<char> = text.chars().collect();
+        let Some(m) = self.match_text(&input, auto_fill && self.auto_fill) else { return false; };
+        *text = m.text;
+        true
+    }
+
+    fn is_valid(&self, text: &str) -> bool {
+        let input: Vec<char> = text.chars().collect();
+        self.match_text(&input, false).is_some_and(|x| x.complete)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(validator: &dyn Validator, text: &str, auto_fill: bool) -> Option<String> {
+        let mut text = text.to_string();
+        if validator.is_valid_input(&mut text, auto_fill) { Some(text) } else { None }
+    }
+
+    #[test]
+    fn picture_time() {
+        let v = PictureValidator::new("{##:##,#:##}[ {AM,PM}]", false);
+        assert_eq!(input(&v, "12:3", false).as_deref(), Some("12:3"));
+        assert_eq!(input(&v, "9:30 p", false).as_deref(), Some("9:30 P"));
+        assert_eq!(input(&v, "9:30 pm", false).as_deref(), Some("9:30 PM"));
+        assert_eq!(input(&v, "1a", false), None);
+        assert_eq!(input(&v, "9:30 X", false), None);
+        assert!(v.is_valid("12:30"));
+        assert!(v.is_valid("9:30 PM"));
+        assert!(!v.is_valid("12:3"));
+        assert!(!v.is_valid("9:30 "));
+    }
+
+    #[test]
+    fn picture_auto_fill() {
+        let v = PictureValidator::new("##-##", true);
+        assert_eq!(input(&v, "12", true).as_deref(), Some("12-"));
+        assert_eq!(input(&v, "12", false).as_deref(), Some("12"));
+        let v = PictureValidator::new("##-##", false);
+        assert_eq!(input(&v, "12", true).as_deref(), Some("12"));
+    }
+
+    #[test]
+    fn picture_repeat() {
+        let v = PictureValidator::new("*3#", false);
+        assert!(v.is_valid("123"));
+        assert!(!v.is_valid("12"));
+        assert_eq!(input(&v, "12", false).as_deref(), Some("12"));
+        assert_eq!(input(&v, "1234", false), None);
+        let v = PictureValidator::new("*#", false);
+        assert!(v.is_valid(""));
+        assert!(v.is_valid("12345"));
+        assert_eq!(input(&v, "12a", false), None);
+    }
+
+    #[test]
+    fn picture_repeat_is_greedy() {
+        let v = PictureValidator::new("*##", false);
+        assert_eq!(input(&v, "12", false).as_deref(), Some("12"));
+        assert!(!v.is_valid("12"));
+        let v = PictureValidator::new("*#-", false);
+        assert!(v.is_valid("12-"));
+    }
+
+    #[test]
+    fn picture_escape() {
+        let v = PictureValidator::new(";##", false);
+        assert!(v.is_valid("#5"));
+        assert_eq!(input(&v, "55", false), None);
+    }
+
+    #[test]
+    fn picture_upper() {
+        let v = PictureValidator::new("&&", false);
+        assert_eq!(input(&v, "ab", false).as_deref(), Some("AB"));
+        assert_eq!(input(&v, "a1", false), None);
+        let v = PictureValidator::new("!!", false);
+        assert_eq!(input(&v, "1b", false).as_deref(), Some("1B"));
+    }
+
+    #[test]
+    fn picture_errors() {
+        assert_eq!(PictureValidator::try_new("{#", false).err(), Some(ParsePictureError));
+        assert_eq!(PictureValidator::try_new("*,", false).err(), Some(ParsePictureError));
+        assert_eq!(PictureValidator::try_new("]", false).err(), Some(ParsePictureError));
+        assert!(PictureValidator::try_new("{#,##}", false).is_ok());
+    }
+
+    #[test]
+    fn list() {
+        let items = vec!["Apple".to_string(), "Banana".to_string()];
+        let v = ListValidator::new(items.clone(), true);
+        assert!(input(&v, "ap", false).is_some());
+        assert!(input(&v, "c", false).is_none());
+        assert!(v.is_valid("APPLE"));
+        assert!(!v.is_valid("app"));
+        let v = ListValidator::new(items, false);
+        assert!(input(&v, "ap", false).is_none());
+        assert!(input(&v, "Ap", false).is_some());
+        assert!(!v.is_valid("apple"));
+    }
+
+    #[test]
+    fn range() {
+        let v = RangeValidator::new(-10, 5);
+        assert!(input(&v, "-", false).is_some());
+        assert!(input(&v, "-3", false).is_some());
+        assert!(input(&v, "3a", false).is_none());
+        assert!(v.is_valid("-10"));
+        assert!(v.is_valid("5"));
+        assert!(!v.is_valid("-11"));
+        assert!(!v.is_valid("6"));
+        assert!(!v.is_valid("-"));
+        let v = RangeValidator::new(0, 10);
+        assert!(input(&v, "-1", false).is_none());
+    }
+}
@@ -9,10 +9,11 @@ use std::mem::replace;
 use std::ptr::addr_eq;
 use std::rc::{self};
 use std::str::FromStr;
-use crate::base::{HAlign, VAlign, option_addr_eq};
+use crate::base::{HAlign, VAlign, option_addr_eq, MouseAction, MouseButton};
 use crate::event_handler::EventHandler;
 use crate::template::{Template, NameResolver};
 use crate::app::{App, AppExt};
+use crate::command::{CommandExt, CommandBindingExt};
 use crate::obj_col::ObjCol;
 
 import! { pub layout:
@@ -112,8 +113,9 @@ impl Layout {
 import! { pub view:
     use [obj basic_oop::obj];
     use std::rc::Rc;
-    use crate::base::{Key, Vector, Rect, Thickness, Point};
+    use crate::base::{Key, Vector, Rect, Thickness, Point, MouseEvent};
     use crate::app::IsApp;
+    use crate::command::{IsCommand, IsCommandBinding};
     use crate::obj_col::IsObjCol;
     use crate::render_port::RenderPort;
 }
@@ -242,6 +244,36 @@ pub fn deserialize_optional_i16<'de, D>(
     }
 }
 
+/// Mouse handling shared by clickable views, e.g. buttons.
+///
+/// Focuses the view and captures mouse on the left button press, and calls `click`
+/// if the button is released inside the view. Other events are passed to [`View::mouse_impl`].
+pub fn click_on_mouse(
+    this: &Rc<dyn IsView>,
+    event: MouseEvent,
+    original_source: &Rc<dyn IsView>,
+    click: impl FnOnce()
+) -> bool {
+    let app = this.app().unwrap();
+    match event.action {
+        MouseAction::Down(MouseButton::Left) => {
+            app.focus(Some(this), None);
+            app.capture_mouse(Some(this));
+            true
+        },
+        MouseAction::Up(MouseButton::Left) => {
+            let captured = app.mouse_capture().is_some_and(|x| addr_eq(Rc::as_ptr(&x), Rc::as_ptr(this)));
+            if !captured { return View::mouse_impl(this, event, original_source); }
+            app.capture_mouse(None);
+            if this.inner_render_bounds().contains(event.point) {
+                click();
+            }
+            true
+        },
+        _ => View::mouse_impl(this, event, original_source)
+    }
+}
+
 #[macro_export]
 macro_rules! view_template {
     (
@@ -321,6 +353,9 @@ macro_rules! view_template {
                 #[serde(default)]
                 #[serde(skip_serializing_if="Option::is_none")]
                 pub tab_index: Option<i8>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub help_context: Option<u16>,
                 $($(
                     $(#[$field_attr])*
                     pub $field_name : $field_ty
@@ -358,6 +393,7 @@ macro_rules! view_apply_template {
             $this.secondary_focus_keys.map(|x| obj.set_secondary_focus_keys(x));
             $this.visibility.map(|x| obj.set_visibility(x));
             $this.tab_index.map(|x| obj.set_tab_index(x));
+            $this.help_context.map(|x| obj.set_help_context(x));
         }
     };
 }
@@ -410,15 +446,20 @@ struct ViewData {
     allow_focus: bool,
     inherited_is_enabled: bool,
     is_enabled_core: bool,
+    is_enabled_by_command: bool,
     changing_is_enabled: bool,
     is_focused_primary: bool,
     is_focused_secondary: bool,
     preview_key_handler: EventHandler<Option<Box<dyn FnMut(Key, &Rc<dyn IsView>) -> bool>>>,
     key_handler: EventHandler<Option<Box<dyn FnMut(Key, &Rc<dyn IsView>) -> bool>>>,
+    preview_mouse_handler: EventHandler<Option<Box<dyn FnMut(MouseEvent, &Rc<dyn IsView>) -> bool>>>,
+    mouse_handler: EventHandler<Option<Box<dyn FnMut(MouseEvent, &Rc<dyn IsView>) -> bool>>>,
     secondary_focus_keys: SecondaryFocusKeys,
     secondary_focus_root: rc::Weak<dyn IsView>,
     visibility: Visibility,
     tab_index: i8,
+    help_context: u16,
+    command_bindings: Vec<Rc<dyn IsCommandBinding>>,
     bind_handler: EventHandler<Option<Box<dyn FnMut(&Rc<dyn IsView>, usize, &Rc<dyn IsView>) -> bool>>>,
     unbind_handler: EventHandler<Option<Box<dyn FnMut(&Rc<dyn IsView>, usize, &Rc<dyn IsView>) -> bool>>>,
 }
@@ -496,6 +537,8 @@ pub struct View {
     is_enabled: fn() -> bool,
     #[non_virt]
     set_is_enabled: fn(value: bool),
+    #[non_virt]
+    _set_is_enabled_by_command: fn(value: bool),
     #[virt]
     is_enabled_changed: fn(),
     #[non_virt]
@@ -533,6 +576,8 @@ pub struct View {
     #[virt]
     visual_child: fn(index: usize) -> Rc<dyn IsView>,
     #[virt]
+    visual_child_clip: fn(index: usize) -> Option<Rect>,
+    #[virt]
     render: fn(rp: &mut RenderPort),
     #[virt]
     is_focused_changed: fn(primary_focus: bool),
@@ -540,6 +585,8 @@ pub struct View {
     is_focused: fn(primary_focus: Option<bool>) -> bool,
     #[non_virt]
     _set_is_focused: fn(primary_focus: bool, value: bool),
+    #[virt]
+    descendant_focused: fn(descendant: &Rc<dyn IsView>, primary_focus: bool),
     #[non_virt]
     is_visual_ancestor_of: fn(descendant: Rc<dyn IsView>) -> bool,
     #[virt]
@@ -558,11 +605,49 @@ pub struct View {
     handle_key: fn(handler: Option<Box<dyn FnMut(Key, &Rc<dyn IsView>) -> bool>>),
     #[non_virt]
     _raise_key: fn(key: Key) -> bool,
+    #[virt]
+    paste: fn(text: &str, original_source: &Rc<dyn IsView>) -> bool,
+    #[non_virt]
+    _raise_paste: fn(text: &str) -> bool,
+    #[non_virt]
+    command_bindings: fn() -> Vec<Rc<dyn IsCommandBinding>>,
+    #[non_virt]
+    add_command_binding: fn(binding: Rc<dyn IsCommandBinding>),
+    #[non_virt]
+    remove_command_binding: fn(binding: &Rc<dyn IsCommandBinding>),
+    #[non_virt]
+    _raise_can_execute: fn(command: &Rc<dyn IsCommand>) -> bool,
+    #[non_virt]
+    _raise_execute: fn(command: &Rc<dyn IsCommand>) -> bool,
+    #[virt]
+    _requery_commands: fn(),
+    #[virt]
+    preview_mouse: fn(event: MouseEvent, original_source: &Rc<dyn IsView>) -> bool,
+    #[virt]
+    mouse: fn(event: MouseEvent, original_source: &Rc<dyn IsView>) -> bool,
+    #[non_virt]
+    handle_preview_mouse: fn(handler: Option<Box<dyn FnMut(MouseEvent, &Rc<dyn IsView>) -> bool>>),
+    #[non_virt]
+    handle_mouse: fn(handler: Option<Box<dyn FnMut(MouseEvent, &Rc<dyn IsView>) -> bool>>),
+    #[non_virt]
+    _raise_mouse: fn(event: MouseEvent) -> bool,
+    #[non_virt]
+    screen_to_inner: fn(p: Point) -> Point,
+    #[non_virt]
+    inner_to_screen: fn(p: Point) -> Point,
+    #[non_virt]
+    hit_test: fn(p: Point) -> Option<Rc<dyn IsView>>,
     #[non_virt]
     secondary_focus_keys: fn() -> SecondaryFocusKeys,
     #[non_virt]
     set_secondary_focus_keys: fn(value: SecondaryFocusKeys),
     #[non_virt]
+    help_context: fn() -> u16,
+    #[non_virt]
+    set_help_context: fn(value: u16),
+    #[virt]
+    help_context_changed: fn(),
+    #[non_virt]
     handle_bind: fn(handler: Option<Box<dyn FnMut(&Rc<dyn IsView>, usize, &Rc<dyn IsView>) -> bool>>),
     #[non_virt]
     handle_unbind: fn(handler: Option<Box<dyn FnMut(&Rc<dyn IsView>, usize, &Rc<dyn IsView>) -> bool>>),
@@ -608,15 +693,20 @@ impl View {
                 app: <rc::Weak::<App>>::new(),
                 inherited_is_enabled: true,
                 is_enabled_core: true,
+                is_enabled_by_command: true,
                 changing_is_enabled: false,
                 is_focused_primary: false,
                 is_focused_secondary: false,
                 preview_key_handler: Default::default(),
                 key_handler: Default::default(),
+                preview_mouse_handler: Default::default(),
+                mouse_handler: Default::default(),
                 secondary_focus_keys: SecondaryFocusKeys::None,
                 secondary_focus_root: <rc::Weak::<View>>::new(),
                 visibility: Visibility::Visible,
                 tab_index: i8::MAX,
+                help_context: 0,
+                command_bindings: Vec::new(),
                 bind_handler: Default::default(),
                 unbind_handler: Default::default(),
             })
@@ -671,18 +761,31 @@ impl View {
 
     pub fn is_enabled_impl(this: &Rc<dyn IsView>) -> bool {
         let data = this.view().data.borrow();
-        data.is_enabled_core && data.inherited_is_enabled
+        data.is_enabled_core && data.is_enabled_by_command && data.inherited_is_enabled
     }
 
     pub fn set_is_enabled_impl(this: &Rc<dyn IsView>, value: bool) {
-        {
+        Self::set_own_is_enabled(this, |data| data.is_enabled_core = value);
+    }
+
+    /// Enables or disables the view on behalf of its command.
+    /// The view is enabled only if both this flag and [`is_enabled_core`](ViewExt::is_enabled_core) are set.
+    pub fn _set_is_enabled_by_command_impl(this: &Rc<dyn IsView>, value: bool) {
+        Self::set_own_is_enabled(this, |data| data.is_enabled_by_command = value);
+    }
+
+    fn set_own_is_enabled(this: &Rc<dyn IsView>, f: impl FnOnce(&mut ViewData)) {
+        let value = {
             let mut data = this.view().data.borrow_mut();
-            if data.is_enabled_core == value { return; }
+            let old = data.is_enabled_core && data.is_enabled_by_command;
+            f(&mut data);
+            let value = data.is_enabled_core && data.is_enabled_by_command;
+            if old == value { return; }
             assert!(!data.changing_is_enabled);
-            data.is_enabled_core = value;
             if !data.inherited_is_enabled { return; }
             data.changing_is_enabled = true;
-        }
+            value
+        };
         this.is_enabled_changed();
         for i in 0 .. this.visual_children_count() {
             let child = this.visual_child(i);
@@ -696,7 +799,7 @@ impl View {
             let mut data = this.view().data.borrow_mut();
             assert!(!data.changing_is_enabled);
             data.inherited_is_enabled = is_enabled;
-            if !data.is_enabled_core { return; }
+            if !(data.is_enabled_core && data.is_enabled_by_command) { return; }
             data.changing_is_enabled = true;
         }
         this.is_enabled_changed();
@@ -1050,6 +1153,9 @@ impl View {
         if pre_post_process.contains(PrePostProcess::POST_PROCESS) {
             app._remove_post_process(this);
         }
+        if app.mouse_capture().is_some_and(|x| addr_eq(Rc::as_ptr(&x), Rc::as_ptr(this))) {
+            app.capture_mouse(None);
+        }
         for i in 0 .. this.visual_children_count() {
             this.visual_child(i)._detach_from_app();
         }
@@ -1078,7 +1184,7 @@ impl View {
         child.invalidate_render();
         let is_enabled = {
             let data = this.view().data.borrow();
-            data.is_enabled_core && data.inherited_is_enabled
+            data.is_enabled_core && data.is_enabled_by_command && data.inherited_is_enabled
         };
         if !is_enabled {
             Self::update_is_enabled(child, false);
@@ -1120,7 +1226,7 @@ impl View {
         child.invalidate_render();
         let is_enabled = {
             let data = this.view().data.borrow();
-            data.is_enabled_core && data.inherited_is_enabled
+            data.is_enabled_core && data.is_enabled_by_command && data.inherited_is_enabled
         };
         if !is_enabled {
             Self::update_is_enabled(child, true);
@@ -1136,6 +1242,12 @@ impl View {
         panic!("visual child index out of bounds")
     }
 
+    /// The rect in inner coordinates the visual child is clipped to when rendered and hit-tested,
+    /// or `None` if it is clipped to this view bounds only.
+    pub fn visual_child_clip_impl(_this: &Rc<dyn IsView>, _index: usize) -> Option<Rect> {
+        None
+    }
+
     pub fn render_impl(_this: &Rc<dyn IsView>, _rp: &mut RenderPort) { }
 
     pub fn is_focused_changed_impl(_this: &Rc<dyn IsView>, _primary_focus: bool) { }
@@ -1159,8 +1271,18 @@ impl View {
             }
         }
         this.is_focused_changed(primary_focus);
+        if value {
+            let mut parent = this.visual_parent();
+            while let Some(view) = parent {
+                view.descendant_focused(this, primary_focus);
+                parent = view.visual_parent();
+            }
+        }
     }
 
+    /// Called on every visual ancestor of a view getting focus.
+    pub fn descendant_focused_impl(_this: &Rc<dyn IsView>, _descendant: &Rc<dyn IsView>, _primary_focus: bool) { }
+
     pub fn pre_post_process_impl(_this: &Rc<dyn IsView>) -> PrePostProcess {
         PrePostProcess::empty()
     }
@@ -1185,7 +1307,15 @@ impl View {
     pub fn _raise_key_impl(this: &Rc<dyn IsView>, key: Key) -> bool {
         let handled = Self::raise_preview(this, |x| x.preview_key(key, this)).0;
         if handled { return true; }
-        Self::raise(this, |x| x.key(key, this))
+        let handled = Self::raise(this, |x| x.key(key, this));
+        if handled { return true; }
+        Self::raise(this, |x| {
+            let bindings = x.view().data.borrow().command_bindings.clone();
+            let Some(binding) = bindings.into_iter().find(|x| x.command().key() == Some(key)) else { return false; };
+            if !binding.can_execute(this) { return false; }
+            binding.execute(this);
+            true
+        })
     }
 
     pub fn preview_key_impl(this: &Rc<dyn IsView>, key: Key, original_source: &Rc<dyn IsView>) -> bool {
@@ -1224,6 +1354,131 @@ impl View {
         this.view().data.borrow_mut().key_handler.set(handler);
     }
 
+    pub fn _raise_paste_impl(this: &Rc<dyn IsView>, text: &str) -> bool {
+        Self::raise(this, |x| x.paste(text, this))
+    }
+
+    pub fn command_bindings_impl(this: &Rc<dyn IsView>) -> Vec<Rc<dyn IsCommandBinding>> {
+        this.view().data.borrow().command_bindings.clone()
+    }
+
+    pub fn add_command_binding_impl(this: &Rc<dyn IsView>, binding: Rc<dyn IsCommandBinding>) {
+        this.view().data.borrow_mut().command_bindings.push(binding);
+        if let Some(app) = this.app() {
+            app.invalidate_commands();
+        }
+    }
+
+    pub fn remove_command_binding_impl(this: &Rc<dyn IsView>, binding: &Rc<dyn IsCommandBinding>) {
+        {
+            let mut data = this.view().data.borrow_mut();
+            let index = data.command_bindings.iter().position(|x| addr_eq(Rc::as_ptr(x), Rc::as_ptr(binding))).unwrap();
+            data.command_bindings.remove(index);
+        }
+        if let Some(app) = this.app() {
+            app.invalidate_commands();
+        }
+    }
+
+    fn command_binding(this: &Rc<dyn IsView>, command: &Rc<dyn IsCommand>) -> Option<Rc<dyn IsCommandBinding>> {
+        let data = this.view().data.borrow();
+        data.command_bindings.iter().find(|x| addr_eq(Rc::as_ptr(&x.command()), Rc::as_ptr(command))).cloned()
+    }
+
+    pub fn _raise_can_execute_impl(this: &Rc<dyn IsView>, command: &Rc<dyn IsCommand>) -> bool {
+        let mut view = Some(this.clone());
+        while let Some(v) = view {
+            if let Some(binding) = Self::command_binding(&v, command) {
+                return binding.can_execute(this);
+            }
+            view = v.visual_parent();
+        }
+        false
+    }
+
+    pub fn _raise_execute_impl(this: &Rc<dyn IsView>, command: &Rc<dyn IsCommand>) -> bool {
+        Self::raise(this, |x| {
+            let Some(binding) = Self::command_binding(x, command) else { return false; };
+            binding.execute(this);
+            true
+        })
+    }
+
+    /// Called after user input, focus changes
+    /// and [`App::invalidate_commands`](crate::app::App::invalidate_commands_impl)
+    /// to update the view state depending on commands.
+    pub fn _requery_commands_impl(_this: &Rc<dyn IsView>) { }
+
+    pub fn paste_impl(_this: &Rc<dyn IsView>, _text: &str, _original_source: &Rc<dyn IsView>) -> bool {
+        false
+    }
+
+    pub fn _raise_mouse_impl(this: &Rc<dyn IsView>, event: MouseEvent) -> bool {
+        let local = |x: &Rc<dyn IsView>| MouseEvent { point: x.screen_to_inner(event.point), ..event };
+        let handled = Self::raise_preview(this, |x| x.preview_mouse(local(x), this)).0;
+        if handled { return true; }
+        Self::raise(this, |x| x.mouse(local(x), this))
+    }
+
+    pub fn preview_mouse_impl(this: &Rc<dyn IsView>, event: MouseEvent, original_source: &Rc<dyn IsView>) -> bool {
+        let mut invoke = this.view().data.borrow_mut().preview_mouse_handler.begin_invoke();
+        let handled = invoke.as_mut().map_or(false, |x| x(event, original_source));
+        this.view().data.borrow_mut().preview_mouse_handler.end_invoke(invoke);
+        handled
+    }
+
+    pub fn mouse_impl(this: &Rc<dyn IsView>, event: MouseEvent, original_source: &Rc<dyn IsView>) -> bool {
+        let mut invoke = this.view().data.borrow_mut().mouse_handler.begin_invoke();
+        let handled = invoke.as_mut().map_or(false, |x| x(event, original_source));
+        this.view().data.borrow_mut().mouse_handler.end_invoke(invoke);
+        handled
+    }
+
+    pub fn handle_preview_mouse_impl(
+        this: &Rc<dyn IsView>,
+        handler: Option<Box<dyn FnMut(MouseEvent, &Rc<dyn IsView>) -> bool>>
+    ) {
+        this.view().data.borrow_mut().preview_mouse_handler.set(handler);
+    }
+
+    pub fn handle_mouse_impl(
+        this: &Rc<dyn IsView>,
+        handler: Option<Box<dyn FnMut(MouseEvent, &Rc<dyn IsView>) -> bool>>
+    ) {
+        this.view().data.borrow_mut().mouse_handler.set(handler);
+    }
+
+    pub fn screen_to_inner_impl(this: &Rc<dyn IsView>, p: Point) -> Point {
+        let mut p = p;
+        let mut view = Some(this.clone());
+        while let Some(x) = view {
+            let tl = x.view().data.borrow().real_render_bounds.tl;
+            p = Point { x: p.x.wrapping_sub(tl.x), y: p.y.wrapping_sub(tl.y) };
+            view = x.visual_parent();
+        }
+        p
+    }
+
+    pub fn inner_to_screen_impl(this: &Rc<dyn IsView>, p: Point) -> Point {
+        let origin = this.screen_to_inner(Point { x: 0, y: 0 });
+        Point { x: p.x.wrapping_sub(origin.x), y: p.y.wrapping_sub(origin.y) }
+    }
+
+    pub fn hit_test_impl(this: &Rc<dyn IsView>, p: Point) -> Option<Rc<dyn IsView>> {
+        if this.visibility() != Visibility::Visible { return None; }
+        if !this.inner_render_bounds().contains(p) { return None; }
+        for i in (0 .. this.visual_children_count()).rev() {
+            if this.visual_child_clip(i).is_some_and(|x| !x.contains(p)) { continue; }
+            let child = this.visual_child(i);
+            let tl = child.view().data.borrow().real_render_bounds.tl;
+            let child_p = Point { x: p.x.wrapping_sub(tl.x), y: p.y.wrapping_sub(tl.y) };
+            if let Some(hit) = child.hit_test(child_p) {
+                return Some(hit);
+            }
+        }
+        Some(this.clone())
+    }
+
     pub fn handle_bind_impl(
         this: &Rc<dyn IsView>,
         handler: Option<Box<dyn FnMut(&Rc<dyn IsView>, usize, &Rc<dyn IsView>) -> bool>>
@@ -1256,9 +1511,9 @@ impl View {
         index: usize,
         original_source: &Rc<dyn IsView>
     ) -> bool {
-        let mut invoke = this.view().data.borrow_mut().bind_handler.begin_invoke();
+        let mut invoke = this.view().data.borrow_mut().unbind_handler.begin_invoke();
         let handled = invoke.as_mut().map_or(false, |x| x(item, index, original_source));
-        this.view().data.borrow_mut().bind_handler.end_invoke(invoke);
+        this.view().data.borrow_mut().unbind_handler.end_invoke(invoke);
         handled
     }
 
@@ -1287,6 +1542,25 @@ impl View {
         }
     }
 
+    pub fn help_context_impl(this: &Rc<dyn IsView>) -> u16 {
+        this.view().data.borrow().help_context
+    }
+
+    pub fn set_help_context_impl(this: &Rc<dyn IsView>, value: u16) {
+        {
+            let mut data = this.view().data.borrow_mut();
+            if data.help_context == value { return; }
+            data.help_context = value;
+        }
+        if let Some(app) = this.app() {
+            app._update_help_context();
+        }
+    }
+
+    /// Called on pre- and post-process views when [`App::help_context`](crate::app::App::help_context_impl)
+    /// changes.
+    pub fn help_context_changed_impl(_this: &Rc<dyn IsView>) { }
+
     fn set_secondary_focus_root(mut view: Rc<dyn IsView>, sfr: &Rc<dyn IsView>) {
         loop {
             let parent = {
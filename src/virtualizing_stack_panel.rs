@@ -0,0 +1,436 @@
+use basic_oop::{class_unsafe, import, Vtable};
+use dynamic_cast::dyn_cast_rc;
+use std::cell::RefCell;
+use std::cmp::{max, min};
+use std::ops::Range;
+use std::ptr::addr_eq;
+use std::rc::{self};
+use crate::base::MouseAction;
+use crate::event_handler::EventHandler;
+use crate::items_control::{IsItemsControl, ItemsControl, ItemsControlExt};
+use crate::template::{Template, NameResolver};
+use crate::view_vec::ViewVecExt;
+
+import! { pub virtualizing_stack_panel:
+    use [panel crate::panel];
+}
+
+const WHEEL_ITEMS: usize = 3;
+
+/// Swaps vector components for horizontal stacking, so the layout code can always
+/// treat `y` as the stacking direction.
+fn stack(v: Vector, vertical: bool) -> Vector {
+    if vertical { v } else { Vector { x: v.y, y: v.x } }
+}
+
+struct VirtualizingStackPanelData {
+    vertical: bool,
+    items_control: rc::Weak<dyn IsItemsControl>,
+    offset: usize,
+    first: usize,
+    extent: usize,
+    viewport: usize,
+    measured_offset: usize,
+    recycled: Vec<Rc<dyn IsView>>,
+    scroll_change_handler: EventHandler<Option<Box<dyn FnMut()>>>,
+}
+
+/// An items panel realizing views only for the items it shows.
+///
+/// The panel is driven by the [`ItemsControl`] it is loaded into from the panel template.
+/// Its children are the realized item views, starting from the item with the
+/// [`offset`](VirtualizingStackPanel::offset_impl) index. Views scrolled out are
+/// unbound and kept for reuse by the items scrolled in.
+///
+/// Only a limited measure constraint in the stacking direction restricts the realized range,
+/// so the panel should not be placed in a `ScrollViewer` or a `StackPanel` of the same direction.
+/// Instead, a `ScrollBar` can follow the panel [`extent`](VirtualizingStackPanel::extent_impl),
+/// [`viewport`](VirtualizingStackPanel::viewport_impl) and [`offset`](VirtualizingStackPanel::offset_impl),
+/// updated in the [`handle_scroll_change`](VirtualizingStackPanel::handle_scroll_change_impl) handler.
+#[class_unsafe(inherits_Panel)]
+pub struct VirtualizingStackPanel {
+    data: RefCell<VirtualizingStackPanelData>,
+    #[non_virt]
+    vertical: fn() -> bool,
+    #[non_virt]
+    set_vertical: fn(value: bool),
+    #[non_virt]
+    offset: fn() -> usize,
+    #[non_virt]
+    set_offset: fn(value: usize),
+    #[non_virt]
+    extent: fn() -> usize,
+    #[non_virt]
+    viewport: fn() -> usize,
+    #[non_virt]
+    realized_items: fn() -> Range<usize>,
+    #[non_virt]
+    realized_item: fn(index: usize) -> Option<Rc<dyn IsView>>,
+    #[non_virt]
+    scroll_into_view: fn(index: usize),
+    #[non_virt]
+    items_control: fn() -> Option<Rc<dyn IsItemsControl>>,
+    #[non_virt]
+    _set_items_control: fn(value: Option<&Rc<dyn IsItemsControl>>),
+    #[non_virt]
+    _items_count_changed: fn(),
+    #[non_virt]
+    handle_scroll_change: fn(handler: Option<Box<dyn FnMut()>>),
+    #[virt]
+    scroll_changed: fn(),
+    #[over]
+    measure_override: (),
+    #[over]
+    arrange_override: (),
+    #[over]
+    mouse: (),
+}
+
+impl VirtualizingStackPanel {
+    pub fn new() -> Rc<dyn IsVirtualizingStackPanel> {
+        let res: Rc<dyn IsVirtualizingStackPanel>
+            = Rc::new(unsafe { Self::new_raw(VIRTUALIZING_STACK_PANEL_VTABLE.as_ptr()) });
+        res._init();
+        res
+    }
+
+    pub unsafe fn new_raw(vtable: Vtable) -> Self {
+        VirtualizingStackPanel {
+            panel: unsafe { Panel::new_raw(vtable) },
+            data: RefCell::new(VirtualizingStackPanelData {
+                vertical: true,
+                items_control: <rc::Weak::<ItemsControl>>::new(),
+                offset: 0,
+                first: 0,
+                extent: 0,
+                viewport: 1,
+                measured_offset: 0,
+                recycled: Vec::new(),
+                scroll_change_handler: Default::default(),
+            }),
+        }
+    }
+
+    pub fn vertical_impl(this: &Rc<dyn IsVirtualizingStackPanel>) -> bool {
+        this.virtualizing_stack_panel().data.borrow().vertical
+    }
+
+    pub fn set_vertical_impl(this: &Rc<dyn IsVirtualizingStackPanel>, value: bool) {
+        {
+            let mut data = this.virtualizing_stack_panel().data.borrow_mut();
+            if data.vertical == value { return; }
+            data.vertical = value;
+        }
+        this.invalidate_measure();
+    }
+
+    /// The index of the first shown item.
+    pub fn offset_impl(this: &Rc<dyn IsVirtualizingStackPanel>) -> usize {
+        this.virtualizing_stack_panel().data.borrow().offset
+    }
+
+    /// Scrolls to show items starting from the specified index. The value is clamped on the next measure.
+    pub fn set_offset_impl(this: &Rc<dyn IsVirtualizingStackPanel>, value: usize) {
+        {
+            let mut data = this.virtualizing_stack_panel().data.borrow_mut();
+            if data.offset == value { return; }
+            data.offset = value;
+        }
+        this.invalidate_measure();
+    }
+
+    /// The items count at the last measure.
+    pub fn extent_impl(this: &Rc<dyn IsVirtualizingStackPanel>) -> usize {
+        this.virtualizing_stack_panel().data.borrow().extent
+    }
+
+    /// The number of items fully fitting in the panel at the last measure, at least one.
+    pub fn viewport_impl(this: &Rc<dyn IsVirtualizingStackPanel>) -> usize {
+        this.virtualizing_stack_panel().data.borrow().viewport
+    }
+
+    /// The indices of the items having views.
+    pub fn realized_items_impl(this: &Rc<dyn IsVirtualizingStackPanel>) -> Range<usize> {
+        let first = this.virtualizing_stack_panel().data.borrow().first;
+        first .. first + this.children().len()
+    }
+
+    pub fn realized_item_impl(this: &Rc<dyn IsVirtualizingStackPanel>, index: usize) -> Option<Rc<dyn IsView>> {
+        let first = this.virtualizing_stack_panel().data.borrow().first;
+        let position = index.checked_sub(first)?;
+        let children = this.children();
+        if position >= children.len() { return None; }
+        Some(children.at(position))
+    }
+
+    /// Changes the offset the least possible to show the item with the specified index.
+    pub fn scroll_into_view_impl(this: &Rc<dyn IsVirtualizingStackPanel>, index: usize) {
+        let (offset, viewport) = {
+            let data = this.virtualizing_stack_panel().data.borrow();
+            (data.offset, data.viewport)
+        };
+        if index < offset {
+            this.set_offset(index);
+        } else if index >= offset + viewport {
+            this.set_offset(index + 1 - viewport);
+        }
+    }
+
+    pub fn items_control_impl(this: &Rc<dyn IsVirtualizingStackPanel>) -> Option<Rc<dyn IsItemsControl>> {
+        this.virtualizing_stack_panel().data.borrow().items_control.upgrade()
+    }
+
+    pub fn _set_items_control_impl(
+        this: &Rc<dyn IsVirtualizingStackPanel>,
+        value: Option<&Rc<dyn IsItemsControl>>
+    ) {
+        let old = this.items_control();
+        if let Some(old) = old.as_ref() {
+            if value.is_some_and(|x| addr_eq(Rc::as_ptr(x), Rc::as_ptr(old))) { return; }
+            let first = this.virtualizing_stack_panel().data.borrow().first;
+            Self::recycle_from(this, old, first);
+        } else if value.is_none() {
+            return;
+        }
+        {
+            let mut data = this.virtualizing_stack_panel().data.borrow_mut();
+            data.items_control = match value {
+                Some(value) => Rc::downgrade(value),
+                None => <rc::Weak::<ItemsControl>>::new(),
+            };
+            data.recycled.clear();
+            data.offset = 0;
+            data.first = 0;
+        }
+        this.invalidate_measure();
+    }
+
+    /// Unbinds views of the items removed by the items count change.
+    pub fn _items_count_changed_impl(this: &Rc<dyn IsVirtualizingStackPanel>) {
+        let Some(items_control) = this.items_control() else { return; };
+        let first = this.virtualizing_stack_panel().data.borrow().first;
+        Self::recycle_from(this, &items_control, max(first, items_control.items_count()));
+        this.invalidate_measure();
+    }
+
+    pub fn handle_scroll_change_impl(this: &Rc<dyn IsVirtualizingStackPanel>, handler: Option<Box<dyn FnMut()>>) {
+        this.virtualizing_stack_panel().data.borrow_mut().scroll_change_handler.set(handler);
+    }
+
+    /// Called after a measure changing the offset, the extent, or the viewport.
+    pub fn scroll_changed_impl(this: &Rc<dyn IsVirtualizingStackPanel>) {
+        let mut invoke = this.virtualizing_stack_panel().data.borrow_mut().scroll_change_handler.begin_invoke();
+        invoke.as_mut().map(|x| x());
+        this.virtualizing_stack_panel().data.borrow_mut().scroll_change_handler.end_invoke(invoke);
+    }
+
+    fn load_item(this: &Rc<dyn IsVirtualizingStackPanel>, items_control: &Rc<dyn IsItemsControl>) -> Rc<dyn IsView> {
+        let recycled = this.virtualizing_stack_panel().data.borrow_mut().recycled.pop();
+        recycled.unwrap_or_else(|| dyn_cast_rc(items_control.item_template().load_root().0).expect("View"))
+    }
+
+    fn recycle(
+        this: &Rc<dyn IsVirtualizingStackPanel>,
+        items_control: &Rc<dyn IsItemsControl>,
+        position: usize,
+        index: usize
+    ) {
+        let children = this.children();
+        let item = children.at(position);
+        items_control._raise_unbind(&item, index);
+        children.remove(position);
+        this.virtualizing_stack_panel().data.borrow_mut().recycled.push(item);
+    }
+
+    /// Recycles views of the items with indices starting from `end`.
+    fn recycle_from(this: &Rc<dyn IsVirtualizingStackPanel>, items_control: &Rc<dyn IsItemsControl>, end: usize) {
+        let first = this.virtualizing_stack_panel().data.borrow().first;
+        let len = this.children().len();
+        for position in (end.saturating_sub(first) .. len).rev() {
+            Self::recycle(this, items_control, position, first + position);
+        }
+    }
+
+    /// Makes the item with the `offset` index the first realized one,
+    /// keeping views of the items still shown.
+    fn realize_from(this: &Rc<dyn IsVirtualizingStackPanel>, items_control: &Rc<dyn IsItemsControl>, offset: usize) {
+        let first = this.virtualizing_stack_panel().data.borrow().first;
+        let children = this.children();
+        let len = children.len();
+        if offset >= first && offset < first + len {
+            for index in first .. offset {
+                Self::recycle(this, items_control, 0, index);
+            }
+        } else if offset < first && first - offset < len {
+            for index in (offset .. first).rev() {
+                let item = Self::load_item(this, items_control);
+                children.insert(0, item.clone());
+                items_control._raise_bind(&item, index);
+            }
+        } else {
+            Self::recycle_from(this, items_control, first);
+        }
+        this.virtualizing_stack_panel().data.borrow_mut().first = offset;
+    }
+
+    fn realize(
+        this: &Rc<dyn IsVirtualizingStackPanel>,
+        items_control: &Rc<dyn IsItemsControl>,
+        index: usize
+    ) -> Rc<dyn IsView> {
+        if let Some(item) = this.realized_item(index) { return item; }
+        let item = Self::load_item(this, items_control);
+        this.children().push(item.clone());
+        items_control._raise_bind(&item, index);
+        item
+    }
+
+    pub fn measure_override_impl(this: &Rc<dyn IsView>, w: Option<i16>, h: Option<i16>) -> Vector {
+        let this: Rc<dyn IsVirtualizingStackPanel> = dyn_cast_rc(this.clone()).unwrap();
+        let Some(items_control) = this.items_control() else { return Vector::null(); };
+        let vertical = this.vertical();
+        let (cross, limit) = if vertical { (w, h) } else { (h, w) };
+        let count = items_control.items_count();
+        let offset = {
+            let mut data = this.virtualizing_stack_panel().data.borrow_mut();
+            data.offset = min(data.offset, count.saturating_sub(1));
+            data.offset
+        };
+        Self::realize_from(&this, &items_control, offset);
+        let mut size = Vector::null();
+        let mut visible_count = 0;
+        let mut index = offset;
+        while index < count && limit.is_none_or(|limit| size.y < limit) {
+            let item = Self::realize(&this, &items_control, index);
+            if vertical {
+                item.measure(cross, None);
+            } else {
+                item.measure(None, cross);
+            }
+            let item_size = stack(item.desired_size(), vertical);
+            size = Vector { x: size.x.max(item_size.x), y: size.y.saturating_add(item_size.y) };
+            if limit.is_none_or(|limit| size.y <= limit) {
+                visible_count += 1;
+            }
+            index += 1;
+        }
+        Self::recycle_from(&this, &items_control, index);
+        let scroll_changed = {
+            let mut data = this.virtualizing_stack_panel().data.borrow_mut();
+            let viewport = max(visible_count, 1);
+            let changed = data.measured_offset != offset || data.extent != count || data.viewport != viewport;
+            data.measured_offset = offset;
+            data.extent = count;
+            data.viewport = viewport;
+            changed
+        };
+        if scroll_changed {
+            this.scroll_changed();
+        }
+        stack(size, vertical)
+    }
+
+    pub fn arrange_override_impl(this: &Rc<dyn IsView>, bounds: Rect) -> Vector {
+        let this: Rc<dyn IsVirtualizingStackPanel> = dyn_cast_rc(this.clone()).unwrap();
+        let vertical = this.vertical();
+        let bounds_size = stack(bounds.size, vertical);
+        let mut pos = 0i16;
+        for child in this.children().iter() {
+            let item_size = stack(child.desired_size(), vertical);
+            child.arrange(Rect {
+                tl: bounds.tl.offset(stack(Vector { x: 0, y: pos }, vertical)),
+                size: stack(Vector { x: bounds_size.x, y: item_size.y }, vertical),
+            });
+            pos = pos.saturating_add(item_size.y);
+        }
+        bounds.size
+    }
+
+    pub fn mouse_impl(this: &Rc<dyn IsView>, event: MouseEvent, original_source: &Rc<dyn IsView>) -> bool {
+        let panel: Rc<dyn IsVirtualizingStackPanel> = dyn_cast_rc(this.clone()).unwrap();
+        match event.action {
+            MouseAction::WheelUp => {
+                panel.set_offset(panel.offset().saturating_sub(WHEEL_ITEMS));
+                true
+            },
+            MouseAction::WheelDown => {
+                let count = panel.items_control().map_or(0, |x| x.items_count());
+                let offset = panel.offset();
+                let max_offset = count.saturating_sub(panel.viewport());
+                panel.set_offset(max(offset, min(offset + WHEEL_ITEMS, max_offset)));
+                true
+            },
+            _ => View::mouse_impl(this, event, original_source)
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! virtualizing_stack_panel_template {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident in $mod:ident {
+            $(use $path:path as $import:ident;)*
+
+            $($(
+                $(#[$field_attr:meta])*
+                pub $field_name:ident : $field_ty:ty
+            ),+ $(,)?)?
+        }
+    ) => {
+        $crate::panel_template! {
+            $(#[$attr])*
+            $vis struct $name in $mod {
+                $(use $path as $import;)*
+
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub vertical: Option<bool>,
+                $($(
+                    $(#[$field_attr])*
+                    pub $field_name : $field_ty
+                ),+)?
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! virtualizing_stack_panel_apply_template {
+    ($this:ident, $instance:ident, $names:ident) => {
+        $crate::panel_apply_template!($this, $instance, $names);
+        {
+            use $crate::virtualizing_stack_panel::VirtualizingStackPanelExt;
+
+            let obj: $crate::alloc_rc_Rc<dyn $crate::virtualizing_stack_panel::IsVirtualizingStackPanel>
+                = $crate::dynamic_cast_dyn_cast_rc($instance.clone()).unwrap();
+            $this.vertical.map(|x| obj.set_vertical(x));
+        }
+    };
+}
+
+virtualizing_stack_panel_template! {
+    #[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+    #[serde(rename="VirtualizingStackPanel")]
+    pub struct VirtualizingStackPanelTemplate in template { }
+}
+
+#[typetag::serde(name="VirtualizingStackPanel")]
+impl Template for VirtualizingStackPanelTemplate {
+    fn is_name_scope(&self) -> bool {
+        self.is_name_scope
+    }
+
+    fn name(&self) -> Option<&String> {
+        Some(&self.name)
+    }
+
+    fn create_instance(&self) -> Rc<dyn IsObj> {
+        VirtualizingStackPanel::new()
+    }
+
+    fn apply(&self, instance: &Rc<dyn IsObj>, names: &mut NameResolver) {
+        let this = self;
+        virtualizing_stack_panel_apply_template!(this, instance, names);
+    }
+}
@@ -0,0 +1,444 @@
+use basic_oop::{class_unsafe, import, Vtable};
+use dynamic_cast::dyn_cast_rc;
+use std::cell::RefCell;
+use std::mem::take;
+use std::ptr::addr_eq;
+use crate::app::AppExt;
+use crate::base::{text_width, Modifiers, SpecialKey, MouseAction, MouseButton};
+use crate::canvas::{IsCanvasLayout, CanvasLayoutExt};
+use crate::desktop::{IsDesktop, DesktopExt};
+use crate::template::{Template, NameResolver};
+
+import! { pub window:
+    use [decorator crate::decorator];
+    use crate::base::{Fg, Bg};
+}
+
+const MIN_SIZE: Vector = Vector { x: 16, y: 3 };
+
+const CLOSE_GLYPH: &str = "[■]";
+
+const ZOOM_GLYPH: &str = "[↑]";
+
+const UNZOOM_GLYPH: &str = "[↕]";
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Drag {
+    Move(Point),
+    Resize,
+}
+
+struct WindowData {
+    title: Rc<String>,
+    color: (Fg, Bg),
+    is_zoomed: bool,
+    drag: Option<Drag>,
+    is_keyboard_drag: bool,
+    /// The window was activated by the current mouse press, so the press is not a click on a glyph,
+    /// which were not visible while the window was inactive.
+    activated_by_press: bool,
+}
+
+#[class_unsafe(inherits_Decorator)]
+pub struct Window {
+    data: RefCell<WindowData>,
+    #[non_virt]
+    title: fn() -> Rc<String>,
+    #[non_virt]
+    set_title: fn(value: Rc<String>),
+    #[non_virt]
+    color: fn() -> (Fg, Bg),
+    #[non_virt]
+    set_color: fn(value: (Fg, Bg)),
+    #[non_virt]
+    is_zoomed: fn() -> bool,
+    #[non_virt]
+    set_is_zoomed: fn(value: bool),
+    #[non_virt]
+    desktop: fn() -> Option<Rc<dyn IsDesktop>>,
+    #[non_virt]
+    is_active: fn() -> bool,
+    #[non_virt]
+    activate: fn(),
+    #[non_virt]
+    close: fn(),
+    #[non_virt]
+    move_by: fn(offset: Vector),
+    #[non_virt]
+    resize_to: fn(size: Vector),
+    #[over]
+    _init: (),
+    #[over]
+    measure_override: (),
+    #[over]
+    arrange_override: (),
+    #[over]
+    render: (),
+    #[over]
+    preview_key: (),
+    #[over]
+    key: (),
+    #[over]
+    preview_mouse: (),
+    #[over]
+    mouse: (),
+}
+
+impl Window {
+    pub fn new() -> Rc<dyn IsWindow> {
+        let res: Rc<dyn IsWindow> = Rc::new(unsafe { Self::new_raw(WINDOW_VTABLE.as_ptr()) });
+        res._init();
+        res
+    }
+
+    pub unsafe fn new_raw(vtable: Vtable) -> Self {
+        Window {
+            decorator: unsafe { Decorator::new_raw(vtable) },
+            data: RefCell::new(WindowData {
+                title: Rc::new(String::new()),
+                color: (Fg::White, Bg::Blue),
+                is_zoomed: false,
+                drag: None,
+                is_keyboard_drag: false,
+                activated_by_press: false,
+            }),
+        }
+    }
+
+    pub fn _init_impl(this: &Rc<dyn IsView>) {
+        View::_init_impl(this);
+        this.set_allow_focus(true);
+    }
+
+    pub fn title_impl(this: &Rc<dyn IsWindow>) -> Rc<String> {
+        this.window().data.borrow().title.clone()
+    }
+
+    pub fn set_title_impl(this: &Rc<dyn IsWindow>, value: Rc<String>) {
+        {
+            let mut data = this.window().data.borrow_mut();
+            if addr_eq(Rc::as_ptr(&data.title), Rc::as_ptr(&value)) { return; }
+            data.title = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn color_impl(this: &Rc<dyn IsWindow>) -> (Fg, Bg) {
+        this.window().data.borrow().color
+    }
+
+    pub fn set_color_impl(this: &Rc<dyn IsWindow>, value: (Fg, Bg)) {
+        {
+            let mut data = this.window().data.borrow_mut();
+            if data.color == value { return; }
+            data.color = value;
+        }
+        this.invalidate_render();
+    }
+
+    pub fn is_zoomed_impl(this: &Rc<dyn IsWindow>) -> bool {
+        this.window().data.borrow().is_zoomed
+    }
+
+    pub fn set_is_zoomed_impl(this: &Rc<dyn IsWindow>, value: bool) {
+        {
+            let mut data = this.window().data.borrow_mut();
+            if data.is_zoomed == value { return; }
+            data.is_zoomed = value;
+            data.is_keyboard_drag = false;
+        }
+        this.invalidate_measure();
+        this.layout_parent().map(|x| x.invalidate_measure());
+        this.invalidate_render();
+    }
+
+    pub fn desktop_impl(this: &Rc<dyn IsWindow>) -> Option<Rc<dyn IsDesktop>> {
+        this.layout_parent().and_then(|x| dyn_cast_rc(x))
+    }
+
+    /// A window that does not belong to a desktop, e.g. a modal dialog, is always active.
+    pub fn is_active_impl(this: &Rc<dyn IsWindow>) -> bool {
+        let Some(desktop) = this.desktop() else { return true; };
+        desktop.active_window().is_some_and(|x| addr_eq(Rc::as_ptr(&x), Rc::as_ptr(this)))
+    }
+
+    pub fn activate_impl(this: &Rc<dyn IsWindow>) {
+        if let Some(desktop) = this.desktop() {
+            desktop.activate(this);
+        }
+    }
+
+    /// Removes the window from its desktop. A modal window is closed with result 0.
+    pub fn close_impl(this: &Rc<dyn IsWindow>) {
+        if let Some(desktop) = this.desktop() {
+            desktop.remove_window(this);
+        } else if let Some(app) = this.app() {
+            let this: Rc<dyn IsView> = this.clone();
+            app.close_modal(&this, 0);
+        }
+    }
+
+    pub fn move_by_impl(this: &Rc<dyn IsWindow>, offset: Vector) {
+        let layout: Option<Rc<dyn IsCanvasLayout>> = dyn_cast_rc(this.layout());
+        let Some(layout) = layout else { return; };
+        layout.set_tl(layout.tl().offset(offset));
+    }
+
+    pub fn resize_to_impl(this: &Rc<dyn IsWindow>, size: Vector) {
+        let size = size.max(MIN_SIZE);
+        this.set_width(Some(size.x));
+        this.set_height(Some(size.y));
+    }
+
+    pub fn measure_override_impl(this: &Rc<dyn IsView>, w: Option<i16>, h: Option<i16>) -> Vector {
+        let this: Rc<dyn IsDecorator> = dyn_cast_rc(this.clone()).unwrap();
+        let size = if let Some(child) = this.child() {
+            let available_size = Vector { x: w.unwrap_or(0), y: h.unwrap_or(0) };
+            let child_size = Thickness::all(1).shrink_rect_size(available_size);
+            let child_width = if w.is_none() { None } else { Some(child_size.x) };
+            let child_height = if h.is_none() { None } else { Some(child_size.y) };
+            child.measure(child_width, child_height);
+            child.desired_size()
+        } else {
+            Vector::null()
+        };
+        Thickness::all(1).expand_rect_size(size).max(MIN_SIZE)
+    }
+
+    pub fn arrange_override_impl(this: &Rc<dyn IsView>, bounds: Rect) -> Vector {
+        let this: Rc<dyn IsDecorator> = dyn_cast_rc(this.clone()).unwrap();
+        if let Some(child) = this.child() {
+            let child_bounds = Thickness::all(1).shrink_rect(bounds);
+            child.arrange(child_bounds);
+        }
+        bounds.size
+    }
+
+    pub fn render_impl(this: &Rc<dyn IsView>, rp: &mut RenderPort) {
+        let this: Rc<dyn IsWindow> = dyn_cast_rc(this.clone()).unwrap();
+        let bounds = this.inner_render_bounds();
+        let is_active = this.is_active();
+        let data = this.window().data.borrow();
+        let double = is_active && !data.is_keyboard_drag;
+        rp.fill_bg(data.color);
+        rp.h_line(bounds.tl, bounds.w(), double, data.color);
+        rp.h_line(bounds.bl_inner(), bounds.w(), double, data.color);
+        rp.v_line(bounds.tl, bounds.h(), double, data.color);
+        rp.v_line(bounds.tr_inner(), bounds.h(), double, data.color);
+        rp.tl_edge(bounds.tl, double, data.color);
+        rp.tr_edge(bounds.tr_inner(), double, data.color);
+        rp.bl_edge(bounds.bl_inner(), double, data.color);
+        if is_active && !data.is_zoomed && this.desktop().is_some() {
+            rp.br_edge(bounds.br_inner(), false, data.color);
+        } else {
+            rp.br_edge(bounds.br_inner(), double, data.color);
+        }
+        let title_width = text_width(&data.title);
+        if title_width != 0 {
+            let x = bounds.w().wrapping_sub(title_width).wrapping_sub(2) / 2;
+            let x = x.max(5);
+            rp.text(Point { x, y: 0 }, data.color, " ");
+            rp.text(Point { x: x.wrapping_add(1), y: 0 }, data.color, &data.title);
+            rp.text(Point { x: x.wrapping_add(1).wrapping_add(title_width), y: 0 }, data.color, " ");
+        }
+        if is_active {
+            rp.text(Point { x: 2, y: 0 }, data.color, CLOSE_GLYPH);
+            if this.desktop().is_some() {
+                let glyph = if data.is_zoomed { UNZOOM_GLYPH } else { ZOOM_GLYPH };
+                rp.text(Point { x: bounds.w().wrapping_sub(5), y: 0 }, data.color, glyph);
+            }
+        }
+    }
+
+    pub fn preview_key_impl(this: &Rc<dyn IsView>, key: Key, original_source: &Rc<dyn IsView>) -> bool {
+        let window: Rc<dyn IsWindow> = dyn_cast_rc(this.clone()).unwrap();
+        if !window.window().data.borrow().is_keyboard_drag {
+            return View::preview_key_impl(this, key, original_source);
+        }
+        let size = this.inner_render_bounds().size;
+        match key {
+            Key::Left => window.move_by(Vector { x: -1, y: 0 }),
+            Key::Right => window.move_by(Vector { x: 1, y: 0 }),
+            Key::Up => window.move_by(Vector { x: 0, y: -1 }),
+            Key::Down => window.move_by(Vector { x: 0, y: 1 }),
+            Key::Modified(m, SpecialKey::Left) if m == Modifiers::SHIFT =>
+                window.resize_to(Vector { x: size.x.wrapping_sub(1), y: size.y }),
+            Key::Modified(m, SpecialKey::Right) if m == Modifiers::SHIFT =>
+                window.resize_to(Vector { x: size.x.wrapping_add(1), y: size.y }),
+            Key::Modified(m, SpecialKey::Up) if m == Modifiers::SHIFT =>
+                window.resize_to(Vector { x: size.x, y: size.y.wrapping_sub(1) }),
+            Key::Modified(m, SpecialKey::Down) if m == Modifiers::SHIFT =>
+                window.resize_to(Vector { x: size.x, y: size.y.wrapping_add(1) }),
+            Key::Enter | Key::Escape => {
+                window.window().data.borrow_mut().is_keyboard_drag = false;
+                this.invalidate_render();
+            },
+            _ => { },
+        }
+        true
+    }
+
+    pub fn key_impl(this: &Rc<dyn IsView>, key: Key, original_source: &Rc<dyn IsView>) -> bool {
+        let window: Rc<dyn IsWindow> = dyn_cast_rc(this.clone()).unwrap();
+        let has_desktop = window.desktop().is_some();
+        match key {
+            Key::Modified(m, SpecialKey::F5) if m == Modifiers::CTRL && has_desktop => {
+                if window.is_zoomed() { return true; }
+                window.window().data.borrow_mut().is_keyboard_drag = true;
+                this.invalidate_render();
+                true
+            },
+            Key::F5 if has_desktop => {
+                window.set_is_zoomed(!window.is_zoomed());
+                true
+            },
+            Key::Modified(m, SpecialKey::F3) if m == Modifiers::ALT => {
+                window.close();
+                true
+            },
+            _ => View::key_impl(this, key, original_source)
+        }
+    }
+
+    pub fn preview_mouse_impl(this: &Rc<dyn IsView>, event: MouseEvent, original_source: &Rc<dyn IsView>) -> bool {
+        if let MouseAction::Down(_) = event.action {
+            let window: Rc<dyn IsWindow> = dyn_cast_rc(this.clone()).unwrap();
+            let activated = !window.is_active();
+            window.window().data.borrow_mut().activated_by_press = activated;
+            if activated {
+                window.activate();
+            }
+        }
+        View::preview_mouse_impl(this, event, original_source)
+    }
+
+    pub fn mouse_impl(this: &Rc<dyn IsView>, event: MouseEvent, original_source: &Rc<dyn IsView>) -> bool {
+        let window: Rc<dyn IsWindow> = dyn_cast_rc(this.clone()).unwrap();
+        let app = this.app().unwrap();
+        let bounds = this.inner_render_bounds();
+        let has_desktop = window.desktop().is_some();
+        match event.action {
+            MouseAction::Down(MouseButton::Left) => {
+                let p = event.point;
+                let glyphs = !take(&mut window.window().data.borrow_mut().activated_by_press);
+                if glyphs && p.y == 0 && p.x >= 2 && p.x < 5 {
+                    window.close();
+                } else if glyphs && has_desktop && p.y == 0 && p.x >= bounds.w().wrapping_sub(5) && p.x < bounds.w().wrapping_sub(2) {
+                    window.set_is_zoomed(!window.is_zoomed());
+                } else if glyphs && has_desktop && !window.is_zoomed() && p == bounds.br_inner() {
+                    window.window().data.borrow_mut().drag = Some(Drag::Resize);
+                    app.capture_mouse(Some(this));
+                } else if has_desktop && !window.is_zoomed() && p.y == 0 {
+                    window.window().data.borrow_mut().drag = Some(Drag::Move(p));
+                    app.capture_mouse(Some(this));
+                } else if app.focused(true).is_none_or(|x| !this.is_visual_ancestor_of(x)) {
+                    app.focus(Some(this), Some(true));
+                }
+                true
+            },
+            MouseAction::DoubleClick(MouseButton::Left) if has_desktop && event.point.y == 0 => {
+                window.set_is_zoomed(!window.is_zoomed());
+                true
+            },
+            MouseAction::Drag(MouseButton::Left) => {
+                let drag = window.window().data.borrow().drag;
+                match drag {
+                    Some(Drag::Move(anchor)) => window.move_by(Vector {
+                        x: event.point.x.wrapping_sub(anchor.x),
+                        y: event.point.y.wrapping_sub(anchor.y)
+                    }),
+                    Some(Drag::Resize) => window.resize_to(Vector {
+                        x: event.point.x.wrapping_add(1),
+                        y: event.point.y.wrapping_add(1)
+                    }),
+                    None => return View::mouse_impl(this, event, original_source),
+                }
+                true
+            },
+            MouseAction::Up(MouseButton::Left) => {
+                if window.window().data.borrow_mut().drag.take().is_none() {
+                    return View::mouse_impl(this, event, original_source);
+                }
+                app.capture_mouse(None);
+                true
+            },
+            _ => View::mouse_impl(this, event, original_source)
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! window_template {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident in $mod:ident {
+            $(use $path:path as $import:ident;)*
+            $($(
+                $(#[$field_attr:meta])*
+                pub $field_name:ident : $field_ty:ty
+            ),+ $(,)?)?
+        }
+    ) => {
+        $crate::decorator_template! {
+            $(#[$attr])*
+            $vis struct $name in $mod {
+                $(use $path as $import;)*
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub title: Option<String>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub color: Option<($crate::base::Fg, $crate::base::Bg)>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub is_zoomed: Option<bool>,
+                $($(
+                    $(#[$field_attr])*
+                    pub $field_name : $field_ty
+                ),+)?
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! window_apply_template {
+    ($this:ident, $instance:ident, $names:ident) => {
+        $crate::decorator_apply_template!($this, $instance, $names);
+        {
+            use $crate::window::WindowExt;
+
+            let obj: $crate::alloc_rc_Rc<dyn $crate::window::IsWindow>
+                = $crate::dynamic_cast_dyn_cast_rc($instance.clone()).unwrap();
+            $this.title.as_ref().map(|x| obj.set_title($crate::alloc_rc_Rc::new(x.clone())));
+            $this.color.map(|x| obj.set_color(x));
+            $this.is_zoomed.map(|x| obj.set_is_zoomed(x));
+        }
+    };
+}
+
+window_template! {
+    #[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+    #[serde(rename="Window@Child")]
+    pub struct WindowTemplate in template { }
+}
+
+#[typetag::serde(name="Window")]
+impl Template for WindowTemplate {
+    fn is_name_scope(&self) -> bool {
+        self.is_name_scope
+    }
+
+    fn name(&self) -> Option<&String> {
+        Some(&self.name)
+    }
+
+    fn create_instance(&self) -> Rc<dyn IsObj> {
+        Window::new()
+    }
+
+    fn apply(&self, instance: &Rc<dyn IsObj>, names: &mut NameResolver) {
+        let this = self;
+        window_apply_template!(this, instance, names);
+    }
+}
@@ -0,0 +1,233 @@
+use basic_oop::{class_unsafe, import, Vtable};
+use dynamic_cast::dyn_cast_rc;
+use std::cell::Cell;
+use crate::template::{Template, NameResolver};
+use crate::view::Visibility;
+use crate::view_vec::ViewVecExt;
+
+import! { pub wrap_panel:
+    use [panel crate::panel];
+}
+
+#[class_unsafe(inherits_Panel)]
+pub struct WrapPanel {
+    vertical: Cell<bool>,
+    item_width: Cell<Option<i16>>,
+    item_height: Cell<Option<i16>>,
+    #[non_virt]
+    vertical: fn() -> bool,
+    #[non_virt]
+    set_vertical: fn(value: bool),
+    #[non_virt]
+    item_width: fn() -> Option<i16>,
+    #[non_virt]
+    set_item_width: fn(value: Option<i16>),
+    #[non_virt]
+    item_height: fn() -> Option<i16>,
+    #[non_virt]
+    set_item_height: fn(value: Option<i16>),
+    #[over]
+    measure_override: (),
+    #[over]
+    arrange_override: (),
+}
+
+/// Swaps vector components for vertical flow, so the layout code can always
+/// treat `x` as the direction along a line and `y` as the direction across lines.
+fn flow(v: Vector, vertical: bool) -> Vector {
+    if vertical { Vector { x: v.y, y: v.x } } else { v }
+}
+
+impl WrapPanel {
+    pub fn new() -> Rc<dyn IsWrapPanel> {
+        let res: Rc<dyn IsWrapPanel> = Rc::new(unsafe { Self::new_raw(WRAP_PANEL_VTABLE.as_ptr()) });
+        res._init();
+        res
+    }
+
+    pub unsafe fn new_raw(vtable: Vtable) -> Self {
+        WrapPanel {
+            panel: unsafe { Panel::new_raw(vtable) },
+            vertical: Cell::new(false),
+            item_width: Cell::new(None),
+            item_height: Cell::new(None),
+        }
+    }
+
+    pub fn vertical_impl(this: &Rc<dyn IsWrapPanel>) -> bool {
+        this.wrap_panel().vertical.get()
+    }
+
+    pub fn set_vertical_impl(this: &Rc<dyn IsWrapPanel>, value: bool) {
+        let old = this.wrap_panel().vertical.replace(value);
+        if old == value { return; }
+        this.invalidate_measure();
+    }
+
+    pub fn item_width_impl(this: &Rc<dyn IsWrapPanel>) -> Option<i16> {
+        this.wrap_panel().item_width.get()
+    }
+
+    /// Sets the width given to every child. `None` means each child gets its desired width.
+    pub fn set_item_width_impl(this: &Rc<dyn IsWrapPanel>, value: Option<i16>) {
+        let old = this.wrap_panel().item_width.replace(value);
+        if old == value { return; }
+        this.invalidate_measure();
+    }
+
+    pub fn item_height_impl(this: &Rc<dyn IsWrapPanel>) -> Option<i16> {
+        this.wrap_panel().item_height.get()
+    }
+
+    /// Sets the height given to every child. `None` means each child gets its desired height.
+    pub fn set_item_height_impl(this: &Rc<dyn IsWrapPanel>, value: Option<i16>) {
+        let old = this.wrap_panel().item_height.replace(value);
+        if old == value { return; }
+        this.invalidate_measure();
+    }
+
+    fn item_size(this: &Rc<dyn IsWrapPanel>, child: &Rc<dyn IsView>) -> Vector {
+        if child.visibility() == Visibility::Collapsed { return Vector::null(); }
+        let desired_size = child.desired_size();
+        Vector {
+            x: this.wrap_panel().item_width.get().unwrap_or(desired_size.x),
+            y: this.wrap_panel().item_height.get().unwrap_or(desired_size.y),
+        }
+    }
+
+    pub fn measure_override_impl(this: &Rc<dyn IsView>, w: Option<i16>, h: Option<i16>) -> Vector {
+        let this: Rc<dyn IsWrapPanel> = dyn_cast_rc(this.clone()).unwrap();
+        let vertical = this.wrap_panel().vertical.get();
+        let item_width = this.wrap_panel().item_width.get();
+        let item_height = this.wrap_panel().item_height.get();
+        let line_limit = if vertical { h } else { w };
+        let mut size = Vector::null();
+        let mut line = Vector::null();
+        for child in this.children().iter() {
+            child.measure(item_width.or(w), item_height.or(h));
+            if child.visibility() == Visibility::Collapsed { continue; }
+            let child_size = flow(Self::item_size(&this, &child), vertical);
+            if line.x != 0 && line_limit.is_some_and(|x| line.x.saturating_add(child_size.x) > x) {
+                size = Vector { x: size.x.max(line.x), y: size.y.saturating_add(line.y) };
+                line = Vector::null();
+            }
+            line = Vector { x: line.x.saturating_add(child_size.x), y: line.y.max(child_size.y) };
+        }
+        size = Vector { x: size.x.max(line.x), y: size.y.saturating_add(line.y) };
+        flow(size, vertical)
+    }
+
+    pub fn arrange_override_impl(this: &Rc<dyn IsView>, bounds: Rect) -> Vector {
+        let this: Rc<dyn IsWrapPanel> = dyn_cast_rc(this.clone()).unwrap();
+        let vertical = this.wrap_panel().vertical.get();
+        let line_limit = flow(bounds.size, vertical).x;
+        let mut size = Vector::null();
+        let mut line = Vector::null();
+        let mut line_children = Vec::new();
+        let arrange_line = |line_children: &mut Vec<(Rc<dyn IsView>, Vector)>, offset: i16, line_size: i16| {
+            let mut pos = 0i16;
+            for (child, child_size) in line_children.drain(..) {
+                let tl = flow(Vector { x: pos, y: offset }, vertical);
+                let size = flow(Vector { x: child_size.x, y: line_size }, vertical);
+                child.arrange(Rect { tl: bounds.tl.offset(tl), size });
+                pos = pos.saturating_add(child_size.x);
+            }
+        };
+        for child in this.children().iter() {
+            if child.visibility() == Visibility::Collapsed { continue; }
+            let child_size = flow(Self::item_size(&this, &child), vertical);
+            if line.x != 0 && line.x.saturating_add(child_size.x) > line_limit {
+                arrange_line(&mut line_children, size.y, line.y);
+                size = Vector { x: size.x.max(line.x), y: size.y.saturating_add(line.y) };
+                line = Vector::null();
+            }
+            line = Vector { x: line.x.saturating_add(child_size.x), y: line.y.max(child_size.y) };
+            line_children.push((child, child_size));
+        }
+        arrange_line(&mut line_children, size.y, line.y);
+        size = Vector { x: size.x.max(line.x), y: size.y.saturating_add(line.y) };
+        flow(size, vertical)
+    }
+}
+
+#[macro_export]
+macro_rules! wrap_panel_template {
+    (
+        $(#[$attr:meta])*
+        $vis:vis struct $name:ident in $mod:ident {
+            $(use $path:path as $import:ident;)*
+
+            $($(
+                $(#[$field_attr:meta])*
+                pub $field_name:ident : $field_ty:ty
+            ),+ $(,)?)?
+        }
+    ) => {
+        $crate::panel_template! {
+            $(#[$attr])*
+            $vis struct $name in $mod {
+                $(use $path as $import;)*
+
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                pub vertical: Option<bool>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                #[serde(serialize_with="tvxaml_view_serialize_optional_i16")]
+                #[serde(deserialize_with="tvxaml_view_deserialize_optional_i16")]
+                pub item_width: Option<Option<i16>>,
+                #[serde(default)]
+                #[serde(skip_serializing_if="Option::is_none")]
+                #[serde(serialize_with="tvxaml_view_serialize_optional_i16")]
+                #[serde(deserialize_with="tvxaml_view_deserialize_optional_i16")]
+                pub item_height: Option<Option<i16>>,
+                $($(
+                    $(#[$field_attr])*
+                    pub $field_name : $field_ty
+                ),+)?
+            }
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! wrap_panel_apply_template {
+    ($this:ident, $instance:ident, $names:ident) => {
+        $crate::panel_apply_template!($this, $instance, $names);
+        {
+            use $crate::wrap_panel::WrapPanelExt;
+
+            let obj: $crate::alloc_rc_Rc<dyn $crate::wrap_panel::IsWrapPanel>
+                = $crate::dynamic_cast_dyn_cast_rc($instance.clone()).unwrap();
+            $this.vertical.map(|x| obj.set_vertical(x));
+            $this.item_width.map(|x| obj.set_item_width(x));
+            $this.item_height.map(|x| obj.set_item_height(x));
+        }
+    };
+}
+
+wrap_panel_template! {
+    #[derive(serde::Serialize, serde::Deserialize, Default, Clone)]
+    #[serde(rename="WrapPanel@Children")]
+    pub struct WrapPanelTemplate in template { }
+}
+
+#[typetag::serde(name="WrapPanel")]
+impl Template for WrapPanelTemplate {
+    fn is_name_scope(&self) -> bool {
+        self.is_name_scope
+    }
+
+    fn name(&self) -> Option<&String> {
+        Some(&self.name)
+    }
+
+    fn create_instance(&self) -> Rc<dyn IsObj> {
+        WrapPanel::new()
+    }
+
+    fn apply(&self, instance: &Rc<dyn IsObj>, names: &mut NameResolver) {
+        let this = self;
+        wrap_panel_apply_template!(this, instance, names);
+    }
+}
@@ -0,0 +1,135 @@
+use dynamic_cast::dyn_cast_rc;
+use std::rc::Rc;
+use std::sync::Mutex;
+use timer_no_std::MonoClock;
+use tvxaml::app::{App, AppExt};
+use tvxaml::base::{Attr, Bg, Ctrl, Fg, Key, Modifiers, Point, SpecialKey, TextWrapping, Vector};
+use tvxaml::input_line::InputLine;
+use tvxaml::static_text::{StaticText, StaticTextExt};
+use tvxaml::text_editor::{TextEditor, TextEditorExt};
+use tvxaml::view::IsView;
+use tvxaml_screen_headless::Screen;
+
+/// Only one clock can be alive at a time.
+static CLOCK: Mutex<()> = Mutex::new(());
+
+fn run(screen: &Screen, root: &Rc<dyn IsView>, focus: bool) -> Result<u8, tvxaml::base::Error> {
+    let _clock_guard = CLOCK.lock().unwrap_or_else(|x| x.into_inner());
+    let mut clock = Some(unsafe { MonoClock::new() });
+    let app = App::new(Box::new(screen.clone()));
+    {
+        let app = Rc::downgrade(&app);
+        screen.handle_exhausted(Some(Box::new(move || app.upgrade().unwrap().quit())));
+    }
+    app.run(&mut clock, root, Some(&mut || if focus { app.focus(Some(root), Some(true)); }))
+}
+
+#[test]
+fn static_text() {
+    let screen = Screen::new(Vector { x: 8, y: 2 });
+    let text = StaticText::new();
+    text.set_text(Rc::new("Hello".to_string()));
+    text.set_color((Fg::Yellow, Bg::Blue));
+    let root: Rc<dyn IsView> = dyn_cast_rc(text).unwrap();
+    assert_eq!(run(&screen, &root, false).unwrap(), 0);
+    assert_eq!(screen.lines(), ["Hello   ", "        "]);
+    assert_eq!(screen.fg(Point { x: 0, y: 0 }), Some(Fg::Yellow));
+    assert_eq!(screen.bg(Point { x: 7, y: 1 }), Some(Bg::Blue));
+    assert_eq!(screen.attr(Point { x: 0, y: 0 }), Some(Attr::empty()));
+    assert_eq!(screen.cursor(), None);
+}
+
+#[test]
+fn input_line_typing() {
+    let screen = Screen::new(Vector { x: 10, y: 1 });
+    screen.push_text("abc");
+    screen.push_key(Key::Left);
+    screen.push_text("d");
+    let root: Rc<dyn IsView> = dyn_cast_rc(InputLine::new()).unwrap();
+    assert_eq!(run(&screen, &root, true).unwrap(), 0);
+    assert_eq!(screen.pending_events(), 0);
+    assert_eq!(screen.line(0), " abdc     ");
+    assert_eq!(screen.fg(Point { x: 1, y: 0 }), Some(Fg::LightGray));
+    assert_eq!(screen.bg(Point { x: 0, y: 0 }), Some(Bg::Blue));
+    assert_eq!(screen.cursor(), Some(Point { x: 4, y: 0 }));
+}
+
+#[test]
+fn text_editor_undo_merge() {
+    let screen = Screen::new(Vector { x: 10, y: 2 });
+    screen.push_text("ab cd");
+    screen.push_key(Key::Enter);
+    screen.push_text("x");
+    screen.push_key(Key::Ctrl(Ctrl::Z));
+    screen.push_key(Key::Ctrl(Ctrl::Z));
+    screen.push_key(Key::Ctrl(Ctrl::Z));
+    screen.push_key(Key::Ctrl(Ctrl::Y));
+    let editor = TextEditor::new();
+    let root: Rc<dyn IsView> = dyn_cast_rc(editor.clone()).unwrap();
+    assert_eq!(run(&screen, &root, true).unwrap(), 0);
+    assert_eq!(editor.text(), "ab cd");
+    assert!(editor.can_undo());
+    assert!(editor.can_redo());
+    assert_eq!(screen.lines(), ["ab cd     ", "          "]);
+    assert_eq!(screen.cursor(), Some(Point { x: 5, y: 0 }));
+}
+
+#[test]
+fn text_editor_wrapping() {
+    let screen = Screen::new(Vector { x: 8, y: 6 });
+    let editor = TextEditor::new();
+    editor.set_text("hello world foo\nabcdefghij\na\tb");
+    editor.set_text_wrapping(TextWrapping::Wrap);
+    let root: Rc<dyn IsView> = dyn_cast_rc(editor.clone()).unwrap();
+    assert_eq!(run(&screen, &root, false).unwrap(), 0);
+    assert_eq!(screen.lines(), [
+        "hello   ",
+        "world   ",
+        "foo     ",
+        "abcdefgh",
+        "ij      ",
+        "a   b   ",
+    ]);
+}
+
+#[test]
+fn text_editor_graphemes() {
+    let screen = Screen::new(Vector { x: 10, y: 1 });
+    screen.push_key(Key::Right);
+    screen.push_key(Key::Right);
+    screen.push_key(Key::Backspace);
+    let editor = TextEditor::new();
+    editor.set_text("\u{4E2D}e\u{301}x");
+    let root: Rc<dyn IsView> = dyn_cast_rc(editor.clone()).unwrap();
+    assert_eq!(run(&screen, &root, true).unwrap(), 0);
+    assert_eq!(editor.text(), "\u{4E2D}x");
+    assert_eq!(editor.cursor_column(), 2);
+    assert_eq!(screen.cursor(), Some(Point { x: 2, y: 0 }));
+}
+
+#[test]
+fn text_editor_selection_edit() {
+    let screen = Screen::new(Vector { x: 12, y: 1 });
+    screen.push_key(Key::Modified(Modifiers::CTRL, SpecialKey::Right));
+    screen.push_key(Key::Modified(Modifiers::SHIFT, SpecialKey::End));
+    screen.push_text("there");
+    screen.push_key(Key::Ctrl(Ctrl::Z));
+    screen.push_key(Key::Backspace);
+    let editor = TextEditor::new();
+    editor.set_text("hello world");
+    let root: Rc<dyn IsView> = dyn_cast_rc(editor.clone()).unwrap();
+    assert_eq!(run(&screen, &root, true).unwrap(), 0);
+    assert_eq!(editor.text(), "hello ");
+    assert_eq!(editor.selection(), None);
+    assert_eq!(screen.cursor(), Some(Point { x: 6, y: 0 }));
+}
+
+#[test]
+fn exhausted_without_handler() {
+    let screen = Screen::new(Vector { x: 4, y: 1 });
+    let _clock_guard = CLOCK.lock().unwrap_or_else(|x| x.into_inner());
+    let mut clock = Some(unsafe { MonoClock::new() });
+    let app = App::new(Box::new(screen.clone()));
+    let root: Rc<dyn IsView> = dyn_cast_rc(StaticText::new()).unwrap();
+    assert!(app.run(&mut clock, &root, None).is_err());
+}